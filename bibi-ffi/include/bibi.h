@@ -0,0 +1,78 @@
+#ifndef BIBI_H
+#define BIBI_H
+
+/* Generated by cbindgen from bibi-ffi, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The outcome of a call.
+ */
+typedef enum BibiStatus {
+  /**
+   * The conversion succeeded.
+   */
+  BIBI_STATUS_OK = 0,
+  /**
+   * A pointer that can't be null was null.
+   */
+  BIBI_STATUS_NULL_POINTER = 1,
+  /**
+   * The input isn't valid UTF-8.
+   */
+  BIBI_STATUS_INVALID_UTF8 = 2,
+  /**
+   * The input contains a tag that is never closed, in a strict conversion.
+   */
+  BIBI_STATUS_PARSE = 3,
+  /**
+   * The output contains a NUL character, so it can't be returned as a C string.
+   */
+  BIBI_STATUS_INTERIOR_NUL = 4,
+  /**
+   * The conversion failed for any other reason, including bugs in bibi, which never unwind into the caller.
+   */
+  BIBI_STATUS_FAILED = 5,
+} BibiStatus;
+
+/**
+ * Converts NERDZ BBCode to Markdown.
+ *
+ * # Safety
+ *
+ * `input` must be null or point to a NUL-terminated string, and `output` must be null or valid for writes.
+ */
+BibiStatus bibi_to_markdown(const char *input, char **output);
+
+/**
+ * Converts NERDZ BBCode to Markdown like [`bibi_to_markdown`], but fails with [`BibiStatus::Parse`] on tags that
+ * are never closed rather than leaving them as-is.
+ *
+ * # Safety
+ *
+ * Same as [`bibi_to_markdown`].
+ */
+BibiStatus bibi_to_markdown_strict(const char *input, char **output);
+
+/**
+ * Converts Markdown to NERDZ BBCode.
+ *
+ * # Safety
+ *
+ * Same as [`bibi_to_markdown`].
+ */
+BibiStatus bibi_to_bbcode(const char *input, char **output);
+
+/**
+ * Releases a string returned by bibi. Null is ignored.
+ *
+ * # Safety
+ *
+ * `s` must be null or a string returned by bibi, which hasn't been released yet.
+ */
+void bibi_string_free(char *s);
+
+#endif  /* BIBI_H */
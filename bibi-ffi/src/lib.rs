@@ -0,0 +1,141 @@
+//! C bindings of bibi, for embedding it through FFI in programs written in other languages, such as PHP.
+//!
+//! Every conversion takes a NUL-terminated UTF-8 string and, if it succeeds, stores a newly allocated one in
+//! `*output`, which must be released with [`bibi_string_free`] and nothing else. If it fails, `*output` is set to
+//! null and the returned [`BibiStatus`] tells why.
+//!
+//! The header for C, `include/bibi.h`, is generated by cbindgen as told by `cbindgen.toml`.
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    panic::{self, UnwindSafe},
+    ptr,
+};
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BibiStatus {
+    /// The conversion succeeded.
+    Ok = 0,
+    /// A pointer that can't be null was null.
+    NullPointer = 1,
+    /// The input isn't valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The input contains a tag that is never closed, in a strict conversion.
+    Parse = 3,
+    /// The output contains a NUL character, so it can't be returned as a C string.
+    InteriorNul = 4,
+    /// The conversion failed for any other reason, including bugs in bibi, which never unwind into the caller.
+    Failed = 5,
+}
+
+/// Converts the string at `input` with `convert`, storing the result in `*output`.
+unsafe fn convert(
+    input: *const c_char,
+    output: *mut *mut c_char,
+    convert: impl FnOnce(&str) -> bibi::Result<String> + UnwindSafe,
+) -> BibiStatus {
+    if output.is_null() {
+        return BibiStatus::NullPointer;
+    }
+
+    *output = ptr::null_mut();
+
+    if input.is_null() {
+        return BibiStatus::NullPointer;
+    }
+
+    let Ok(input) = CStr::from_ptr(input).to_str() else {
+        return BibiStatus::InvalidUtf8;
+    };
+
+    let converted = match panic::catch_unwind(|| convert(input)) {
+        Ok(Ok(converted)) => converted,
+        Ok(Err(bibi::Error::Parse { .. })) => return BibiStatus::Parse,
+        Ok(Err(bibi::Error::Encoding { .. })) => return BibiStatus::InvalidUtf8,
+        Ok(Err(_)) | Err(_) => return BibiStatus::Failed,
+    };
+
+    match CString::new(converted) {
+        Ok(converted) => {
+            *output = converted.into_raw();
+
+            BibiStatus::Ok
+        }
+        Err(_) => BibiStatus::InteriorNul,
+    }
+}
+
+/// Converts NERDZ BBCode to Markdown.
+///
+/// # Safety
+///
+/// `input` must be null or point to a NUL-terminated string, and `output` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bibi_to_markdown(
+    input: *const c_char,
+    output: *mut *mut c_char,
+) -> BibiStatus {
+    convert(input, output, |input| Ok(bibi::to_markdown(input)))
+}
+
+/// Converts NERDZ BBCode to Markdown like [`bibi_to_markdown`], but fails with [`BibiStatus::Parse`] on tags that
+/// are never closed rather than leaving them as-is.
+///
+/// # Safety
+///
+/// Same as [`bibi_to_markdown`].
+#[no_mangle]
+pub unsafe extern "C" fn bibi_to_markdown_strict(
+    input: *const c_char,
+    output: *mut *mut c_char,
+) -> BibiStatus {
+    convert(input, output, bibi::to_markdown_strict)
+}
+
+/// Converts Markdown to NERDZ BBCode.
+///
+/// # Safety
+///
+/// Same as [`bibi_to_markdown`].
+#[no_mangle]
+pub unsafe extern "C" fn bibi_to_bbcode(
+    input: *const c_char,
+    output: *mut *mut c_char,
+) -> BibiStatus {
+    convert(input, output, bibi::to_bbcode)
+}
+
+/// Releases a string returned by bibi. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by bibi, which hasn't been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn bibi_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    #[test]
+    fn strict_conversions_fail_on_unclosed_tags() {
+        let mut output = ptr::null_mut();
+
+        let status = unsafe { bibi_to_markdown_strict(c"[b]Hi".as_ptr(), &mut output) };
+        assert_eq!((status, output), (BibiStatus::Parse, ptr::null_mut()));
+
+        let status = unsafe { bibi_to_markdown_strict(c"[b]Hi[/b]".as_ptr(), &mut output) };
+        assert_eq!(status, BibiStatus::Ok);
+        assert_eq!(unsafe { CStr::from_ptr(output) }, c"**Hi**");
+
+        unsafe { bibi_string_free(output) };
+    }
+}
@@ -0,0 +1,78 @@
+//! Errors thrown to JS as `Error` objects telling what went wrong and where, so that editors can point at it.
+
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+/// A failed conversion, thrown as an `Error` with its message and these properties:
+///
+/// - `kind`, either `"parse"` (only thrown by `to_markdown_strict`), `"encoding"`, `"config"`, `"output"`,
+///   `"options"` or `"other"`;
+/// - `offset`, the byte offset in the input the error refers to, if any;
+/// - `tag`, the name of the tag the error refers to, if any.
+pub struct ConvertError {
+    kind: &'static str,
+    message: String,
+    offset: Option<usize>,
+    tag: Option<String>,
+}
+
+impl ConvertError {
+    /// An error in the arguments of a call, rather than in the input.
+    pub fn options(message: String) -> Self {
+        Self {
+            kind: "options",
+            message,
+            offset: None,
+            tag: None,
+        }
+    }
+}
+
+impl From<bibi::Error> for ConvertError {
+    fn from(err: bibi::Error) -> Self {
+        use bibi::Error::*;
+
+        let (kind, offset, tag) = match &err {
+            Parse { offset, tag } => ("parse", Some(*offset), Some(tag.clone())),
+            Encoding { offset } => ("encoding", Some(*offset), None),
+            Config { .. } => ("config", None, None),
+            Io(_) | Fmt(_) => ("output", None, None),
+            _ => ("other", None, None),
+        };
+
+        Self {
+            kind,
+            message: err.to_string(),
+            offset,
+            tag,
+        }
+    }
+}
+
+impl From<serde_wasm_bindgen::Error> for ConvertError {
+    fn from(err: serde_wasm_bindgen::Error) -> Self {
+        Self::options(format!("invalid options: {err}"))
+    }
+}
+
+impl From<ConvertError> for JsValue {
+    fn from(err: ConvertError) -> Self {
+        let ret = js_sys::Error::new(&err.message);
+
+        let set = |key: &str, value: JsValue| {
+            Reflect::set(&ret, &key.into(), &value).expect("a new Error is always extensible");
+        };
+
+        set("kind", err.kind.into());
+
+        if let Some(offset) = err.offset {
+            set("offset", (offset as f64).into());
+        }
+
+        if let Some(tag) = err.tag {
+            set("tag", tag.into());
+        }
+
+        ret.into()
+    }
+}
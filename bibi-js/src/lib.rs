@@ -1,28 +1,94 @@
+mod error;
+mod options;
 mod utils;
 
+use bibi::{BbcodeParser, Direction, StreamConverter};
+use error::ConvertError;
+use options::convert_options;
+use pulldown_cmark::html;
 use utils::set_panic_hook;
 use wasm_bindgen::prelude::*;
 
-use bibi::{dump_bbcode, dump_markdown};
-
+/// Converts Markdown to NERDZ BBCode. `opts` is an optional object such as `{ lineEnding: "crlf" }`, whose keys are
+/// `dialect`, `lineEnding`, `escaping` and `userUrl`.
+///
+/// Failures are thrown as `Error`s with a `kind` property, plus the `offset` in bytes and the `tag` they refer to, if
+/// any, so that editors can point at them.
 #[wasm_bindgen]
-pub fn to_bbcode(s: &str) -> Result<String, JsError> {
+pub fn to_bbcode(s: &str, opts: JsValue) -> Result<String, ConvertError> {
     set_panic_hook(); // run this once when the feature is enabled
 
-    let mut writer = Vec::new();
+    Ok(bibi::to_bbcode_with(s, &convert_options(opts)?)?)
+}
+
+/// Converts NERDZ BBCode to Markdown, with the same options as `to_bbcode`.
+#[wasm_bindgen]
+pub fn to_markdown(s: &str, opts: JsValue) -> Result<String, ConvertError> {
+    set_panic_hook(); // see above
 
-    dump_bbcode(&mut writer, s)?;
+    Ok(bibi::to_markdown_with(s, &convert_options(opts)?))
+}
+
+/// Same as `to_markdown`, but throws an `Error` whose `kind` is `"parse"` on the first tag that is never closed,
+/// pointing at it with its `offset` and `tag`, rather than leaving it as-is.
+#[wasm_bindgen]
+pub fn to_markdown_strict(s: &str, opts: JsValue) -> Result<String, ConvertError> {
+    set_panic_hook(); // see above
 
-    Ok(String::from_utf8(writer)?)
+    Ok(bibi::to_markdown_strict_with(s, &convert_options(opts)?)?)
 }
 
+/// Renders NERDZ BBCode as HTML, going through the Markdown it converts to, so that posts can be previewed as they'll
+/// look once converted. Takes the same options as `to_markdown`, and renders footnotes and task lists too.
 #[wasm_bindgen]
-pub fn to_markdown(s: &str) -> Result<String, JsError> {
+pub fn to_html(s: &str, opts: JsValue) -> Result<String, ConvertError> {
     set_panic_hook(); // see above
 
-    let mut writer = Vec::new();
+    let mut ret = String::with_capacity(s.len() * 2);
+    html::push_html(
+        &mut ret,
+        BbcodeParser::with_options(s, &convert_options(opts)?),
+    );
+
+    Ok(ret)
+}
+
+/// Converts text pushed a piece at a time, e.g. read from a `ReadableStream` or inside a worker, so that large texts
+/// never have to be held, or copied across the boundary with wasm, as a whole.
+#[wasm_bindgen]
+pub struct Converter(StreamConverter);
+
+#[wasm_bindgen]
+impl Converter {
+    /// Creates a converter going in the given direction, either `"bb2md"` or `"md2bb"`, with the same options as
+    /// `to_markdown` and `to_bbcode`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(direction: &str, opts: JsValue) -> Result<Converter, ConvertError> {
+        set_panic_hook(); // see above
+
+        let direction = match direction {
+            "bb2md" => Direction::ToMarkdown,
+            "md2bb" => Direction::ToBBCode,
+            _ => {
+                return Err(ConvertError::options(format!(
+                    "unknown direction {direction:?}, expected \"bb2md\" or \"md2bb\""
+                )))
+            }
+        };
+
+        Ok(Self(StreamConverter::new(
+            direction,
+            convert_options(opts)?,
+        )))
+    }
 
-    dump_markdown(&mut writer, s)?;
+    /// Pushes the next chunk of the input, returning whatever could be converted so far.
+    pub fn push(&mut self, chunk: &str) -> Result<String, ConvertError> {
+        Ok(self.0.push(chunk)?)
+    }
 
-    Ok(String::from_utf8(writer)?)
+    /// Ends the input, returning the rest of the output. The converter can't be used anymore afterwards.
+    pub fn finish(self) -> Result<String, ConvertError> {
+        Ok(self.0.finish()?)
+    }
 }
@@ -0,0 +1,72 @@
+//! The options JS callers can pass to the conversions, as a plain object mapping onto [`ConvertOptions`].
+
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::error::ConvertError;
+
+use bibi::{ConvertOptions, Dialect, EscapePolicy, LineEnding};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsDialect {
+    Nerdz,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsLineEnding {
+    Lf,
+    Crlf,
+    Match,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsEscaping {
+    Verbatim,
+    Markdown,
+}
+
+/// Options such as `{ dialect: "nerdz", lineEnding: "crlf", escaping: "markdown", userUrl: "https://nerdz.eu/" }`,
+/// where every key is optional.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct JsOptions {
+    dialect: Option<JsDialect>,
+    line_ending: Option<JsLineEnding>,
+    escaping: Option<JsEscaping>,
+    user_url: Option<String>,
+}
+
+/// Reads the options passed from JS, if any, leaving whatever they don't set to its default.
+pub fn convert_options(opts: JsValue) -> Result<ConvertOptions, ConvertError> {
+    let Some(opts): Option<JsOptions> = serde_wasm_bindgen::from_value(opts)? else {
+        return Ok(ConvertOptions::default());
+    };
+
+    let mut ret = ConvertOptions::default();
+
+    if let Some(JsDialect::Nerdz) = opts.dialect {
+        ret.dialect = Dialect::Nerdz;
+    }
+
+    if let Some(line_ending) = opts.line_ending {
+        ret.line_ending = match line_ending {
+            JsLineEnding::Lf => LineEnding::Lf,
+            JsLineEnding::Crlf => LineEnding::Crlf,
+            JsLineEnding::Match => LineEnding::MatchInput,
+        };
+    }
+
+    if let Some(escaping) = opts.escaping {
+        ret.escaping = match escaping {
+            JsEscaping::Verbatim => EscapePolicy::Verbatim,
+            JsEscaping::Markdown => EscapePolicy::Markdown,
+        };
+    }
+
+    ret.user_url = opts.user_url;
+
+    Ok(ret)
+}
@@ -0,0 +1,169 @@
+//! Python bindings of bibi, so that posts can be converted in-process, e.g. while analyzing dumps of NERDZ, rather
+//! than by running the CLI once per post.
+//!
+//! The module is named `bibi` and is built with [maturin](https://www.maturin.rs), e.g. `maturin develop` from this
+//! directory:
+//!
+//! ```python
+//! import bibi
+//!
+//! bibi.to_markdown("[b]Hi[/b]")  # '**Hi**'
+//!
+//! converter = bibi.Converter(line_ending="crlf", target="html")
+//! converter.to_markdown("[u]Hi[/u]\n")  # '<ins>Hi</ins>\r\n'
+//! ```
+
+use std::collections::BTreeMap;
+
+use pyo3::{create_exception, exceptions::PyValueError, prelude::*};
+
+use bibi::{CommentPolicy, ConvertOptions, Dialect, EscapePolicy, LineEnding, MarkdownTarget};
+
+create_exception!(
+    bibi,
+    ConversionError,
+    PyValueError,
+    "Raised when the input contains markup that can't be converted."
+);
+
+fn conversion_error(err: bibi::Error) -> PyErr {
+    ConversionError::new_err(err.to_string())
+}
+
+/// Reads the value of an option, telling the values it could have had otherwise.
+fn parse_option<T: Copy>(name: &str, value: &str, choices: &[(&str, T)]) -> PyResult<T> {
+    match choices
+        .iter()
+        .find(|(choice, _)| choice.eq_ignore_ascii_case(value))
+    {
+        Some(&(_, ret)) => Ok(ret),
+        None => {
+            let names: Vec<_> = choices
+                .iter()
+                .map(|(choice, _)| format!("{choice:?}"))
+                .collect();
+
+            Err(PyValueError::new_err(format!(
+                "invalid {name} {value:?}, expected one of {}",
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/// Converts NERDZ BBCode to Markdown.
+#[pyfunction]
+fn to_markdown(py: Python<'_>, s: &str) -> String {
+    py.allow_threads(|| bibi::to_markdown(s))
+}
+
+/// Converts Markdown to NERDZ BBCode, raising `ConversionError` if it can't.
+#[pyfunction]
+fn to_bbcode(py: Python<'_>, s: &str) -> PyResult<String> {
+    py.allow_threads(|| bibi::to_bbcode(s))
+        .map_err(conversion_error)
+}
+
+/// Converts with the given options, which are all keyword-only and optional:
+///
+/// - `dialect`, either `"nerdz"` or `"phpbb"`;
+/// - `line_ending`, either `"lf"`, `"crlf"` or `"match"`, to use whatever the input uses;
+/// - `escaping`, either `"verbatim"` or `"markdown"`, to escape the text of the Markdown;
+/// - `target`, either `"plain"` or `"html"`, to turn tags Markdown has no syntax for into HTML;
+/// - `comment_tag`, the tag Markdown comments are kept in, rather than dropped;
+/// - `user_url`, the URL the names of users in `[user]` tags are appended to;
+/// - `synonyms`, a dict of the names of tags by their synonyms.
+#[pyclass]
+struct Converter(ConvertOptions);
+
+#[pymethods]
+impl Converter {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        dialect = "nerdz",
+        line_ending = "lf",
+        escaping = "verbatim",
+        target = "plain",
+        comment_tag = None,
+        user_url = None,
+        synonyms = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        dialect: &str,
+        line_ending: &str,
+        escaping: &str,
+        target: &str,
+        comment_tag: Option<String>,
+        user_url: Option<String>,
+        synonyms: Option<BTreeMap<String, String>>,
+    ) -> PyResult<Self> {
+        Ok(Self(ConvertOptions {
+            dialect: parse_option(
+                "dialect",
+                dialect,
+                &[("nerdz", Dialect::Nerdz), ("phpbb", Dialect::Phpbb)],
+            )?,
+            line_ending: parse_option(
+                "line ending",
+                line_ending,
+                &[
+                    ("lf", LineEnding::Lf),
+                    ("crlf", LineEnding::Crlf),
+                    ("match", LineEnding::MatchInput),
+                ],
+            )?,
+            escaping: parse_option(
+                "escaping",
+                escaping,
+                &[
+                    ("verbatim", EscapePolicy::Verbatim),
+                    ("markdown", EscapePolicy::Markdown),
+                ],
+            )?,
+            target: parse_option(
+                "target",
+                target,
+                &[
+                    ("plain", MarkdownTarget::Plain),
+                    ("html", MarkdownTarget::Html),
+                ],
+            )?,
+            comments: comment_tag.map_or(CommentPolicy::Strip, CommentPolicy::Tag),
+            user_url,
+            synonyms: synonyms.unwrap_or_default(),
+            ..Default::default()
+        }))
+    }
+
+    /// Creates a converter with the options of a TOML configuration, like the `bibi.toml` files of the CLI.
+    #[staticmethod]
+    fn from_config(toml: &str) -> PyResult<Self> {
+        ConvertOptions::from_config(toml)
+            .map(Self)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Converts BBCode to Markdown.
+    fn to_markdown(&self, py: Python<'_>, s: &str) -> String {
+        py.allow_threads(|| bibi::to_markdown_with(s, &self.0))
+    }
+
+    /// Converts Markdown to BBCode, raising `ConversionError` if it can't.
+    fn to_bbcode(&self, py: Python<'_>, s: &str) -> PyResult<String> {
+        py.allow_threads(|| bibi::to_bbcode_with(s, &self.0))
+            .map_err(conversion_error)
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "bibi")]
+fn bibi_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(to_markdown, m)?)?;
+    m.add_function(wrap_pyfunction!(to_bbcode, m)?)?;
+    m.add_class::<Converter>()?;
+    m.add("ConversionError", m.py().get_type::<ConversionError>())?;
+
+    Ok(())
+}
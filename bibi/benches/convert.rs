@@ -0,0 +1,134 @@
+use std::io;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use bibi::{dump_bbcode, dump_markdown};
+
+use generate::Generator;
+
+#[allow(dead_code)]
+#[path = "../src/bin/main/generate.rs"]
+mod generate;
+
+/// A post made of a single enormous line without any tag, such as pasted minified JSON.
+fn long_tagless_line() -> String {
+    r#"{"id":12345,"name":"nerdz","values":[1,2,3],"nested":{"a":"b"}},"#.repeat(50_000)
+}
+
+/// A short tagged paragraph followed by a single enormous line without any tag.
+fn long_line_after_tags() -> String {
+    format!(
+        "[b]log dump[/b] from [url=https://nerdz.eu]nerdz[/url]:\n{}\n[i]end[/i]",
+        "2023-04-01T00:00:00Z INFO request served in 12ms; ".repeat(50_000)
+    )
+}
+
+/// A list with many items, optionally numbered, such as a changelog or a dump of links.
+fn long_list(head: &str) -> String {
+    let items: String = (0..10_000)
+        .map(|i| format!("[*]item number {i}\n"))
+        .collect();
+
+    format!("[list{head}]\n{items}[/list]")
+}
+
+/// A long post where every line is full of inline tags, such as a whole thread pasted as a single post.
+fn tag_heavy_post() -> String {
+    concat!(
+        "[b]bold[/b] and [i]italic[/i], [del]struck[/del] and [cur][b]both[/b][/cur], with a ",
+        "[url=https://nerdz.eu]link[/url] and [img]https://nerdz.eu/static/logo.png[/img]\n",
+        "[big]a heading[/big]\n",
+    )
+    .repeat(20_000)
+}
+
+fn long_lines(c: &mut Criterion) {
+    let tagless = long_tagless_line();
+    let after_tags = long_line_after_tags();
+
+    c.bench_function("long tagless line", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&tagless)))
+    });
+
+    c.bench_function("long line after tags", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&after_tags)))
+    });
+}
+
+fn long_lists(c: &mut Criterion) {
+    let unordered = long_list("");
+    let numbered = long_list(r#" type="I" start="5""#);
+
+    c.bench_function("10k item list", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&unordered)))
+    });
+
+    c.bench_function("10k item numbered list", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&numbered)))
+    });
+}
+
+fn inline_tags(c: &mut Criterion) {
+    let post = tag_heavy_post();
+
+    c.bench_function("tag heavy post", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&post)))
+    });
+}
+
+/// Code tags that are never closed, or that look like code tags but aren't, such as text mangled by a broken editor.
+fn broken_code_tags(c: &mut Criterion) {
+    let unclosed = "[code]x ".repeat(30_000);
+    let unclosed_inline = "[c]x\n".repeat(30_000);
+    let truncated = format!("{}[/code]", "[code=a".repeat(30_000));
+
+    c.bench_function("30k unclosed code tags", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&unclosed)))
+    });
+
+    c.bench_function("30k unclosed inline code tags", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&unclosed_inline)))
+    });
+
+    c.bench_function("30k truncated code tags", |b| {
+        b.iter(|| dump_markdown(io::sink(), black_box(&truncated)))
+    });
+}
+
+/// Many generated posts of a typical size, as in a forum dump, with both sparse and dense tags.
+fn generated_posts(c: &mut Criterion) {
+    for density in [0.05, 0.4] {
+        let mut generator = Generator::new(0, density);
+        let posts: Vec<_> = (0..1000).map(|_| generator.post(2000)).collect();
+        let markdown: Vec<_> = posts.iter().map(|post| bibi::to_markdown(post)).collect();
+
+        c.bench_function(&format!("1k generated posts, density {density}"), |b| {
+            b.iter(|| {
+                for post in &posts {
+                    dump_markdown(io::sink(), black_box(post)).unwrap();
+                }
+            })
+        });
+
+        c.bench_function(
+            &format!("1k generated posts to BBCode, density {density}"),
+            |b| {
+                b.iter(|| {
+                    for post in &markdown {
+                        dump_bbcode(io::sink(), black_box(post)).unwrap();
+                    }
+                })
+            },
+        );
+    }
+}
+
+criterion_group!(
+    benches,
+    long_lines,
+    long_lists,
+    inline_tags,
+    broken_code_tags,
+    generated_posts
+);
+criterion_main!(benches);
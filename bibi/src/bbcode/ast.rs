@@ -0,0 +1,235 @@
+//! A syntax tree of BBCode, built from the [`tokenize`](crate::tokenize) tokens by matching every opening tag with
+//! its closing one, for tools that need the structure of a post rather than its conversion.
+//!
+//! Like the tokens, the tree knows nothing about which tags a dialect supports: unknown tags are elements like any
+//! other. The only exceptions are code tags, whose content is always text, and list items, which never need to be
+//! closed.
+
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bbcode::{tokenize, TokenKind},
+    options::ConvertOptions,
+};
+
+/// A piece of a BBCode syntax tree, as returned by [`parse_ast`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "type", rename_all = "lowercase")
+)]
+#[non_exhaustive]
+pub enum Node {
+    /// Text, including anything that looks like a tag but isn't well-formed and closing tags that close nothing.
+    Text {
+        text: String,
+        /// Where the text is in the input, in bytes.
+        span: Range<usize>,
+    },
+    /// A tag, along with everything within it.
+    Element(Element),
+}
+
+impl Node {
+    /// Where the node is in the input, in bytes.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Node::Text { span, .. } => span.clone(),
+            Node::Element(element) => element.span.clone(),
+        }
+    }
+}
+
+/// A tag and its content, such as `[url=https://nerdz.eu]NERDZ[/url]`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Element {
+    /// The name of the tag, as written.
+    pub name: String,
+    /// The value of tags such as `[url=P]`, without the surrounding quotes.
+    pub value: Option<String>,
+    /// The attributes of tags such as `[list type="a"]`, in the order they're written.
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Node>,
+    /// Whether the tag has a closing tag of its own, rather than being closed by the end of its parent or of the
+    /// input. List items never do.
+    pub closed: bool,
+    /// Where the element is in the input, in bytes, from its opening tag to its closing one, or else to the end of
+    /// its content.
+    pub span: Range<usize>,
+}
+
+/// A named attribute of an [`Element`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Attribute {
+    pub name: String,
+    /// The value of the attribute, without the surrounding quotes.
+    pub value: String,
+}
+
+/// Parses BBCode into a syntax tree, returning the nodes at its top level.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{parse_ast, Node};
+///
+/// let nodes = parse_ast("[quote=mcilloni][b]Hi[/b][/quote] [code]a[b][/code]");
+///
+/// let Node::Element(quote) = &nodes[0] else { panic!("not an element") };
+/// assert_eq!(quote.name, "quote");
+/// assert_eq!(quote.value.as_deref(), Some("mcilloni"));
+/// assert_eq!(quote.span, 0..33);
+///
+/// let Node::Element(bold) = &quote.children[0] else { panic!("not an element") };
+/// assert_eq!(bold.name, "b");
+/// assert!(bold.closed);
+///
+/// // the content of code tags is never parsed
+/// let Node::Element(code) = &nodes[2] else { panic!("not an element") };
+/// assert!(matches!(&code.children[..], [Node::Text { text, .. }] if text == "a[b]"));
+/// ```
+pub fn parse_ast(content: &str) -> Vec<Node> {
+    parse_ast_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`parse_ast`], but the code tags are those of the dialect of the given [`ConvertOptions`].
+pub fn parse_ast_with(content: &str, options: &ConvertOptions) -> Vec<Node> {
+    let inline_code = options.dialect.names().inline_code;
+    let is_code = |name: &str| {
+        name.eq_ignore_ascii_case("code")
+            || inline_code.is_some_and(|code| name.eq_ignore_ascii_case(code))
+    };
+
+    let mut tree = Tree::default();
+
+    for token in tokenize(content) {
+        let raw = &content[token.span.clone()];
+
+        // the content of a code tag is text up to its closing tag; its attributes are within its opening tag, which
+        // already covers them
+        if let Some(code) = tree.open.last().filter(|top| is_code(&top.name)) {
+            match token.kind {
+                TokenKind::Close { name } if name.eq_ignore_ascii_case(&code.name) => {}
+                TokenKind::Attribute { .. } => continue,
+                _ => {
+                    tree.push_text(raw, token.span);
+                    continue;
+                }
+            }
+        }
+
+        match token.kind {
+            TokenKind::Text(text) => tree.push_text(text, token.span),
+            TokenKind::Open { name } => {
+                // list items end where the next one starts
+                if name == "*" && tree.open.last().is_some_and(|top| top.name == "*") {
+                    tree.pop(None);
+                }
+
+                tree.open.push(Element {
+                    name: name.into(),
+                    value: None,
+                    attributes: Vec::new(),
+                    children: Vec::new(),
+                    closed: false,
+                    span: token.span,
+                });
+            }
+            TokenKind::Attribute { name, value } => {
+                let Some(element) = tree.open.last_mut() else {
+                    continue;
+                };
+
+                match name {
+                    Some(name) => element.attributes.push(Attribute {
+                        name: name.into(),
+                        value: value.into(),
+                    }),
+                    None => element.value = Some(value.into()),
+                }
+            }
+            TokenKind::Close { name } => {
+                let Some(depth) = tree
+                    .open
+                    .iter()
+                    .rposition(|element| element.name.eq_ignore_ascii_case(name))
+                else {
+                    tree.push_text(raw, token.span);
+                    continue;
+                };
+
+                while tree.open.len() > depth + 1 {
+                    tree.pop(None);
+                }
+
+                tree.pop(Some(token.span.end));
+            }
+        }
+    }
+
+    while !tree.open.is_empty() {
+        tree.pop(None);
+    }
+
+    tree.top
+}
+
+/// A tree being built, along with the elements still open.
+#[derive(Default)]
+struct Tree {
+    top: Vec<Node>,
+    open: Vec<Element>,
+}
+
+impl Tree {
+    /// The nodes new ones are added to.
+    fn children(&mut self) -> &mut Vec<Node> {
+        match self.open.last_mut() {
+            Some(element) => &mut element.children,
+            None => &mut self.top,
+        }
+    }
+
+    /// Adds some text, merging it with the text right before it, if any.
+    fn push_text(&mut self, text: &str, span: Range<usize>) {
+        if let Some(Node::Text {
+            text: last,
+            span: last_span,
+        }) = self.children().last_mut()
+        {
+            if last_span.end == span.start {
+                last.push_str(text);
+                last_span.end = span.end;
+
+                return;
+            }
+        }
+
+        self.children().push(Node::Text {
+            text: text.into(),
+            span,
+        });
+    }
+
+    /// Closes the innermost open element, either with a closing tag ending at `close_end` or implicitly.
+    fn pop(&mut self, close_end: Option<usize>) {
+        let Some(mut element) = self.open.pop() else {
+            return;
+        };
+
+        element.closed = close_end.is_some();
+        element.span.end = close_end
+            .or_else(|| element.children.last().map(|child| child.span().end))
+            .unwrap_or(element.span.end)
+            .max(element.span.end);
+
+        self.children().push(Node::Element(element));
+    }
+}
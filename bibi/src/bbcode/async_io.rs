@@ -0,0 +1,116 @@
+//! Conversions writing to Tokio's [`AsyncWrite`], for async services that send the output to a socket as it's
+//! converted rather than holding all of it first.
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    bbcode::{stream::StreamConverter, Direction},
+    error::Result,
+    options::ConvertOptions,
+};
+
+/// Same as [`dump_markdown`](crate::dump_markdown), but writes to an [`AsyncWrite`], awaiting the write of each part
+/// of the output as soon as it has been converted. See [`dump_markdown_async_with`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use std::{error::Error, str};
+/// use bibi::dump_markdown_async;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let dump = "[b]First[/b] post\n\n[quote]Second\n\npost[/quote]\n";
+///
+///     let mut writer = Vec::new();
+///     dump_markdown_async(&mut writer, dump).await?;
+///
+///     assert_eq!(str::from_utf8(&writer)?, bibi::to_markdown(dump));
+///
+///     Ok(())
+/// }
+/// ```
+pub async fn dump_markdown_async(writer: impl AsyncWrite + Unpin, content: &str) -> Result<()> {
+    dump_markdown_async_with(writer, content, &ConvertOptions::default()).await
+}
+
+/// Same as [`dump_markdown_async`], but allows tweaking the generated Markdown through the given
+/// [`ConvertOptions`].
+///
+/// The content is split and converted a part at a time like
+/// [`dump_markdown_streaming_with`](crate::dump_markdown_streaming_with) does, so the output is the same as that of
+/// [`dump_markdown_with`](crate::dump_markdown_with), but only a part of it is ever held in memory.
+pub async fn dump_markdown_async_with(
+    writer: impl AsyncWrite + Unpin,
+    content: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    stream(
+        writer,
+        content,
+        StreamConverter::new(Direction::ToMarkdown, options.clone()),
+    )
+    .await
+}
+
+/// Same as [`dump_bbcode`](crate::dump_bbcode), but writes to an [`AsyncWrite`], awaiting the write of each part of
+/// the output as soon as it has been converted. See [`dump_bbcode_async_with`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use std::{error::Error, str};
+/// use bibi::dump_bbcode_async;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<(), Box<dyn Error>> {
+///     let dump = "**First** post\n\n```\nsecond\n\npost\n```\n";
+///
+///     let mut writer = Vec::new();
+///     dump_bbcode_async(&mut writer, dump).await?;
+///
+///     assert_eq!(str::from_utf8(&writer)?, bibi::to_bbcode(dump)?);
+///
+///     Ok(())
+/// }
+/// ```
+pub async fn dump_bbcode_async(writer: impl AsyncWrite + Unpin, contents: &str) -> Result<()> {
+    dump_bbcode_async_with(writer, contents, &ConvertOptions::default()).await
+}
+
+/// Same as [`dump_bbcode_async`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+///
+/// The contents are split and converted a part at a time like
+/// [`dump_bbcode_streaming_with`](crate::dump_bbcode_streaming_with) does, so links can only refer to the
+/// definitions above them.
+pub async fn dump_bbcode_async_with(
+    writer: impl AsyncWrite + Unpin,
+    contents: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    stream(
+        writer,
+        contents,
+        StreamConverter::new(Direction::ToBBCode, options.clone()),
+    )
+    .await
+}
+
+/// Feeds every line of `content` to `converter`, writing what it converts as soon as it's available.
+async fn stream(
+    mut writer: impl AsyncWrite + Unpin,
+    content: &str,
+    mut converter: StreamConverter,
+) -> Result<()> {
+    for line in content.split_inclusive('\n') {
+        let part = converter.push(line)?;
+
+        if !part.is_empty() {
+            writer.write_all(part.as_bytes()).await?;
+        }
+    }
+
+    writer.write_all(converter.finish()?.as_bytes()).await?;
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! Records of every rewrite a conversion performs, so that migrations can account for exactly how their content
+//! has been transformed.
+
+use std::ops::Range;
+
+use serde_json::json;
+
+use crate::{
+    bbcode::{
+        read::markdown_map, span::SpanMap, tokenize, write::bbcode_map, Direction, TokenKind,
+    },
+    error::Result,
+    options::ConvertOptions,
+};
+
+/// A piece of the input that a conversion has rewritten, rather than copied as-is into the output.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Substitution {
+    /// The name of the first BBCode tag involved, as written, if any. Rewrites without tags are escapes, or Markdown
+    /// syntax without a BBCode counterpart.
+    pub tag: Option<String>,
+    /// Where the piece is in the input, in bytes.
+    pub input: Range<usize>,
+    /// Where what it has been rewritten to is in the output, in bytes.
+    pub output: Range<usize>,
+    /// The piece itself.
+    pub original: String,
+    /// What it has been rewritten to, which may be empty if it has been dropped.
+    pub replacement: String,
+}
+
+impl Substitution {
+    /// Serializes the substitution as a JSON object, e.g. as a line of a [JSON Lines](https://jsonlines.org) log.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let (_, audit) = bibi::to_markdown_audited("[b]Hi[/b]");
+    ///
+    /// assert_eq!(
+    ///     audit[0].to_json(),
+    ///     r#"{"input":[0,3],"original":"[b]","output":[0,2],"replacement":"**","tag":"b"}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        json!({
+            "tag": self.tag,
+            "input": [self.input.start, self.input.end],
+            "output": [self.output.start, self.output.end],
+            "original": self.original,
+            "replacement": self.replacement,
+        })
+        .to_string()
+    }
+}
+
+/// The name of the first tag in `bbcode`, if any. Rewritten pieces may hold just part of a tag, e.g. the `[code=`
+/// before the language of a code block, so a piece starting like a tag counts too.
+fn first_tag(bbcode: &str) -> Option<String> {
+    let tag = tokenize(bbcode).find_map(|token| match token.kind {
+        TokenKind::Open { name } | TokenKind::Close { name } => Some(name),
+        _ => None,
+    });
+
+    let partial = || {
+        let rest = bbcode.strip_prefix('[')?;
+        let rest = rest.strip_prefix('/').unwrap_or(rest);
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '*')
+            .unwrap_or(rest.len());
+
+        Some(&rest[..len]).filter(|name| !name.is_empty())
+    };
+
+    tag.or_else(partial).map(str::to_owned)
+}
+
+/// Lists the rewrites `map` records from `input` to `output`, leaving out those that changed nothing.
+fn substitutions(
+    input: &str,
+    output: &str,
+    map: &SpanMap,
+    direction: Direction,
+) -> Vec<Substitution> {
+    map.rewrites()
+        .filter(|(src, dst)| input[src.clone()] != output[dst.clone()])
+        .map(|(src, dst)| {
+            let (original, replacement) = (&input[src.clone()], &output[dst.clone()]);
+
+            let tag = match direction {
+                Direction::ToMarkdown => first_tag(original),
+                Direction::ToBBCode => first_tag(replacement),
+            };
+
+            Substitution {
+                tag,
+                input: src,
+                output: dst,
+                original: original.to_owned(),
+                replacement: replacement.to_owned(),
+            }
+        })
+        .collect()
+}
+
+/// Converts BBCode to Markdown like [`to_markdown`](crate::to_markdown), also returning a [`Substitution`] for
+/// every piece of the input that has been rewritten, in the order they appear in the input. Whatever isn't
+/// covered by any substitution has been copied as-is.
+///
+/// # Examples
+///
+/// ```
+/// let (markdown, audit) = bibi::to_markdown_audited("[b]Hi[/b] there");
+///
+/// assert_eq!(markdown, "**Hi** there");
+///
+/// let rewrites: Vec<_> = audit
+///     .iter()
+///     .map(|sub| (sub.tag.as_deref(), sub.original.as_str(), sub.replacement.as_str()))
+///     .collect();
+///
+/// assert_eq!(rewrites, [(Some("b"), "[b]", "**"), (Some("b"), "[/b]", "**")]);
+/// ```
+pub fn to_markdown_audited(content: &str) -> (String, Vec<Substitution>) {
+    to_markdown_audited_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`to_markdown_audited`], but allows tweaking the generated Markdown through the given
+/// [`ConvertOptions`].
+pub fn to_markdown_audited_with(
+    content: &str,
+    options: &ConvertOptions,
+) -> (String, Vec<Substitution>) {
+    let (markdown, map) = markdown_map(content, options);
+    let audit = substitutions(content, &markdown, &map, Direction::ToMarkdown);
+
+    (markdown, audit)
+}
+
+/// Converts Markdown to BBCode like [`to_bbcode`](crate::to_bbcode), also returning a [`Substitution`] for every
+/// piece of the input that has been rewritten. See [`to_markdown_audited`] for details.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// # fn main() -> bibi::Result<()> {
+/// let (bbcode, audit) = bibi::to_bbcode_audited("*Hi* there")?;
+///
+/// assert_eq!(bbcode, "[cur]Hi[/cur] there\n\n");
+/// assert_eq!(audit[0].tag.as_deref(), Some("cur"));
+/// assert_eq!((audit[0].original.as_str(), audit[0].replacement.as_str()), ("*", "[cur]"));
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+pub fn to_bbcode_audited(contents: &str) -> Result<(String, Vec<Substitution>)> {
+    to_bbcode_audited_with(contents, &ConvertOptions::default())
+}
+
+/// Same as [`to_bbcode_audited`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+pub fn to_bbcode_audited_with(
+    contents: &str,
+    options: &ConvertOptions,
+) -> Result<(String, Vec<Substitution>)> {
+    let (bbcode, map) = bbcode_map(contents, options)?;
+    let audit = substitutions(contents, &bbcode, &map, Direction::ToBBCode);
+
+    Ok((bbcode, audit))
+}
@@ -0,0 +1,164 @@
+//! A reusable converter, holding its options along with whatever they need to be applied.
+
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use crate::{
+    bbcode::{
+        read::{inline_rules, markdown_of, InlineRules},
+        write::to_bbcode_with,
+        Direction,
+    },
+    error::Result,
+    options::ConvertOptions,
+};
+
+/// Converts between BBCode and Markdown with the given [`ConvertOptions`], which are worked out only once when the
+/// converter is created rather than on every conversion.
+///
+/// This is meant for long-running programs converting lots of posts, possibly with a different configuration
+/// for each board: every converter carries its own options and filters, and can be shared between threads.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "nerdz", feature = "phpbb"))] {
+/// use std::error::Error;
+/// use bibi::{ConvertOptions, Converter, Dialect};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let nerdz = Converter::default();
+///     let phpbb = Converter::new(ConvertOptions {
+///         dialect: Dialect::Phpbb,
+///         ..Default::default()
+///     });
+///
+///     assert_eq!(nerdz.to_markdown("[cur]Hi[/cur] [del]there[/del]"), "*Hi* ~~there~~");
+///     assert_eq!(phpbb.to_markdown("[i]Hi[/i] [s]there[/s]"), "*Hi* ~~there~~");
+///
+///     assert_eq!(nerdz.to_bbcode("*Hi*")?, "[cur]Hi[/cur]\n\n");
+///     assert_eq!(phpbb.to_bbcode("*Hi*")?, "[i]Hi[/i]\n\n");
+///
+///     Ok(())
+/// }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone)]
+pub struct Converter {
+    options: ConvertOptions,
+    rules: &'static InlineRules,
+    pre_filters: Vec<Filter>,
+    post_filters: Vec<Filter>,
+}
+
+/// A filter rewriting the whole text of a conversion going in the given direction.
+type Filter = Arc<dyn Fn(&str, Direction) -> String + Send + Sync>;
+
+impl Converter {
+    /// Creates a converter applying the given options.
+    pub fn new(options: ConvertOptions) -> Self {
+        Self {
+            rules: inline_rules(options.dialect),
+            options,
+            pre_filters: vec![],
+            post_filters: vec![],
+        }
+    }
+
+    /// Adds a filter rewriting the input of every conversion before it's converted, e.g. to turn a board's own
+    /// emoticon codes into something the conversion knows about. Filters run in the order they were added, and are
+    /// told which way the conversion goes, so that they can leave alone the inputs they don't care about.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bibi::{Converter, Direction};
+    ///
+    /// let converter = Converter::default()
+    ///     .pre_filter(|text, direction| match direction {
+    ///         Direction::ToMarkdown => text.replace(":lol:", "😂"),
+    ///         Direction::ToBBCode => text.to_owned(),
+    ///     })
+    ///     .post_filter(|text, _| text.replace("http://old.example", "https://new.example"));
+    ///
+    /// assert_eq!(
+    ///     converter.to_markdown(":lol: [url]http://old.example/t/1[/url]"),
+    ///     "😂 [](https://new.example/t/1)"
+    /// );
+    /// ```
+    pub fn pre_filter(
+        mut self,
+        filter: impl Fn(&str, Direction) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.pre_filters.push(Arc::new(filter));
+        self
+    }
+
+    /// Adds a filter rewriting the output of every conversion, e.g. to point links to a board's old domain to the
+    /// new one. Filters run in the order they were added. See [`Converter::pre_filter`] for details.
+    pub fn post_filter(
+        mut self,
+        filter: impl Fn(&str, Direction) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.post_filters.push(Arc::new(filter));
+        self
+    }
+
+    /// The options this converter applies.
+    pub fn options(&self) -> &ConvertOptions {
+        &self.options
+    }
+
+    /// Converts BBCode to Markdown, like [`to_markdown_with`](crate::to_markdown_with) does, running the filters
+    /// around it.
+    pub fn to_markdown(&self, content: &str) -> String {
+        let direction = Direction::ToMarkdown;
+        let content = filter_input(&self.pre_filters, content, direction);
+
+        filter_output(
+            &self.post_filters,
+            markdown_of(&content, &self.options, self.rules),
+            direction,
+        )
+    }
+
+    /// Converts Markdown to BBCode, like [`to_bbcode_with`](crate::to_bbcode_with) does, running the filters
+    /// around it.
+    pub fn to_bbcode(&self, content: &str) -> Result<String> {
+        let direction = Direction::ToBBCode;
+        let content = filter_input(&self.pre_filters, content, direction);
+
+        Ok(filter_output(
+            &self.post_filters,
+            to_bbcode_with(&content, &self.options)?,
+            direction,
+        ))
+    }
+}
+
+fn filter_input<'a>(filters: &[Filter], text: &'a str, direction: Direction) -> Cow<'a, str> {
+    filters.iter().fold(Cow::Borrowed(text), |text, filter| {
+        Cow::Owned(filter(&text, direction))
+    })
+}
+
+fn filter_output(filters: &[Filter], text: String, direction: Direction) -> String {
+    filters
+        .iter()
+        .fold(text, |text, filter| filter(&text, direction))
+}
+
+impl fmt::Debug for Converter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Converter")
+            .field("options", &self.options)
+            .field("pre_filters", &self.pre_filters.len())
+            .field("post_filters", &self.post_filters.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Self::new(ConvertOptions::default())
+    }
+}
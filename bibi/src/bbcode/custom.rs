@@ -0,0 +1,200 @@
+//! Tags the dialects don't know, converted to Markdown as the options tell, after every tag they do know.
+
+use alloc::{borrow::ToOwned, format, string::String, vec, vec::Vec};
+
+use crate::{
+    bbcode::{
+        pattern::{replace_all, PatternSet, Replacement, TagMatch, TagPattern, TagValue},
+        span::{compose, Mapped, SpanMap},
+    },
+    options::{ConvertOptions, CustomTag, MarkdownTarget},
+};
+
+/// How many names the patterns of a single [`PatternSet`] can accept at most.
+const MAX_NAMES: usize = 64;
+
+/// The HTML the tags Markdown has no syntax for become with [`MarkdownTarget::Html`], by name, as templates like
+/// those of [`CustomTag`]s.
+const HTML_FALLBACKS: &[(&str, &str)] = &[
+    ("u", "<ins>{content}</ins>"),
+    ("color", "<span style=\"color: {value}\">{content}</span>"),
+    ("center", "<div align=\"center\">{content}</div>"),
+    ("left", "<div align=\"left\">{content}</div>"),
+    ("right", "<div align=\"right\">{content}</div>"),
+    ("sup", "<sup>{content}</sup>"),
+    ("sub", "<sub>{content}</sub>"),
+    (
+        "spoiler",
+        "<details><summary>Spoiler</summary>{content}</details>",
+    ),
+];
+
+/// What a custom tag is rewritten to.
+#[derive(Clone, Copy)]
+enum Custom<'o> {
+    /// The template of a [`CustomTag`].
+    Template(&'o str),
+    /// One of the [`HTML_FALLBACKS`], whose value is escaped since it ends up within an attribute.
+    Html(&'static str),
+    /// A link to the profile of a user, given the URL names are appended to.
+    User(&'o str),
+}
+
+impl Custom<'_> {
+    fn replacement(&self, m: &TagMatch) -> Replacement {
+        match *self {
+            Custom::Template(template) => fill(template, m.value.unwrap_or_default()),
+            Custom::Html(template) => fill(template, &escape_html(m.value.unwrap_or_default())),
+            Custom::User(url) => Replacement {
+                open: "[".into(),
+                close: format!("]({url}{})", m.content.replace(' ', "%20")).into(),
+                decode: false,
+            },
+        }
+    }
+}
+
+/// Escapes the characters that are special within HTML, including attribute values.
+pub(super) fn escape_html(value: &str) -> String {
+    value
+        .chars()
+        .fold(String::with_capacity(value.len()), |mut acc, c| {
+            match c {
+                '&' => acc.push_str("&amp;"),
+                '<' => acc.push_str("&lt;"),
+                '>' => acc.push_str("&gt;"),
+                '"' => acc.push_str("&quot;"),
+                '\'' => acc.push_str("&#39;"),
+                c => acc.push(c),
+            }
+
+            acc
+        })
+}
+
+/// Fills in the value of a template, splitting it around its content.
+fn fill(template: &str, value: &str) -> Replacement {
+    let template = template.replace("{value}", value);
+
+    let (open, close) = match template.split_once("{content}") {
+        Some((open, close)) => (open.to_owned(), close.to_owned()),
+        None => (template, String::new()),
+    };
+
+    Replacement {
+        open: open.into(),
+        close: close.into(),
+        decode: false,
+    }
+}
+
+/// The names of a custom tag, along with what it's rewritten to.
+fn custom_tags(options: &ConvertOptions) -> impl Iterator<Item = (Vec<String>, Custom<'_>)> {
+    let tags = options.custom_tags.iter().map(
+        |CustomTag {
+             name,
+             aliases,
+             markdown,
+         }| {
+            let names = Some(name).into_iter().chain(aliases).cloned().collect();
+
+            (names, Custom::Template(markdown))
+        },
+    );
+
+    let user = options
+        .user_url
+        .as_deref()
+        .map(|url| (vec!["user".to_owned()], Custom::User(url)));
+
+    // custom tags take precedence over the fallbacks with the same name
+    let taken = |name: &str| {
+        options.custom_tags.iter().any(|tag| {
+            Some(&tag.name)
+                .into_iter()
+                .chain(&tag.aliases)
+                .any(|n| n.eq_ignore_ascii_case(name))
+        })
+    };
+
+    let fallbacks = HTML_FALLBACKS
+        .iter()
+        .filter(move |_| options.target == MarkdownTarget::Html)
+        .filter(move |(name, _)| !taken(name))
+        .map(|&(name, template)| (vec![name.to_owned()], Custom::Html(template)));
+
+    tags.chain(user).chain(fallbacks)
+}
+
+/// Tells whether `name` is one of the custom tags of `options`.
+pub(super) fn is_custom(name: &str, options: &ConvertOptions) -> bool {
+    custom_tags(options).any(|(names, _)| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+}
+
+/// The custom tags of some options, ready to be matched.
+pub(super) struct CustomRules<'o>(Vec<PatternSet<Custom<'o>>>);
+
+impl<'o> CustomRules<'o> {
+    pub(super) fn new(options: &'o ConvertOptions) -> Self {
+        let mut sets = vec![];
+        let mut patterns = vec![];
+        let mut names = 0;
+
+        for (tag_names, custom) in custom_tags(options) {
+            // a tag with too many names gets a set of its own, where the names past the limit are left out
+            if names + tag_names.len() > MAX_NAMES && !patterns.is_empty() {
+                sets.push(PatternSet::new(patterns));
+                patterns = vec![];
+                names = 0;
+            }
+
+            let tag_names: Vec<_> = tag_names.into_iter().take(MAX_NAMES).collect();
+            names += tag_names.len();
+
+            // tags with a value go first, so that `[tag=value]` isn't left to the pattern of `[tag]`
+            patterns.push((TagPattern::single(tag_names.clone(), TagValue::Any), custom));
+            patterns.push((TagPattern::single(tag_names, TagValue::Absent), custom));
+        }
+
+        if !patterns.is_empty() {
+            sets.push(PatternSet::new(patterns));
+        }
+
+        Self(sets)
+    }
+
+    /// Rewrites the custom tags in `text`, returning `None` if it has none.
+    pub(super) fn apply(&self, text: &str, track: bool) -> Option<Mapped> {
+        let mut ret: Option<Mapped> = None;
+
+        for set in &self.0 {
+            let current = ret.as_ref().map_or(text, |(text, _)| text);
+
+            let Some((replaced, map)) =
+                replace_all(current, set, track, |custom, m| Some(custom.replacement(m)))
+            else {
+                continue;
+            };
+
+            ret = Some(match ret {
+                Some((_, prev)) => (replaced, compose(prev, map)),
+                None => (replaced, map),
+            });
+        }
+
+        ret
+    }
+}
+
+/// Rewrites the custom tags in `text`, if any, composing the map of the rewrite with `map`.
+pub(super) fn replace_custom(
+    text: String,
+    map: Option<SpanMap>,
+    rules: &CustomRules,
+    track: bool,
+) -> Mapped {
+    match rules.apply(&text, track) {
+        Some((replaced, next)) => (replaced, compose(map, next)),
+        None => (text, map),
+    }
+}
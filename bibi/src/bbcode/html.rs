@@ -0,0 +1,222 @@
+//! A minimal reader of the HTML found in Markdown, splitting it into tags and the text between them, for the few
+//! tags BBCode has a counterpart of. It's no HTML parser: anything that doesn't look like a tag is text.
+
+use std::{borrow::Cow, iter};
+
+/// A piece of HTML, as returned by [`pieces`].
+pub(super) enum Piece<'h> {
+    /// Text between tags, with its character references still to be decoded, see [`unescape`].
+    Text(&'h str),
+    /// An opening, closing or self-closing tag.
+    Tag(HtmlTag<'h>),
+}
+
+/// A tag such as `<a href="https://nerdz.eu">` or `</b>`.
+pub(super) struct HtmlTag<'h> {
+    /// The name of the tag, as written.
+    pub name: &'h str,
+    /// Whether it's a closing tag, such as `</b>`.
+    pub closing: bool,
+    /// What follows the name, up to the end of the tag.
+    attributes: &'h str,
+}
+
+impl<'h> HtmlTag<'h> {
+    /// Whether the tag has the given name, which is case-insensitive.
+    pub fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    /// The decoded value of the attribute with the given name, if the tag has it.
+    pub fn attribute(&self, name: &str) -> Option<Cow<'h, str>> {
+        let mut rest = self.attributes;
+
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+
+            if rest.is_empty() {
+                return None;
+            }
+
+            let len = rest
+                .find(|c: char| c.is_ascii_whitespace() || matches!(c, '=' | '/'))
+                .unwrap_or(rest.len());
+            let (key, after) = rest.split_at(len);
+            let after = after.trim_start();
+
+            // attributes without a value, such as `disabled`
+            let Some(after) = after.strip_prefix('=') else {
+                if key.eq_ignore_ascii_case(name) {
+                    return Some(Cow::Borrowed(""));
+                }
+
+                rest = after;
+                continue;
+            };
+
+            let after = after.trim_start();
+
+            let (value, next) = match after.chars().next()? {
+                quote @ ('"' | '\'') => {
+                    let end = after[1..].find(quote)? + 1;
+
+                    (&after[1..end], &after[end + 1..])
+                }
+                _ => after.split_at(
+                    after
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(after.len()),
+                ),
+            };
+
+            if key.eq_ignore_ascii_case(name) {
+                return Some(unescape(value));
+            }
+
+            rest = next;
+        }
+    }
+}
+
+/// Reads a tag at the start of `html`, returning it along with its length, or `None` if it doesn't start with one.
+fn tag(html: &str) -> Option<(HtmlTag<'_>, usize)> {
+    let inner = html.strip_prefix('<')?;
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner),
+        None => (false, inner),
+    };
+
+    if !inner.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let name_len = inner
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .unwrap_or(inner.len());
+    let (name, after) = inner.split_at(name_len);
+
+    if !after.starts_with(|c: char| c.is_ascii_whitespace() || matches!(c, '/' | '>')) {
+        return None;
+    }
+
+    // the tag ends at the first `>` outside of quotes
+    let mut quote = None;
+    let end = after.char_indices().find_map(|(pos, c)| {
+        match (quote, c) {
+            (None, '>') => return Some(pos),
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            _ => {}
+        }
+
+        None
+    })?;
+
+    let tag = HtmlTag {
+        name,
+        closing,
+        attributes: &after[..end],
+    };
+
+    Some((tag, html.len() - after.len() + end + 1))
+}
+
+/// Splits `html` into tags and the text between them. Comments, declarations and anything else starting with `<`
+/// that isn't a tag are text.
+pub(super) fn pieces(html: &str) -> impl Iterator<Item = Piece<'_>> {
+    // no tag starts after the last `>`, which spares looking for tags in text full of `<`
+    let tags_end = html.rfind('>').unwrap_or(0);
+    let mut pos = 0;
+
+    iter::from_fn(move || {
+        let rest = &html[pos..];
+
+        if rest.is_empty() {
+            return None;
+        }
+
+        if let Some((tag, len)) = tag(rest) {
+            pos += len;
+
+            return Some(Piece::Tag(tag));
+        }
+
+        // the text goes on up to the next tag, past the `<` it may start with
+        let end = html
+            .get(pos..tags_end)
+            .into_iter()
+            .flat_map(|text| text.match_indices('<'))
+            .map(|(at, _)| pos + at)
+            .find(|&at| at > pos && tag(&html[at..]).is_some())
+            .unwrap_or(html.len());
+
+        let text = &html[pos..end];
+        pos = end;
+
+        Some(Piece::Text(text))
+    })
+}
+
+/// How long a character reference can be at most, `&` and `;` included.
+const MAX_REFERENCE: usize = 32;
+
+/// Decodes the character references of some HTML text, such as `&amp;` or `&#128512;`. Only the named references
+/// of the characters HTML reserves are known; the others are left as they are.
+pub(super) fn unescape(text: &str) -> Cow<'_, str> {
+    const NAMED: &[(&str, char)] = &[
+        ("amp", '&'),
+        ("lt", '<'),
+        ("gt", '>'),
+        ("quot", '"'),
+        ("apos", '\''),
+        ("nbsp", '\u{a0}'),
+    ];
+
+    if !text.contains('&') {
+        return text.into();
+    }
+
+    let mut ret = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        ret.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // references are short, so the `;` ending them is never far
+        let end = rest.bytes().take(MAX_REFERENCE).position(|b| b == b';');
+
+        let decoded = end.and_then(|end| {
+            let name = &rest[1..end];
+
+            let c = match name.strip_prefix('#') {
+                Some(code) => match code.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code.parse().ok(),
+                }
+                .and_then(char::from_u32),
+                None => NAMED
+                    .iter()
+                    .find(|(named, _)| *named == name)
+                    .map(|&(_, c)| c),
+            }?;
+
+            Some((c, end + 1))
+        });
+
+        match decoded {
+            Some((c, len)) => {
+                ret.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                ret.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    ret.push_str(rest);
+
+    ret.into()
+}
@@ -0,0 +1,242 @@
+//! Checks of converted text against the limits of the platform it's meant for, so that users find out a post is
+//! too long before pasting it rather than after.
+
+use std::fmt;
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::bbcode::{markdown_options, tokenize, Direction, Token, TokenKind};
+
+/// What a platform accepts at most, where `None` stands for no known limit. Lengths are in characters, i.e.
+/// Unicode scalar values.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{check_limits, to_markdown, Direction, LimitKind, Limits};
+///
+/// let limits = Limits {
+///     post_length: Some(20),
+///     quote_depth: Some(1),
+///     ..Default::default()
+/// };
+///
+/// let markdown = to_markdown("[quote][quote]Hi[/quote] there[/quote]\n");
+/// let exceeded = check_limits(&markdown, Direction::ToMarkdown, &limits);
+///
+/// assert_eq!(exceeded.len(), 1);
+/// assert_eq!(exceeded[0].kind, LimitKind::QuoteDepth);
+/// assert_eq!(exceeded[0].to_string(), "byte 2: quotes are nested 2 deep, more than 1");
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Limits {
+    /// How long a post can be.
+    pub post_length: Option<usize>,
+    /// How long the content of a code block can be.
+    pub code_block_length: Option<usize>,
+    /// How deeply quotes can be nested, where 1 means that quotes can't be nested at all.
+    pub quote_depth: Option<usize>,
+}
+
+/// Which limit some text exceeds.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum LimitKind {
+    /// [`Limits::post_length`].
+    PostLength,
+    /// [`Limits::code_block_length`].
+    CodeBlockLength,
+    /// [`Limits::quote_depth`].
+    QuoteDepth,
+}
+
+/// A limit some text exceeds, as found by [`check_limits`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct LimitExceeded {
+    /// Which limit is exceeded.
+    pub kind: LimitKind,
+    /// The byte offset in the text where it's exceeded: the first character past the limit for the length of the
+    /// post, or the start of the code block or of the quote that's too deep otherwise.
+    pub offset: usize,
+    /// The length or depth of what exceeds the limit.
+    pub actual: usize,
+    /// The limit.
+    pub limit: usize,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LimitKind::*;
+
+        let Self {
+            kind,
+            offset,
+            actual,
+            limit,
+        } = self;
+
+        match kind {
+            PostLength => write!(
+                f,
+                "byte {offset}: the post is {actual} characters long, more than {limit}"
+            ),
+            CodeBlockLength => write!(
+                f,
+                "byte {offset}: the code block is {actual} characters long, more than {limit}"
+            ),
+            QuoteDepth => write!(
+                f,
+                "byte {offset}: quotes are nested {actual} deep, more than {limit}"
+            ),
+        }
+    }
+}
+
+/// Tracks how deeply quotes are nested, reporting each quote that first goes past the limit along with the deepest
+/// nesting found within it.
+struct QuoteDepth {
+    limit: Option<usize>,
+    depth: usize,
+    /// The index in the report of the quote past the limit that's still open, if any.
+    exceeded: Option<usize>,
+}
+
+impl QuoteDepth {
+    fn new(limits: &Limits) -> Self {
+        Self {
+            limit: limits.quote_depth,
+            depth: 0,
+            exceeded: None,
+        }
+    }
+
+    fn open(&mut self, offset: usize, ret: &mut Vec<LimitExceeded>) {
+        self.depth += 1;
+
+        let Some(limit) = self.limit.filter(|&limit| self.depth > limit) else {
+            return;
+        };
+
+        match self.exceeded {
+            Some(idx) => ret[idx].actual = ret[idx].actual.max(self.depth),
+            None => {
+                self.exceeded = Some(ret.len());
+
+                ret.push(LimitExceeded {
+                    kind: LimitKind::QuoteDepth,
+                    offset,
+                    actual: self.depth,
+                    limit,
+                });
+            }
+        }
+    }
+
+    fn close(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+
+        if self.limit.is_some_and(|limit| self.depth <= limit) {
+            self.exceeded = None;
+        }
+    }
+}
+
+fn check_code_block(start: usize, len: usize, limits: &Limits, ret: &mut Vec<LimitExceeded>) {
+    if let Some(limit) = limits.code_block_length.filter(|&limit| len > limit) {
+        ret.push(LimitExceeded {
+            kind: LimitKind::CodeBlockLength,
+            offset: start,
+            actual: len,
+            limit,
+        });
+    }
+}
+
+fn check_markdown(text: &str, limits: &Limits, ret: &mut Vec<LimitExceeded>) {
+    let mut quotes = QuoteDepth::new(limits);
+    let mut code: Option<(usize, String)> = None;
+
+    for (event, range) in Parser::new_ext(text, markdown_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::BlockQuote) => quotes.open(range.start, ret),
+            Event::End(Tag::BlockQuote) => quotes.close(),
+            Event::Start(Tag::CodeBlock(_)) => code = Some((range.start, String::new())),
+            Event::Text(text) => {
+                if let Some((_, content)) = &mut code {
+                    content.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some((start, content)) = code.take() {
+                    let len = content.trim_matches('\n').chars().count();
+
+                    check_code_block(start, len, limits, ret);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_bbcode(text: &str, limits: &Limits, ret: &mut Vec<LimitExceeded>) {
+    let mut quotes = QuoteDepth::new(limits);
+    let mut code: Option<(usize, usize)> = None;
+
+    for Token { kind, span } in tokenize(text) {
+        match (kind, code) {
+            (TokenKind::Close { name }, Some((start, end)))
+                if name.eq_ignore_ascii_case("code") =>
+            {
+                let len = text[end..span.start].trim_matches('\n').chars().count();
+
+                check_code_block(start, len, limits, ret);
+                code = None;
+            }
+            // anything within a code block is copied verbatim
+            (_, Some(_)) => {}
+            (TokenKind::Open { name }, None) if name.eq_ignore_ascii_case("code") => {
+                code = Some((span.start, span.end));
+            }
+            (TokenKind::Open { name }, None) if name.eq_ignore_ascii_case("quote") => {
+                quotes.open(span.start, ret)
+            }
+            (TokenKind::Close { name }, None) if name.eq_ignore_ascii_case("quote") => {
+                quotes.close()
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the output of a conversion in the given direction, i.e. Markdown for [`Direction::ToMarkdown`] and BBCode
+/// for [`Direction::ToBBCode`], against the given limits, returning those it exceeds in the order of their offsets.
+pub fn check_limits(text: &str, direction: Direction, limits: &Limits) -> Vec<LimitExceeded> {
+    let mut ret = vec![];
+
+    if let Some(limit) = limits.post_length {
+        let len = text.chars().count();
+
+        if len > limit {
+            let offset = text
+                .char_indices()
+                .nth(limit)
+                .map_or(text.len(), |(pos, _)| pos);
+
+            ret.push(LimitExceeded {
+                kind: LimitKind::PostLength,
+                offset,
+                actual: len,
+                limit,
+            });
+        }
+    }
+
+    match direction {
+        Direction::ToMarkdown => check_markdown(text, limits, &mut ret),
+        Direction::ToBBCode => check_bbcode(text, limits, &mut ret),
+    }
+
+    ret.sort_by_key(|exceeded| exceeded.offset);
+
+    ret
+}
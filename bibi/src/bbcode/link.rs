@@ -0,0 +1,366 @@
+//! Sanitization of where links and images point to, as [`LinkPolicy`] tells, both for the `[url]` and `[img]` tags
+//! of the BBCode read and for the links and images of the Markdown read.
+
+use alloc::{borrow::Cow, format, string::String, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    bbcode::{
+        decode_attribute, encode_attribute,
+        span::{Mapped, Rewriter, SpanMap},
+        synonym::synonym_of,
+        tokenize, Token, TokenKind,
+    },
+    options::{ConvertOptions, LinkPolicy},
+};
+
+/// The scheme of a destination, as [`scheme`] reads it.
+enum Scheme {
+    /// A well-formed scheme, lowercase.
+    Named(String),
+    /// Something ending with `:` that isn't a scheme, such as `1x:`.
+    Unreadable,
+    /// No scheme at all, i.e. a relative destination.
+    Relative,
+}
+
+/// Decodes the character reference at the start of `text`, returning the character along with the length of the
+/// reference. Only those that could hide a scheme are known: numeric ones, whose `;` browsers don't require, and
+/// the named ones of the characters ending or splitting a scheme.
+fn reference(text: &str) -> Option<(char, usize)> {
+    const NAMED: &[(&str, char)] = &[("&colon;", ':'), ("&tab;", '\t'), ("&newline;", '\n')];
+
+    if let Some(&(name, c)) = NAMED.iter().find(|(name, _)| {
+        text.get(..name.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(name))
+    }) {
+        return Some((c, name.len()));
+    }
+
+    let code = text.strip_prefix("&#")?;
+    let (digits, radix) = match code.strip_prefix(['x', 'X']) {
+        Some(hex) => (hex, 16),
+        None => (code, 10),
+    };
+
+    let len = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    let c = u32::from_str_radix(&digits[..len], radix)
+        .ok()
+        .and_then(char::from_u32)?;
+
+    let semicolon = usize::from(digits[len..].starts_with(';'));
+
+    Some((c, text.len() - digits.len() + len + semicolon))
+}
+
+/// Reads the scheme of `dest` as a browser would, i.e. regardless of case, of character references, of the
+/// backslashes escaping punctuation in Markdown and of any whitespace or control character.
+fn scheme(dest: &str) -> Scheme {
+    let mut name = String::new();
+    let mut rest = dest;
+
+    while let Some(c) = rest.chars().next() {
+        let (c, len) = match c {
+            '&' => reference(rest).unwrap_or(('&', 1)),
+            '\\' if rest[1..].starts_with(|c: char| c.is_ascii_punctuation()) => {
+                rest = &rest[1..];
+
+                continue;
+            }
+            _ => (c, c.len_utf8()),
+        };
+
+        rest = &rest[len..];
+
+        match c {
+            ':' => return scheme_named(name),
+            '/' | '?' | '#' => return Scheme::Relative,
+            _ if c.is_ascii_whitespace() || c.is_control() => {}
+            _ => name.push(c.to_ascii_lowercase()),
+        }
+    }
+
+    Scheme::Relative
+}
+
+/// Checks that what comes before the first `:` of a destination is a scheme.
+fn scheme_named(name: String) -> Scheme {
+    let well_formed = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+
+    match well_formed {
+        true => Scheme::Named(name),
+        false => Scheme::Unreadable,
+    }
+}
+
+/// Removes the `.` and `..` segments of a path, as RFC 3986 does when resolving relative references.
+fn remove_dot_segments(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').collect();
+    let last = segments.len() - 1;
+    let mut ret: Vec<&str> = vec![];
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        match segment {
+            "." | ".." => {
+                // the first segment of an absolute path is the empty one before its `/`
+                if segment == ".." && ret.len() > 1 {
+                    ret.pop();
+                }
+
+                // a path ending with a dot segment still ends with a directory
+                if i == last {
+                    ret.push("");
+                }
+            }
+            _ => ret.push(segment),
+        }
+    }
+
+    ret.join("/")
+}
+
+/// Resolves the relative reference `rel` against `base`, as RFC 3986 does for the references found in practice.
+fn resolve(base: &str, rel: &str) -> String {
+    let base = base.split_once('#').map_or(base, |(base, _)| base);
+
+    let scheme_len = base.find(':').map_or(0, |pos| pos + 1);
+    let authority_len = base[scheme_len..]
+        .strip_prefix("//")
+        .map_or(0, |rest| 2 + rest.find(['/', '?']).unwrap_or(rest.len()));
+
+    let origin = &base[..scheme_len + authority_len];
+    let path = base[origin.len()..]
+        .split_once('?')
+        .map_or(&base[origin.len()..], |(path, _)| path);
+
+    // the query and fragment of the reference are kept as they are
+    let path_len = rel.find(['?', '#']).unwrap_or(rel.len());
+    let (rel_path, suffix) = rel.split_at(path_len);
+
+    match rel_path {
+        _ if rel.starts_with("//") => format!("{}{rel}", &base[..scheme_len]),
+        "" if rel.is_empty() || rel.starts_with('#') => format!("{base}{rel}"),
+        "" => format!("{origin}{path}{rel}"),
+        _ if rel_path.starts_with('/') => {
+            format!("{origin}{}{suffix}", remove_dot_segments(rel_path))
+        }
+        _ => {
+            let dir = match path.rfind('/') {
+                Some(pos) => &path[..=pos],
+                None if authority_len > 0 => "/",
+                None => "",
+            };
+
+            format!(
+                "{origin}{}{suffix}",
+                remove_dot_segments(&format!("{dir}{rel_path}"))
+            )
+        }
+    }
+}
+
+impl LinkPolicy {
+    /// Where a link or an image pointing to `dest` should point to instead, or `None` if it's not allowed and should
+    /// be dropped. Relative destinations are resolved against [`LinkPolicy::base_url`], if any.
+    ///
+    /// Schemes are read the way browsers read them, so that `JavaScript:`, `java&#115;cript:` or `java\tscript:`
+    /// are no way around [`LinkPolicy::schemes`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bibi::LinkPolicy;
+    ///
+    /// let policy = LinkPolicy {
+    ///     base_url: Some("https://nerdz.eu/pages/faq.php".to_owned()),
+    ///     ..LinkPolicy::untrusted()
+    /// };
+    ///
+    /// assert_eq!(policy.apply("java&#115;cript:alert(1)"), None);
+    /// assert_eq!(policy.apply("https://nerdz.eu/").as_deref(), Some("https://nerdz.eu/"));
+    /// assert_eq!(policy.apply("../rules#top").as_deref(), Some("https://nerdz.eu/rules#top"));
+    /// assert_eq!(policy.apply("//nerdz.eu").as_deref(), Some("https://nerdz.eu"));
+    /// ```
+    pub fn apply<'d>(&self, dest: &'d str) -> Option<Cow<'d, str>> {
+        match scheme(dest) {
+            Scheme::Named(name) => self.allows(&name).then_some(dest.into()),
+            Scheme::Unreadable => self.schemes.is_none().then_some(dest.into()),
+            Scheme::Relative => match &self.base_url {
+                Some(base) => Some(resolve(base, dest.trim()).into()),
+                None => Some(dest.into()),
+            },
+        }
+    }
+
+    fn allows(&self, scheme: &str) -> bool {
+        self.schemes.as_ref().is_none_or(|schemes| {
+            schemes
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(scheme))
+        })
+    }
+
+    /// Whether the policy restricts schemes, in which case the content is untrusted and no raw HTML is let through.
+    pub(crate) fn is_restrictive(&self) -> bool {
+        self.schemes.is_some()
+    }
+
+    /// Whether the policy leaves every destination as it is.
+    pub(crate) fn allows_any(&self) -> bool {
+        self.schemes.is_none() && self.base_url.is_none()
+    }
+}
+
+/// A `[url]` or `[img]` tag along with the tag closing it.
+struct LinkTag<'t> {
+    name: &'t str,
+    is_img: bool,
+    open: Range<usize>,
+    /// The value of the tag, e.g. `P` in `[url=P]`, if it has one.
+    value: Option<&'t str>,
+    /// Whether the tag has any other attribute.
+    attributes: bool,
+    /// Whether there's nothing but text between the tags.
+    plain: bool,
+    close: Range<usize>,
+}
+
+/// Finds the `[url]` and `[img]` tags of `text` that are closed, in the order they're opened.
+fn link_tags<'t>(text: &'t str, options: &ConvertOptions) -> Vec<LinkTag<'t>> {
+    let mut ret = vec![];
+    // the tags still open, along with how many tokens were read when they were
+    let mut open: Vec<(LinkTag<'t>, usize)> = vec![];
+    let mut read = 0;
+
+    for Token { kind, span } in tokenize(text) {
+        read += 1;
+
+        let (name, is_close) = match kind {
+            TokenKind::Attribute { name, value } => {
+                if let Some((tag, _)) = open.last_mut().filter(|(tag, _)| tag.open.end >= span.end)
+                {
+                    match name {
+                        None => tag.value = Some(value),
+                        Some(_) => tag.attributes = true,
+                    }
+
+                    // attributes don't count as what's between the tags
+                    read -= 1;
+                }
+
+                continue;
+            }
+            TokenKind::Open { name } => (name, false),
+            TokenKind::Close { name } => (name, true),
+            TokenKind::Text(_) => continue,
+        };
+
+        let canonical = synonym_of(name, options).unwrap_or(name);
+        let is_img = canonical.eq_ignore_ascii_case("img");
+
+        if !(is_img || canonical.eq_ignore_ascii_case("url")) {
+            continue;
+        }
+
+        if !is_close {
+            let tag = LinkTag {
+                name,
+                is_img,
+                open: span,
+                value: None,
+                attributes: false,
+                plain: false,
+                close: 0..0,
+            };
+
+            open.push((tag, read));
+
+            continue;
+        }
+
+        if let Some(pos) = open.iter().rposition(|(tag, _)| tag.is_img == is_img) {
+            let (mut tag, opened) = open.remove(pos);
+
+            // only the text between the tags, if any, was read since the opening one
+            tag.plain = read - opened <= 2;
+            tag.close = span;
+
+            ret.push(tag);
+        }
+    }
+
+    ret.sort_by_key(|tag| tag.open.start);
+
+    ret
+}
+
+/// Applies [`ConvertOptions::link_policy`] to the `[url]` and `[img]` tags of some BBCode. Links that aren't
+/// allowed lose their tags but keep their text, while images that aren't allowed are dropped altogether.
+pub(super) fn sanitize_links(text: String, options: &ConvertOptions, track: bool) -> Mapped {
+    let policy = &options.link_policy;
+
+    if policy.allows_any() || !text.contains('[') {
+        let len = text.len();
+
+        return (text, track.then(|| SpanMap::identity(len)));
+    }
+
+    // what replaces which part of the text
+    let mut edits: Vec<(Range<usize>, String)> = vec![];
+
+    for tag in link_tags(&text, options) {
+        let content = &text[tag.open.end..tag.close.start];
+
+        let dest = match (tag.is_img, tag.value) {
+            (true, _) if tag.attributes || tag.value.is_some() => continue,
+            (false, Some(value)) => decode_attribute(value),
+            _ if !tag.plain => continue,
+            _ => content.into(),
+        };
+
+        match (policy.apply(&dest), tag.value) {
+            (None, _) if tag.is_img => edits.push((tag.open.start..tag.close.end, String::new())),
+            (None, _) => {
+                edits.push((tag.open, String::new()));
+                edits.push((tag.close, String::new()));
+            }
+            (Some(Cow::Owned(resolved)), Some(_)) => edits.push((
+                tag.open,
+                format!("[{}={}]", tag.name, encode_attribute(&resolved)),
+            )),
+            (Some(Cow::Owned(resolved)), None) => {
+                edits.push((tag.open.end..tag.close.start, resolved))
+            }
+            (Some(Cow::Borrowed(_)), _) => {}
+        }
+    }
+
+    if edits.is_empty() {
+        let len = text.len();
+
+        return (text, track.then(|| SpanMap::identity(len)));
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+
+    let mut ret = Rewriter::new(&text, track);
+    let mut copied = 0;
+
+    ret.reserve(text.len());
+
+    for (range, replacement) in edits {
+        ret.copy(&text[copied..range.start]);
+        ret.push_str(&replacement);
+
+        copied = range.end;
+    }
+
+    ret.copy(&text[copied..]);
+
+    ret.finish()
+}
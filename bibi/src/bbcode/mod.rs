@@ -1,5 +1,205 @@
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::{String, ToString},
+    vec::Vec,
+};
+
+#[cfg(feature = "std")]
+use pulldown_cmark::Options;
+
+use span::{Rewriter, SpanMap};
+
+mod ast;
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(feature = "std")]
+mod audit;
+#[cfg(feature = "std")]
+mod converter;
+mod custom;
+#[cfg(feature = "std")]
+mod html;
+#[cfg(feature = "std")]
+mod limits;
+mod link;
+#[cfg(feature = "std")]
+mod parser;
+mod pattern;
 mod read;
+mod recover;
+mod reference;
+mod report;
+#[cfg(feature = "std")]
+mod shared;
+mod signature;
+mod smiley;
+#[cfg(feature = "std")]
+mod snapshot;
+mod span;
+#[cfg(feature = "std")]
+mod stream;
+mod synonym;
+mod tags;
+mod token;
+#[cfg(feature = "std")]
 mod write;
 
-pub use read::dump_markdown;
-pub use write::dump_bbcode;
+pub use tags::{supported_tags, Dialect, Direction, Fidelity, TagInfo};
+
+#[cfg(feature = "std")]
+pub(crate) use tags::ListSyntax;
+pub(crate) use tags::TagNames;
+
+pub use ast::{parse_ast, parse_ast_with, Attribute, Element, Node};
+#[cfg(feature = "tokio")]
+pub use async_io::{
+    dump_bbcode_async, dump_bbcode_async_with, dump_markdown_async, dump_markdown_async_with,
+};
+#[cfg(feature = "std")]
+pub use audit::{
+    to_bbcode_audited, to_bbcode_audited_with, to_markdown_audited, to_markdown_audited_with,
+    Substitution,
+};
+#[cfg(feature = "std")]
+pub use converter::Converter;
+#[cfg(feature = "std")]
+pub use limits::{check_limits, LimitExceeded, LimitKind, Limits};
+#[cfg(feature = "std")]
+pub use parser::BbcodeParser;
+#[cfg(feature = "std")]
+pub use read::{dump_markdown, dump_markdown_with};
+pub use read::{
+    dump_markdown_fmt, dump_markdown_fmt_with, to_markdown, to_markdown_inline,
+    to_markdown_inline_with, to_markdown_spans, to_markdown_spans_with, to_markdown_with,
+};
+pub use report::{
+    convert_with_report, convert_with_report_with, count_tags, count_tags_with, to_markdown_strict,
+    to_markdown_strict_with, Diagnostic, DiagnosticKind,
+};
+#[cfg(feature = "std")]
+pub use shared::{BlockWriter, SharedWriter};
+pub use signature::{to_markdown_post, to_markdown_post_with, Post};
+#[cfg(feature = "std")]
+pub use snapshot::Snapshot;
+pub use span::Spans;
+#[cfg(feature = "std")]
+pub use stream::{
+    dump_bbcode_streaming, dump_bbcode_streaming_with, dump_markdown_streaming,
+    dump_markdown_streaming_with, StreamConverter,
+};
+pub use token::{tokenize, Token, TokenKind};
+#[cfg(feature = "std")]
+pub use write::{
+    dump_bbcode, dump_bbcode_fmt, dump_bbcode_fmt_with, dump_bbcode_with, to_bbcode,
+    to_bbcode_inline, to_bbcode_inline_with, to_bbcode_spans, to_bbcode_spans_with, to_bbcode_with,
+    write_bbcode, write_bbcode_fmt, write_bbcode_fmt_with, write_bbcode_with,
+};
+
+pub(crate) const DEFAULT_ANON_CODELANG: &str = "code";
+pub(crate) const DEFAULT_ANON_ICODELANG: &str = "inline";
+
+/// The Markdown extensions understood when parsing Markdown, i.e. strikethrough, footnotes and task lists, to parse
+/// Markdown with [`pulldown_cmark`] the same way the conversions do.
+///
+/// # Examples
+///
+/// ```
+/// use pulldown_cmark::{html, Parser};
+///
+/// let mut out = String::new();
+/// html::push_html(&mut out, Parser::new_ext("Hi ~~there~~[^1]", bibi::markdown_options()));
+///
+/// assert!(out.starts_with("<p>Hi <del>there</del><sup class=\"footnote-reference\">"));
+/// ```
+#[cfg(feature = "std")]
+pub fn markdown_options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    options
+}
+
+/// Rewrites every line break in `text` so that it uses the given terminator.
+pub(crate) fn normalize_newlines<'a>(text: &'a str, ending: &str) -> Cow<'a, str> {
+    if !text.contains('\n') || (ending == "\n" && !text.contains('\r')) {
+        return text.into();
+    }
+
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join(ending)
+        .into()
+}
+
+/// Joins the lines of `text` with single spaces, for conversions of inline snippets.
+pub(crate) fn single_line(text: &str) -> Cow<'_, str> {
+    if !text.contains('\n') {
+        return text.into();
+    }
+
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .into()
+}
+
+/// The [`SpanMap`] of what [`normalize_newlines`] does to `text`.
+pub(crate) fn map_newlines(text: &str, ending: &str) -> SpanMap {
+    let mut ret = Rewriter::new(text, true);
+
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            ret.push_str(ending);
+        }
+
+        ret.copy(line.strip_suffix('\r').unwrap_or(line));
+    }
+
+    ret.finish().1.expect("spans are always tracked here")
+}
+
+/// Characters that can't appear verbatim inside a tag value without breaking the tag, alongside their
+/// percent-encoded form. `=` is deliberately missing: values always extend up to the closing `]`, so it's
+/// harmless, and encoding it would mangle query strings.
+const ATTRIBUTE_ESCAPES: &[(char, &str)] =
+    &[('"', "%22"), ('[', "%5B"), (']', "%5D"), (' ', "%20")];
+
+/// Percent-encodes the characters in `value` that would otherwise break a tag such as `[url=...]`.
+pub(crate) fn encode_attribute(value: &str) -> Cow<'_, str> {
+    if !value.contains(|c| ATTRIBUTE_ESCAPES.iter().any(|&(esc, _)| esc == c)) {
+        return value.into();
+    }
+
+    value
+        .chars()
+        .fold(String::with_capacity(value.len()), |mut acc, c| {
+            match ATTRIBUTE_ESCAPES.iter().find(|&&(esc, _)| esc == c) {
+                Some((_, encoded)) => acc.push_str(encoded),
+                None => acc.push(c),
+            }
+
+            acc
+        })
+        .into()
+}
+
+/// Reverts [`encode_attribute`]. Spaces are kept encoded, because Markdown link destinations can't contain them.
+pub(crate) fn decode_attribute(value: &str) -> Cow<'_, str> {
+    if !value.contains('%') {
+        return value.into();
+    }
+
+    ATTRIBUTE_ESCAPES
+        .iter()
+        .filter(|&&(c, _)| c != ' ')
+        .fold(value.to_owned(), |acc, (c, encoded)| {
+            acc.replace(encoded, &c.to_string())
+                .replace(&encoded.to_lowercase(), &c.to_string())
+        })
+        .into()
+}
@@ -0,0 +1,320 @@
+use std::{borrow::Cow, vec};
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Parser, Tag};
+
+use crate::{
+    bbcode::{custom::escape_html, markdown_options, read::to_markdown_with},
+    options::{ConvertOptions, LinkPolicy},
+};
+
+/// A parser turning BBCode into the [pulldown-cmark](https://docs.rs/pulldown-cmark/0.9) events of its Markdown
+/// equivalent, so that it can be fed into any consumer of those, such as an HTML renderer.
+///
+/// The BBCode is converted and parsed as a whole when the parser is created, with the same rules as
+/// [`to_markdown`](crate::to_markdown).
+///
+/// # Examples
+///
+/// ```
+/// use bibi::BbcodeParser;
+/// use pulldown_cmark::html;
+///
+/// let mut out = String::new();
+/// html::push_html(&mut out, BbcodeParser::new("[b]Hi[/b] [url=https://nerdz.eu]there[/url]"));
+///
+/// assert_eq!(out, "<p><strong>Hi</strong> <a href=\"https://nerdz.eu\">there</a></p>\n");
+/// ```
+#[derive(Clone, Debug)]
+pub struct BbcodeParser<'a> {
+    events: vec::IntoIter<Event<'a>>,
+}
+
+impl<'a> BbcodeParser<'a> {
+    /// Creates a parser for the given BBCode.
+    pub fn new(content: &'a str) -> Self {
+        Self::with_options(content, &ConvertOptions::default())
+    }
+
+    /// Same as [`BbcodeParser::new`], but allows tweaking the conversion through the given [`ConvertOptions`].
+    ///
+    /// [`ConvertOptions::link_policy`] also applies to any Markdown link the BBCode held as text, and links become
+    /// HTML events if they're to be marked with `rel="nofollow"`.
+    ///
+    /// ```
+    /// use bibi::{BbcodeParser, ConvertOptions, LinkPolicy};
+    /// use pulldown_cmark::html;
+    ///
+    /// let options = ConvertOptions {
+    ///     link_policy: LinkPolicy {
+    ///         nofollow: true,
+    ///         ..LinkPolicy::untrusted()
+    ///     },
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let mut out = String::new();
+    /// html::push_html(
+    ///     &mut out,
+    ///     BbcodeParser::with_options("[url=https://nerdz.eu]NERDZ[/url] [url]javascript:alert(1)[/url]", &options),
+    /// );
+    ///
+    /// assert_eq!(
+    ///     out,
+    ///     "<p><a href=\"https://nerdz.eu\" rel=\"nofollow\">NERDZ</a> javascript:alert(1)</p>\n"
+    /// );
+    /// ```
+    pub fn with_options(content: &'a str, options: &ConvertOptions) -> Self {
+        let markdown = to_markdown_with(content, options);
+
+        let events = Parser::new_ext(&markdown, markdown_options()).map(owned_event);
+
+        Self {
+            events: options.link_policy.apply_to_events(events),
+        }
+    }
+}
+
+impl<'a> Iterator for BbcodeParser<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.events.size_hint()
+    }
+}
+
+impl LinkPolicy {
+    /// Applies the policy to the links and images of some [pulldown-cmark](https://docs.rs/pulldown-cmark/0.9)
+    /// events, such as those of the Markdown generated by [`to_markdown`](crate::to_markdown), dropping those it
+    /// doesn't allow but not their text, and turning links into HTML if they're to be marked with
+    /// `rel="nofollow"`. Raw HTML is turned into text if [`LinkPolicy::schemes`] is set, as it could run scripts
+    /// whatever the links. [`BbcodeParser`] already does it for its events.
+    ///
+    /// ```
+    /// use bibi::LinkPolicy;
+    /// use pulldown_cmark::{html, Parser};
+    ///
+    /// let mut out = String::new();
+    /// html::push_html(
+    ///     &mut out,
+    ///     LinkPolicy::untrusted().apply_to_events(Parser::new("[win](javascript:alert(1))")),
+    /// );
+    ///
+    /// assert_eq!(out, "<p>win</p>\n");
+    /// ```
+    pub fn apply_to_events<'a>(
+        &self,
+        events: impl IntoIterator<Item = Event<'a>>,
+    ) -> vec::IntoIter<Event<'a>> {
+        // whether each link or image still open was kept
+        let mut kept = vec![];
+        let mut ret = vec![];
+
+        for event in events {
+            match event {
+                Event::Start(Tag::Link(kind, dest, title)) => {
+                    let dest = sanitize(dest, self);
+                    kept.push(dest.is_some());
+
+                    match dest {
+                        Some(dest) if self.nofollow => {
+                            let title = match title.is_empty() {
+                                true => String::new(),
+                                false => format!(" title=\"{}\"", escape_html(&title)),
+                            };
+
+                            ret.push(Event::Html(
+                                format!(
+                                    "<a href=\"{}\"{title} rel=\"nofollow\">",
+                                    escape_html(&dest)
+                                )
+                                .into(),
+                            ));
+                        }
+                        Some(dest) => ret.push(Event::Start(Tag::Link(kind, dest, title))),
+                        None => {}
+                    }
+                }
+                Event::Start(Tag::Image(kind, dest, title)) => {
+                    let dest = sanitize(dest, self);
+                    kept.push(dest.is_some());
+
+                    if let Some(dest) = dest {
+                        ret.push(Event::Start(Tag::Image(kind, dest, title)));
+                    }
+                }
+                Event::End(tag @ (Tag::Link(..) | Tag::Image(..))) => match kept.pop() {
+                    Some(true) if self.nofollow && matches!(tag, Tag::Link(..)) => {
+                        ret.push(Event::Html("</a>".into()))
+                    }
+                    Some(true) => ret.push(Event::End(tag)),
+                    _ => {}
+                },
+                Event::Html(html) if self.is_restrictive() => ret.push(Event::Text(html)),
+                event => ret.push(event),
+            }
+        }
+
+        ret.into_iter()
+    }
+}
+
+/// Applies a link policy to a destination, returning the one to use instead, if any.
+fn sanitize<'a>(dest: CowStr<'a>, policy: &LinkPolicy) -> Option<CowStr<'a>> {
+    let resolved = match policy.apply(&dest)? {
+        Cow::Borrowed(_) => None,
+        Cow::Owned(resolved) => Some(resolved),
+    };
+
+    Some(resolved.map_or(dest, CowStr::from))
+}
+
+fn owned_str(text: CowStr<'_>) -> CowStr<'static> {
+    match text {
+        CowStr::Boxed(text) => CowStr::Boxed(text),
+        CowStr::Borrowed(text) => CowStr::Boxed(text.into()),
+        CowStr::Inlined(text) => CowStr::Inlined(text),
+    }
+}
+
+fn owned_tag(tag: Tag<'_>) -> Tag<'static> {
+    use Tag::*;
+
+    match tag {
+        Paragraph => Paragraph,
+        // ids and classes are only parsed with the heading attributes extension, which is never enabled
+        Heading(level, ..) => Heading(level, None, vec![]),
+        BlockQuote => BlockQuote,
+        CodeBlock(CodeBlockKind::Indented) => CodeBlock(CodeBlockKind::Indented),
+        CodeBlock(CodeBlockKind::Fenced(info)) => CodeBlock(CodeBlockKind::Fenced(owned_str(info))),
+        List(start) => List(start),
+        Item => Item,
+        FootnoteDefinition(label) => FootnoteDefinition(owned_str(label)),
+        Table(alignments) => Table(alignments),
+        TableHead => TableHead,
+        TableRow => TableRow,
+        TableCell => TableCell,
+        Emphasis => Emphasis,
+        Strong => Strong,
+        Strikethrough => Strikethrough,
+        Link(kind, dest, title) => Link(kind, owned_str(dest), owned_str(title)),
+        Image(kind, dest, title) => Image(kind, owned_str(dest), owned_str(title)),
+    }
+}
+
+/// Detaches an event from the Markdown it was parsed from.
+fn owned_event(event: Event<'_>) -> Event<'static> {
+    use Event::*;
+
+    match event {
+        Start(tag) => Start(owned_tag(tag)),
+        End(tag) => End(owned_tag(tag)),
+        Text(text) => Text(owned_str(text)),
+        Code(text) => Code(owned_str(text)),
+        Html(html) => Html(owned_str(html)),
+        FootnoteReference(label) => FootnoteReference(owned_str(label)),
+        SoftBreak => SoftBreak,
+        HardBreak => HardBreak,
+        Rule => Rule,
+        TaskListMarker(checked) => TaskListMarker(checked),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Parser};
+
+    use crate::{BbcodeParser, ConvertOptions, EscapePolicy, LinkPolicy};
+
+    const PAYLOADS: &[&str] = &[
+        "<script>alert(1)</script>",
+        "<img src=x onerror=alert(1)>",
+        "[b]<svg onload=alert(1)>[/b]",
+        "[quote]<iframe src=javascript:alert(1)></iframe>[/quote]",
+        "&lt;script&gt; <a href=\"javascript:alert(1)\">x</a>",
+    ];
+
+    fn render(bbcode: &str, options: &ConvertOptions) -> String {
+        let mut ret = String::new();
+        html::push_html(&mut ret, BbcodeParser::with_options(bbcode, options));
+
+        ret
+    }
+
+    fn untrusted(escaping: EscapePolicy) -> ConvertOptions {
+        ConvertOptions {
+            escaping,
+            link_policy: LinkPolicy::untrusted(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn html_in_bbcode_is_text() {
+        for escaping in [EscapePolicy::Verbatim, EscapePolicy::Markdown] {
+            let options = untrusted(escaping);
+
+            for payload in PAYLOADS {
+                let html = render(payload, &options);
+
+                assert!(!html.contains("<script"), "{payload:?} -> {html:?}");
+                assert!(!html.contains("<img"), "{payload:?} -> {html:?}");
+                assert!(!html.contains("<svg"), "{payload:?} -> {html:?}");
+                assert!(!html.contains("<iframe"), "{payload:?} -> {html:?}");
+                assert!(!html.contains("<a "), "{payload:?} -> {html:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn html_in_bbcode_shows_as_written() {
+        let options = untrusted(EscapePolicy::Verbatim);
+
+        assert_eq!(
+            render("[b]<script>alert(1)</script>[/b] &amp;", &options),
+            "<p><strong>&lt;script&gt;alert(1)&lt;/script&gt;</strong> &amp;amp;</p>\n"
+        );
+    }
+
+    #[test]
+    fn html_in_markdown_is_text() {
+        let markdown = "<a href=\"javascript:alert(1)\">x</a>\n\n<script>alert(1)</script>\n";
+
+        let mut html = String::new();
+        html::push_html(
+            &mut html,
+            LinkPolicy::untrusted().apply_to_events(Parser::new(markdown)),
+        );
+
+        assert!(!html.contains("<a "), "{html:?}");
+        assert!(!html.contains("<script"), "{html:?}");
+        assert!(html.contains("&lt;script&gt;"), "{html:?}");
+    }
+
+    #[test]
+    fn nofollow_links_stay_html() {
+        let options = ConvertOptions {
+            link_policy: LinkPolicy {
+                nofollow: true,
+                ..LinkPolicy::untrusted()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render("[url=https://nerdz.eu]<b>[/url]", &options),
+            "<p><a href=\"https://nerdz.eu\" rel=\"nofollow\">&lt;b&gt;</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn html_is_kept_without_a_policy() {
+        assert_eq!(
+            render("<b>hi</b>", &ConvertOptions::default()),
+            "<p><b>hi</b></p>\n"
+        );
+    }
+}
@@ -0,0 +1,573 @@
+//! Matching of tag pairs such as `[b]P[/b]`, which is what most of the BBCode to Markdown conversion boils down to.
+//!
+//! The text is scanned for tags only once, and the patterns are then matched against those tags alone, in order of
+//! priority, as if they were applied one after the other. Every matched tag is finally rewritten in place, and the
+//! text between them copied, into a single output buffer, so that the cost of a conversion doesn't depend on how
+//! many patterns there are.
+
+use alloc::{borrow::Cow, collections::VecDeque, string::String, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::bbcode::{
+    decode_attribute,
+    span::{Mapped, Rewriter},
+};
+
+/// The value a pattern accepts in its outermost opening tag.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum TagValue {
+    /// No value, i.e. `[b]`.
+    Absent,
+    /// Either a quoted value, which may contain `]`, or an unquoted one extending up to the first `]`.
+    Any,
+    /// A number, optionally quoted.
+    Number,
+}
+
+/// A match of a [`TagPattern`].
+#[derive(Debug)]
+pub(crate) struct TagMatch<'t> {
+    /// The value of the outermost tag, if the pattern accepts one.
+    pub value: Option<&'t str>,
+    /// The text enclosed by the tags, as written. It's never empty and never spans multiple lines.
+    pub content: &'t str,
+}
+
+/// What the tags of a match are rewritten to. The content between them is kept, after converting whatever other
+/// tags it contains.
+#[derive(Debug)]
+pub(crate) struct Replacement {
+    pub open: Cow<'static, str>,
+    pub close: Cow<'static, str>,
+    /// Whether the content is a link destination, whose percent-encoded characters must be decoded.
+    pub decode: bool,
+}
+
+/// A sequence of nested tags enclosing some text on a single line, e.g. `[big][b]P[/b][/big]`. Tag names are
+/// case-insensitive.
+#[derive(Debug)]
+pub(crate) struct TagPattern {
+    /// The nested tags, outermost first. Each one lists the names it accepts.
+    tags: Vec<Vec<Cow<'static, str>>>,
+    value: TagValue,
+    /// Whether the pattern must span a whole line, leading and trailing blanks aside.
+    whole_line: bool,
+}
+
+impl TagPattern {
+    pub(crate) fn new(
+        tags: Vec<&'static [&'static str]>,
+        value: TagValue,
+        whole_line: bool,
+    ) -> Self {
+        assert!(!tags.is_empty(), "a pattern needs at least one tag");
+
+        Self {
+            tags: tags
+                .into_iter()
+                .map(|names| names.iter().map(|&name| name.into()).collect())
+                .collect(),
+            value,
+            whole_line,
+        }
+    }
+
+    /// A pattern made of a single tag whose names aren't known in advance, e.g. because they come from a
+    /// configuration file.
+    pub(crate) fn single(names: Vec<String>, value: TagValue) -> Self {
+        Self {
+            tags: vec![names.into_iter().map(Cow::Owned).collect()],
+            value,
+            whole_line: false,
+        }
+    }
+}
+
+/// Some patterns in order of priority, along with whatever the scanner needs to match them, so that it's worked
+/// out only once for every conversion using them.
+#[derive(Debug)]
+pub(crate) struct PatternSet<R> {
+    patterns: Vec<(TagPattern, R)>,
+    names: Names,
+    /// The names each tag of each pattern accepts, as bitmasks.
+    masks: Vec<Vec<u64>>,
+}
+
+impl<R> PatternSet<R> {
+    pub(crate) fn new(patterns: Vec<(TagPattern, R)>) -> Self {
+        let names = Names::new(patterns.iter().map(|(pattern, _)| pattern));
+
+        let masks = patterns
+            .iter()
+            .map(|(pattern, _)| pattern.tags.iter().map(|tags| names.mask(tags)).collect())
+            .collect();
+
+        Self {
+            patterns,
+            names,
+            masks,
+        }
+    }
+}
+
+/// The tag names some patterns accept, so that every tag is looked up only once. Sets of names are bitmasks of
+/// their positions in here.
+#[derive(Debug)]
+struct Names(Vec<Cow<'static, str>>);
+
+impl Names {
+    fn new<'p>(patterns: impl IntoIterator<Item = &'p TagPattern>) -> Self {
+        let mut ret = Self(vec![]);
+
+        for name in patterns
+            .into_iter()
+            .flat_map(|pattern| &pattern.tags)
+            .flatten()
+        {
+            if ret.find(name).is_none() {
+                ret.0.push(name.clone());
+            }
+        }
+
+        assert!(ret.0.len() <= 64, "too many tag names");
+
+        ret
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|known| known.eq_ignore_ascii_case(name))
+    }
+
+    fn mask(&self, names: &[Cow<str>]) -> u64 {
+        names
+            .iter()
+            .filter_map(|name| self.find(name))
+            .fold(0, |mask, idx| mask | 1 << idx)
+    }
+}
+
+/// A tag found in the text, i.e. `[name]`, `[/name]` or the start of `[name=value]`.
+#[derive(Debug)]
+struct Tag {
+    /// The position of the `[`.
+    start: usize,
+    /// Where the name ends, i.e. the position of the `]` or `=` after it.
+    name_end: usize,
+    /// The position of the name in [`Names`].
+    name: usize,
+    closing: bool,
+}
+
+impl Tag {
+    fn is(&self, names: u64, closing: bool) -> bool {
+        self.closing == closing && names & 1 << self.name != 0
+    }
+}
+
+/// Finds every tag in `text` whose name is among `names`, sorted by position.
+fn scan_tags(text: &str, names: &Names) -> Vec<Tag> {
+    let bytes = text.as_bytes();
+
+    text.match_indices('[')
+        .filter_map(|(start, _)| {
+            let closing = bytes.get(start + 1) == Some(&b'/');
+            let name_start = start + 1 + usize::from(closing);
+            let name_len = bytes[name_start..]
+                .iter()
+                .position(|b| !b.is_ascii_alphanumeric())?;
+
+            let name_end = name_start + name_len;
+
+            match bytes[name_end] {
+                b']' => {}
+                b'=' if !closing => {}
+                _ => return None,
+            }
+
+            Some(Tag {
+                start,
+                name_end,
+                name: names.find(&text[name_start..name_end])?,
+                closing,
+            })
+        })
+        .collect()
+}
+
+/// Every way the value of a tag can be read, in order of preference, as the value and what follows the closing `]`.
+fn values(text: &str, value: TagValue) -> [Option<(Option<&str>, &str)>; 2] {
+    match (value, text.strip_prefix('=')) {
+        (TagValue::Absent, _) => [text.strip_prefix(']').map(|rest| (None, rest)), None],
+        (TagValue::Any, Some(text)) => {
+            let quoted = text.strip_prefix('"').and_then(|quoted| {
+                let end = quoted.find('"')?;
+
+                Some((Some(&quoted[..end]), quoted[end + 1..].strip_prefix(']')?))
+            });
+
+            let unquoted = text
+                .find(']')
+                .filter(|&end| end > 0)
+                .map(|end| (Some(&text[..end]), &text[end + 1..]));
+
+            [quoted, unquoted]
+        }
+        (TagValue::Number, Some(text)) => {
+            let unquoted = text.strip_prefix('"').unwrap_or(text);
+            let digits = unquoted.len()
+                - unquoted
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+
+            let rest = &unquoted[digits..];
+            let rest = rest.strip_prefix('"').unwrap_or(rest);
+
+            let number = rest
+                .strip_prefix(']')
+                .filter(|_| digits > 0)
+                .map(|rest| (Some(&unquoted[..digits]), rest));
+
+            [number, None]
+        }
+        (_, None) => [None, None],
+    }
+}
+
+/// A match, as the ranges of its opening and closing tags, leading and trailing blanks included for whole lines.
+/// The content is what lies in between.
+struct Found<'t> {
+    open: Range<usize>,
+    /// The index of the outermost opening tag.
+    open_idx: usize,
+    value: Option<&'t str>,
+    close: Range<usize>,
+    /// The index of the innermost closing tag.
+    close_idx: usize,
+}
+
+/// The state of a scan: the tags of the text, and which of them are gone, either because they've been matched or
+/// because they were part of the value of a matched tag.
+struct Scan<'t> {
+    text: &'t str,
+    tags: Vec<Tag>,
+    used: Vec<bool>,
+    /// The positions of the line breaks.
+    newlines: Vec<usize>,
+}
+
+impl<'t> Scan<'t> {
+    fn new(text: &'t str, names: &Names) -> Self {
+        let tags = scan_tags(text, names);
+
+        Self {
+            text,
+            used: vec![false; tags.len()],
+            tags,
+            newlines: text.match_indices('\n').map(|(pos, _)| pos).collect(),
+        }
+    }
+
+    /// Marks the tags from the one at `idx` up to `end` as gone, or as still around.
+    fn set_used(&mut self, idx: usize, end: usize, used: bool) {
+        let len = self.tags[idx..]
+            .iter()
+            .take_while(|tag| tag.start < end)
+            .count();
+
+        self.used[idx..idx + len].fill(used);
+    }
+
+    /// Matches the given names, as a sequence of adjacent tags without values starting with the one at `idx`,
+    /// which must be at `pos`, returning where they end.
+    fn adjacent(
+        &self,
+        idx: usize,
+        pos: usize,
+        names: impl IntoIterator<Item = u64>,
+        closing: bool,
+    ) -> Option<usize> {
+        names
+            .into_iter()
+            .zip(idx..)
+            .try_fold(pos, |pos, (names, idx)| {
+                let tag = self.tags.get(idx).filter(|tag| tag.start == pos)?;
+                let end = tag.name_end;
+
+                (!self.used[idx] && tag.is(names, closing) && self.text.as_bytes()[end] == b']')
+                    .then_some(end + 1)
+            })
+    }
+
+    /// Tries to match a pattern, whose tags accept the given names, starting with the tag at `idx`. The content
+    /// must end right before one of `closes`, given as their position and index. Both `closes` and `newlines` may
+    /// leave out what comes before the tag.
+    fn match_at(
+        &self,
+        pattern: &TagPattern,
+        names: &[u64],
+        idx: usize,
+        closes: &[(usize, usize)],
+        newlines: &[usize],
+    ) -> Option<Found<'t>> {
+        let text = self.text;
+        let tag = &self.tags[idx];
+
+        let open_start = match pattern.whole_line {
+            true => {
+                let before = text[..tag.start].trim_end_matches([' ', '\t']);
+
+                if !before.is_empty() && !before.ends_with('\n') {
+                    return None;
+                }
+
+                before.len()
+            }
+            false => tag.start,
+        };
+
+        values(&text[tag.name_end..], pattern.value)
+            .into_iter()
+            .flatten()
+            .find_map(|(value, rest)| {
+                let after_value = text.len() - rest.len();
+
+                let content_start = match &names[1..] {
+                    [] => after_value,
+                    nested => {
+                        let next = idx
+                            + 1
+                            + skip_before(&self.tags[idx + 1..], after_value, |tag| tag.start);
+
+                        self.adjacent(next, after_value, nested.iter().copied(), false)?
+                    }
+                };
+
+                let line_end = newlines
+                    .iter()
+                    .copied()
+                    .find(|&nl| nl >= content_start)
+                    .unwrap_or(text.len());
+
+                let trimmed_end = text[..line_end].trim_end_matches([' ', '\t']).len();
+                let first = skip_before(closes, content_start + 1, |&(start, _)| start);
+
+                // the content is as short as possible, but never empty and never spanning more than a line
+                closes[first..]
+                    .iter()
+                    .take_while(|&&(start, _)| start < line_end)
+                    .find_map(|&(content_end, close_idx)| {
+                        let close_end = self.adjacent(
+                            close_idx,
+                            content_end,
+                            names.iter().rev().copied(),
+                            true,
+                        )?;
+
+                        let close_end = match pattern.whole_line {
+                            true => (close_end == trimmed_end).then_some(line_end)?,
+                            false => close_end,
+                        };
+
+                        Some(Found {
+                            open: open_start..content_start,
+                            open_idx: idx,
+                            value,
+                            close: content_end..close_end,
+                            close_idx,
+                        })
+                    })
+            })
+    }
+
+    /// Finds every non-overlapping match of `pattern`, whose tags accept the given names, among the tags still
+    /// around, leftmost first, and marks their tags as gone.
+    fn find_all(&mut self, pattern: &TagPattern, names: &[u64]) -> Vec<Found<'t>> {
+        let (outer, inner) = (names[0], names[names.len() - 1]);
+
+        let closes: Vec<_> = (0..self.tags.len())
+            .filter(|&idx| !self.used[idx] && self.tags[idx].is(inner, true))
+            .map(|idx| (self.tags[idx].start, idx))
+            .collect();
+
+        let mut ret = vec![];
+        let mut from = 0;
+
+        // the closing tags and line breaks before the current tag, which only moves forward
+        let (mut closes_before, mut newlines_before) = (0, 0);
+
+        for idx in 0..self.tags.len() {
+            let tag = &self.tags[idx];
+
+            if self.used[idx] || tag.start < from || !tag.is(outer, false) {
+                continue;
+            }
+
+            closes_before += skip_before(&closes[closes_before..], tag.start, |&(start, _)| start);
+            newlines_before += skip_before(&self.newlines[newlines_before..], tag.start, |&nl| nl);
+
+            let found = self.match_at(
+                pattern,
+                names,
+                idx,
+                &closes[closes_before..],
+                &self.newlines[newlines_before..],
+            );
+
+            if let Some(found) = found {
+                from = found.close.end;
+
+                self.set_used(found.open_idx, found.open.end, true);
+                self.set_used(found.close_idx, found.close.end, true);
+
+                ret.push(found);
+            }
+        }
+
+        ret
+    }
+}
+
+/// Counts the items at the start of `items`, which are sorted by position, that come before `pos`.
+fn skip_before<T>(items: &[T], pos: usize, position: impl Fn(&T) -> usize) -> usize {
+    items.iter().take_while(|item| position(item) < pos).count()
+}
+
+/// A rewritten piece of the text, and whether it starts or ends the content of a link destination.
+struct Edit {
+    range: Range<usize>,
+    text: Cow<'static, str>,
+    decode: isize,
+}
+
+/// Replaces the matches of the given patterns in `text` with what `replacer` returns for them. Returns `None` if
+/// nothing matched.
+///
+/// Each pattern is matched leftmost first against the tags the patterns before it left alone, so the order of the
+/// patterns is their priority. A match `replacer` returns `None` for is left as-is.
+pub(crate) fn replace_all<'t, R>(
+    text: &'t str,
+    patterns: &PatternSet<R>,
+    track: bool,
+    mut replacer: impl FnMut(&R, &TagMatch<'t>) -> Option<Replacement>,
+) -> Option<Mapped> {
+    let mut scan = Scan::new(text, &patterns.names);
+
+    if scan.tags.is_empty() {
+        return None;
+    }
+
+    // the edits of each pattern, sorted by position
+    let mut passes = vec![];
+
+    for ((pattern, repl), names) in patterns.patterns.iter().zip(&patterns.masks) {
+        let mut edits = VecDeque::new();
+
+        for found in scan.find_all(pattern, names) {
+            let Found {
+                open,
+                open_idx,
+                value,
+                close,
+                close_idx,
+            } = found;
+
+            let m = TagMatch {
+                value,
+                content: &text[open.end..close.start],
+            };
+
+            let Some(replacement) = replacer(repl, &m) else {
+                // the tags are still around for the patterns that come next
+                scan.set_used(open_idx, open.end, false);
+                scan.set_used(close_idx, close.end, false);
+
+                continue;
+            };
+
+            let decode = isize::from(replacement.decode);
+
+            edits.extend([
+                Edit {
+                    range: open,
+                    text: replacement.open,
+                    decode,
+                },
+                Edit {
+                    range: close,
+                    text: replacement.close,
+                    decode: -decode,
+                },
+            ]);
+        }
+
+        passes.push(edits);
+    }
+
+    let mut ret = Rewriter::new(text, track);
+    let mut last = 0;
+    let mut decode = 0;
+
+    ret.reserve(text.len());
+
+    // matches never overlap, so merging the edits of all the patterns yields them in order
+    while let Some(edit) = passes
+        .iter_mut()
+        .min_by_key(|edits| edits.front().map_or(usize::MAX, |edit| edit.range.start))
+        .and_then(VecDeque::pop_front)
+    {
+        copy(&mut ret, &text[last..edit.range.start], decode > 0);
+        ret.push_str(&edit.text);
+
+        decode += edit.decode;
+        last = edit.range.end;
+    }
+
+    if last == 0 {
+        return None;
+    }
+
+    copy(&mut ret, &text[last..], false);
+
+    Some(ret.finish())
+}
+
+/// Copies a piece of the original, decoding it first if it's part of a link destination.
+fn copy<'t>(out: &mut Rewriter<'t>, piece: &'t str, decode: bool) {
+    match decode.then(|| decode_attribute(piece)) {
+        Some(Cow::Owned(decoded)) => out.push_str(&decoded),
+        _ => out.copy(piece),
+    }
+}
+
+/// Finds the first occurrence of `needle` in `haystack`, ignoring ASCII case.
+pub(crate) fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    let first = needle.chars().next()?;
+
+    let mut from = 0;
+
+    // only check the positions where the first character matches, which is a plain `[` most of the time
+    while let Some(pos) = match first.is_ascii_alphabetic() {
+        true => haystack[from..].find(|c: char| c.eq_ignore_ascii_case(&first)),
+        false => haystack[from..].find(first),
+    } {
+        let start = from + pos;
+
+        if strip_prefix_ignore_case(&haystack[start..], needle).is_some() {
+            return Some(start);
+        }
+
+        from = start + first.len_utf8();
+    }
+
+    None
+}
+
+/// Strips `prefix` from the start of `text`, ignoring ASCII case.
+pub(crate) fn strip_prefix_ignore_case<'t>(text: &'t str, prefix: &str) -> Option<&'t str> {
+    let head = text.as_bytes().get(..prefix.len())?;
+
+    head.eq_ignore_ascii_case(prefix.as_bytes())
+        .then(|| &text[prefix.len()..])
+}
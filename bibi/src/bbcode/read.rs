@@ -1,26 +1,61 @@
-use std::{borrow::Cow, collections::HashSet, io, ops::ControlFlow};
+use alloc::{
+    borrow::{Cow, ToOwned},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt, iter,
+    ops::{Range, RangeInclusive},
+    slice,
+};
+#[cfg(feature = "std")]
+use std::io;
 
-use lazy_static::lazy_static;
+use spin::Lazy;
 
-use itertools::Itertools;
 use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::{char, digit1, space1},
-    combinator::{map, map_res, value},
+    combinator::{map, map_res, opt, recognize, value},
     multi::fold_many0,
-    sequence::{delimited, preceded, separated_pair},
+    sequence::{delimited, pair, preceded, separated_pair},
     IResult,
 };
 
-use numerals::roman::Roman;
-use regex::{Captures, Regex};
 use strum::{EnumIter, IntoEnumIterator};
 
-use crate::bbcode::write::{DEFAULT_ANON_CODELANG, DEFAULT_ANON_ICODELANG};
+use crate::{
+    bbcode::{
+        custom::{replace_custom, CustomRules},
+        decode_attribute,
+        link::sanitize_links,
+        map_newlines, normalize_newlines,
+        pattern::{
+            find_ignore_case, replace_all, strip_prefix_ignore_case, PatternSet, Replacement,
+            TagMatch, TagPattern, TagValue,
+        },
+        recover::recover,
+        reference::{reference_links, References},
+        report::annotate,
+        single_line,
+        smiley::replace_smileys,
+        span::{compose, Mapped, Rewriter, SpanMap, Spans},
+        synonym::replace_synonyms,
+        Dialect, TagNames,
+    },
+    error::Result,
+    options::{ConvertOptions, EscapePolicy, HeadingMap, HeadingStyle, LinkStyle, ThematicBreak},
+    writefmt::{FmtWriter, WriteFmt},
+};
+
+#[cfg(feature = "std")]
+use crate::writefmt::IoWriter;
 
 #[derive(Clone, Copy, Debug, EnumIter, Eq, PartialEq)]
-enum CodeKind {
+pub(super) enum CodeKind {
     Inline,
     Multiline,
 }
@@ -39,188 +74,286 @@ impl CodeKind {
         }
     }
 
-    fn is_default_value(self, val: &str) -> bool {
+    fn is_default_value(self, val: &str, options: &ConvertOptions) -> bool {
         use CodeKind::*;
 
-        val == match self {
-            Inline => DEFAULT_ANON_ICODELANG,
-            Multiline => DEFAULT_ANON_CODELANG,
-        }
+        val.is_empty()
+            || val
+                == match self {
+                    Inline => &options.inline_code_lang,
+                    Multiline => &options.code_lang,
+                }
     }
 
+    /// Whether the dialect in use has this kind of code block at all.
+    fn is_supported(self, options: &ConvertOptions) -> bool {
+        self == CodeKind::Multiline || options.dialect.names().inline_code.is_some()
+    }
+
+    /// The start of the opening tag, which must be followed by either `=lang]` or just `]`.
     const fn start_seq(self) -> &'static str {
         use CodeKind::*;
 
         match self {
-            Inline => "[c=",
-            Multiline => "[code=",
+            Inline => "[c",
+            Multiline => "[code",
         }
     }
 }
 
 #[derive(Debug)]
-enum TextChunk<'a> {
-    Chars(Cow<'a, str>),
+pub(super) enum TextChunk<'a> {
+    /// Text outside of any code block, as it is.
+    Chars(&'a str),
     Code {
         kind: CodeKind,
         lang: Option<&'a str>,
         content: &'a str,
+        /// The whole block, tags included.
+        block: &'a str,
     },
 }
 
-fn extract_inner(tag_block: &str, kind: CodeKind) -> &str {
-    // assume starts and ends have alredy been checked
-    let tag_end = tag_block.find(']').expect("this can never happen") + 1;
+/// Parses the rest of an opening code tag, i.e. either `=lang]` or `]`. A missing language is returned as `""`, and
+/// one holding a `[` is no language, but the start of another tag.
+fn parse_lang(content: &str) -> Option<&str> {
+    if content.starts_with(']') {
+        return Some("");
+    }
 
-    &tag_block[tag_end..(tag_block.len() - kind.end_seq().len())]
-}
+    let value = content.strip_prefix('=')?.trim_start();
+    let value = value.strip_prefix('"').unwrap_or(value);
 
-fn next_codestart(content: &str) -> Option<(usize, CodeKind)> {
-    const PROBE: &str = CodeKind::common_start();
+    let end = value.find(['"', ']'])?;
+    let rest = value[end..].strip_prefix('"').unwrap_or(&value[end..]);
 
-    content.find(PROBE).and_then(|pos| {
-        let tag = &content[pos..];
+    let lang = value[..end].trim_end();
 
-        CodeKind::iter()
-            .find_map(|bt| tag.starts_with(bt.start_seq()).then_some((pos, bt)))
-            .or_else(|| {
-                next_codestart(&tag[PROBE.len()..]).map(|(rel_pos, bt)| (pos + rel_pos, bt))
-            })
-    })
+    (!lang.is_empty() && !lang.contains('[') && rest.trim_start().starts_with(']')).then_some(lang)
 }
 
-fn next_codeend(content: &str, kind: CodeKind) -> Option<usize> {
-    const NEWLINES: &[u8] = b"\r\n";
-
-    let probe = kind.end_seq().as_bytes();
-
-    for i in 0..content.len() {
-        let cur = content[i..].as_bytes();
+/// The next match of something in a text scanned from start to end, remembered until the scan goes past it, so that
+/// looking for it from every position of the scan costs a single pass over the text overall.
+#[derive(Default)]
+struct NextMatch {
+    at: Option<usize>,
+    /// Whether there's no match left at all.
+    exhausted: bool,
+}
 
-        if cur.starts_with(probe) {
-            return Some(i + probe.len());
+impl NextMatch {
+    /// The first match at or after `pos`, found with `find` when the one remembered is behind `pos`.
+    fn from(&mut self, pos: usize, find: impl FnOnce(usize) -> Option<usize>) -> Option<usize> {
+        if self.exhausted {
+            return None;
         }
 
-        // interpret the text as ASCII and check if the current position is a newline
-        // if this is an inline code block this is illegal
-        if kind == CodeKind::Inline && NEWLINES.contains(&cur[0]) {
-            break;
+        if let Some(at) = self.at.filter(|&at| at >= pos) {
+            return Some(at);
         }
+
+        self.at = find(pos);
+        self.exhausted = self.at.is_none();
+
+        self.at
     }
+}
 
-    None
+/// How long the opening tag of a code block can be at most in bytes, language and brackets included, so that text
+/// full of `[code=` isn't read all over again for each of them looking for the end of the tag. Longer tags aren't
+/// tags at all, and what they'd enclose is converted as any other text.
+const MAX_CODE_TAG: usize = 256;
+
+/// Finds the code blocks of some BBCode in a single pass, without ever scanning the same text twice however many
+/// tags look like code but aren't, or are never closed.
+struct CodeScanner<'a, 'o> {
+    content: &'a str,
+    options: &'o ConvertOptions,
+    /// Where the search for the next opening tag resumes.
+    pos: usize,
+    /// The next closing tag of inline code, and of code blocks.
+    ends: [NextMatch; 2],
+    /// The next line break, which inline code can't span.
+    newline: NextMatch,
+    /// The next `]`, which ends any opening tag.
+    bracket: NextMatch,
+    /// Whether a code block is opened but never closed.
+    unclosed: bool,
 }
 
-fn parse_lang(content: &str) -> Option<&str> {
-    lazy_static! {
-        static ref LANG_TAG: Regex = Regex::new(r#"^\s*"?([^"]+?)"?\s*\]"#).unwrap();
+impl<'a, 'o> CodeScanner<'a, 'o> {
+    fn new(content: &'a str, options: &'o ConvertOptions) -> Self {
+        Self {
+            content,
+            options,
+            pos: 0,
+            ends: Default::default(),
+            newline: NextMatch::default(),
+            bracket: NextMatch::default(),
+            unclosed: false,
+        }
+    }
+
+    /// The kind of code opened by the tag starting at `at`, if it is one.
+    fn kind_at(&self, at: usize) -> Option<CodeKind> {
+        let tag = &self.content[at..];
+
+        CodeKind::iter()
+            .filter(|kind| kind.is_supported(self.options))
+            .find(|kind| {
+                tag.strip_prefix(kind.start_seq())
+                    .is_some_and(|after| after.starts_with(['=', ']']))
+            })
     }
 
-    LANG_TAG
-        .captures(content)
-        .and_then(|c| c.get(1))
-        .map(|m| m.as_str())
+    /// Where the code of the given kind opened by the tag starting at `at` is closed, closing tag included.
+    fn end_of(&mut self, at: usize, kind: CodeKind) -> Option<usize> {
+        let end_seq = kind.end_seq();
+        let content = self.content;
+        let find = |pos: usize| content[pos..].find(end_seq).map(|found| pos + found);
+
+        let end = self.ends[kind as usize].from(at, find)?;
+
+        if kind == CodeKind::Inline {
+            let newline = self.newline.from(at, |pos| {
+                content[pos..].find(['\r', '\n']).map(|found| pos + found)
+            });
+
+            if newline.is_some_and(|newline| newline < end) {
+                return None;
+            }
+        }
+
+        Some(end + end_seq.len())
+    }
 }
 
-fn compact(chunks: Vec<TextChunk>) -> Vec<TextChunk> {
-    use TextChunk::*;
+impl<'a> Iterator for CodeScanner<'a, '_> {
+    /// The range of a code block in the content, along with its kind and its language as written, if any.
+    type Item = (Range<usize>, CodeKind, &'a str);
 
-    let (rem, mut ret) = chunks
-        .into_iter()
-        .fold((None, vec![]), |(current, mut acc), chunk| {
-            let current = match (current, chunk) {
-                (None, code @ Code { .. }) => {
-                    acc.push(code);
-                    None
-                }
-                (None, Chars(cs)) => Some(cs),
-                (Some(text), code @ Code { .. }) => {
-                    acc.extend([Chars(text), code]);
-                    None
-                }
-                (Some(text), Chars(more)) => Some(format!("{text}{more}").into()),
+    fn next(&mut self) -> Option<Self::Item> {
+        const PROBE: &str = CodeKind::common_start();
+
+        let content = self.content;
+
+        loop {
+            let at = self.pos + content[self.pos..].find(PROBE)?;
+
+            let Some(kind) = self.kind_at(at) else {
+                self.pos = at + PROBE.len();
+                continue;
             };
 
-            (current, acc)
-        });
+            let tag_start = at + kind.start_seq().len();
 
-    if let Some(reminder) = rem {
-        ret.push(Chars(reminder));
-    }
+            let Some(end) = self.end_of(at, kind) else {
+                self.unclosed |= kind == CodeKind::Multiline;
+
+                // the start of the tag is text, and the scan resumes right after it
+                self.pos = tag_start;
+                continue;
+            };
+
+            // the opening tag ends at the first `]`, which is always there since the block is closed; it isn't a tag
+            // if that's the one of the closing tag, or if it's too far away
+            let tag_end = self
+                .bracket
+                .from(tag_start, |pos| {
+                    content[pos..].find(']').map(|found| pos + found)
+                })
+                .expect("closing tags hold a `]`");
 
-    ret
+            let lang = (tag_end < end - kind.end_seq().len() && tag_end - at < MAX_CODE_TAG)
+                .then(|| parse_lang(&content[tag_start..=tag_end]))
+                .flatten();
+
+            match lang {
+                Some(lang) => {
+                    self.pos = end;
+
+                    return Some((at..end, kind, lang));
+                }
+                None => self.pos = tag_start,
+            }
+        }
+    }
 }
 
-fn slurp_codetags(mut content: &str) -> Vec<TextChunk<'_>> {
+pub(super) fn slurp_codetags<'a>(content: &'a str, options: &ConvertOptions) -> Vec<TextChunk<'a>> {
     use TextChunk::*;
 
     let mut chunks = vec![];
+    let mut copied = 0;
 
-    while let Some((at, kind)) = next_codestart(content) {
-        let (before, start) = content.split_at(at);
-
-        chunks.push(Chars(before.into()));
-
-        let start_tok_len = kind.start_seq().len();
-        let (tag, at_langstart) = start.split_at(start_tok_len);
-
-        let (block, rem) = next_codeend(start, kind)
-            .and_then(|pos| {
-                let (code_block, rest) = start.split_at(pos);
-
-                // skip the initial chunk and start with the `"`
-                parse_lang(&code_block[start_tok_len..]).map(|lang| {
-                    let inside = extract_inner(code_block, kind);
-
-                    (
-                        Code {
-                            kind,
-                            lang: if kind.is_default_value(lang) {
-                                None
-                            } else {
-                                Some(lang)
-                            },
-                            content: inside,
-                        },
-                        rest,
-                    )
-                })
-            })
-            .unwrap_or_else(|| {
-                // the first part of the tag becomes a char block, and we continue straight after it
-                (Chars(tag.into()), at_langstart)
-            });
+    for (range, kind, lang) in CodeScanner::new(content, options) {
+        if copied < range.start {
+            chunks.push(Chars(&content[copied..range.start]));
+        }
+
+        let block = &content[range.clone()];
+        let inner_start = block
+            .find(']')
+            .expect("code blocks always have an opening tag")
+            + 1;
+
+        chunks.push(Code {
+            kind,
+            lang: (!kind.is_default_value(lang, options)).then_some(lang),
+            content: &block[inner_start..block.len() - kind.end_seq().len()],
+            block,
+        });
 
-        chunks.push(block);
-        content = rem;
+        copied = range.end;
     }
 
-    chunks.push(Chars(content.into()));
+    if copied < content.len() || chunks.is_empty() {
+        chunks.push(Chars(&content[copied..]));
+    }
 
-    compact(chunks)
+    chunks
 }
 
-fn code_str(kind: CodeKind, lang: Option<&str>, content: &str) -> String {
-    use CodeKind::*;
+/// Whether `content` ends within a code block, i.e. it opens a code block that it never closes. Inline code can't
+/// span multiple lines, so it's never left open by content ending with a line break.
+#[cfg(feature = "std")]
+pub(super) fn ends_in_code(content: &str, options: &ConvertOptions) -> bool {
+    let mut scanner = CodeScanner::new(content, options);
 
-    let lang = lang.unwrap_or_default();
+    scanner.by_ref().for_each(drop);
+
+    scanner.unclosed
+}
+
+fn push_code<'s>(out: &mut Rewriter<'s>, kind: CodeKind, lang: Option<&'s str>, content: &'s str) {
+    use CodeKind::*;
 
     match kind {
-        Inline => format!("`{content}`"),
-        Multiline => format!("```{lang}\n{content}\n```\n",),
+        Inline => {
+            out.push('`');
+            out.copy(content);
+            out.push('`');
+        }
+        Multiline => {
+            out.push_str("```");
+            out.copy(lang.unwrap_or_default());
+            out.push('\n');
+            out.copy(content);
+            out.push_str("\n```\n");
+        }
     }
 }
 
 struct ListHead {
     ltype: ListType,
-    start: i16,
+    start: i64,
 }
 
 impl Default for ListHead {
     fn default() -> Self {
         Self {
             ltype: ListType::Unordered,
-            start: 0i16,
+            start: 1,
         }
     }
 }
@@ -248,53 +381,102 @@ impl NumberingStyle {
         matches!(self, UpperAlpha | UpperRoman)
     }
 
-    fn iter_from(self, start: i16) -> impl Iterator<Item = String> {
-        NumberingIterator::new(self, start)
+    /// Appends the marker of the item numbered `n` to `out`, e.g. `iv. `.
+    fn push_marker(self, out: &mut impl fmt::Write, n: i64) {
+        use NumberingStyle::*;
+
+        let upper = self.is_upper();
+
+        let written = match self {
+            LowerAlpha | UpperAlpha if n > 0 => write!(out, "{}. ", Alpha { n, upper }),
+            LowerRoman | UpperRoman if Roman::RANGE.contains(&n) => {
+                write!(out, "{}. ", Roman { n, upper })
+            }
+            // like browsers do, fall back to decimals for the numbers letters and numerals can't represent
+            _ => write!(out, "{n}. "),
+        };
+
+        written.expect("writing to a string never fails");
     }
 }
 
-struct NumberingIterator {
-    style: NumberingStyle,
-    current: i16,
+/// A positive number, displayed as letters like spreadsheet columns are, i.e. `a`, …, `z`, `aa`, `ab`, ….
+struct Alpha {
+    n: i64,
+    upper: bool,
 }
 
-impl NumberingIterator {
-    fn new(style: NumberingStyle, start: i16) -> Self {
-        Self {
-            style,
-            current: start,
+impl fmt::Display for Alpha {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let base = if self.upper { b'A' } else { b'a' };
+
+        // 14 letters are enough for any i64, and they come out from the last one
+        let mut letters = [0u8; 14];
+        let mut start = letters.len();
+        let mut n = self.n;
+
+        while n > 0 {
+            n -= 1;
+            start -= 1;
+            letters[start] = base + (n % 26) as u8;
+            n /= 26;
         }
+
+        f.write_str(core::str::from_utf8(&letters[start..]).expect("letters are always ASCII"))
     }
 }
 
-impl Iterator for NumberingIterator {
-    type Item = String;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        use NumberingStyle::*;
+/// A number between 1 and 3999, displayed as Roman numerals.
+struct Roman {
+    n: i64,
+    upper: bool,
+}
 
-        let mut ret = match self.style {
-            Decimal => self.current.to_string(),
-            LowerAlpha | UpperAlpha => {
-                let base = if self.style.is_upper() { b'A' } else { b'a' };
+impl Roman {
+    /// The numbers Roman numerals can represent without any overline.
+    const RANGE: RangeInclusive<i64> = 1..=3999;
+
+    /// The numerals, along with their subtractive pairs, from the largest to the smallest.
+    const NUMERALS: [(i64, &'static str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+}
 
-                char::from((self.current % 26) as u8 + base).to_string()
-            }
-            LowerRoman => format!("{:x}", Roman::from(self.current)),
-            UpperRoman => format!("{:X}", Roman::from(self.current)),
-        };
+impl fmt::Display for Roman {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut n = self.n;
 
-        ret.push_str(". ");
+        for (value, numeral) in Self::NUMERALS {
+            while n >= value {
+                n -= value;
 
-        self.current += 1;
+                if self.upper {
+                    f.write_str(numeral)?;
+                } else {
+                    f.write_str(&numeral.to_ascii_lowercase())?;
+                }
+            }
+        }
 
-        Some(ret)
+        Ok(())
     }
 }
 
 #[derive(Debug, Eq, Hash, PartialEq)]
 enum ListHeadElement {
-    Start(i16),
+    Start(i64),
     Type(NumberingStyle),
 }
 
@@ -305,11 +487,14 @@ fn list_head(input: &str) -> IResult<&str, ListHead> {
     // bit of lookahead, so we can't use a single regex here. NERDZ uses several to achieve this
     // but it's undesirable due to the sheer amount of code repetition. Nom is faster and clearer TBH.
 
-    let integer = map_res(digit1, str::parse);
-    let start_spec = separated_pair(tag("start"), char('='), quoted(integer));
-    let type_spec = separated_pair(tag("type"), char('='), quoted(ol_type));
+    let integer = map_res(recognize(pair(opt(char('-')), digit1)), str::parse);
+    let start_spec = separated_pair(tag("start"), char('='), optionally_quoted(integer));
+    let type_spec = separated_pair(tag("type"), char('='), optionally_quoted(ol_type));
 
-    let (reminder, collected_tags) = fold_many0(
+    // phpBB-style heads just specify the type as the value of the tag, e.g. `[list=a]`
+    let (input, value_type) = opt(preceded(char('='), optionally_quoted(ol_type)))(input)?;
+
+    let (reminder, mut collected_tags) = fold_many0(
         preceded(
             space1,
             alt((
@@ -317,13 +502,15 @@ fn list_head(input: &str) -> IResult<&str, ListHead> {
                 map(type_spec, |(_, ty)| Type(ty)),
             )),
         ),
-        HashSet::new,
+        Vec::new,
         |mut acc, tag| {
-            acc.insert(tag);
+            acc.push(tag);
             acc
         },
     )(input)?;
 
+    collected_tags.extend(value_type.map(Type));
+
     if !reminder.trim().is_empty() {
         use nom::{error::Error as NomError, error::ErrorKind as NomErrorKind, Err as NomErr};
 
@@ -361,135 +548,693 @@ fn ol_type(input: &str) -> IResult<&str, NumberingStyle> {
     ))(input)
 }
 
-fn quoted<'a, O, E, F>(parser: F) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+fn optionally_quoted<'a, O, E, F>(parser: F) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
 where
     E: nom::error::ParseError<&'a str>,
     F: nom::Parser<&'a str, O, E>,
 {
-    delimited(char('"'), parser, char('"'))
+    delimited(opt(char('"')), parser, opt(char('"')))
 }
 
-fn to_markdown_list(head: &str, content: &str) -> Option<String> {
-    lazy_static! {
-        static ref BBCODE_BULLET: Regex = Regex::new(r"\[\*\]\s*").unwrap();
+fn to_markdown_list<'s>(
+    out: &mut Rewriter<'s>,
+    head: ListHead,
+    content: &'s str,
+    options: &ConvertOptions,
+) {
+    let ListHead { ltype, start } = head;
+
+    use ListType::*;
+
+    let mut items = content.split("[*]");
+
+    out.reserve(content.len());
+
+    // keep whatever comes before the first bullet, unless it's just the newline after the opening tag
+    match items.next().unwrap_or_default() {
+        preamble if preamble.trim().is_empty() => {}
+        preamble => out.copy(preamble),
     }
 
-    let Ok(ListHead{ltype, start}) = list_head(head).map(|(_, lh)| lh) else {
-        return None;
+    let mut number = start;
+
+    // everything is written straight into `out`, so that huge lists don't cost an allocation per item
+    for item in items.map(str::trim_start) {
+        let mut lines = item.trim_end_matches(['\r', '\n']).split_inclusive('\n');
+        let marker_start = out.len();
+
+        match ltype {
+            Unordered => out.push_str("- "),
+            Ordered(style) => {
+                style.push_marker(out, number);
+                number = number.saturating_add(1);
+            }
+        }
+
+        let marker_len = out.len() - marker_start;
+        let first = lines.next().unwrap_or_default();
+
+        // items starting with a checkbox are task list items
+        match options.task_markers.strip(first) {
+            Some((checked, rest)) => {
+                out.push_str(if checked { "[x] " } else { "[ ] " });
+                out.copy(rest);
+            }
+            None => out.copy(first),
+        }
+
+        // continuation lines (i.e. nested blocks) must be indented past the marker, or they would end the item
+        for line in lines {
+            if !line.trim().is_empty() {
+                out.extend(iter::repeat_n(' ', marker_len));
+            }
+
+            out.copy(line);
+        }
+
+        out.push('\n');
+    }
+}
+
+fn to_markdown_quote<'s>(out: &mut Rewriter<'s>, text: &'s str) {
+    let text = text.trim_matches(|c| c == '\r' || c == '\n');
+
+    if text.is_empty() {
+        return;
+    }
+
+    for (i, line) in text.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        if i > 0 {
+            out.push('\n');
+        }
+
+        match line.is_empty() {
+            true => out.push('>'),
+            false => {
+                out.push_str("> ");
+                out.copy(line);
+            }
+        }
+    }
+}
+
+/// Converts `[quote]` and `[list]` blocks, innermost first, so that nested blocks compose correctly: a list inside
+/// a quote is rendered before being quoted, and an item containing a block gets the block indented under it.
+fn replace_blocks(mut text: String, mut map: Option<SpanMap>, options: &ConvertOptions) -> Mapped {
+    const BLOCK_ENDS: [(&str, bool); 2] = [("[/quote]", true), ("[/list]", false)];
+
+    let mut from = 0;
+
+    // the first closing tag after `from`, as its range and whether it's a quote
+    let next_end = |text: &str, from: usize| {
+        BLOCK_ENDS
+            .iter()
+            .filter_map(|&(tag, is_quote)| {
+                find_ignore_case(&text[from..], tag)
+                    .map(|pos| (from + pos, from + pos + tag.len(), is_quote))
+            })
+            .min()
     };
 
-    use ListType::*;
+    while let Some((end_start, end_end, is_quote)) = next_end(&text, from) {
+        // the innermost block is the one opened last before the first closing tag
+        let opening = match is_quote {
+            true => quote_starts(&text[..end_start]).last(),
+            false => list_starts(&text[..end_start]).last(),
+        };
 
-    Some(match ltype {
-        Unordered => BBCODE_BULLET.replace_all(content, "- ").to_string(),
-        Ordered(num) => {
-            use ControlFlow::*;
+        let rendered = opening.and_then(|(opening, head)| {
+            let head = match is_quote {
+                true => None,
+                false => Some(list_head(head).ok()?.1),
+            };
 
-            let processed = num
-                .iter_from(start)
-                .try_fold(content.to_string(), |current, it| {
-                    let new = BBCODE_BULLET.replace(&current, &it);
+            let mut out = Rewriter::new(&text[opening.start..end_end], map.is_some());
 
-                    match new != current {
-                        true => Continue(new.to_string()),
-                        false => Break(current),
+            // blocks must start on their own line, e.g. a list opened right after an item's text
+            if opening.start > 0 && !text[..opening.start].ends_with('\n') {
+                out.push('\n');
+            }
+
+            let inner = &text[opening.end..end_start];
+
+            match head {
+                Some(head) => to_markdown_list(&mut out, head, inner, options),
+                None => to_markdown_quote(&mut out, inner),
+            }
+
+            Some((opening.start, out.finish()))
+        });
+
+        match rendered {
+            Some((start, (rendered, block_map))) => {
+                match map.take().zip(block_map) {
+                    Some((old, block_map)) => {
+                        let mut out = Rewriter::new(&text, true);
+
+                        out.copy(&text[..start]);
+                        out.embed(&text[start..end_end], &rendered, Some(block_map));
+                        out.copy(&text[end_end..]);
+
+                        let (new_text, new_map) = out.finish();
+
+                        text = new_text;
+                        map = compose(Some(old), new_map);
                     }
-                });
+                    None => text.replace_range(start..end_end, &rendered),
+                }
 
-            match processed {
-                Continue(s) | Break(s) => s,
+                from = start + rendered.len();
             }
+            None => from = end_end,
         }
+    }
+
+    (text, map)
+}
+
+/// Iterates over the `[quote]` tags in `text`, as their range and their (always empty) attributes.
+fn quote_starts(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    const QUOTE_START: &str = "[quote]";
+
+    let mut from = 0;
+
+    iter::from_fn(move || {
+        let start = from + find_ignore_case(&text[from..], QUOTE_START)?;
+
+        from = start + QUOTE_START.len();
+
+        Some((start..from, ""))
     })
 }
 
-fn to_markdown_quote(text: &str) -> String {
-    text.lines()
-        .map(|line| format!("> {}", line))
-        .intersperse_with(|| "\n".to_owned())
-        .collect()
+/// Iterates over the `[list]` tags in `text`, as their range and their attributes, e.g. ` start="3"`.
+fn list_starts(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    const LIST_START: &str = "[list";
+
+    let mut from = 0;
+
+    iter::from_fn(move || {
+        let start = from + find_ignore_case(&text[from..], LIST_START)?;
+        let head_start = start + LIST_START.len();
+        let head_end = head_start + text[head_start..].find(']')?;
+
+        from = head_end + 1;
+
+        Some((start..from, &text[head_start..head_end]))
+    })
 }
 
-fn replace_bbcode(text: String) -> String {
-    type ReplacerFn = fn(&Captures<'_>) -> String;
-    lazy_static! {
-        static ref REPLACEMENTS: [(Regex, ReplacerFn); 10] = [
-            (
-                Regex::new(r#"(?i)\[url="?(.+?)"?\](.+?)\[/url\]"#).unwrap(),
-                |caps| format!("[{}]({})", &caps[2], &caps[1])
-            ),
-            (
-                Regex::new(r#"(?i)\[url\](.+?)\[/url\]"#).unwrap(),
-                |caps| format!("[]({})", &caps[1])
-            ),
-            (
-                Regex::new(r#"(?mi)^[ \t]*\[big\](.+?)\[/big\][ \t]*$"#).unwrap(),
-                |caps| format!("# {}", &caps[1])
-            ),
-            (
-                Regex::new(r#"(?i)\[cur\](.+?)\[/cur\]"#).unwrap(),
-                |caps| format!("*{}*", &caps[1])
-            ),
-            (
-                Regex::new(r#"(?i)\[b\](.+?)\[/b\]"#).unwrap(),
-                |caps| format!("**{}**", &caps[1])
-            ),
-            (
-                Regex::new(r#"(?i)\[(?:i|cur)\](.+?)\[/(?:i|cur)\]"#).unwrap(),
-                |caps| format!("*{}*", &caps[1])
-            ),
-            (
-                Regex::new(r#"(?i)\[del\](.+?)\[/del\]"#).unwrap(),
-                |caps| format!("~~{}~~", &caps[1])
-            ),
-            (
-                Regex::new(r#"(?i)\[img\](.+?)\[/img\]"#).unwrap(),
-                |caps| format!("![]({})", &caps[1])
-            ),
+/// Links `[url=P]Q[/url]` or, without a destination, `[url]P[/url]`, whose text is the destination itself.
+fn to_markdown_link(dest: Option<&str>, text: &str, style: LinkStyle) -> Replacement {
+    use LinkStyle::*;
+
+    let (open, close, decode) = match (style, dest.map(decode_attribute)) {
+        (Autolink, None) => ("<", ">".into(), true),
+        (_, None) => ("[](", ")".into(), true),
+        (Autolink, Some(dest)) if text == dest => ("<", ">".into(), false),
+        (_, Some(dest)) => ("[", format!("]({dest})").into(), false),
+    };
+
+    Replacement {
+        open: open.into(),
+        close,
+        decode,
+    }
+}
+
+/// Wraps some text between two markers, e.g. `**`.
+fn wrap(marker: &'static str) -> Replacement {
+    Replacement {
+        open: marker.into(),
+        close: marker.into(),
+        decode: false,
+    }
+}
+
+/// Turns a line wrapped in a heading style into a Markdown heading, or returns `None` if no level uses `style`.
+fn to_markdown_heading(style: HeadingStyle, options: &ConvertOptions) -> Option<Replacement> {
+    options.headings.level_of(style).map(|level| Replacement {
+        open: format!(
+            "{} ",
+            "#".repeat(level.min(options.max_heading_level.max(1)))
+        )
+        .into(),
+        close: "".into(),
+        decode: false,
+    })
+}
+
+fn to_markdown_emphasis(options: &ConvertOptions) -> Replacement {
+    wrap(options.emphasis_marker.emphasis())
+}
+
+fn to_markdown_strong(options: &ConvertOptions) -> Replacement {
+    wrap(options.strong_marker.strong())
+}
+
+fn escape_markdown(text: &str, track: bool) -> Mapped {
+    // list bullets are still BBCode at this point, and they must survive until the list pass
+    const BULLET: &str = "[*]";
+    const SPECIAL: &[char] = &['\\', '*', '_', '`', '~', '<', '&'];
+    const LINE_START: &[char] = &['#', '>'];
+
+    let mut ret = Rewriter::new(text, track);
+    let mut at_line_start = true;
+    let mut rest = text;
+
+    ret.reserve(text.len());
+
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with(BULLET) {
+            ret.copy(&rest[..BULLET.len()]);
+            rest = &rest[BULLET.len()..];
+            at_line_start = false;
+
+            continue;
+        }
+
+        if SPECIAL.contains(&c) || (at_line_start && LINE_START.contains(&c)) {
+            ret.push('\\');
+        }
+
+        ret.copy(&rest[..c.len_utf8()]);
+
+        at_line_start = c == '\n' || (at_line_start && c.is_whitespace());
+        rest = &rest[c.len_utf8()..];
+    }
+
+    ret.finish()
+}
+
+/// Escapes what would be read as HTML in some text, which NERDZ shows as it's written, so that `<script>` isn't
+/// run once the generated Markdown is rendered.
+fn escape_html(text: String, track: bool) -> Mapped {
+    if !text.contains(['<', '&']) {
+        let len = text.len();
+
+        return (text, track.then(|| SpanMap::identity(len)));
+    }
+
+    let mut ret = Rewriter::new(&text, track);
+    let mut copied = 0;
+
+    ret.reserve(text.len());
+
+    for (pos, c) in text.match_indices(['<', '&']) {
+        ret.copy(&text[copied..pos]);
+        ret.push_str(match c {
+            "<" => "&lt;",
+            _ => "&amp;",
+        });
+
+        copied = pos + 1;
+    }
+
+    ret.copy(&text[copied..]);
+
+    ret.finish()
+}
+
+/// What the tags of a match are rewritten to, or `None` to leave them as they are.
+type ReplacerFn = fn(&TagMatch<'_>, &ConvertOptions) -> Option<Replacement>;
+
+/// The inline substitutions of a dialect, ready to be matched.
+pub(super) type InlineRules = PatternSet<ReplacerFn>;
+
+/// Builds the inline substitutions for the tags of a dialect, highest priority first.
+fn build_replacements(names: &'static TagNames) -> InlineRules {
+    use TagValue::*;
+
+    let bold = slice::from_ref(&names.bold);
+
+    let mut ret: Vec<(TagPattern, ReplacerFn)> = vec![
+        (
+            // quoted values may contain `]`, unquoted ones extend up to the first `]`
+            TagPattern::new(vec![&["url"]], Any, false),
+            |m, opts| Some(to_markdown_link(m.value, m.content, opts.links)),
+        ),
+        (TagPattern::new(vec![&["url"]], Absent, false), |m, opts| {
+            Some(to_markdown_link(None, m.content, opts.links))
+        }),
+    ];
+
+    if let Some(big) = &names.big {
+        let big = slice::from_ref(big);
+
+        ret.extend([
             (
-                Regex::new(r#"(?si)\[quote\](.+?)\[/quote\]"#).unwrap(),
-                |caps| to_markdown_quote(&caps[1])
+                TagPattern::new(vec![big, bold], Absent, true),
+                (|_, opts| to_markdown_heading(HeadingStyle::BigBold, opts)) as ReplacerFn,
             ),
-            (
-                // parse a BBCode list with start= or type= attributes
-                Regex::new(r#"(?si)\[list(.*?)\](.+?)\[/list\]"#).unwrap(),
-                |caps| match to_markdown_list(&caps[1], &caps[2]) {
-                    Some(s) => s,
-                    None => caps[0].to_owned(),
-                }
-            )
-        ];
+            (TagPattern::new(vec![big], Absent, true), |_, opts| {
+                to_markdown_heading(HeadingStyle::Big, opts)
+                    .or_else(|| Some(to_markdown_strong(opts)))
+            }),
+        ]);
     }
 
-    REPLACEMENTS.iter().fold(text, |cur, (rx, repl)| {
-        use Cow::*;
+    ret.extend([
+        (
+            TagPattern::new(vec![&["size"]], Number, true),
+            (|m, opts| {
+                let size = m.value?.parse().ok()?;
+
+                to_markdown_heading(HeadingStyle::Size(size), opts)
+            }) as ReplacerFn,
+        ),
+        (
+            // the preferred italic tag goes first, so that it's matched before any bold tag it contains
+            TagPattern::new(vec![&names.italic[..1]], Absent, false),
+            |_, opts| Some(to_markdown_emphasis(opts)),
+        ),
+        (TagPattern::new(vec![bold], Absent, false), |_, opts| {
+            Some(to_markdown_strong(opts))
+        }),
+        (
+            TagPattern::new(vec![names.italic], Absent, false),
+            |_, opts| Some(to_markdown_emphasis(opts)),
+        ),
+        (
+            TagPattern::new(vec![names.strike], Absent, false),
+            |_, _| Some(wrap("~~")),
+        ),
+        (TagPattern::new(vec![&["img"]], Absent, false), |_, _| {
+            Some(Replacement {
+                open: "![](".into(),
+                close: ")".into(),
+                decode: true,
+            })
+        }),
+    ]);
+
+    PatternSet::new(ret)
+}
+
+/// The substitutions for the given dialect, built once per dialect.
+pub(super) fn inline_rules(dialect: Dialect) -> &'static InlineRules {
+    use Dialect::*;
+
+    #[cfg(feature = "nerdz")]
+    static NERDZ: Lazy<InlineRules> = Lazy::new(|| build_replacements(Nerdz.names()));
+
+    #[cfg(feature = "phpbb")]
+    static PHPBB: Lazy<InlineRules> = Lazy::new(|| build_replacements(Phpbb.names()));
 
-        match rx.replace_all(&cur, *repl) {
-            Borrowed(_) => cur,
-            Owned(new_string) => new_string,
+    match dialect {
+        #[cfg(feature = "nerdz")]
+        Nerdz => &NERDZ,
+        #[cfg(feature = "phpbb")]
+        Phpbb => &PHPBB,
+    }
+}
+
+fn replace_bbcode(
+    text: String,
+    options: &ConvertOptions,
+    rules: &InlineRules,
+    custom: &CustomRules,
+    track: bool,
+) -> Mapped {
+    let replaced = replace_all(&text, rules, track, |repl, m| repl(m, options));
+
+    let (text, map) = match replaced {
+        Some(replaced) => replaced,
+        None => {
+            let len = text.len();
+
+            (text, track.then(|| SpanMap::identity(len)))
         }
+    };
+
+    let (text, map) = replace_custom(text, map, custom, track);
+
+    replace_blocks(text, map, options)
+}
+
+/// Tracks how many multi-line blocks (`[quote]` and `[list]`) are still open after the given line.
+pub(super) fn block_depth(depth: usize, line: &str) -> usize {
+    let is_block = |tag: &str| {
+        ["quote]", "list"]
+            .iter()
+            .any(|name| strip_prefix_ignore_case(tag, name).is_some())
+    };
+
+    line.split('[')
+        .skip(1)
+        .fold(depth, |depth, tag| match tag.strip_prefix('/') {
+            Some(tag) if is_block(tag) => depth.saturating_sub(1),
+            None if is_block(tag) => depth + 1,
+            _ => depth,
+        })
+}
+
+/// Checks whether `text` contains something that looks like the start of a tag, i.e. a `[` followed by a letter,
+/// `/` or `*`. Brackets in JSON arrays and the like don't count.
+fn may_contain_tags(text: &str) -> bool {
+    let bytes = text.as_bytes();
+
+    text.match_indices('[').any(|(pos, _)| {
+        bytes
+            .get(pos + 1)
+            .is_some_and(|&b| b.is_ascii_alphabetic() || b == b'/' || b == b'*')
     })
 }
 
-fn convert_bbcode(content: &str) -> String {
+/// Same as [`replace_bbcode`], but lines without any tag that are not enclosed in a multi-line block are copied
+/// as-is, without going through the substitution passes at all. This matters a lot for huge lines, such as
+/// minified JSON or logs pasted in a post.
+fn replace_bbcode_lines(
+    text: String,
+    options: &ConvertOptions,
+    rules: &InlineRules,
+    custom: &CustomRules,
+    track: bool,
+) -> Mapped {
+    if !may_contain_tags(&text) {
+        let len = text.len();
+
+        return (text, track.then(|| SpanMap::identity(len)));
+    }
+
+    let mut ret = Rewriter::new(&text, track);
+    let mut pending = 0;
+    let mut depth = 0;
+    let mut pos = 0;
+
+    ret.reserve(text.len());
+
+    for line in text.split_inclusive('\n') {
+        let end = pos + line.len();
+
+        if depth == 0 && !may_contain_tags(line) {
+            if pending < pos {
+                let chunk = &text[pending..pos];
+                let (replaced, map) =
+                    replace_bbcode(chunk.to_owned(), options, rules, custom, track);
+
+                ret.embed(chunk, &replaced, map);
+            }
+
+            ret.copy(line);
+            pending = end;
+        } else {
+            depth = block_depth(depth, line);
+        }
+
+        pos = end;
+    }
+
+    if pending < text.len() {
+        let chunk = &text[pending..];
+        let (replaced, map) = replace_bbcode(chunk.to_owned(), options, rules, custom, track);
+
+        ret.embed(chunk, &replaced, map);
+    }
+
+    ret.finish()
+}
+
+/// Whether `line` holds nothing but a horizontal rule, i.e. `[hr]`, `[hr/]` or a long line of dashes.
+pub(super) fn is_rule(line: &str) -> bool {
+    const MIN_DASHES: usize = 4;
+
+    let line = line.trim();
+
+    line.eq_ignore_ascii_case("[hr]")
+        || line.eq_ignore_ascii_case("[hr/]")
+        || (line.len() >= MIN_DASHES && line.bytes().all(|b| b == b'-'))
+}
+
+/// Turns the lines holding nothing but a horizontal rule into Markdown thematic breaks.
+fn replace_rules(text: String, options: &ConvertOptions, track: bool) -> Mapped {
+    if find_ignore_case(&text, "[hr").is_none() && !text.contains("----") {
+        let len = text.len();
+
+        return (text, track.then(|| SpanMap::identity(len)));
+    }
+
+    let mut ret = Rewriter::new(&text, track);
+    let mut after_blank = true;
+
+    ret.reserve(text.len());
+
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+
+        if !is_rule(body) {
+            ret.copy(line);
+            after_blank = body.trim().is_empty();
+
+            continue;
+        }
+
+        // dashes right below a line of text would turn it into a heading
+        if !after_blank && options.thematic_break == ThematicBreak::Dashes {
+            ret.push('\n');
+        }
+
+        ret.push_str(options.thematic_break.marker());
+        ret.copy(&line[body.len()..]);
+
+        after_blank = false;
+    }
+
+    ret.finish()
+}
+
+/// Turns the superscript labels footnotes are written as, e.g. `[sup][1][/sup]`, back into Markdown's footnote
+/// references, e.g. `[^1]`. Those starting a line and followed by `:` become definitions in turn.
+fn replace_footnotes(text: String, track: bool) -> Mapped {
+    const OPEN: &str = "[sup][";
+    const CLOSE: &str = "][/sup]";
+
+    // labels can't be empty, nor hold whitespace or brackets
+    let label_len = |rest: &str| {
+        let len = rest.find(|c: char| c.is_whitespace() || c == '[' || c == ']')?;
+
+        (len > 0 && strip_prefix_ignore_case(&rest[len..], CLOSE).is_some()).then_some(len)
+    };
+
+    let mut ret = Rewriter::new(&text, track);
+    let mut copied = 0;
+    let mut from = 0;
+
+    while let Some(pos) = find_ignore_case(&text[from..], OPEN) {
+        let start = from + pos;
+        let label_start = start + OPEN.len();
+
+        from = label_start;
+
+        let Some(len) = label_len(&text[label_start..]) else {
+            continue;
+        };
+
+        ret.copy(&text[copied..start]);
+        ret.push_str("[^");
+        ret.copy(&text[label_start..label_start + len]);
+        ret.push(']');
+
+        copied = label_start + len + CLOSE.len();
+        from = copied;
+    }
+
+    if copied == 0 {
+        let len = text.len();
+
+        return (text, track.then(|| SpanMap::identity(len)));
+    }
+
+    ret.copy(&text[copied..]);
+
+    ret.finish()
+}
+
+fn convert_bbcode(
+    content: &str,
+    options: &ConvertOptions,
+    rules: &InlineRules,
+    track: bool,
+) -> Mapped {
+    let (converted, map) = match recover(content, options, track) {
+        Some((recovered, map)) => {
+            let (converted, next) = convert_tags(&recovered, options, rules, track);
+
+            (converted, compose(map, next))
+        }
+        None => convert_tags(content, options, rules, track),
+    };
+
+    if options.links != LinkStyle::Reference {
+        return (converted, map);
+    }
+
+    let mut references = References::default();
+    let (linked, next) = reference_links(&converted, &mut references, track);
+
+    let mut ret = Rewriter::new(&linked, track);
+
+    ret.copy(&linked);
+    ret.push_str(&references.suffix(linked.ends_with('\n'), "\n"));
+
+    let (defined, definitions) = ret.finish();
+
+    (defined, compose(compose(map, next), definitions))
+}
+
+/// Converts BBCode whose dangling tags, if any, have already been recovered.
+fn convert_tags(
+    content: &str,
+    options: &ConvertOptions,
+    rules: &InlineRules,
+    track: bool,
+) -> Mapped {
     use TextChunk::*;
 
-    slurp_codetags(content)
-        .into_iter()
-        .fold(String::new(), |mut s, blk| {
-            let nxt = match blk {
-                Chars(text) => replace_bbcode(text.into_owned()),
-                Code {
-                    kind,
-                    lang,
-                    content,
-                } => code_str(kind, lang, content),
-            };
+    let custom = CustomRules::new(options);
+    let mut ret = Rewriter::new(content, track);
 
-            s.push_str(&nxt);
+    for chunk in slurp_codetags(content, options) {
+        match chunk {
+            Chars(piece) => {
+                // smileys go first, as they may be made of tags
+                let (text, smileys_map) = replace_smileys(piece, options, track);
+                let (text, links_map) = sanitize_links(text, options, track);
 
-            s
-        })
+                let (text, escape_map) = match options.escaping {
+                    EscapePolicy::Verbatim if options.link_policy.is_restrictive() => {
+                        escape_html(text, track)
+                    }
+                    EscapePolicy::Verbatim => {
+                        let len = text.len();
+
+                        (text, track.then(|| SpanMap::identity(len)))
+                    }
+                    EscapePolicy::Markdown => escape_markdown(&text, track),
+                };
+
+                let (text, synonyms_map) = replace_synonyms(text, options, track);
+                let (text, footnotes_map) = replace_footnotes(text, track);
+                let (text, rules_map) = replace_rules(text, options, track);
+                let (text, map) = replace_bbcode_lines(text, options, rules, &custom, track);
+
+                let before = compose(compose(smileys_map, links_map), escape_map);
+                let before = compose(compose(before, synonyms_map), footnotes_map);
+                let before = compose(before, rules_map);
+                ret.embed(piece, &text, compose(before, map));
+            }
+            Code {
+                kind,
+                lang,
+                content,
+                ..
+            } => push_code(&mut ret, kind, lang, content),
+        }
+    }
+
+    ret.finish()
 }
 
 /// Writes the given content to the given writer, attempting to convert NERDZ BBCode to Markdown.
@@ -509,10 +1254,16 @@ fn convert_bbcode(content: &str) -> String {
 /// - `[list type="i"][*]P[/list]` -> i. P (multiline, with optional `start="N"`)
 /// - `[list type="I"][*]P[/list]` -> I. P (multiline, with optional `start="N"`)
 /// - `[list start="N"][*]P[/list]` -> N. P (multiline, optionally with `type="1"`)
-/// 
+/// - `<newline>[hr]<newline>` -> --- (also `[hr/]` and lines of four or more dashes)
+/// - `[sup][L][/sup]` -> [^L] (a footnote, as written when converting Markdown to BBCode, defined by a line starting
+///   with `[sup][L][/sup]: `)
+/// - `[code=L]P[/code]` -> a block of code in language L, with everything in it kept verbatim; opening tags longer than
+///   256 bytes, language included, are left as text, and so is what they enclose
+///
 /// # Examples
 ///
 /// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
 /// use std::{error::Error, io::{self, Write}, str};
 /// use bibi::dump_markdown;
 ///
@@ -525,7 +1276,497 @@ fn convert_bbcode(content: &str) -> String {
 ///     assert_eq!(str::from_utf8(&writer)?, "**Hello** ~~everybody~~");
 ///
 ///     Ok(())
-/// } 
-pub fn dump_markdown(mut writer: impl io::Write, content: &str) -> io::Result<()> {
-    write!(writer, "{}", convert_bbcode(content))
+/// }
+/// # main().unwrap() }
+/// ```
+#[cfg(feature = "std")]
+pub fn dump_markdown(writer: impl io::Write, content: &str) -> Result<()> {
+    dump_markdown_with(writer, content, &ConvertOptions::default())
+}
+
+/// Same as [`dump_markdown`], but allows tweaking the generated Markdown through the given [`ConvertOptions`].
+///
+/// # Examples
+///
+/// ```
+/// use std::{error::Error, io::{self, Write}, str};
+/// use bibi::{dump_markdown_with, ConvertOptions, EmphasisMarker, EscapePolicy, LinkStyle};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let mut writer = Vec::new();
+///     let options = ConvertOptions {
+///         emphasis_marker: EmphasisMarker::Underscore,
+///         links: LinkStyle::Autolink,
+///         escaping: EscapePolicy::Markdown,
+///         ..Default::default()
+///     };
+///
+///     dump_markdown_with(
+///         &mut writer,
+///         "[b]snake_case[/b] [i]at[/i] [url]https://nerdz.eu[/url]",
+///         &options,
+///     )?;
+///
+///     assert_eq!(str::from_utf8(&writer)?, r"**snake\_case** _at_ <https://nerdz.eu>");
+///
+///     Ok(())
+/// }
+#[cfg(feature = "std")]
+pub fn dump_markdown_with(
+    writer: impl io::Write,
+    content: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    convert(IoWriter(writer), content, options)
+}
+
+/// Same as [`dump_markdown`], but writes into a [`fmt::Write`](std::fmt::Write), such as a `String`.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// use std::error::Error;
+/// use bibi::dump_markdown_fmt;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let mut post = String::from("Quoting myself: ");
+///     dump_markdown_fmt(&mut post, "[del]yolo[/del]")?;
+///
+///     assert_eq!(post, "Quoting myself: ~~yolo~~");
+///
+///     Ok(())
+/// }
+/// # main().unwrap() }
+/// ```
+pub fn dump_markdown_fmt(writer: impl fmt::Write, content: &str) -> Result<()> {
+    dump_markdown_fmt_with(writer, content, &ConvertOptions::default())
+}
+
+/// Same as [`dump_markdown_fmt`], but allows tweaking the generated Markdown through the given [`ConvertOptions`].
+pub fn dump_markdown_fmt_with(
+    writer: impl fmt::Write,
+    content: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    convert(FmtWriter(writer), content, options)
+}
+
+fn convert(mut writer: impl WriteFmt, content: &str, options: &ConvertOptions) -> Result<()> {
+    writer.write_str(&to_markdown_with(content, options))
+}
+
+/// Converts the given NERDZ BBCode to Markdown, returning it as a string. See [`dump_markdown`] for details.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "nerdz")] {
+/// assert_eq!(bibi::to_markdown("[cur]Hello[/cur]"), "*Hello*");
+///
+/// // nested blocks are rendered from the inside out
+/// assert_eq!(
+///     bibi::to_markdown("[quote][list][*]a\n[*]b[/list][/quote]"),
+///     "> - a\n> - b"
+/// );
+///
+/// // code is found anywhere, however many characters it's surrounded by or holds
+/// assert_eq!(
+///     bibi::to_markdown("🎉[c]🦀 ok[/c]é\n[code=rust]let ñ = '🎉';[/code]"),
+///     "🎉`🦀 ok`é\n```rust\nlet ñ = '🎉';\n```\n"
+/// );
+/// # }
+/// ```
+pub fn to_markdown(content: &str) -> String {
+    to_markdown_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`to_markdown`], but allows tweaking the generated Markdown through the given [`ConvertOptions`].
+pub fn to_markdown_with(content: &str, options: &ConvertOptions) -> String {
+    markdown_of(content, options, inline_rules(options.dialect))
+}
+
+/// Same as [`to_markdown`], but reads the BBCode as a single inline run, such as a title or a status message, and
+/// returns Markdown on a single line: line breaks become spaces, and `[big]` is bold rather than a heading. Blocks
+/// such as quotes, lists and code have no inline form, and are best left out of such snippets.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "nerdz")] {
+/// assert_eq!(bibi::to_markdown_inline("[big]Welcome[/big]\n"), "**Welcome**");
+///
+/// assert_eq!(
+///     bibi::to_markdown_inline("[b]Welcome[/b] to\n[url=https://nerdz.eu]NERDZ[/url]\n"),
+///     "**Welcome** to [NERDZ](https://nerdz.eu)"
+/// );
+/// # }
+/// ```
+pub fn to_markdown_inline(content: &str) -> String {
+    to_markdown_inline_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`to_markdown_inline`], but allows tweaking the generated Markdown through the given [`ConvertOptions`].
+/// Its headings are ignored, since snippets have none.
+pub fn to_markdown_inline_with(content: &str, options: &ConvertOptions) -> String {
+    let options = ConvertOptions {
+        headings: HeadingMap::uniform(HeadingStyle::Plain),
+        // references would end up on the same line as the text
+        links: match options.links {
+            LinkStyle::Reference => LinkStyle::Inline,
+            links => links,
+        },
+        ..options.clone()
+    };
+
+    let markdown = to_markdown_with(&single_line(content), &options);
+
+    single_line(markdown.trim()).into_owned()
+}
+
+/// Converts BBCode to Markdown, matching its inline tags against `rules`, which must belong to the dialect of
+/// `options`.
+pub(super) fn markdown_of(content: &str, options: &ConvertOptions, rules: &InlineRules) -> String {
+    if options.annotate_losses {
+        let (markdown, map) = mapped_markdown(content, options, rules);
+
+        return annotate(content, &markdown, &map, options);
+    }
+
+    let line_ending = options.line_ending.resolve(content);
+    let (converted, _) = convert_bbcode(content, options, rules, false);
+
+    match normalize_newlines(&converted, line_ending) {
+        Cow::Borrowed(_) => converted,
+        Cow::Owned(normalized) => normalized,
+    }
+}
+
+/// Same as [`to_markdown`], but also returns which part of the BBCode produced which part of the Markdown, as
+/// [`Spans`], e.g. for previews in editors.
+///
+/// Text copied as-is gets its own spans, while tags map to whatever they've been rewritten to. Parts of the input
+/// that produced no output at all have no span.
+///
+/// # Examples
+///
+/// ```
+/// let (markdown, spans) = bibi::to_markdown_spans("[b]Hi[/b] there");
+///
+/// assert_eq!(markdown, "**Hi** there");
+/// assert_eq!(spans, [(0..3, 0..2), (3..5, 2..4), (5..9, 4..6), (9..15, 6..12)]);
+/// ```
+pub fn to_markdown_spans(content: &str) -> (String, Spans) {
+    to_markdown_spans_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`to_markdown_spans`], but allows tweaking the generated Markdown through the given [`ConvertOptions`].
+pub fn to_markdown_spans_with(content: &str, options: &ConvertOptions) -> (String, Spans) {
+    let (markdown, map) = markdown_map(content, options);
+
+    (markdown, map.into_spans())
+}
+
+/// Converts BBCode to Markdown, returning the [`SpanMap`] of the conversion.
+pub(super) fn markdown_map(content: &str, options: &ConvertOptions) -> (String, SpanMap) {
+    mapped_markdown(content, options, inline_rules(options.dialect))
+}
+
+fn mapped_markdown(
+    content: &str,
+    options: &ConvertOptions,
+    rules: &InlineRules,
+) -> (String, SpanMap) {
+    let line_ending = options.line_ending.resolve(content);
+    let (converted, map) = convert_bbcode(content, options, rules, true);
+    let map = map.expect("spans are always tracked here");
+
+    match normalize_newlines(&converted, line_ending) {
+        Cow::Borrowed(_) => (converted, map),
+        Cow::Owned(normalized) => {
+            let newlines = map_newlines(&converted, line_ending);
+
+            (normalized, map.then(&newlines))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String, vec::Vec};
+
+    use super::{to_markdown_with, CodeKind, CodeScanner, NumberingStyle, MAX_CODE_TAG};
+    use crate::{bbcode::Dialect, options::ConvertOptions};
+
+    /// Conversions every dialect does the same way.
+    const SHARED: &[(&str, &str)] = &[
+        ("[b]a[/b]", "**a**"),
+        ("[B]a[/b]", "**a**"),
+        ("[i]a[/i]", "*a*"),
+        ("[url]http://a.b[/url]", "[](http://a.b)"),
+        ("[url=http://a.b]x[/url]", "[x](http://a.b)"),
+        ("[url=\"http://a.b/]\"]x[/url]", "[x](http://a.b/])"),
+        ("[img]http://a.b/c.png[/img]", "![](http://a.b/c.png)"),
+        ("[code]a\n*b*[/code]", "```\na\n*b*\n```\n"),
+        ("[code=rust]a[/code]", "```rust\na\n```\n"),
+        ("[quote]a\nb[/quote]", "> a\n> b"),
+        ("[list][*]a[*]b[/list]", "- a\n- b\n"),
+        ("[list=1][*]a[*]b[/list]", "1. a\n2. b\n"),
+        ("[list type=\"a\"][*]a[*]b[/list]", "a. a\nb. b\n"),
+        ("[list start=\"3\"][*]a[/list]", "3. a\n"),
+        ("x\n[hr]\ny", "x\n\n---\ny"),
+        ("x\n[size=20]a[/size]\ny", "x\n[size=20]a[/size]\ny"),
+        // tags are paired with the closest closing tag on the same line, and crossing ones are converted anyway
+        ("[b]a[b]b[/b]c[/b]", "**a[b]b**c[/b]"),
+        ("[b]a\nb[/b]", "[b]a\nb[/b]"),
+        ("[i][b]a[/i][/b]", "***a***"),
+        ("[b][i]a[/b][/i]", "***a***"),
+        // the text of a link is never read again looking for tags
+        ("[url=http://x]i]a[/i][/url]", "[i]a[/i]](http://x)"),
+        ("[quote=x]a[/quote]", "[quote=x]a[/quote]"),
+        ("[u]a[/u]", "[u]a[/u]"),
+    ];
+
+    fn assert_converts(dialect: Dialect, cases: &[(&str, &str)]) {
+        let options = ConvertOptions {
+            dialect,
+            ..Default::default()
+        };
+
+        for (bbcode, markdown) in SHARED.iter().chain(cases) {
+            assert_eq!(to_markdown_with(bbcode, &options), *markdown, "{bbcode:?}");
+        }
+    }
+
+    #[cfg(feature = "nerdz")]
+    #[test]
+    fn nerdz_replacements() {
+        assert_converts(
+            Dialect::Nerdz,
+            &[
+                ("[cur]a[/cur]", "*a*"),
+                ("[del]a[/del]", "~~a~~"),
+                ("[s]a[/s]", "[s]a[/s]"),
+                ("[strike]a[/strike]", "[strike]a[/strike]"),
+                ("x\n[big]a[/big]\ny", "x\n# a\ny"),
+                ("x\n[big][b]a[/b][/big]\ny", "x\n# **a**\ny"),
+                ("[big]a[/big] b", "[big]a[/big] b"),
+                ("[c]a*b[/c]", "`a*b`"),
+                ("[c=js]a[/c]", "`a`"),
+            ],
+        );
+    }
+
+    #[cfg(feature = "phpbb")]
+    #[test]
+    fn phpbb_replacements() {
+        assert_converts(
+            Dialect::Phpbb,
+            &[
+                ("[cur]a[/cur]", "[cur]a[/cur]"),
+                ("[del]a[/del]", "[del]a[/del]"),
+                ("[s]a[/s]", "~~a~~"),
+                ("[strike]a[/strike]", "~~a~~"),
+                ("x\n[big]a[/big]\ny", "x\n[big]a[/big]\ny"),
+                ("x\n[big][b]a[/b][/big]\ny", "x\n[big]**a**[/big]\ny"),
+                ("[c]a*b[/c]", "[c]a*b[/c]"),
+            ],
+        );
+    }
+
+    /// The code blocks found in `content`, as they're written and with their language.
+    fn scan(content: &str) -> Vec<(&str, CodeKind, &str)> {
+        CodeScanner::new(content, &ConvertOptions::default())
+            .map(|(range, kind, lang)| (&content[range], kind, lang))
+            .collect()
+    }
+
+    #[test]
+    fn code_around_emoji() {
+        use CodeKind::*;
+
+        assert_eq!(
+            scan("😀[code=rust]fn 🦀() {}[/code]😀[code]👍🏽\n\n🏳️‍🌈[/code]"),
+            [
+                ("[code=rust]fn 🦀() {}[/code]", Multiline, "rust"),
+                ("[code]👍🏽\n\n🏳️‍🌈[/code]", Multiline, ""),
+            ]
+        );
+        assert_eq!(
+            scan("[code=🦀]x[/code][code=\"日本語\"]y[/code]"),
+            [
+                ("[code=🦀]x[/code]", Multiline, "🦀"),
+                ("[code=\"日本語\"]y[/code]", Multiline, "日本語"),
+            ]
+        );
+        assert_eq!(scan("[code😀]x[/code] [code=]x[/code] [code=😀"), []);
+    }
+
+    #[cfg(feature = "nerdz")]
+    #[test]
+    fn inline_code_around_emoji() {
+        use CodeKind::*;
+
+        assert_eq!(
+            scan("[c]🦀[/c][c=js]🎉\r\n[/c] [c]a[/c]"),
+            [("[c]🦀[/c]", Inline, ""), ("[c]a[/c]", Inline, "")]
+        );
+    }
+
+    #[test]
+    fn adversarial_code() {
+        use CodeKind::*;
+
+        // the first closing tag ends the block, and nothing before an opening tag does
+        assert_eq!(
+            scan("[/code][code][code]x[/code][/code]"),
+            [("[code][code]x[/code]", Multiline, "")]
+        );
+        // the `]` of a closing tag doesn't end an opening one
+        assert_eq!(scan("[code=x[/code]"), []);
+        assert_eq!(
+            scan("[code=a]b[code=x[/code]"),
+            [("[code=a]b[code=x[/code]", Multiline, "a")]
+        );
+        assert_eq!(scan("[code]x"), []);
+        // nor does a language hold other tags
+        assert_eq!(scan("[code=[b]x[/code]"), []);
+        assert_eq!(
+            scan("[code=[code]x[/code]"),
+            [("[code]x[/code]", Multiline, "")]
+        );
+
+        // lots of tags that are never closed, or never end, take a single pass
+        let content = "[code=".repeat(50_000) + "[code]x[/code]" + &"[code]".repeat(50_000);
+        assert_eq!(scan(&content), [("[code]x[/code]", Multiline, "")]);
+
+        let options = ConvertOptions::default();
+        let mut scanner = CodeScanner::new(&content, &options);
+        scanner.by_ref().for_each(drop);
+        assert!(scanner.unclosed);
+    }
+
+    #[test]
+    fn code_tags_have_a_maximum_length() {
+        // `[code=` and `]` take 7 bytes, the rest is a language with multibyte characters
+        let tag = |lang_len| {
+            let lang = "🦀".repeat(lang_len / 4) + &"a".repeat(lang_len % 4);
+
+            ["[code=", &lang, "][b]x[/b][/code]"].concat()
+        };
+
+        let longest = tag(MAX_CODE_TAG - 7);
+        assert_eq!(longest.find(']'), Some(MAX_CODE_TAG - 1));
+        assert_eq!(scan(&longest).len(), 1);
+
+        let too_long = tag(MAX_CODE_TAG - 6);
+        assert_eq!(scan(&too_long), []);
+
+        // what a tag too long would have enclosed is converted as any other text
+        let options = ConvertOptions::default();
+        assert!(super::to_markdown_with(&longest, &options).ends_with("\n[b]x[/b]\n```\n"));
+        assert!(super::to_markdown_with(&too_long, &options).ends_with("]**x**[/code]"));
+    }
+
+    #[test]
+    fn superscript_labels_are_footnotes() {
+        let options = ConvertOptions::default();
+
+        assert_eq!(
+            to_markdown_with(
+                "a[sup][1][/sup] [SUP][x_y][/Sup]\n[sup][1][/sup]: b",
+                &options
+            ),
+            "a[^1] [^x_y]\n[^1]: b"
+        );
+        // anything else in superscript is left alone, and so is code
+        assert_eq!(
+            to_markdown_with(
+                "mc[sup]2[/sup] [sup][a b][/sup] [sup][][/sup] [sup][x][/sub]",
+                &options
+            ),
+            "mc[sup]2[/sup] [sup][a b][/sup] [sup][][/sup] [sup][x][/sub]"
+        );
+        assert_eq!(
+            to_markdown_with("[code][sup][1][/sup][/code]", &options),
+            "```\n[sup][1][/sup]\n```\n"
+        );
+    }
+
+    fn marker(style: NumberingStyle, n: i64) -> String {
+        let mut ret = String::new();
+        style.push_marker(&mut ret, n);
+
+        ret
+    }
+
+    #[test]
+    fn alpha_numbering_is_bijective() {
+        use NumberingStyle::*;
+
+        let markers: Vec<_> = [1, 2, 26, 27, 28, 52, 53, 702, 703, 18_278, 18_279]
+            .into_iter()
+            .map(|n| marker(LowerAlpha, n))
+            .collect();
+        assert_eq!(
+            markers,
+            [
+                "a. ", "b. ", "z. ", "aa. ", "ab. ", "az. ", "ba. ", "zz. ", "aaa. ", "zzz. ",
+                "aaaa. "
+            ]
+        );
+
+        assert_eq!(marker(UpperAlpha, 702), "ZZ. ");
+        assert_eq!(marker(UpperAlpha, 703), "AAA. ");
+        assert_eq!(marker(UpperAlpha, i64::MAX), "CRPXNLSKVLJFHG. ");
+
+        // there are no letters for zero or negative numbers
+        assert_eq!(marker(LowerAlpha, 0), "0. ");
+        assert_eq!(marker(UpperAlpha, -27), "-27. ");
+        assert_eq!(marker(LowerAlpha, i64::MIN), format!("{}. ", i64::MIN));
+    }
+
+    #[test]
+    fn roman_numbering_falls_back_to_decimals() {
+        use NumberingStyle::*;
+
+        assert_eq!(marker(LowerRoman, 1), "i. ");
+        assert_eq!(marker(LowerRoman, 4), "iv. ");
+        assert_eq!(marker(UpperRoman, 1994), "MCMXCIV. ");
+        assert_eq!(marker(UpperRoman, 3999), "MMMCMXCIX. ");
+        assert_eq!(marker(UpperRoman, 4000), "4000. ");
+        assert_eq!(marker(LowerRoman, 0), "0. ");
+        assert_eq!(marker(LowerRoman, -4), "-4. ");
+    }
+
+    #[test]
+    fn numbering_goes_on_across_styles_and_signs() {
+        let options = ConvertOptions::default();
+
+        for (bbcode, markdown) in [
+            (
+                "[list type=\"a\" start=\"26\"][*]x[*]y[/list]",
+                "z. x\naa. y\n",
+            ),
+            (
+                "[list type=\"A\" start=\"702\"][*]x[*]y[/list]",
+                "ZZ. x\nAAA. y\n",
+            ),
+            (
+                "[list type=\"i\" start=\"3998\"][*]x[*]y[*]z[/list]",
+                "mmmcmxcviii. x\nmmmcmxcix. y\n4000. z\n",
+            ),
+            ("[list start=\"0\"][*]x[*]y[/list]", "0. x\n1. y\n"),
+            (
+                "[list type=\"a\" start=\"-1\"][*]x[*]y[*]z[/list]",
+                "-1. x\n0. y\na. z\n",
+            ),
+            (
+                "[list type=\"I\" start=\"-1\"][*]x[*]y[*]z[/list]",
+                "-1. x\n0. y\nI. z\n",
+            ),
+        ] {
+            assert_eq!(to_markdown_with(bbcode, &options), markdown, "{bbcode:?}");
+        }
+    }
 }
@@ -0,0 +1,330 @@
+//! Recovery of the tags that are opened but never closed, such as `[b]` in `[b]oops`, which would otherwise be left
+//! as they are in the middle of the converted text. What becomes of them is up to [`UnclosedTags`].
+
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{cmp::Reverse, ops::Range};
+
+use crate::{
+    bbcode::{
+        custom::is_custom,
+        report::{is_code_tag, is_task_marker, CodeEnds, VOID_TAGS},
+        smiley::tag_smiley_end,
+        span::{Mapped, Rewriter},
+        supported_tags,
+        synonym::synonym_of,
+        tokenize, Direction, Token, TokenKind,
+    },
+    options::{ConvertOptions, UnclosedTags},
+};
+
+/// Tags holding blocks, which may span more than one paragraph.
+const BLOCK_TAGS: &[&str] = &["quote", "list"];
+
+/// Whether `name` is a tag holding a block, or a list item.
+fn is_block(name: &str) -> bool {
+    name == "*"
+        || BLOCK_TAGS
+            .iter()
+            .any(|block| block.eq_ignore_ascii_case(name))
+}
+
+/// Where the lines of `text`, starting at `offset` in the whole content, end.
+fn line_ends(text: &str, offset: usize) -> impl Iterator<Item = usize> + '_ {
+    text.match_indices('\n').map(move |(pos, _)| offset + pos)
+}
+
+/// A tag that's opened but never closed.
+pub(super) struct Dangling<'c> {
+    /// Where the opening tag is, along with its attributes.
+    pub span: Range<usize>,
+    /// The name of the tag, as written.
+    pub name: &'c str,
+    /// The name of the tag the written one stands for, if it's a synonym.
+    pub tag: &'c str,
+    /// Where the tag is closed when recovering it: at the end of its line, for inline tags, or where the tag
+    /// enclosing it is closed, or where the input ends, whichever comes first. Inline tags never span blocks, so
+    /// their lines also end where a block, a list item or code starts or ends.
+    pub close_at: usize,
+}
+
+/// The tags of some content that the converter can't pair with a counterpart, and thus leaves as they are.
+#[derive(Default)]
+pub(super) struct Unpaired<'c> {
+    /// The tags the dialect doesn't know, opening or closing, as their offset and their name as written.
+    pub unknown: Vec<(usize, &'c str)>,
+    /// The code tags that are never closed, as their offset and name.
+    pub unclosed_code: Vec<(usize, &'c str)>,
+    /// The tags that are opened but never closed, in the order they're opened.
+    pub dangling: Vec<Dangling<'c>>,
+    /// The closing tags that don't close anything, as their offset and the name of the tag they stand for.
+    pub stray: Vec<(usize, &'c str)>,
+}
+
+/// A tag still open while looking for the dangling ones.
+struct Open<'c> {
+    span: Range<usize>,
+    written: &'c str,
+    /// The name of the tag the written one stands for, if it's a synonym.
+    name: &'c str,
+    /// Where the first tag enclosing this one is closed, if it is.
+    limit: Option<usize>,
+}
+
+/// Finds the tags of `content` that the converter can't pair, pairing them as it does: inline tags are only
+/// closed on the line they're opened on, with no code in between, while quotes and lists may span any number of
+/// lines. Whatever is within code is left out, and so are the tags smileys are made of, which are never converted
+/// as tags.
+///
+/// This is what both recovering and reporting lost tags rely on, so that they agree with the converter, whether
+/// the content is converted at once or a paragraph at a time.
+pub(super) fn unpaired<'c>(content: &'c str, options: &'c ConvertOptions) -> Unpaired<'c> {
+    let known = supported_tags(Direction::ToMarkdown, options.dialect);
+
+    let mut ret = Unpaired::default();
+    let mut open: Vec<Open<'c>> = vec![];
+    // where lines end, in order, as far as inline tags are concerned
+    let mut breaks = vec![];
+    // where the last line break or code tag is, which inline tags can't be paired across
+    let mut barrier = 0;
+    let mut code_ends = CodeEnds::default();
+    let mut skip_to = 0;
+    // where the last list item starts its content, to tell its checkbox, such as `[x]`, apart from a tag
+    let mut item_start = None;
+
+    for Token { kind, span } in tokenize(content) {
+        let (written, is_close) = match kind {
+            _ if span.start < skip_to => continue,
+            TokenKind::Text(text) => {
+                breaks.extend(line_ends(text, span.start));
+
+                if let Some(pos) = text.rfind('\n') {
+                    barrier = span.start + pos;
+                }
+
+                continue;
+            }
+            TokenKind::Open { .. } if is_task_marker(content, item_start, span.start, options) => {
+                continue
+            }
+            TokenKind::Open { name } => (name, false),
+            TokenKind::Close { name } => (name, true),
+            _ => continue,
+        };
+
+        // the tags smileys are made of are converted along with them
+        if let Some(end) = tag_smiley_end(content, span.start, options).filter(|_| !is_close) {
+            skip_to = end;
+            continue;
+        }
+
+        if written == "*" {
+            item_start = Some(span.end);
+        }
+
+        let name = synonym_of(written, options).unwrap_or(written);
+
+        if is_block(name) || is_code_tag(name, options) {
+            breaks.push(span.start);
+        }
+
+        if !(known.iter().any(|tag| tag.matches(name)) || is_custom(name, options)) {
+            ret.unknown.push((span.start, written));
+
+            continue;
+        }
+
+        if VOID_TAGS.contains(&name) {
+            continue;
+        }
+
+        if is_close {
+            let Some(pos) = open
+                .iter()
+                .rposition(|opened| opened.name.eq_ignore_ascii_case(name))
+                // inline tags can't be paired across lines or code
+                .filter(|&pos| is_block(name) || open[pos].span.end > barrier)
+            else {
+                ret.stray.push((span.start, name));
+
+                continue;
+            };
+
+            open.remove(pos);
+
+            // anything still open within the tag can't be closed any later than here
+            for inner in &mut open[pos..] {
+                inner.limit.get_or_insert(span.start);
+            }
+
+            continue;
+        }
+
+        if is_code_tag(name, options) {
+            barrier = span.start;
+
+            match code_ends.find(content, span.end, name) {
+                Some(end) => skip_to = end,
+                None => ret.unclosed_code.push((span.start, name)),
+            }
+
+            continue;
+        }
+
+        open.push(Open {
+            span,
+            written,
+            name,
+            limit: None,
+        });
+    }
+
+    ret.dangling = open
+        .into_iter()
+        .map(|tag| {
+            let limit = tag.limit.unwrap_or(content.len());
+
+            let end = match is_block(tag.name) {
+                true => limit,
+                false => {
+                    let next = breaks.partition_point(|&pos| pos < tag.span.end);
+
+                    breaks.get(next).map_or(limit, |&pos| pos.min(limit))
+                }
+            };
+
+            // the tag is closed right after its content, rather than after the line break ending it
+            let close_at = tag.span.end + content[tag.span.end..end].trim_end().len();
+
+            Dangling {
+                span: tag.span,
+                name: tag.written,
+                tag: tag.name,
+                close_at,
+            }
+        })
+        .collect();
+
+    ret
+}
+
+/// Closes or removes the dangling tags of `content` as told by [`ConvertOptions::unclosed`], returning `None` if
+/// there's nothing to recover.
+pub(super) fn recover(content: &str, options: &ConvertOptions, track: bool) -> Option<Mapped> {
+    if options.unclosed == UnclosedTags::Keep {
+        return None;
+    }
+
+    let dangling = unpaired(content, options).dangling;
+
+    if dangling.is_empty() {
+        return None;
+    }
+
+    // what replaces which part of the content, along with where the tag it's about is opened
+    let mut edits: Vec<(Range<usize>, String, usize)> = dangling
+        .into_iter()
+        .map(|tag| match options.unclosed {
+            // tags without any content are just dropped, rather than becoming empty markup
+            UnclosedTags::Close if tag.close_at > tag.span.end => (
+                tag.close_at..tag.close_at,
+                format!("[/{}]", tag.name),
+                tag.span.start,
+            ),
+            _ => (tag.span.clone(), String::new(), tag.span.start),
+        })
+        .collect();
+
+    // tags closed at the same place are closed from the innermost one, i.e. the last one opened
+    edits.sort_by_key(|(range, _, opened)| (range.start, range.end, Reverse(*opened)));
+
+    let mut ret = Rewriter::new(content, track);
+    let mut copied = 0;
+
+    ret.reserve(content.len() + edits.len() * 8);
+
+    for (range, text, _) in edits {
+        ret.copy(&content[copied..range.start]);
+        ret.push_str(&text);
+
+        copied = range.end;
+    }
+
+    ret.copy(&content[copied..]);
+
+    Some(ret.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use crate::{to_markdown_with, ConvertOptions, UnclosedTags};
+
+    fn recovered(bbcode: &str, unclosed: UnclosedTags) -> String {
+        let options = ConvertOptions {
+            unclosed,
+            ..Default::default()
+        };
+
+        to_markdown_with(bbcode, &options)
+    }
+
+    #[test]
+    fn inline_tags_are_closed_where_their_line_ends() {
+        use UnclosedTags::*;
+
+        assert_eq!(recovered("[b]a\nb", Close), "**a**\nb");
+        assert_eq!(recovered("[b]a\nb", Strip), "a\nb");
+        assert_eq!(recovered("[b]a [i]b[/i]\n\nc", Close), "**a *b***\n\nc");
+    }
+
+    #[test]
+    fn tags_crossing_lines_are_dangling() {
+        use UnclosedTags::*;
+
+        assert_eq!(recovered("[b]a\n\nb[/b]", Keep), "[b]a\n\nb[/b]");
+        assert_eq!(recovered("[b]a\n\nb[/b]", Close), "**a**\n\nb[/b]");
+        assert_eq!(recovered("[b]a\n\nb[/b]", Strip), "a\n\nb[/b]");
+        assert_eq!(recovered("[b]a\nb[/b]", Close), "**a**\nb[/b]");
+        assert_eq!(
+            recovered("[quote]a\n\n[b]b[/quote]\n\nc[/b]", Close),
+            "> a\n>\n> **b**\n\nc[/b]"
+        );
+        assert_eq!(
+            recovered("[quote]a\n\n[b]b[/quote]\n\nc[/b]", Strip),
+            "> a\n>\n> b\n\nc[/b]"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "nerdz")]
+    fn tags_crossing_code_are_dangling() {
+        assert_eq!(
+            recovered("[b]a [c]x[/c] b[/b]", UnclosedTags::Close),
+            "**a** `x` b[/b]"
+        );
+    }
+
+    #[test]
+    fn paired_tags_are_left_alone() {
+        use UnclosedTags::*;
+
+        for unclosed in [Close, Strip] {
+            assert_eq!(recovered("[b]a[/b]\n[b]b[/b]", unclosed), "**a**\n**b**");
+            assert_eq!(recovered("[quote]a\n\nb[/quote]", unclosed), "> a\n>\n> b");
+            assert_eq!(recovered("[quote][b]a[/quote][/b]", unclosed), "> **a**");
+        }
+    }
+
+    #[test]
+    fn blocks_are_closed_where_the_input_ends() {
+        assert_eq!(
+            recovered("[quote]a\n\nb", UnclosedTags::Close),
+            "> a\n>\n> b"
+        );
+        assert_eq!(
+            recovered("[list]\n[*][b]a\n[*]b", UnclosedTags::Close),
+            "- **a**\n- b\n"
+        );
+    }
+}
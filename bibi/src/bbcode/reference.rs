@@ -0,0 +1,291 @@
+//! Reference-style links, i.e. `[Q][1]` along with a `[1]: P` definition at the end of the document, which the
+//! inline links of the generated Markdown become with [`LinkStyle::Reference`](crate::LinkStyle::Reference).
+//!
+//! Links are found by a pass over the generated Markdown, so that those nested within any construct are found alike.
+//! It's no Markdown parser: it only knows enough to skip code and escapes and to match brackets.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::ops::Range;
+
+use crate::bbcode::span::{Mapped, Rewriter};
+
+/// The destinations of the links turned into references so far, numbered from 1 in the order they're found. A
+/// destination linked more than once is only numbered once.
+#[derive(Clone, Debug, Default)]
+pub(super) struct References {
+    /// The labels of the destinations, each with its title, as written.
+    labels: BTreeMap<String, usize>,
+    definitions: Vec<String>,
+}
+
+impl References {
+    /// The label of a destination, numbering it if it has none yet.
+    fn label(&mut self, definition: &str) -> usize {
+        if let Some(&label) = self.labels.get(definition) {
+            return label;
+        }
+
+        self.definitions.push(definition.into());
+        self.labels
+            .insert(definition.into(), self.definitions.len());
+
+        self.definitions.len()
+    }
+
+    /// The definitions of the references, to be appended to the Markdown they're used in after a blank line, or
+    /// nothing if there are none. They end with a line break only if the Markdown does.
+    pub(super) fn suffix(&self, after_newline: bool, line_ending: &str) -> String {
+        if self.definitions.is_empty() {
+            return String::new();
+        }
+
+        let definitions = self
+            .definitions
+            .iter()
+            .enumerate()
+            .map(|(i, definition)| format!("[{}]: {definition}", i + 1))
+            .collect::<Vec<_>>()
+            .join(line_ending);
+
+        match after_newline {
+            true => format!("{line_ending}{definitions}{line_ending}"),
+            false => format!("{line_ending}{line_ending}{definitions}"),
+        }
+    }
+}
+
+/// The length of the run of `c` at the start of `text`.
+fn run_of(text: &str, c: u8) -> usize {
+    text.bytes().take_while(|&b| b == c).count()
+}
+
+/// A fence opening or closing a fenced code block.
+#[derive(Clone, Copy)]
+struct Fence {
+    c: u8,
+    len: usize,
+    /// Whether nothing follows the fence, as is the case of those closing blocks.
+    bare: bool,
+}
+
+/// Reads the fence at the start of `line`, past the markers of the quotes and the indentation of the lists it's in.
+fn fence(line: &str) -> Option<Fence> {
+    let line = line.trim_start_matches([' ', '\t', '>']);
+    let c = *line
+        .as_bytes()
+        .first()
+        .filter(|&&c| c == b'`' || c == b'~')?;
+    let len = run_of(line, c);
+
+    (len >= 3).then(|| Fence {
+        c,
+        len,
+        bare: line[len..].trim().is_empty(),
+    })
+}
+
+/// Where the bracket and the parentheses of a link end, and what they hold.
+struct Destination {
+    /// From the `(` to the `)` included.
+    span: Range<usize>,
+    /// The destination and its title, if any, as written.
+    definition: Range<usize>,
+}
+
+/// Reads the destination of an inline link, i.e. `(P)` or `(P "title")`, starting with the `(` at `open`.
+fn destination(text: &str, open: usize) -> Option<Destination> {
+    let bytes = text.as_bytes();
+    let start = open + 1 + run_of(&text[open + 1..], b' ');
+    let mut pos = start;
+
+    match bytes.get(pos)? {
+        b'<' => {
+            pos += text[pos..]
+                .find(['>', '\n'])
+                .filter(|&end| bytes[pos + end] == b'>')?
+                + 1
+        }
+        _ => {
+            let mut depth = 0usize;
+
+            while let Some(&b) = bytes.get(pos) {
+                match b {
+                    b'\\' if pos + 1 < bytes.len() => pos += 1,
+                    b'(' => depth += 1,
+                    b')' if depth == 0 => break,
+                    b')' => depth -= 1,
+                    _ if b.is_ascii_whitespace() || b.is_ascii_control() => break,
+                    _ => {}
+                }
+
+                pos += 1;
+            }
+
+            if depth > 0 {
+                return None;
+            }
+        }
+    }
+
+    if pos == start || text[start..pos] == *"<>" {
+        return None;
+    }
+
+    let mut end = pos;
+    pos += run_of(&text[pos..], b' ');
+
+    // the title, if any, is kept along with the destination
+    if let Some(&quote @ (b'"' | b'\'' | b'(')) = bytes.get(pos).filter(|_| pos > end) {
+        let close = match quote {
+            b'(' => b')',
+            _ => quote,
+        };
+
+        pos += 1;
+
+        while *bytes.get(pos)? != close {
+            pos += 1 + usize::from(bytes[pos] == b'\\');
+        }
+
+        pos += 1;
+        end = pos;
+        pos += run_of(&text[pos..], b' ');
+    }
+
+    (bytes.get(pos) == Some(&b')')).then(|| Destination {
+        span: open..pos + 1,
+        definition: start..end,
+    })
+}
+
+/// Turns the inline links of some Markdown into references numbered after those of `references`, which gets their
+/// definitions. Links without text, such as `[](P)`, and images are left inline, as references would make them no
+/// easier to read.
+pub(super) fn reference_links(text: &str, references: &mut References, track: bool) -> Mapped {
+    let bytes = text.as_bytes();
+
+    let mut ret = Rewriter::new(text, track);
+    let mut copied = 0;
+
+    // the brackets still open, along with whether they open images
+    let mut brackets: Vec<(usize, bool)> = vec![];
+    // the lengths of the runs of backticks known to have no run closing them
+    let mut unclosed_code = BTreeSet::new();
+    // the fenced code block the text is in, if any
+    let mut fenced: Option<Fence> = None;
+    let mut at_line_start = true;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let b = bytes[pos];
+
+        if at_line_start {
+            let line_end = text[pos..].find('\n').map_or(text.len(), |end| pos + end);
+            let line = &text[pos..line_end];
+
+            match (fenced, fence(line)) {
+                (Some(open), Some(close))
+                    if close.c == open.c && close.len >= open.len && close.bare =>
+                {
+                    fenced = None;
+                    pos = line_end;
+
+                    continue;
+                }
+                (None, Some(opening)) => {
+                    fenced = Some(opening);
+                    brackets.clear();
+                    pos = line_end;
+
+                    continue;
+                }
+                (Some(_), _) => {
+                    pos = line_end + 1;
+
+                    continue;
+                }
+                (None, None) => {}
+            }
+
+            at_line_start = false;
+        }
+
+        match b {
+            b'\n' => {
+                at_line_start = true;
+
+                // links can span lines, but not paragraphs
+                if text[pos + 1..].starts_with('\n') {
+                    brackets.clear();
+                }
+            }
+            b'\\' if bytes.get(pos + 1).is_some_and(u8::is_ascii_punctuation) => pos += 1,
+            b'`' => {
+                let len = run_of(&text[pos..], b'`');
+                let after = pos + len;
+
+                let close = match unclosed_code.contains(&len) {
+                    true => None,
+                    false => text[after..]
+                        .match_indices('`')
+                        .map(|(at, _)| after + at)
+                        .find(|&at| bytes[at - 1] != b'`' && run_of(&text[at..], b'`') == len),
+                };
+
+                match close {
+                    Some(close) => pos = close + len,
+                    None => {
+                        unclosed_code.insert(len);
+                        pos = after;
+                    }
+                }
+
+                continue;
+            }
+            b'[' => brackets.push((pos, pos > 0 && bytes[pos - 1] == b'!')),
+            b']' => {
+                let Some((start, is_image)) = brackets.pop() else {
+                    pos += 1;
+
+                    continue;
+                };
+
+                let Some(dest) = bytes
+                    .get(pos + 1)
+                    .filter(|&&next| next == b'(')
+                    .and_then(|_| destination(text, pos + 1))
+                else {
+                    pos += 1;
+
+                    continue;
+                };
+
+                if !is_image && pos > start + 1 {
+                    let label = references.label(&text[dest.definition]);
+
+                    ret.copy(&text[copied..dest.span.start]);
+                    ret.push_str(&format!("[{label}]"));
+
+                    copied = dest.span.end;
+                }
+
+                pos = dest.span.end;
+
+                continue;
+            }
+            _ => {}
+        }
+
+        pos += 1;
+    }
+
+    ret.copy(&text[copied..]);
+
+    ret.finish()
+}
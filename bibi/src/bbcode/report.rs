@@ -0,0 +1,443 @@
+use alloc::{borrow::ToOwned, collections::BTreeMap, format, string::String, vec::Vec};
+use core::fmt;
+
+use crate::{
+    bbcode::{
+        pattern::find_ignore_case,
+        recover::{unpaired, Unpaired},
+        span::SpanMap,
+        to_markdown_with, tokenize, Token, TokenKind,
+    },
+    error::{Error, Result},
+    options::{ConvertOptions, UnclosedTags},
+};
+
+/// What went wrong with a tag.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// The tag isn't supported by the dialect, so it was copied to the output as-is.
+    UnknownTag,
+    /// The tag is opened but never closed, so it couldn't be converted.
+    UnclosedTag,
+    /// The tag is closed but never opened, so it couldn't be converted.
+    UnmatchedClose,
+    /// The tag is opened but never closed, so it's been closed or removed as told by
+    /// [`ConvertOptions::unclosed`].
+    RecoveredTag,
+}
+
+/// A tag that couldn't be translated, found while converting BBCode to Markdown.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Diagnostic {
+    /// The byte offset of the tag in the input.
+    pub offset: usize,
+    /// The name of the tag, as written in the input.
+    pub tag: String,
+    /// What went wrong.
+    pub kind: DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DiagnosticKind::*;
+
+        let Self { offset, tag, kind } = self;
+
+        match kind {
+            UnknownTag => write!(f, "byte {offset}: unknown tag [{tag}]"),
+            UnclosedTag => write!(f, "byte {offset}: [{tag}] is never closed"),
+            UnmatchedClose => write!(f, "byte {offset}: [/{tag}] has no opening tag"),
+            RecoveredTag => write!(f, "byte {offset}: [{tag}] is never closed, recovered"),
+        }
+    }
+}
+
+/// Tags that never have a closing counterpart.
+pub(super) const VOID_TAGS: &[&str] = &["*", "hr"];
+
+/// Whether everything up to the closing tag of `name` is copied verbatim, i.e. whether it's a code tag.
+pub(super) fn is_code_tag(name: &str, options: &ConvertOptions) -> bool {
+    name.eq_ignore_ascii_case("code")
+        || options
+            .dialect
+            .names()
+            .inline_code
+            .is_some_and(|c| name.eq_ignore_ascii_case(c))
+}
+
+/// Finds where code tags are closed, remembering which closing tags are missing from the rest of the content, so
+/// that code tags that are never closed don't make every later one search the rest of the content again.
+#[derive(Default)]
+pub(super) struct CodeEnds {
+    missing: Vec<String>,
+}
+
+impl CodeEnds {
+    /// Where the code tag `name` opened right before `from` is closed in `content`, closing tag included.
+    pub(super) fn find(&mut self, content: &str, from: usize, name: &str) -> Option<usize> {
+        if self
+            .missing
+            .iter()
+            .any(|missing| missing.eq_ignore_ascii_case(name))
+        {
+            return None;
+        }
+
+        let end_tag = format!("[/{name}]");
+
+        match find_ignore_case(&content[from..], &end_tag) {
+            Some(pos) => Some(from + pos + end_tag.len()),
+            None => {
+                self.missing.push(name.to_owned());
+
+                None
+            }
+        }
+    }
+}
+
+/// Whether what starts at `pos` is the checkbox of the list item whose content starts at `item_start`, if any.
+pub(super) fn is_task_marker(
+    content: &str,
+    item_start: Option<usize>,
+    pos: usize,
+    options: &ConvertOptions,
+) -> bool {
+    item_start.is_some_and(|start| content[start..pos].trim().is_empty())
+        && options.task_markers.strip(&content[pos..]).is_some()
+}
+
+/// Finds the tags in `content` that the converter can't translate, pairing them as it does.
+fn diagnose(content: &str, options: &ConvertOptions) -> Vec<Diagnostic> {
+    use DiagnosticKind::*;
+
+    let Unpaired {
+        unknown,
+        unclosed_code,
+        dangling,
+        stray,
+    } = unpaired(content, options);
+
+    let diagnostic = |kind| {
+        move |(offset, tag): (usize, &str)| Diagnostic {
+            offset,
+            tag: tag.to_owned(),
+            kind,
+        }
+    };
+
+    // dangling tags are converted after all, if they're recovered
+    let dangling_kind = match options.unclosed {
+        UnclosedTags::Keep => UnclosedTag,
+        UnclosedTags::Close | UnclosedTags::Strip => RecoveredTag,
+    };
+
+    let mut ret: Vec<_> = unknown
+        .into_iter()
+        .map(diagnostic(UnknownTag))
+        .chain(unclosed_code.into_iter().map(diagnostic(UnclosedTag)))
+        .chain(
+            dangling
+                .into_iter()
+                .map(|tag| (tag.span.start, tag.tag))
+                .map(diagnostic(dangling_kind)),
+        )
+        .chain(stray.into_iter().map(diagnostic(UnmatchedClose)))
+        .collect();
+
+    ret.sort_by_key(|diag| diag.offset);
+
+    ret
+}
+
+/// The comment marking a tag that couldn't be converted, for [`ConvertOptions::annotate_losses`], or `None` for
+/// the closing tags of unknown tags, which the comment on the opening one already covers, and for recovered tags,
+/// which were converted after all.
+fn loss_comment(content: &str, diag: &Diagnostic) -> Option<String> {
+    use DiagnosticKind::*;
+
+    let rest = &content[diag.offset..];
+    let tag = &rest[..rest.find(']').map_or(rest.len(), |end| end + 1)];
+
+    let what = match diag.kind {
+        UnknownTag if tag.starts_with("[/") => return None,
+        UnknownTag => format!("unknown tag {tag} left as-is"),
+        UnclosedTag => format!("{tag} is never closed, left as-is"),
+        UnmatchedClose => format!("{tag} has no opening tag, left as-is"),
+        RecoveredTag => return None,
+    };
+
+    // `--` can't appear within a comment
+    Some(format!("<!-- bibi: {} -->", what.replace("--", "- -")))
+}
+
+/// Inserts a comment before every tag of `content` that couldn't be converted to `markdown`, finding where it ended
+/// up through `map`.
+pub(super) fn annotate(
+    content: &str,
+    markdown: &str,
+    map: &SpanMap,
+    options: &ConvertOptions,
+) -> String {
+    let mut ret = String::with_capacity(markdown.len());
+    let mut copied = 0;
+
+    for diag in diagnose(content, options) {
+        let Some(comment) = loss_comment(content, &diag) else {
+            continue;
+        };
+
+        let pos = map.target_pos(diag.offset).max(copied);
+
+        ret.push_str(&markdown[copied..pos]);
+        ret.push_str(&comment);
+        copied = pos;
+    }
+
+    ret.push_str(&markdown[copied..]);
+
+    ret
+}
+
+/// Converts the given BBCode to Markdown like [`to_markdown`](crate::to_markdown), also returning a
+/// [`Diagnostic`] for every tag that couldn't be translated and has thus been left as-is in the output.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "nerdz")] {
+/// use bibi::{convert_with_report, DiagnosticKind};
+///
+/// let (markdown, diagnostics) = convert_with_report("[b]Hi[/b] [spoiler]boo[/spoiler] [cur]there");
+///
+/// assert_eq!(markdown, "**Hi** [spoiler]boo[/spoiler] [cur]there");
+///
+/// let found: Vec<_> = diagnostics.iter().map(|d| (d.offset, d.tag.as_str(), d.kind)).collect();
+/// assert_eq!(
+///     found,
+///     [
+///         (10, "spoiler", DiagnosticKind::UnknownTag),
+///         (22, "spoiler", DiagnosticKind::UnknownTag),
+///         (33, "cur", DiagnosticKind::UnclosedTag),
+///     ]
+/// );
+/// # }
+/// ```
+pub fn convert_with_report(content: &str) -> (String, Vec<Diagnostic>) {
+    convert_with_report_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`convert_with_report`], but allows tweaking the generated Markdown through the given
+/// [`ConvertOptions`].
+pub fn convert_with_report_with(
+    content: &str,
+    options: &ConvertOptions,
+) -> (String, Vec<Diagnostic>) {
+    (
+        to_markdown_with(content, options),
+        diagnose(content, options),
+    )
+}
+
+/// Converts the given BBCode to Markdown like [`to_markdown`](crate::to_markdown), but fails with
+/// [`Error::Parse`] on the first tag that is never closed rather than leaving it as-is, e.g. to reject posts that
+/// can't be converted faithfully. Unknown tags and closing tags that are never opened are still copied as they are.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::Error;
+///
+/// assert_eq!(bibi::to_markdown_strict("[b]Hi[/b] [spoiler]there").unwrap(), "**Hi** [spoiler]there");
+///
+/// match bibi::to_markdown_strict("[b]Hi[/b] [i]there") {
+///     Err(Error::Parse { offset, tag }) => assert_eq!((offset, tag.as_str()), (10, "i")),
+///     other => panic!("unexpected result: {other:?}"),
+/// }
+/// ```
+pub fn to_markdown_strict(content: &str) -> Result<String> {
+    to_markdown_strict_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`to_markdown_strict`], but allows tweaking the generated Markdown through the given
+/// [`ConvertOptions`]. Unless [`ConvertOptions::unclosed`] is [`UnclosedTags::Keep`], tags that are never closed
+/// are recovered as told instead, and never fail the conversion.
+pub fn to_markdown_strict_with(content: &str, options: &ConvertOptions) -> Result<String> {
+    if let Some(Diagnostic { offset, tag, .. }) = diagnose(content, options)
+        .into_iter()
+        .find(|diag| diag.kind == DiagnosticKind::UnclosedTag)
+    {
+        return Err(Error::Parse { offset, tag });
+    }
+
+    Ok(to_markdown_with(content, options))
+}
+
+/// Counts how many times every tag is opened in the given BBCode, by name in lowercase, e.g. to survey which tags a
+/// forum uses. Unknown tags are counted as well, and told apart by [`convert_with_report`], while anything within
+/// code blocks is left out, since it's never converted.
+///
+/// # Examples
+///
+/// ```
+/// let counts = bibi::count_tags("[b]a[/b] [B]b[/B] [spoiler]c[/spoiler] [code][b]d[/code]");
+///
+/// assert_eq!(
+///     counts.into_iter().collect::<Vec<_>>(),
+///     [("b".to_owned(), 2), ("code".to_owned(), 1), ("spoiler".to_owned(), 1)]
+/// );
+/// ```
+pub fn count_tags(content: &str) -> BTreeMap<String, usize> {
+    count_tags_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`count_tags`], but with code blocks spelled as the dialect of the given [`ConvertOptions`] does.
+pub fn count_tags_with(content: &str, options: &ConvertOptions) -> BTreeMap<String, usize> {
+    let mut ret = BTreeMap::new();
+    let mut code_ends = CodeEnds::default();
+    let mut skip_to = 0;
+
+    for Token { kind, span } in tokenize(content) {
+        let name = match kind {
+            TokenKind::Open { name } if span.start >= skip_to => name,
+            _ => continue,
+        };
+
+        *ret.entry(name.to_lowercase()).or_default() += 1;
+
+        if is_code_tag(name, options) {
+            if let Some(end) = code_ends.find(content, span.end, name) {
+                skip_to = end;
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{
+        convert_with_report, convert_with_report_with, to_markdown_strict_with, to_markdown_with,
+        ConvertOptions, DiagnosticKind, Error, UnclosedTags,
+    };
+
+    fn found(diagnostics: &[crate::Diagnostic]) -> Vec<(usize, &str, DiagnosticKind)> {
+        diagnostics
+            .iter()
+            .map(|diag| (diag.offset, diag.tag.as_str(), diag.kind))
+            .collect()
+    }
+
+    #[test]
+    fn tags_crossing_lines_are_reported() {
+        use DiagnosticKind::*;
+
+        let (markdown, diagnostics) = convert_with_report("[b]a\n\nb[/b]");
+
+        assert_eq!(markdown, "[b]a\n\nb[/b]");
+        assert_eq!(
+            found(&diagnostics),
+            [(0, "b", UnclosedTag), (7, "b", UnmatchedClose)]
+        );
+
+        let (_, diagnostics) = convert_with_report("[url=x]a\nb[/url]");
+
+        assert_eq!(
+            found(&diagnostics),
+            [(0, "url", UnclosedTag), (10, "url", UnmatchedClose)]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "nerdz")]
+    fn tags_crossing_code_are_reported() {
+        use DiagnosticKind::*;
+
+        let (_, diagnostics) = convert_with_report("[b]a [c]x[/c] b[/b]");
+
+        assert_eq!(
+            found(&diagnostics),
+            [(0, "b", UnclosedTag), (15, "b", UnmatchedClose)]
+        );
+    }
+
+    #[test]
+    fn blocks_span_lines() {
+        let (markdown, diagnostics) = convert_with_report("[quote]a\n\n[b]b[/b][/quote]");
+
+        assert_eq!(markdown, "> a\n>\n> **b**");
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    }
+
+    #[test]
+    fn recovered_tags_are_reported_as_such() {
+        use DiagnosticKind::*;
+
+        let options = ConvertOptions {
+            unclosed: crate::UnclosedTags::Close,
+            ..Default::default()
+        };
+
+        let (markdown, diagnostics) =
+            convert_with_report_with("[quote]a\n\n[b]b[/quote]\n\nc[/b] [code]x", &options);
+
+        assert_eq!(markdown, "> a\n>\n> **b**\n\nc[/b] [code]x");
+        assert_eq!(
+            found(&diagnostics),
+            [
+                (10, "b", RecoveredTag),
+                (25, "b", UnmatchedClose),
+                (30, "code", UnclosedTag)
+            ]
+        );
+    }
+
+    #[test]
+    fn losses_across_lines_are_annotated() {
+        let options = ConvertOptions {
+            annotate_losses: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            to_markdown_with("[b]a\n\nb[/b] [img]a.png\n\nb[/img]", &options),
+            "<!-- bibi: [b] is never closed, left as-is -->[b]a\n\n\
+             b<!-- bibi: [/b] has no opening tag, left as-is -->[/b] \
+             <!-- bibi: [img] is never closed, left as-is -->[img]a.png\n\n\
+             b<!-- bibi: [/img] has no opening tag, left as-is -->[/img]"
+        );
+    }
+
+    #[test]
+    fn strict_conversions_fail_on_unclosed_tags() {
+        let failed = |content, options| match to_markdown_strict_with(content, options) {
+            Err(Error::Parse { offset, tag }) => Some((offset, tag)),
+            Ok(_) => None,
+            Err(err) => panic!("unexpected error: {err}"),
+        };
+
+        let keep = ConvertOptions::default();
+        assert_eq!(failed("[b]a\n\nb[/b]", &keep), Some((0, "b".into())));
+        assert_eq!(failed("[b]a[/b] [I]b", &keep), Some((9, "I".into())));
+        assert_eq!(failed("[b]a[/b] [/i] [foo]", &keep), None);
+        assert_eq!(failed("[code][b][/code]", &keep), None);
+
+        let close = ConvertOptions {
+            unclosed: UnclosedTags::Close,
+            ..Default::default()
+        };
+        assert_eq!(failed("[b]a", &close), None);
+        assert_eq!(to_markdown_strict_with("[b]a", &close).unwrap(), "**a**");
+    }
+
+    #[test]
+    fn smileys_and_checkboxes_are_no_tags() {
+        let (_, diagnostics) = convert_with_report("[list][*][x] done[/list]");
+
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    }
+}
@@ -0,0 +1,147 @@
+//! A writer many threads can write into at once, e.g. to convert a batch of documents in parallel into a single JSON
+//! Lines file.
+//!
+//! Every thread writes into a [`BlockWriter`] of its own, buffering a block, such as a whole document or a line, in
+//! memory, and the blocks reach the shared output only once complete, one at a time. Blocks can end up in any
+//! order, but never mixed with each other.
+
+use std::{
+    io::{self, Write},
+    sync::{Mutex, MutexGuard},
+};
+
+/// Wraps an [`io::Write`] so that it can be shared between threads, which write into it through [`BlockWriter`]s.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// use std::{error::Error, io::Write, str, thread};
+/// use bibi::{dump_markdown, SharedWriter};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let posts = ["[b]one[/b]", "[i]two[/i]", "[del]three[/del]"];
+///     let out = SharedWriter::new(Vec::new());
+///
+///     thread::scope(|scope| {
+///         let workers: Vec<_> = posts
+///             .into_iter()
+///             .map(|post| {
+///                 let out = &out;
+///
+///                 scope.spawn(move || -> bibi::Result<()> {
+///                     let mut block = out.block();
+///                     dump_markdown(&mut block, post)?;
+///                     writeln!(block)?;
+///
+///                     Ok(block.finish()?)
+///                 })
+///             })
+///             .collect();
+///
+///         workers
+///             .into_iter()
+///             .try_for_each(|worker| worker.join().expect("the worker panicked"))
+///     })?;
+///
+///     let written = out.into_inner()?;
+///     let mut lines: Vec<_> = str::from_utf8(&written)?.lines().collect();
+///     lines.sort_unstable();
+///
+///     assert_eq!(lines, ["**one**", "*two*", "~~three~~"]);
+///
+///     Ok(())
+/// }
+/// # main().unwrap() }
+/// ```
+#[derive(Debug, Default)]
+pub struct SharedWriter<W> {
+    inner: Mutex<W>,
+}
+
+impl<W: Write> SharedWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            inner: Mutex::new(writer),
+        }
+    }
+
+    /// A writer buffering a block to write into this one.
+    pub fn block(&self) -> BlockWriter<'_, W> {
+        BlockWriter {
+            shared: self,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Writes a whole block at once, without any other block ending up in the middle of it.
+    pub fn write_block(&self, block: &[u8]) -> io::Result<()> {
+        self.lock()?.write_all(block)
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&self) -> io::Result<()> {
+        self.lock()?.flush()
+    }
+
+    /// Unwraps the underlying writer, failing if a thread panicked while writing into it, as a block may then have
+    /// been written only in part.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.inner.into_inner().map_err(|_| poisoned())
+    }
+
+    fn lock(&self) -> io::Result<MutexGuard<'_, W>> {
+        self.inner.lock().map_err(|_| poisoned())
+    }
+}
+
+fn poisoned() -> io::Error {
+    io::Error::other("a thread panicked while writing a block")
+}
+
+/// Buffers what's written into it, writing it into its [`SharedWriter`] as a single block when it's finished or
+/// flushed. Whatever is left is written when dropped, ignoring errors: call [`finish`](Self::finish) to handle them.
+#[derive(Debug)]
+pub struct BlockWriter<'a, W: Write> {
+    shared: &'a SharedWriter<W>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> BlockWriter<'_, W> {
+    /// Writes the block buffered so far, if any, starting a new one.
+    pub fn end_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let written = self.shared.write_block(&self.buf);
+        self.buf.clear();
+
+        written
+    }
+
+    /// Writes the block buffered so far, if any.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.end_block()
+    }
+}
+
+impl<W: Write> Write for BlockWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    /// Ends the current block, then flushes the underlying writer.
+    fn flush(&mut self) -> io::Result<()> {
+        self.end_block()?;
+        self.shared.flush()
+    }
+}
+
+impl<W: Write> Drop for BlockWriter<'_, W> {
+    fn drop(&mut self) {
+        let _ = self.end_block();
+    }
+}
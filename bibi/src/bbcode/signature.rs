@@ -0,0 +1,113 @@
+//! Splitting of forum posts into their body and the signature their author appended to them.
+
+use alloc::string::String;
+use core::ops::Range;
+
+use crate::{
+    bbcode::read::{block_depth, is_rule, slurp_codetags, to_markdown_with, TextChunk},
+    options::{ConvertOptions, SignatureDelimiter},
+};
+
+/// A post converted by [`to_markdown_post`], with its signature kept apart from the rest.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Post {
+    /// Everything before the signature delimiter, or the whole post if it has no signature.
+    pub body: String,
+    /// Everything after the signature delimiter, if any. The delimiter itself belongs to neither.
+    pub signature: Option<String>,
+}
+
+/// Whether `line` holds nothing but a signature delimiter allowed by `delimiter`.
+fn is_delimiter(line: &str, delimiter: SignatureDelimiter) -> bool {
+    (delimiter.allows_dashes() && line.trim_end() == "--")
+        || (delimiter.allows_rule() && is_rule(line))
+}
+
+/// Finds the line holding the last signature delimiter of a post, line terminator included.
+fn find_delimiter(content: &str, options: &ConvertOptions) -> Option<Range<usize>> {
+    use TextChunk::*;
+
+    let mut found = None;
+    let mut depth = 0;
+    let mut pos = 0;
+
+    for chunk in slurp_codetags(content, options) {
+        let text = match chunk {
+            Chars(text) => text,
+            Code { block, .. } => {
+                pos += block.len();
+                continue;
+            }
+        };
+
+        for line in text.split_inclusive('\n') {
+            // the text after a code block doesn't start a line
+            let at_line_start = pos == 0 || content[..pos].ends_with('\n');
+
+            if depth == 0 && at_line_start && is_delimiter(line, options.signature) {
+                found = Some(pos..pos + line.len());
+            }
+
+            depth = block_depth(depth, line);
+            pos += line.len();
+        }
+    }
+
+    found.filter(|delimiter| !content[delimiter.end..].trim().is_empty())
+}
+
+/// Converts the given NERDZ BBCode post to Markdown like [`to_markdown`](crate::to_markdown) does, but converts
+/// its signature separately. See [`to_markdown_post_with`] for details.
+///
+/// As signature recognition is disabled by default, this always returns the whole post as its body.
+pub fn to_markdown_post(content: &str) -> Post {
+    to_markdown_post_with(content, &ConvertOptions::default())
+}
+
+/// Same as [`to_markdown_post`], but allows tweaking the generated Markdown through the given [`ConvertOptions`].
+///
+/// [`ConvertOptions::signature`] tells which lines separate the body of the post from its signature. The two
+/// parts are converted on their own, so that markup can't leak from one to the other, and the line break right
+/// before the delimiter is dropped from the body.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{to_markdown_post_with, ConvertOptions, SignatureDelimiter};
+///
+/// let options = ConvertOptions {
+///     signature: SignatureDelimiter::Any,
+///     ..Default::default()
+/// };
+///
+/// let post = to_markdown_post_with("[b]Hello[/b] everybody\n-- \n[i]Bob[/i]", &options);
+///
+/// assert_eq!(post.body, "**Hello** everybody");
+/// assert_eq!(post.signature.as_deref(), Some("*Bob*"));
+///
+/// // delimiters within quotes belong to the quoted post
+/// let post = to_markdown_post_with("[quote]Hi\n[hr]\nAlice[/quote]\nHi!", &options);
+///
+/// assert_eq!(post.body, "> Hi\n>\n> ---\n> Alice\nHi!");
+/// assert_eq!(post.signature, None);
+/// ```
+pub fn to_markdown_post_with(content: &str, options: &ConvertOptions) -> Post {
+    let Some(delimiter) = find_delimiter(content, options) else {
+        return Post {
+            body: to_markdown_with(content, options),
+            signature: None,
+        };
+    };
+
+    // both parts must agree on the line terminator of the whole post
+    let options = options.with_line_ending_of(content);
+
+    let body = &content[..delimiter.start];
+    let body = body.strip_suffix('\n').unwrap_or(body);
+    let body = body.strip_suffix('\r').unwrap_or(body);
+
+    Post {
+        body: to_markdown_with(body, &options),
+        signature: Some(to_markdown_with(&content[delimiter.end..], &options)),
+    }
+}
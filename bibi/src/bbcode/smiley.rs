@@ -0,0 +1,172 @@
+//! Smileys, such as `:D` or `<3`, which become emoji or shortcodes in the generated Markdown, as
+//! [`SmileyStyle`] tells, and smileys again in the generated BBCode.
+
+use alloc::format;
+#[cfg(feature = "std")]
+use alloc::{borrow::Cow, string::String};
+
+use crate::{
+    bbcode::{
+        span::{Mapped, Rewriter, SpanMap},
+        tokenize, Token, TokenKind,
+    },
+    options::{ConvertOptions, Smiley, SmileyStyle},
+};
+
+/// Whether a smiley can start right after `prev`, i.e. at the start of the text or of a word, or right after a tag.
+fn starts_smiley(prev: Option<char>) -> bool {
+    prev.is_none_or(|c| c.is_whitespace() || c == ']')
+}
+
+/// Whether a smiley can end right before `next`, i.e. at the end of the text or of a word, or right before a tag.
+fn ends_smiley(next: Option<char>) -> bool {
+    next.is_none_or(|c| c.is_whitespace() || matches!(c, '[' | ')' | '.' | ',' | '!' | '?'))
+}
+
+/// The smiley on its own at `pos` of `text`, if any, the one with the longest code if more than one start there.
+fn smiley_starting<'o>(text: &str, pos: usize, options: &'o ConvertOptions) -> Option<&'o Smiley> {
+    if !starts_smiley(text[..pos].chars().next_back()) {
+        return None;
+    }
+
+    let rest = &text[pos..];
+
+    options
+        .smileys
+        .iter()
+        .filter(|smiley| {
+            !smiley.code.is_empty()
+                && rest
+                    .strip_prefix(smiley.code.as_str())
+                    .is_some_and(|after| ends_smiley(after.chars().next()))
+        })
+        .rev()
+        .max_by_key(|smiley| smiley.code.len())
+}
+
+/// Where the smiley starting with the tag at `pos` of `content` ends, if a smiley made of tags starts there and
+/// smileys are converted, so that its tags aren't taken for unknown ones.
+pub(super) fn tag_smiley_end(content: &str, pos: usize, options: &ConvertOptions) -> Option<usize> {
+    if options.smiley_style == SmileyStyle::Keep {
+        return None;
+    }
+
+    smiley_starting(content, pos, options).map(|smiley| pos + smiley.code.len())
+}
+
+/// Replaces the smileys of some BBCode with emoji or shortcodes, as [`ConvertOptions::smiley_style`] tells. Only
+/// text is looked at, and the start of tags for smileys made of tags.
+pub(super) fn replace_smileys(text: &str, options: &ConvertOptions, track: bool) -> Mapped {
+    if options.smiley_style == SmileyStyle::Keep || options.smileys.is_empty() {
+        return (text.into(), track.then(|| SpanMap::identity(text.len())));
+    }
+
+    let mut ret = Rewriter::new(text, track);
+    let mut copied = 0;
+
+    ret.reserve(text.len());
+
+    for Token { kind, span } in tokenize(text) {
+        let starts = match kind {
+            TokenKind::Text(_) => span,
+            TokenKind::Open { .. } => span.start..span.start + 1,
+            _ => continue,
+        };
+
+        for pos in starts {
+            if pos < copied || !text.is_char_boundary(pos) {
+                continue;
+            }
+
+            let Some(smiley) = smiley_starting(text, pos, options) else {
+                continue;
+            };
+
+            ret.copy(&text[copied..pos]);
+
+            match options.smiley_style {
+                SmileyStyle::Shortcode => ret.push_str(&format!(":{}:", smiley.shortcode)),
+                _ => ret.push_str(&smiley.emoji),
+            }
+
+            copied = pos + smiley.code.len();
+        }
+    }
+
+    ret.copy(&text[copied..]);
+
+    ret.finish()
+}
+
+#[cfg(feature = "std")]
+fn modifies_emoji(c: char) -> bool {
+    matches!(c, '\u{200d}' | '\u{1f3fb}'..='\u{1f3ff}')
+}
+
+/// The smiley an emoji or a shortcode at the start of `text` stands for, along with how long it is.
+#[cfg(feature = "std")]
+fn smiley_at<'o>(text: &str, smileys: &'o [Smiley]) -> Option<(&'o Smiley, usize)> {
+    if let Some(rest) = text.strip_prefix(':') {
+        return smileys.iter().find_map(|smiley| {
+            let after = rest.strip_prefix(smiley.shortcode.as_str())?;
+
+            (!smiley.shortcode.is_empty() && after.starts_with(':'))
+                .then_some((smiley, smiley.shortcode.len() + 2))
+        });
+    }
+
+    // emoji may come with or without the selector asking for their colorful form, whatever the table says
+    smileys
+        .iter()
+        .filter_map(|smiley| {
+            let emoji = smiley.emoji.trim_end_matches('\u{fe0f}');
+            let after = text.strip_prefix(emoji).filter(|_| !emoji.is_empty())?;
+
+            let len = match after.strip_prefix('\u{fe0f}') {
+                Some(after) if !after.starts_with(modifies_emoji) => {
+                    emoji.len() + '\u{fe0f}'.len_utf8()
+                }
+                None if !after.starts_with(modifies_emoji) => emoji.len(),
+                _ => return None,
+            };
+
+            Some((smiley, len))
+        })
+        .rev()
+        .max_by_key(|&(_, len)| len)
+}
+
+/// Replaces the emoji and the shortcodes of some text of the Markdown read with the smileys they stand for, unless
+/// [`ConvertOptions::smiley_style`] keeps them. An emoji with more than one smiley becomes the first one.
+#[cfg(feature = "std")]
+pub(super) fn restore_smileys<'t>(text: &'t str, options: &ConvertOptions) -> Cow<'t, str> {
+    if options.smiley_style == SmileyStyle::Keep || options.smileys.is_empty() {
+        return text.into();
+    }
+
+    let mut ret = String::new();
+    let mut copied = 0;
+
+    for (pos, c) in text.char_indices() {
+        if pos < copied || (c != ':' && c.is_ascii()) {
+            continue;
+        }
+
+        let Some((smiley, len)) = smiley_at(&text[pos..], &options.smileys) else {
+            continue;
+        };
+
+        ret.push_str(&text[copied..pos]);
+        ret.push_str(&smiley.code);
+
+        copied = pos + len;
+    }
+
+    if copied == 0 {
+        return text.into();
+    }
+
+    ret.push_str(&text[copied..]);
+
+    ret.into()
+}
@@ -0,0 +1,221 @@
+//! Snapshots of what a parser sees at some point of a text, so that a conversion failing there can be reported
+//! along with enough context to be reproduced and debugged.
+
+use std::{collections::VecDeque, fmt};
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::bbcode::{markdown_options, tokenize, Direction, Token, TokenKind};
+
+/// How many bytes around the offset the excerpt of a snapshot holds, on either side.
+const EXCERPT_RADIUS: usize = 120;
+
+/// How many of the events or tokens right before the offset a snapshot holds.
+const RECENT: usize = 8;
+
+/// How long the description of an event or token can be before it's cut short.
+const MAX_DESCRIPTION: usize = 100;
+
+/// The state of the parser at a byte offset of some converted text, such as where it exceeds a limit, as taken by
+/// [`Snapshot::take`].
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{Direction, Snapshot};
+///
+/// let bbcode = "[quote][list]\n[*]one\n[*]two\n[/list][/quote]";
+/// let snapshot = Snapshot::take(bbcode, Direction::ToBBCode, 24);
+///
+/// assert_eq!(snapshot.open, ["quote", "list", "*"]);
+/// assert_eq!(snapshot.recent.last().unwrap(), "Open { name: \"*\" } at 21..24");
+/// assert_eq!(&bbcode[snapshot.excerpt_start..][..snapshot.excerpt.len()], snapshot.excerpt);
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    /// The byte offset the snapshot was taken at.
+    pub offset: usize,
+    /// The text around the offset.
+    pub excerpt: String,
+    /// Where the excerpt starts in the text, in bytes.
+    pub excerpt_start: usize,
+    /// The constructs, or the tags for BBCode, open at the offset, outermost first.
+    pub open: Vec<String>,
+    /// The events of the Markdown parser, or the BBCode tokens, right before the offset, oldest first.
+    pub recent: Vec<String>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the parser of the output of a conversion in the given direction, i.e. Markdown for
+    /// [`Direction::ToMarkdown`] and BBCode for [`Direction::ToBBCode`], at the given byte offset.
+    pub fn take(text: &str, direction: Direction, offset: usize) -> Self {
+        let offset = floor_char_boundary(text, offset.min(text.len()));
+
+        let excerpt_start = floor_char_boundary(text, offset.saturating_sub(EXCERPT_RADIUS));
+        let excerpt_end = ceil_char_boundary(text, (offset + EXCERPT_RADIUS).min(text.len()));
+
+        let (open, recent) = match direction {
+            Direction::ToMarkdown => markdown_state(text, offset),
+            Direction::ToBBCode => bbcode_state(text, offset),
+        };
+
+        Self {
+            offset,
+            excerpt: text[excerpt_start..excerpt_end].to_owned(),
+            excerpt_start,
+            open,
+            recent: recent.into(),
+        }
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.open.is_empty() {
+            true => writeln!(f, "at byte {}, outside of any construct", self.offset)?,
+            false => writeln!(
+                f,
+                "at byte {}, within {}",
+                self.offset,
+                self.open.join(" > ")
+            )?,
+        }
+
+        writeln!(f, "recent events:")?;
+
+        for event in &self.recent {
+            writeln!(f, "    {event}")?;
+        }
+
+        writeln!(f, "excerpt, from byte {}:", self.excerpt_start)?;
+
+        for line in self.excerpt.lines() {
+            writeln!(f, "    | {line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn floor_char_boundary(text: &str, mut pos: usize) -> usize {
+    while !text.is_char_boundary(pos) {
+        pos -= 1;
+    }
+
+    pos
+}
+
+fn ceil_char_boundary(text: &str, mut pos: usize) -> usize {
+    while !text.is_char_boundary(pos) {
+        pos += 1;
+    }
+
+    pos
+}
+
+/// Adds the description of an event or token to those right before the offset, cutting it short if it's too long.
+fn remember(recent: &mut VecDeque<String>, mut description: String) {
+    if let Some((end, _)) = description.char_indices().nth(MAX_DESCRIPTION) {
+        description.truncate(end);
+        description.push('…');
+    }
+
+    if recent.len() == RECENT {
+        recent.pop_front();
+    }
+
+    recent.push_back(description);
+}
+
+fn construct_name(tag: &Tag) -> &'static str {
+    match tag {
+        Tag::Paragraph => "paragraph",
+        Tag::Heading(..) => "heading",
+        Tag::BlockQuote => "quote",
+        Tag::CodeBlock(_) => "code block",
+        Tag::List(_) => "list",
+        Tag::Item => "item",
+        Tag::FootnoteDefinition(_) => "footnote",
+        Tag::Table(_) => "table",
+        Tag::TableHead => "table head",
+        Tag::TableRow => "table row",
+        Tag::TableCell => "table cell",
+        Tag::Emphasis => "emphasis",
+        Tag::Strong => "strong emphasis",
+        Tag::Strikethrough => "strikethrough",
+        Tag::Link(..) => "link",
+        Tag::Image(..) => "image",
+    }
+}
+
+fn markdown_state(text: &str, offset: usize) -> (Vec<String>, VecDeque<String>) {
+    let mut open = vec![];
+    let mut recent = VecDeque::new();
+
+    for (event, range) in Parser::new_ext(text, markdown_options()).into_offset_iter() {
+        // the end of a construct is where it's closed, anything else is where it starts
+        let pos = match event {
+            Event::End(_) => range.end,
+            _ => range.start,
+        };
+
+        if pos > offset {
+            break;
+        }
+
+        match &event {
+            Event::Start(tag) => open.push(construct_name(tag).to_owned()),
+            Event::End(_) => {
+                open.pop();
+            }
+            _ => {}
+        }
+
+        remember(&mut recent, format!("{event:?} at {range:?}"));
+    }
+
+    (open, recent)
+}
+
+fn bbcode_state(text: &str, offset: usize) -> (Vec<String>, VecDeque<String>) {
+    let mut open: Vec<String> = vec![];
+    let mut recent = VecDeque::new();
+
+    for Token { kind, span } in tokenize(text) {
+        if span.start >= offset {
+            break;
+        }
+
+        // anything within a code block is copied verbatim
+        let in_code = open
+            .last()
+            .is_some_and(|top| top.eq_ignore_ascii_case("code"));
+
+        match kind {
+            TokenKind::Close { name } if in_code && !name.eq_ignore_ascii_case("code") => {}
+            TokenKind::Open { .. } if in_code => {}
+            TokenKind::Open { name } => {
+                // list items end where the next one starts
+                if name == "*" && open.last().is_some_and(|top| top == "*") {
+                    open.pop();
+                }
+
+                open.push(name.to_owned());
+            }
+            TokenKind::Close { name } => {
+                if let Some(pos) = open.iter().rposition(|tag| tag.eq_ignore_ascii_case(name)) {
+                    open.truncate(pos);
+                }
+            }
+            _ => {}
+        }
+
+        remember(&mut recent, format!("{kind:?} at {span:?}"));
+    }
+
+    (open, recent)
+}
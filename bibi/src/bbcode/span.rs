@@ -0,0 +1,308 @@
+//! Tracking of which part of a text produced which part of its conversion, e.g. for editors that want to highlight
+//! the source of whatever is under the cursor in a preview.
+
+use alloc::{string::String, vec::Vec};
+use core::{fmt, ops::Range};
+
+/// A piece of a converted text, alongside the piece of the original it comes from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Segment {
+    src: Range<usize>,
+    dst: Range<usize>,
+    /// Whether `src` has been copied as-is into `dst`, so that they map byte by byte. Otherwise, `src` has been
+    /// rewritten as a whole.
+    verbatim: bool,
+}
+
+/// Maps a text to the text it has been converted to, as a sequence of segments sorted by their position in both.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SpanMap {
+    segments: Vec<Segment>,
+}
+
+/// Which part of a text produced which part of its conversion, as `(src, dst)` pairs of byte ranges sorted by
+/// position in both texts. See [`to_markdown_spans`](crate::to_markdown_spans) for details.
+pub type Spans = Vec<(Range<usize>, Range<usize>)>;
+
+/// A converted text, with its [`SpanMap`] if tracking was requested.
+pub(crate) type Mapped = (String, Option<SpanMap>);
+
+impl SpanMap {
+    /// The map of a text that has been left untouched.
+    pub(crate) fn identity(len: usize) -> Self {
+        let mut ret = Self::default();
+
+        ret.push(0..len, 0..len, true);
+
+        ret
+    }
+
+    /// Appends a segment. Rewritten pieces can't be split any further, so a segment overlapping the ones before it
+    /// is merged with them.
+    pub(crate) fn push(&mut self, src: Range<usize>, dst: Range<usize>, verbatim: bool) {
+        if src.is_empty() && dst.is_empty() {
+            return;
+        }
+
+        let mut new = Segment { src, dst, verbatim };
+
+        while let Some(last) = self.segments.last() {
+            if new.src.start >= last.src.end {
+                break;
+            }
+
+            new = Segment {
+                src: last.src.start.min(new.src.start)..last.src.end.max(new.src.end),
+                dst: last.dst.start..new.dst.end,
+                verbatim: false,
+            };
+
+            self.segments.pop();
+        }
+
+        match self.segments.last_mut() {
+            Some(last)
+                if last.verbatim
+                    && new.verbatim
+                    && last.src.end == new.src.start
+                    && last.dst.end == new.dst.start =>
+            {
+                last.src.end = new.src.end;
+                last.dst.end = new.dst.end;
+            }
+            _ => self.segments.push(new),
+        }
+    }
+
+    /// Maps a position in the converted text to one in the original, which for rewritten pieces is either their
+    /// start or their end.
+    fn source_pos(&self, pos: usize, is_end: bool) -> usize {
+        let idx = match is_end {
+            true => self.segments.partition_point(|seg| seg.dst.end < pos),
+            false => self.segments.partition_point(|seg| seg.dst.end <= pos),
+        };
+
+        match self.segments.get(idx) {
+            Some(seg) if seg.verbatim => seg.src.start + pos.saturating_sub(seg.dst.start),
+            Some(seg) if is_end && seg.dst.start < pos => seg.src.end,
+            Some(seg) => seg.src.start,
+            None => self.segments.last().map_or(0, |seg| seg.src.end),
+        }
+    }
+
+    /// Maps a position in the original text to one in the converted text, which for rewritten pieces is their start.
+    pub(crate) fn target_pos(&self, pos: usize) -> usize {
+        let idx = self.segments.partition_point(|seg| seg.src.end <= pos);
+
+        match self.segments.get(idx) {
+            Some(seg) if seg.verbatim => seg.dst.start + pos.saturating_sub(seg.src.start),
+            Some(seg) => seg.dst.start,
+            None => self.segments.last().map_or(0, |seg| seg.dst.end),
+        }
+    }
+
+    /// Composes two maps, i.e. given this one from A to B and `next` from B to C, returns the map from A to C.
+    pub(crate) fn then(&self, next: &SpanMap) -> SpanMap {
+        let mut ret = SpanMap::default();
+
+        for seg in &next.segments {
+            if !seg.verbatim || seg.src.is_empty() {
+                let start = self.source_pos(seg.src.start, false);
+                let end = self.source_pos(seg.src.end, true).max(start);
+
+                ret.push(start..end, seg.dst.clone(), false);
+
+                continue;
+            }
+
+            // a copied piece maps to whatever produced it in the first place
+            let first = self
+                .segments
+                .partition_point(|prev| prev.dst.end <= seg.src.start);
+
+            for prev in self.segments[first..]
+                .iter()
+                .take_while(|prev| prev.dst.start < seg.src.end)
+            {
+                let start = prev.dst.start.max(seg.src.start);
+                let end = prev.dst.end.min(seg.src.end);
+
+                if start >= end {
+                    continue;
+                }
+
+                let dst =
+                    seg.dst.start + (start - seg.src.start)..seg.dst.start + (end - seg.src.start);
+
+                match prev.verbatim {
+                    true => ret.push(
+                        prev.src.start + (start - prev.dst.start)
+                            ..prev.src.start + (end - prev.dst.start),
+                        dst,
+                        true,
+                    ),
+                    false => ret.push(prev.src.clone(), dst, false),
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Returns the `(src, dst)` pairs of the pieces that have been rewritten rather than copied as-is.
+    #[cfg(feature = "std")]
+    pub(crate) fn rewrites(&self) -> impl Iterator<Item = (Range<usize>, Range<usize>)> + '_ {
+        self.segments
+            .iter()
+            .filter(|seg| !seg.verbatim)
+            .map(|seg| (seg.src.clone(), seg.dst.clone()))
+    }
+
+    /// Returns the `(src, dst)` pairs of this map, leaving out the parts of the original that produced nothing.
+    pub(crate) fn into_spans(self) -> Spans {
+        self.segments
+            .into_iter()
+            .filter(|seg| !seg.dst.is_empty())
+            .map(|seg| (seg.src, seg.dst))
+            .collect()
+    }
+}
+
+/// Composes two optional maps, see [`SpanMap::then`].
+pub(crate) fn compose(map: Option<SpanMap>, next: Option<SpanMap>) -> Option<SpanMap> {
+    map.zip(next).map(|(map, next)| map.then(&next))
+}
+
+/// Builds a text out of pieces of another one and new text, keeping track of where everything comes from if asked
+/// to. Whatever is written between two copied pieces replaces the part of the original between them.
+pub(crate) struct Rewriter<'s> {
+    src: &'s str,
+    out: String,
+    map: Option<SpanMap>,
+    /// How much of `src` and `out` is already covered by `map`.
+    mapped: (usize, usize),
+}
+
+impl<'s> Rewriter<'s> {
+    pub(crate) fn new(src: &'s str, track: bool) -> Self {
+        Self {
+            src,
+            out: String::new(),
+            map: track.then(SpanMap::default),
+            mapped: (0, 0),
+        }
+    }
+
+    /// The offset of `piece` in the original, which it must be a slice of.
+    fn offset_of(&self, piece: &str) -> usize {
+        let offset = piece.as_ptr() as usize - self.src.as_ptr() as usize;
+
+        debug_assert!(
+            offset + piece.len() <= self.src.len(),
+            "not a slice of the original"
+        );
+
+        offset
+    }
+
+    /// Maps what has been written since the last copied piece to the original up to `src_end`.
+    fn flush(&mut self, src_end: usize) {
+        let Some(map) = &mut self.map else {
+            return;
+        };
+
+        let (src_start, dst_start) = self.mapped;
+
+        map.push(
+            src_start..src_end.max(src_start),
+            dst_start..self.out.len(),
+            false,
+        );
+
+        self.mapped = (src_end.max(src_start), self.out.len());
+    }
+
+    /// Copies a piece of the original as-is.
+    pub(crate) fn copy(&mut self, piece: &'s str) {
+        if piece.is_empty() {
+            return;
+        }
+
+        if self.map.is_some() {
+            let start = self.offset_of(piece);
+            let end = start + piece.len();
+            let dst_start = self.out.len();
+
+            self.flush(start);
+
+            if let Some(map) = &mut self.map {
+                map.push(start..end, dst_start..dst_start + piece.len(), true);
+            }
+
+            self.mapped = (end, dst_start + piece.len());
+        }
+
+        self.out.push_str(piece);
+    }
+
+    /// Writes `text`, which is what `piece` of the original has been converted to according to `map`.
+    pub(crate) fn embed(&mut self, piece: &'s str, text: &str, map: Option<SpanMap>) {
+        if self.map.is_some() {
+            let start = self.offset_of(piece);
+            let dst_start = self.out.len();
+
+            self.flush(start);
+
+            // without a map, the text is treated as a rewrite of the piece
+            if let (Some(ret), Some(map)) = (&mut self.map, map) {
+                for seg in map.segments {
+                    ret.push(
+                        start + seg.src.start..start + seg.src.end,
+                        dst_start + seg.dst.start..dst_start + seg.dst.end,
+                        seg.verbatim,
+                    );
+                }
+
+                self.mapped = (start + piece.len(), dst_start + text.len());
+            }
+        }
+
+        self.out.push_str(text);
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.out.reserve(additional);
+    }
+
+    pub(crate) fn push(&mut self, c: char) {
+        self.out.push(c);
+    }
+
+    pub(crate) fn push_str(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.out.len()
+    }
+
+    pub(crate) fn finish(mut self) -> Mapped {
+        self.flush(self.src.len());
+
+        (self.out, self.map)
+    }
+}
+
+impl fmt::Write for Rewriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+
+        Ok(())
+    }
+}
+
+impl Extend<char> for Rewriter<'_> {
+    fn extend<T: IntoIterator<Item = char>>(&mut self, iter: T) {
+        self.out.extend(iter);
+    }
+}
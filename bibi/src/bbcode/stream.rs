@@ -0,0 +1,576 @@
+//! Conversions reading their input a line at a time, for inputs too large to comfortably hold in memory along with
+//! their conversion.
+//!
+//! The input is split right before lines following a blank one, as long as no multi-line construct is still open
+//! at that point, and every piece is converted on its own.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead},
+    mem, str,
+};
+
+use pulldown_cmark::{BrokenLink, Event, Parser};
+
+use crate::{
+    bbcode::{
+        markdown_options,
+        read::{block_depth, ends_in_code, slurp_codetags, to_markdown_with, TextChunk},
+        reference::{reference_links, References},
+        synonym::replace_synonyms,
+        write::{metadata_block, opens_metadata_block, write_bbcode_fmt_with, write_metadata_fmt},
+        Direction,
+    },
+    error::{Error, Result},
+    options::{ConvertOptions, LinkStyle},
+};
+
+/// Reads UTF-8 text a line at a time.
+struct Lines<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// How many bytes have been read so far.
+    offset: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Reads the next line, terminator included, or `None` at the end of the input.
+    fn next_line(&mut self) -> Result<Option<&str>> {
+        self.buf.clear();
+
+        if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+            return Ok(None);
+        }
+
+        let line = str::from_utf8(&self.buf).map_err(|err| Error::Encoding {
+            offset: self.offset + err.valid_up_to(),
+        })?;
+
+        self.offset += line.len();
+
+        Ok(Some(line))
+    }
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Whether the given BBCode can be converted on its own, i.e. it doesn't end within a code block, a quote or a list.
+fn is_complete_bbcode(content: &str, options: &ConvertOptions) -> bool {
+    use TextChunk::*;
+
+    if ends_in_code(content, options) {
+        return false;
+    }
+
+    let depth = slurp_codetags(content, options)
+        .iter()
+        .filter_map(|chunk| match chunk {
+            Chars(text) => Some(text),
+            Code { .. } => None,
+        })
+        .fold(0, |depth, text| {
+            let (text, _) = replace_synonyms(text.to_string(), options, false);
+
+            text.split_inclusive('\n').fold(depth, block_depth)
+        });
+
+    depth == 0
+}
+
+/// Same as [`dump_markdown`](crate::dump_markdown), but reads the BBCode a line at a time from the given reader,
+/// writing each part of it as soon as it has been converted. See [`dump_markdown_streaming_with`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use std::{error::Error, io, str};
+/// use bibi::dump_markdown_streaming;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let dump = "[b]First[/b] post\n\n[quote]Second\n\npost[/quote]\n";
+///
+///     let mut writer = Vec::new();
+///     dump_markdown_streaming(io::Cursor::new(dump), &mut writer)?;
+///
+///     assert_eq!(str::from_utf8(&writer)?, bibi::to_markdown(dump));
+///
+///     Ok(())
+/// }
+/// ```
+pub fn dump_markdown_streaming(reader: impl BufRead, writer: impl io::Write) -> Result<()> {
+    dump_markdown_streaming_with(reader, writer, &ConvertOptions::default())
+}
+
+/// Same as [`dump_markdown_streaming`], but allows tweaking the generated Markdown through the given
+/// [`ConvertOptions`].
+///
+/// The input is split at blank lines, unless they're within a code block, a quote or a list, and every part is
+/// converted on its own, so the output is the same as if the input had been converted as a whole: the other tags
+/// are never paired across lines, so those left unclosed by a split are recovered or annotated the same either way.
+/// Parts that are never closed, such as a `[code]` block with no `[/code]`, are buffered up to the end of the input.
+pub fn dump_markdown_streaming_with(
+    reader: impl BufRead,
+    writer: impl io::Write,
+    options: &ConvertOptions,
+) -> Result<()> {
+    stream(
+        reader,
+        writer,
+        StreamConverter::new(Direction::ToMarkdown, options.clone()),
+    )
+}
+
+/// Link reference definitions as `(dest, title)`, by their label folded the way Markdown compares them.
+type Definitions = HashMap<String, (String, String)>;
+
+fn fold_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Whether `line` starts a new top-level block when appended to `pending`. Markdown is parsed a line at a time,
+/// and nothing below a block can change how it's parsed, so in that case `pending` can be converted on its own.
+fn starts_block(pending: &str, line: &str) -> bool {
+    use Event::*;
+
+    let text = [pending, line].concat();
+    let mut depth = 0usize;
+
+    for (event, range) in Parser::new_ext(&text, markdown_options()).into_offset_iter() {
+        // each line of an HTML block is an event on its own, so they can't tell whether a new block starts
+        if depth == 0 && range.start >= pending.len() {
+            return range.start == pending.len() && !matches!(event, Html(_));
+        }
+
+        match event {
+            Start(_) => depth += 1,
+            End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Same as [`dump_bbcode`](crate::dump_bbcode), but reads the Markdown a line at a time from the given reader,
+/// writing each part of it as soon as it has been converted. See [`dump_bbcode_streaming_with`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use std::{error::Error, io, str};
+/// use bibi::dump_bbcode_streaming;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let dump = "**First** post\n\n```\nsecond\n\npost\n```\n";
+///
+///     let mut writer = Vec::new();
+///     dump_bbcode_streaming(io::Cursor::new(dump), &mut writer)?;
+///
+///     assert_eq!(str::from_utf8(&writer)?, bibi::to_bbcode(dump)?);
+///
+///     Ok(())
+/// }
+/// ```
+pub fn dump_bbcode_streaming(reader: impl BufRead, writer: impl io::Write) -> Result<()> {
+    dump_bbcode_streaming_with(reader, writer, &ConvertOptions::default())
+}
+
+/// Same as [`dump_bbcode_streaming`], but allows tweaking the generated BBCode through the given
+/// [`ConvertOptions`].
+///
+/// The input is split right before lines following a blank one, as long as they start a new top-level block rather
+/// than continuing a list or a code block, and every part is converted on its own. Links can refer to the
+/// definitions of the parts before them, but as the input is never read as a whole, not to those below them.
+pub fn dump_bbcode_streaming_with(
+    reader: impl BufRead,
+    writer: impl io::Write,
+    options: &ConvertOptions,
+) -> Result<()> {
+    stream(
+        reader,
+        writer,
+        StreamConverter::new(Direction::ToBBCode, options.clone()),
+    )
+}
+
+/// Converts a part of the Markdown, resolving the references it doesn't define itself through the definitions of
+/// the parts before it, to which its own are then added. The first part may start with a metadata block.
+fn convert_part(
+    out: &mut String,
+    part: &str,
+    first: bool,
+    definitions: &mut Definitions,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let part = match metadata_block(part).filter(|_| first) {
+        Some((metadata, end)) => {
+            write_metadata_fmt(&mut *out, metadata, options)?;
+            &part[end..]
+        }
+        None => part,
+    };
+
+    let mut lookup = |link: BrokenLink| {
+        definitions
+            .get(&fold_label(&link.reference))
+            .map(|(dest, title)| (dest.clone().into(), title.clone().into()))
+    };
+
+    let parser = Parser::new_with_broken_link_callback(part, markdown_options(), Some(&mut lookup));
+
+    let found: Vec<_> = parser
+        .reference_definitions()
+        .iter()
+        .map(|(label, def)| {
+            let title = def.title.as_deref().unwrap_or_default();
+
+            (fold_label(label), (def.dest.to_string(), title.to_owned()))
+        })
+        .collect();
+
+    write_bbcode_fmt_with(&mut *out, parser, options)?;
+
+    // like in a whole document, the first definition of a label wins
+    for (label, def) in found {
+        definitions.entry(label).or_insert(def);
+    }
+
+    Ok(())
+}
+
+/// Converts a part of some BBCode, turning its links into references numbered after those of the parts before it
+/// if they're written as such. Their definitions are left to the caller, since they only come after the last part.
+fn part_to_markdown(
+    part: &str,
+    options: &mut ConvertOptions,
+    references: &mut References,
+) -> String {
+    if options.links != LinkStyle::Reference {
+        return to_markdown_with(part, options);
+    }
+
+    options.links = LinkStyle::Inline;
+    let markdown = to_markdown_with(part, options);
+    options.links = LinkStyle::Reference;
+
+    reference_links(&markdown, references, false).0
+}
+
+/// Feeds every line of `reader` to `converter`, writing what it converts as soon as it's available.
+fn stream(
+    reader: impl BufRead,
+    mut writer: impl io::Write,
+    mut converter: StreamConverter,
+) -> Result<()> {
+    let mut lines = Lines::new(reader);
+
+    while let Some(line) = lines.next_line()? {
+        writer.write_all(converter.push(line)?.as_bytes())?;
+    }
+
+    writer.write_all(converter.finish()?.as_bytes())?;
+
+    Ok(())
+}
+
+/// Converts text pushed a piece at a time, in either direction, returning each part of the output as soon as it
+/// has been converted. Pieces can be cut anywhere, even in the middle of a line.
+///
+/// This is what [`dump_markdown_streaming_with`] and [`dump_bbcode_streaming_with`] do, for callers that are given
+/// the input as it arrives rather than reading it themselves, e.g. from a network stream: the output is split and
+/// converted the same way, so it's the same as if the input had been converted as a whole.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{ConvertOptions, Direction, StreamConverter};
+///
+/// let dump = "[b]First[/b] post\n\n[quote]Second\n\npost[/quote]\n";
+/// let mut converter = StreamConverter::new(Direction::ToMarkdown, ConvertOptions::default());
+///
+/// let mut markdown = String::new();
+///
+/// for piece in ["[b]Fir", "st[/b] post\n", "\n[quote]Sec", "ond\n\npost[/quote]\n"] {
+///     markdown += &converter.push(piece).unwrap();
+/// }
+///
+/// markdown += &converter.finish().unwrap();
+///
+/// assert_eq!(markdown, bibi::to_markdown(dump));
+/// ```
+#[derive(Clone, Debug)]
+pub struct StreamConverter {
+    direction: Direction,
+    options: ConvertOptions,
+    /// Whether the line ending of the options has been resolved against the first line.
+    resolved: bool,
+    /// The last line pushed, until it's terminated.
+    partial: String,
+    /// The lines not converted yet.
+    pending: String,
+    /// How many bytes of complete lines have been read so far.
+    read: usize,
+    /// Checking whether the pending input can be split means looking at all of it, so after a failed check the
+    /// next one waits until it has doubled in size to keep the whole conversion linear.
+    next_check: usize,
+    /// Whether the last line is blank, when converting to BBCode.
+    after_blank: bool,
+    /// Whether a part has been converted already, when converting to BBCode.
+    converted: bool,
+    /// The link reference definitions of the parts converted so far, when converting to BBCode.
+    definitions: Definitions,
+    /// The links turned into references so far, when converting to Markdown with [`LinkStyle::Reference`], whose
+    /// definitions come after the last part.
+    references: References,
+    /// Whether the Markdown written so far ends with a line break.
+    after_newline: bool,
+}
+
+impl StreamConverter {
+    /// Creates a converter going in the given direction with the given options.
+    pub fn new(direction: Direction, options: ConvertOptions) -> Self {
+        Self {
+            direction,
+            options,
+            resolved: false,
+            partial: String::new(),
+            pending: String::new(),
+            read: 0,
+            next_check: 0,
+            after_blank: false,
+            converted: false,
+            definitions: Definitions::new(),
+            references: References::default(),
+            after_newline: false,
+        }
+    }
+
+    /// Pushes the next piece of the input, returning whatever could be converted so far, which may be nothing.
+    pub fn push(&mut self, piece: &str) -> Result<String> {
+        let mut ret = String::new();
+
+        self.partial.push_str(piece);
+
+        let Some(end) = self.partial.rfind('\n') else {
+            return Ok(ret);
+        };
+
+        let partial = mem::take(&mut self.partial);
+
+        for line in partial[..=end].split_inclusive('\n') {
+            self.push_line(line, &mut ret)?;
+        }
+
+        self.partial = partial[end + 1..].to_owned();
+
+        Ok(ret)
+    }
+
+    /// Ends the input, returning the rest of the output.
+    pub fn finish(mut self) -> Result<String> {
+        let mut ret = String::new();
+
+        let partial = mem::take(&mut self.partial);
+
+        if !partial.is_empty() {
+            self.push_line(&partial, &mut ret)?;
+        }
+
+        if !self.resolved {
+            self.options = self.options.with_line_ending_of("").into_owned();
+        }
+
+        match self.direction {
+            Direction::ToMarkdown if !self.pending.is_empty() => {
+                let markdown =
+                    part_to_markdown(&self.pending, &mut self.options, &mut self.references);
+
+                // a whole input with line breaks loses a trailing `\r`, even if the last part has no line break at
+                // all
+                let markdown = match self.read > self.pending.len() && !self.pending.contains('\n')
+                {
+                    true => markdown.strip_suffix('\r').unwrap_or(&markdown),
+                    false => &markdown,
+                };
+
+                ret.push_str(markdown);
+                self.after_newline = markdown.ends_with('\n');
+            }
+            Direction::ToMarkdown => {}
+            Direction::ToBBCode => convert_part(
+                &mut ret,
+                &self.pending,
+                !self.converted,
+                &mut self.definitions,
+                &self.options,
+            )?,
+        }
+
+        let line_ending = self.options.line_ending.resolve("");
+        ret.push_str(&self.references.suffix(self.after_newline, line_ending));
+
+        Ok(ret)
+    }
+
+    /// Reads a line, writing to `out` whatever can be converted after it.
+    fn push_line(&mut self, line: &str, out: &mut String) -> Result<()> {
+        // the first line tells which line terminator the input uses
+        if !self.resolved {
+            self.options = self.options.with_line_ending_of(line).into_owned();
+            self.resolved = true;
+        }
+
+        self.read += line.len();
+
+        match self.direction {
+            Direction::ToMarkdown => {
+                self.pending.push_str(line);
+
+                // a blank line without a line break is the last one, it can't be split from what comes before
+                if !(is_blank(line) && line.ends_with('\n')) || self.pending.len() < self.next_check
+                {
+                    return Ok(());
+                }
+
+                if is_complete_bbcode(&self.pending, &self.options) {
+                    let markdown =
+                        part_to_markdown(&self.pending, &mut self.options, &mut self.references);
+                    out.push_str(&markdown);
+                    self.after_newline = markdown.ends_with('\n');
+
+                    self.pending.clear();
+                    self.next_check = 0;
+                } else {
+                    self.next_check = self.pending.len() * 2;
+                }
+            }
+            Direction::ToBBCode => {
+                // a metadata block can't be split, even if it holds blank lines
+                let in_metadata = !self.converted
+                    && opens_metadata_block(&self.pending)
+                    && metadata_block(&self.pending).is_none();
+
+                // only lines right after a blank one may start a new block that isn't just a continuation of the
+                // one before
+                if self.after_blank
+                    && !is_blank(line)
+                    && !in_metadata
+                    && self.pending.len() >= self.next_check
+                {
+                    if starts_block(&self.pending, line) {
+                        let first = !self.converted;
+                        convert_part(
+                            out,
+                            &self.pending,
+                            first,
+                            &mut self.definitions,
+                            &self.options,
+                        )?;
+
+                        self.converted = true;
+                        self.pending.clear();
+                        self.next_check = 0;
+                    } else {
+                        self.next_check = self.pending.len() * 2;
+                    }
+                }
+
+                self.after_blank = is_blank(line);
+                self.pending.push_str(line);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamConverter;
+    use crate::{
+        bbcode::{read::to_markdown_with, Direction},
+        options::{ConvertOptions, LinkStyle, UnclosedTags},
+    };
+
+    const CORPUS: &[&str] = &[
+        "[b]a\n\nb[/b]",
+        "[b]a\nb[/b]",
+        "[b]a\nb",
+        "[b][i]a\n\nb[/i][/b]",
+        "[b]a\n[quote]x[/quote]\nb[/b]",
+        "[b]a [c]x[/c] b[/b]",
+        "[/b]\n\n[b]",
+        "[u]a\n\n[/u]",
+        "[i]a\n\n\n\nb[/i]\n",
+        "[s]x\n \nz[/s]",
+        "[img]a.png\n\nb[/img]",
+        "[img]http://a.png[/img]\n\n[img]x\n\n[/img]",
+        "[url=http://a]x\n\ny[/url]",
+        "[url=http://a]x[/url]\n\n[url]http://b[/url]\n\n[url=http://a]y[/url]\n",
+        "[quote][b]a[/quote][/b]",
+        "[quote]a\n\n[b]b[/quote]\n\nc[/b]",
+        "[quote]a\n\n[quote]b[/quote]\n\nc",
+        "[list]\n[*][b]a\n[*]b[/list]",
+        "[list]\n[*]a\n\n[*]b",
+        "[code]a\n\nb",
+        "[code]a\n\nb[/code]\n\n[i]c",
+        "[spoiler]a\n\nb[/spoiler]",
+        "[foo]a\n\nb[/foo]",
+        "a[sup][1][/sup]\n\n[SUP][1][/sup]: b\n\n[sup][1\n\n][/sup]",
+        "a :) b\n\n[b]:D\n\n[/b]",
+        "[b]a[/b]\r\n\r\n[i]b\r\n\r\nc[/i]\r\n",
+    ];
+
+    /// Converts `bbcode` pushing it a few bytes at a time.
+    fn streamed(bbcode: &str, options: &ConvertOptions) -> String {
+        let mut converter = StreamConverter::new(Direction::ToMarkdown, options.clone());
+        let mut ret = String::new();
+        let mut start = 0;
+
+        for (end, _) in bbcode.char_indices().filter(|&(end, _)| end % 3 == 0) {
+            ret += &converter.push(&bbcode[start..end]).unwrap();
+            start = end;
+        }
+
+        ret += &converter.push(&bbcode[start..]).unwrap();
+        ret + &converter.finish().unwrap()
+    }
+
+    #[test]
+    fn streaming_is_whole_conversion() {
+        for unclosed in [UnclosedTags::Keep, UnclosedTags::Close, UnclosedTags::Strip] {
+            for annotate_losses in [false, true] {
+                for links in [LinkStyle::Inline, LinkStyle::Reference] {
+                    let options = ConvertOptions {
+                        unclosed,
+                        annotate_losses,
+                        links,
+                        ..Default::default()
+                    };
+
+                    for bbcode in CORPUS {
+                        assert_eq!(
+                            streamed(bbcode, &options),
+                            to_markdown_with(bbcode, &options),
+                            "{bbcode:?} with {unclosed:?}, {links:?}, annotating: {annotate_losses}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Other names for tags, renamed to the tags they stand for before anything else is read, so that minor differences
+//! between dialects don't need new code.
+
+use alloc::string::String;
+
+use crate::{
+    bbcode::{
+        span::{Mapped, Rewriter, SpanMap},
+        tokenize, Token, TokenKind,
+    },
+    options::ConvertOptions,
+};
+
+/// The name of the tag `name` stands for according to the synonyms of `options`, if it's a synonym at all.
+pub(super) fn synonym_of<'o>(name: &str, options: &'o ConvertOptions) -> Option<&'o str> {
+    options
+        .synonyms
+        .iter()
+        .find(|(synonym, _)| synonym.eq_ignore_ascii_case(name))
+        .map(|(_, name)| name.as_str())
+}
+
+/// Renames every tag in `text` that's a synonym to the tag it stands for.
+pub(super) fn replace_synonyms(text: String, options: &ConvertOptions, track: bool) -> Mapped {
+    if options.synonyms.is_empty() {
+        let len = text.len();
+
+        return (text, track.then(|| SpanMap::identity(len)));
+    }
+
+    let mut ret = Rewriter::new(&text, track);
+    let mut copied = 0;
+
+    ret.reserve(text.len());
+
+    for Token { kind, span } in tokenize(&text) {
+        let (name, start) = match kind {
+            TokenKind::Open { name } => (name, span.start + 1),
+            TokenKind::Close { name } => (name, span.start + 2),
+            _ => continue,
+        };
+
+        let Some(renamed) = synonym_of(name, options) else {
+            continue;
+        };
+
+        ret.copy(&text[copied..start]);
+        ret.push_str(renamed);
+
+        copied = start + name.len();
+    }
+
+    ret.copy(&text[copied..]);
+
+    ret.finish()
+}
@@ -0,0 +1,300 @@
+#[cfg(not(any(feature = "nerdz", feature = "phpbb")))]
+compile_error!("at least one dialect feature (`nerdz` or `phpbb`) must be enabled");
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A BBCode flavour, i.e. the set of tags understood by a given board.
+///
+/// Each dialect is behind a Cargo feature of the same name, so that size-sensitive builds can strip the tables of
+/// the dialects they don't need.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "phpbb"))] {
+/// use std::error::Error;
+/// use bibi::{to_bbcode_with, to_markdown_with, ConvertOptions, Dialect};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let options = ConvertOptions {
+///         dialect: Dialect::Phpbb,
+///         ..Default::default()
+///     };
+///
+///     let bbcode = to_bbcode_with("*hi* ~~there~~ `x`\n\n1. one", &options)?;
+///     assert_eq!(bbcode, "[i]hi[/i] [s]there[/s] [code]x[/code]\n\n[list=1]\n[*]one\n[/list]\n");
+///
+///     let markdown = to_markdown_with("[i]hi[/i] [strike]there[/strike]\n[list=a][*]one[*]two[/list]", &options);
+///     assert_eq!(markdown, "*hi* ~~there~~\na. one\nb. two\n");
+///
+///     Ok(())
+/// }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// The BBCode flavour used by [NERDZ](https://nerdz.eu).
+    #[cfg(feature = "nerdz")]
+    Nerdz,
+    /// The standard BBCode shipped with phpBB, which is what most other boards understand as well.
+    #[cfg(feature = "phpbb")]
+    Phpbb,
+}
+
+impl Dialect {
+    /// Every dialect enabled by the Cargo features.
+    pub const ALL: &'static [Dialect] = &[
+        #[cfg(feature = "nerdz")]
+        Dialect::Nerdz,
+        #[cfg(feature = "phpbb")]
+        Dialect::Phpbb,
+    ];
+
+    /// The name of the dialect, as given to `bibi --dialect` and in configuration files.
+    pub const fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "nerdz")]
+            Dialect::Nerdz => "nerdz",
+            #[cfg(feature = "phpbb")]
+            Dialect::Phpbb => "phpbb",
+        }
+    }
+}
+
+impl Default for Dialect {
+    #[cfg(feature = "nerdz")]
+    fn default() -> Self {
+        Self::Nerdz
+    }
+
+    #[cfg(not(feature = "nerdz"))]
+    fn default() -> Self {
+        Self::Phpbb
+    }
+}
+
+/// How a dialect spells the attributes of ordered lists.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ListSyntax {
+    /// `[list type="a" start="3"]`.
+    #[cfg_attr(not(feature = "nerdz"), allow(dead_code))]
+    Attributes,
+    /// `[list=a]`, without any way to specify a start.
+    #[cfg_attr(not(feature = "phpbb"), allow(dead_code))]
+    Value,
+}
+
+/// The names a dialect uses for the tags the converters care about. When a list holds more than one name, the
+/// first one is the one that gets written, while the others are only recognized when reading.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))] // some are only needed when writing BBCode
+pub(crate) struct TagNames {
+    pub bold: &'static str,
+    pub italic: &'static [&'static str],
+    pub strike: &'static [&'static str],
+    pub big: Option<&'static str>,
+    pub inline_code: Option<&'static str>,
+    pub rule: Option<&'static str>,
+    pub list: ListSyntax,
+}
+
+#[cfg(feature = "nerdz")]
+const NERDZ_NAMES: TagNames = TagNames {
+    bold: "b",
+    italic: &["cur", "i"],
+    strike: &["del"],
+    big: Some("big"),
+    inline_code: Some("c"),
+    rule: Some("hr"),
+    list: ListSyntax::Attributes,
+};
+
+#[cfg(feature = "phpbb")]
+const PHPBB_NAMES: TagNames = TagNames {
+    bold: "b",
+    italic: &["i"],
+    strike: &["s", "strike"],
+    big: None,
+    inline_code: None,
+    rule: None,
+    list: ListSyntax::Value,
+};
+
+impl Dialect {
+    pub(crate) const fn names(self) -> &'static TagNames {
+        use Dialect::*;
+
+        match self {
+            #[cfg(feature = "nerdz")]
+            Nerdz => &NERDZ_NAMES,
+            #[cfg(feature = "phpbb")]
+            Phpbb => &PHPBB_NAMES,
+        }
+    }
+}
+
+/// The direction of a conversion.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    /// From BBCode to Markdown, i.e. what [`dump_markdown`](crate::dump_markdown) does.
+    ToMarkdown,
+    /// From Markdown to BBCode, i.e. what [`dump_bbcode`](crate::dump_bbcode) does.
+    ToBBCode,
+}
+
+/// How faithfully a conversion carries a tag over.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Fidelity {
+    /// The content is kept along with all of its formatting.
+    Exact,
+    /// The formatting is kept, but it looks different, e.g. big text becoming a heading.
+    Approximate,
+    /// Some of the formatting is lost, e.g. the language of inline code.
+    Lossy,
+}
+
+/// Describes a BBCode tag supported by a conversion.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TagInfo {
+    /// The name of the tag, e.g. `b` for `[b]`.
+    pub name: &'static str,
+    /// Other names accepted for the same tag.
+    pub aliases: &'static [&'static str],
+    /// An example of the tag, with `P` standing for its content.
+    pub bbcode: &'static str,
+    /// The Markdown equivalent of `bbcode`.
+    pub markdown: &'static str,
+    /// How faithfully the conversion carries the tag over.
+    pub fidelity: Fidelity,
+}
+
+impl TagInfo {
+    /// Checks whether `name` refers to this tag, either directly or through an alias. Tag names are case-insensitive.
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
+use Fidelity::*;
+
+const fn tag(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    bbcode: &'static str,
+    markdown: &'static str,
+    fidelity: Fidelity,
+) -> TagInfo {
+    TagInfo {
+        name,
+        aliases,
+        bbcode,
+        markdown,
+        fidelity,
+    }
+}
+
+/// Tags recognized when converting NERDZ BBCode to Markdown.
+#[cfg(feature = "nerdz")]
+const NERDZ_TO_MARKDOWN: &[TagInfo] = &[
+    tag("b", &[], "[b]P[/b]", "**P**", Exact),
+    tag("cur", &["i"], "[cur]P[/cur]", "*P*", Exact),
+    tag("del", &[], "[del]P[/del]", "~~P~~", Exact),
+    tag("big", &[], "[big]P[/big]", "# P", Approximate),
+    tag("url", &[], "[url=\"Q\"]P[/url]", "[P](Q)", Exact),
+    tag("img", &[], "[img]P[/img]", "![](P)", Exact),
+    tag("quote", &[], "[quote]P[/quote]", "> P", Exact),
+    tag("list", &[], "[list][*]P[/list]", "- P", Exact),
+    tag("*", &[], "[*]P", "- P", Exact),
+    tag("code", &[], "[code=lang]P[/code]", "```lang\nP\n```", Exact),
+    tag("c", &[], "[c=lang]P[/c]", "`P`", Lossy),
+    tag("hr", &[], "[hr]", "---", Exact),
+];
+
+/// Tags emitted when converting Markdown to NERDZ BBCode.
+#[cfg(feature = "nerdz")]
+const NERDZ_TO_BBCODE: &[TagInfo] = &[
+    tag("b", &[], "[b]P[/b]", "**P**", Exact),
+    tag("cur", &[], "[cur]P[/cur]", "*P*", Exact),
+    tag("del", &[], "[del]P[/del]", "~~P~~", Exact),
+    tag("big", &[], "[big]P[/big]", "# P", Lossy),
+    tag("url", &[], "[url=Q]P[/url]", "[P](Q)", Exact),
+    tag("img", &[], "[img]P[/img]", "![](P)", Exact),
+    tag("quote", &[], "[quote]P[/quote]", "> P", Exact),
+    tag("list", &[], "[list][*]P[/list]", "- P", Exact),
+    tag("*", &[], "[*]P", "- P", Exact),
+    tag("code", &[], "[code=lang]P[/code]", "```lang\nP\n```", Exact),
+    tag("c", &[], "[c=inline]P[/c]", "`P`", Exact),
+    tag("hr", &[], "[hr]", "---", Exact),
+];
+
+/// Tags recognized when converting phpBB BBCode to Markdown.
+#[cfg(feature = "phpbb")]
+const PHPBB_TO_MARKDOWN: &[TagInfo] = &[
+    tag("b", &[], "[b]P[/b]", "**P**", Exact),
+    tag("i", &[], "[i]P[/i]", "*P*", Exact),
+    tag("s", &["strike"], "[s]P[/s]", "~~P~~", Exact),
+    tag("url", &[], "[url=\"Q\"]P[/url]", "[P](Q)", Exact),
+    tag("img", &[], "[img]P[/img]", "![](P)", Exact),
+    tag("quote", &[], "[quote]P[/quote]", "> P", Exact),
+    tag("list", &[], "[list=1][*]P[/list]", "1. P", Exact),
+    tag("*", &[], "[*]P", "- P", Exact),
+    tag("code", &[], "[code]P[/code]", "```\nP\n```", Exact),
+    tag("hr", &[], "[hr]", "---", Exact),
+];
+
+/// Tags emitted when converting Markdown to phpBB BBCode.
+#[cfg(feature = "phpbb")]
+const PHPBB_TO_BBCODE: &[TagInfo] = &[
+    tag("b", &[], "[b]P[/b]", "**P**", Exact),
+    tag("i", &[], "[i]P[/i]", "*P*", Exact),
+    tag("s", &[], "[s]P[/s]", "~~P~~", Exact),
+    tag("url", &[], "[url=Q]P[/url]", "[P](Q)", Exact),
+    tag("img", &[], "[img]P[/img]", "![](P)", Exact),
+    tag("quote", &[], "[quote]P[/quote]", "> P", Exact),
+    tag("list", &[], "[list=1][*]P[/list]", "1. P", Exact),
+    tag("*", &[], "[*]P", "- P", Exact),
+    tag("code", &[], "[code=lang]P[/code]", "```lang\nP\n```", Exact),
+];
+
+/// Lists the tags supported when converting in the given direction with the given dialect, so that UIs can show
+/// which formatting survives a conversion.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "nerdz")] {
+/// use bibi::{supported_tags, Dialect, Direction};
+///
+/// let tags = supported_tags(Direction::ToMarkdown, Dialect::Nerdz);
+///
+/// assert!(tags.iter().any(|tag| tag.matches("I")));
+/// assert!(!tags.iter().any(|tag| tag.matches("spoiler")));
+/// # }
+/// ```
+pub fn supported_tags(direction: Direction, dialect: Dialect) -> &'static [TagInfo] {
+    use Dialect::*;
+    use Direction::*;
+
+    match (direction, dialect) {
+        #[cfg(feature = "nerdz")]
+        (ToMarkdown, Nerdz) => NERDZ_TO_MARKDOWN,
+        #[cfg(feature = "nerdz")]
+        (ToBBCode, Nerdz) => NERDZ_TO_BBCODE,
+        #[cfg(feature = "phpbb")]
+        (ToMarkdown, Phpbb) => PHPBB_TO_MARKDOWN,
+        #[cfg(feature = "phpbb")]
+        (ToBBCode, Phpbb) => PHPBB_TO_BBCODE,
+    }
+}
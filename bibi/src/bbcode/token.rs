@@ -0,0 +1,399 @@
+//! A low-level tokenizer splitting BBCode into tags and the text between them, without any knowledge of which tags
+//! a dialect supports.
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::{iter, ops::Range};
+
+/// What a [`Token`] is.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum TokenKind<'t> {
+    /// Text outside of any tag, including anything that looks like a tag but isn't well-formed.
+    Text(&'t str),
+    /// An opening tag such as `[b]`, `[url=P]` or `[list type="a"]`, with the name as written. Its attributes, if
+    /// any, follow as [`TokenKind::Attribute`] tokens.
+    Open { name: &'t str },
+    /// An attribute of the opening tag right before it. `name` is `None` for the value of tags such as `[url=P]`,
+    /// and `value` never includes the surrounding quotes.
+    Attribute {
+        name: Option<&'t str>,
+        value: &'t str,
+    },
+    /// A closing tag such as `[/b]`, with the name as written.
+    Close { name: &'t str },
+}
+
+/// A piece of BBCode, as returned by [`tokenize`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Token<'t> {
+    /// What the token is.
+    pub kind: TokenKind<'t>,
+    /// Where the token is in the input, in bytes. The spans of text, opening and closing tags cover the input
+    /// without gaps, while those of attributes lie within the opening tag they belong to.
+    pub span: Range<usize>,
+}
+
+/// Reads a tag name at the start of `text`, i.e. either `*` or an ASCII letter followed by letters and digits.
+fn tag_name(text: &str) -> Option<&str> {
+    if text.starts_with('*') {
+        return Some(&text[..1]);
+    }
+
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let len = text
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(text.len());
+
+    Some(&text[..len])
+}
+
+/// Reads an attribute name at the start of `text`, i.e. an ASCII letter followed by letters, digits, `-` or `_`.
+fn attribute_name(text: &str) -> Option<&str> {
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(text.len());
+
+    Some(&text[..len])
+}
+
+/// Reads an attribute value at the start of `text`, either quoted (if `quotes` is set) or extending up to the first
+/// character matching `end`. Values can't span multiple lines. Returns the value without quotes and its length as
+/// written.
+fn attribute_value(text: &str, quotes: bool, end: impl Fn(char) -> bool) -> Option<(&str, usize)> {
+    match text.strip_prefix('"').filter(|_| quotes) {
+        Some(quoted) => {
+            let len = quoted.find(['"', '\n'])?;
+
+            quoted[len..]
+                .starts_with('"')
+                .then(|| (&quoted[..len], len + 2))
+        }
+        None => {
+            let len = text.find(|c| c == '\n' || end(c))?;
+
+            (!text[len..].starts_with('\n')).then(|| (&text[..len], len))
+        }
+    }
+}
+
+/// Reads the tag starting at `start`, which must point to a `[`, returning its tokens and where it ends.
+///
+/// Like the converter does, a value that doesn't parse when quoted is read again as an unquoted one, so that e.g.
+/// `[url="P]` is a tag whose value is `"P`.
+fn read_tag(content: &str, start: usize) -> Option<(Vec<Token<'_>>, usize)> {
+    read_tag_quoted(content, start, true).or_else(|| read_tag_quoted(content, start, false))
+}
+
+fn read_tag_quoted(content: &str, start: usize, quotes: bool) -> Option<(Vec<Token<'_>>, usize)> {
+    use TokenKind::*;
+
+    let mut pos = start + 1;
+
+    let closing = content[pos..].starts_with('/');
+    if closing {
+        pos += 1;
+    }
+
+    let name = tag_name(&content[pos..])?;
+    pos += name.len();
+
+    let mut attributes = vec![];
+
+    if !closing {
+        // the value of the tag itself, e.g. `[url=P]`. Unless quoted, it extends up to the end of the tag
+        if content[pos..].starts_with('=') {
+            pos += 1;
+
+            let (value, len) = attribute_value(&content[pos..], quotes, |c| c == ']')?;
+
+            attributes.push(Token {
+                kind: Attribute { name: None, value },
+                span: pos..pos + len,
+            });
+
+            pos += len;
+        }
+
+        loop {
+            let rest = &content[pos..];
+            let trimmed = rest.trim_start_matches([' ', '\t']);
+
+            if trimmed.len() == rest.len() {
+                break;
+            }
+
+            pos += rest.len() - trimmed.len();
+
+            let Some(key) = attribute_name(trimmed) else {
+                break;
+            };
+
+            let after = trimmed[key.len()..].strip_prefix('=')?;
+            let (value, len) = attribute_value(after, quotes, |c| c == ']' || c.is_whitespace())?;
+            let attr_len = key.len() + 1 + len;
+
+            attributes.push(Token {
+                kind: Attribute {
+                    name: Some(key),
+                    value,
+                },
+                span: pos..pos + attr_len,
+            });
+
+            pos += attr_len;
+        }
+    }
+
+    if !content[pos..].starts_with(']') {
+        return None;
+    }
+
+    pos += 1;
+
+    let tag = Token {
+        kind: match closing {
+            true => Close { name },
+            false => Open { name },
+        },
+        span: start..pos,
+    };
+
+    Some((iter::once(tag).chain(attributes).collect(), pos))
+}
+
+/// Splits the given BBCode into tags, their attributes and the text in between.
+///
+/// Tokenizing is purely syntactic: every well-formed tag is returned, whether or not any dialect supports it, and
+/// tags are not checked for being balanced. Anything that isn't a well-formed tag is returned as text.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{tokenize, TokenKind::*};
+///
+/// let tokens: Vec<_> = tokenize(r#"[b]Hi[/b] [list type="a"][*]x"#).collect();
+/// let kinds: Vec<_> = tokens.iter().map(|token| token.kind).collect();
+///
+/// assert_eq!(
+///     kinds,
+///     [
+///         Open { name: "b" },
+///         Text("Hi"),
+///         Close { name: "b" },
+///         Text(" "),
+///         Open { name: "list" },
+///         Attribute { name: Some("type"), value: "a" },
+///         Open { name: "*" },
+///         Text("x"),
+///     ]
+/// );
+///
+/// assert_eq!(tokens[4].span, 10..25);
+/// assert_eq!(tokens[5].span, 16..24);
+/// ```
+pub fn tokenize(content: &str) -> impl Iterator<Item = Token<'_>> {
+    let mut pos = 0;
+    let mut pending = VecDeque::new();
+
+    iter::from_fn(move || {
+        if let Some(token) = pending.pop_front() {
+            return Some(token);
+        }
+
+        let start = pos;
+        let mut from = pos;
+
+        while let Some(rel_pos) = content[from..].find('[') {
+            let tag_start = from + rel_pos;
+
+            if let Some((tokens, end)) = read_tag(content, tag_start) {
+                pending.extend(tokens);
+                pos = end;
+
+                return match tag_start > start {
+                    true => Some(Token {
+                        kind: TokenKind::Text(&content[start..tag_start]),
+                        span: start..tag_start,
+                    }),
+                    false => pending.pop_front(),
+                };
+            }
+
+            from = tag_start + 1;
+        }
+
+        pos = content.len();
+
+        (start < pos).then(|| Token {
+            kind: TokenKind::Text(&content[start..]),
+            span: start..pos,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::{tokenize, TokenKind, TokenKind::*};
+
+    fn kinds(content: &str) -> Vec<TokenKind<'_>> {
+        tokenize(content).map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn nested_tags() {
+        assert_eq!(
+            kinds("[quote][b]a[/b][/quote]"),
+            [
+                Open { name: "quote" },
+                Open { name: "b" },
+                Text("a"),
+                Close { name: "b" },
+                Close { name: "quote" },
+            ]
+        );
+        // balance is none of the tokenizer's business
+        assert_eq!(
+            kinds("[i][b]a[/i][/b][/u]"),
+            [
+                Open { name: "i" },
+                Open { name: "b" },
+                Text("a"),
+                Close { name: "i" },
+                Close { name: "b" },
+                Close { name: "u" },
+            ]
+        );
+        assert_eq!(
+            kinds("[*]x[/*][h1]"),
+            [
+                Open { name: "*" },
+                Text("x"),
+                Close { name: "*" },
+                Open { name: "h1" },
+            ]
+        );
+    }
+
+    #[test]
+    fn names_are_kept_as_written() {
+        assert_eq!(
+            kinds("[B]a[/b][Url=X]"),
+            [
+                Open { name: "B" },
+                Text("a"),
+                Close { name: "b" },
+                Open { name: "Url" },
+                Attribute {
+                    name: None,
+                    value: "X"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let tokens: Vec<_> = tokenize(r#"[list  type="a" start=3 ]"#).collect();
+
+        assert_eq!(tokens[0].kind, Open { name: "list" });
+        assert_eq!(tokens[0].span, 0..25);
+        assert_eq!(
+            tokens[1].kind,
+            Attribute {
+                name: Some("type"),
+                value: "a"
+            }
+        );
+        assert_eq!(tokens[1].span, 7..15);
+        assert_eq!(
+            tokens[2].kind,
+            Attribute {
+                name: Some("start"),
+                value: "3"
+            }
+        );
+        assert_eq!(tokens[2].span, 16..23);
+
+        assert_eq!(
+            kinds(r#"[url="a b]"]"#),
+            [
+                Open { name: "url" },
+                Attribute {
+                    name: None,
+                    value: "a b]"
+                },
+            ]
+        );
+        // quotes that aren't closed are part of the value
+        assert_eq!(
+            kinds(r#"[url="P]"#),
+            [
+                Open { name: "url" },
+                Attribute {
+                    name: None,
+                    value: "\"P"
+                },
+            ]
+        );
+        // unquoted values end at the first `]`
+        assert_eq!(
+            kinds("[url=a]b]"),
+            [
+                Open { name: "url" },
+                Attribute {
+                    name: None,
+                    value: "a"
+                },
+                Text("b]"),
+            ]
+        );
+        // attributes need a value, closing tags have none, and none spans lines
+        assert_eq!(kinds("[list foo]"), [Text("[list foo]")]);
+        assert_eq!(kinds("[/b x=1]"), [Text("[/b x=1]")]);
+        assert_eq!(kinds("[url=a\nb]"), [Text("[url=a\nb]")]);
+        assert_eq!(kinds("[url=\"a\nb\"]"), [Text("[url=\"a\nb\"]")]);
+    }
+
+    #[test]
+    fn stray_brackets() {
+        assert_eq!(kinds("a]b"), [Text("a]b")]);
+        assert_eq!(kinds("["), [Text("[")]);
+        assert_eq!(kinds("a [ b [b"), [Text("a [ b [b")]);
+        assert_eq!(kinds("[/] [1] [b\n]"), [Text("[/] [1] [b\n]")]);
+        assert_eq!(kinds("[[b]]"), [Text("["), Open { name: "b" }, Text("]")]);
+    }
+
+    #[test]
+    fn spans_cover_the_input() {
+        for content in [
+            "é[b]ü[/b]]",
+            "[[url=\"😀\" x=y]z[/url",
+            "[list type=\"a]",
+            "",
+        ] {
+            let mut end = 0;
+            let mut covered = String::new();
+
+            for token in tokenize(content) {
+                if let Attribute { .. } = token.kind {
+                    continue;
+                }
+
+                assert_eq!(token.span.start, end, "{content:?}");
+                end = token.span.end;
+                covered.push_str(&content[token.span]);
+            }
+
+            assert_eq!(covered, content);
+        }
+    }
+}
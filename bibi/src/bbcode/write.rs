@@ -1,108 +1,466 @@
 use std::{
-    fmt,
-    io::{self, Write},
+    borrow::Cow,
+    fmt, io,
+    iter::{self, Peekable},
+    ops::Range,
 };
 
-use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, LinkType, Parser, Tag};
 
-pub const DEFAULT_ANON_CODELANG: &str = "code";
-pub const DEFAULT_ANON_ICODELANG: &str = "inline";
+use crate::{
+    bbcode::{
+        encode_attribute,
+        html::{self, HtmlTag, Piece},
+        markdown_options, normalize_newlines, single_line,
+        smiley::restore_smileys,
+        span::{SpanMap, Spans},
+        ListSyntax, TagNames,
+    },
+    error::Result,
+    options::{CommentPolicy, ConvertOptions, HeadingStyle, HtmlPolicy, LinkStyle},
+    writefmt::{FmtWriter, IoWriter, Tracked, WriteFmt},
+};
+
+struct BBCode<'o, I: Iterator, W: WriteFmt> {
+    iter: Peekable<I>,
+
+    writer: Tracked<W>,
+    options: &'o ConvertOptions,
+    line_ending: &'static str,
 
-struct BBCode<I, W: io::Write> {
-    iter: I,
+    buf: String,
 
-    writer: W,
+    /// The comment being read, if its HTML has been split across several events.
+    comment: Option<String>,
+    /// Whether each `<a>` of the HTML still open became a link, with [`HtmlPolicy::Translate`].
+    html_links: Vec<bool>,
+    /// Whether each link still open became a link, which it doesn't if the link policy doesn't allow it.
+    links: Vec<bool>,
+    /// Whether the events are within a code block, whose text is copied as-is.
+    in_code_block: bool,
+    /// How many images the events are within, whose alt text BBCode has no place for.
+    in_images: usize,
 
-    at_newline: bool,
-    buf: Vec<u8>,
+    /// The spans of what has been written so far, if tracked.
+    spans: Option<SpanMap>,
+    /// How much of the input has been mapped to the output so far.
+    mapped: usize,
 }
 
-impl<'a, I, W> BBCode<I, W>
+impl<'a, 'o, I, W> BBCode<'o, I, W>
 where
-    I: Iterator<Item = Event<'a>> + 'a,
-    W: io::Write,
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+    W: WriteFmt,
 {
-    fn new(iter: I, writer: W) -> Self {
+    fn new(
+        iter: I,
+        writer: W,
+        options: &'o ConvertOptions,
+        line_ending: &'static str,
+        track: bool,
+    ) -> Self {
         Self {
-            iter,
-            writer,
-            at_newline: true,
-            buf: vec![],
+            iter: iter.peekable(),
+            writer: Tracked::new(writer),
+            options,
+            line_ending,
+            buf: String::new(),
+            comment: None,
+            html_links: vec![],
+            links: vec![],
+            in_code_block: false,
+            in_images: 0,
+            spans: track.then(SpanMap::default),
+            mapped: 0,
         }
     }
 
-    fn ensure_newline(&mut self) -> io::Result<()> {
-        if !self.at_newline {
-            #[cfg(windows)]
-            const LINE_END: &str = "\r\n";
+    fn ensure_newline(&mut self) -> Result<()> {
+        if !self.writer.at_line_start() {
+            writeln!(self)?;
+        }
 
-            #[cfg(not(windows))]
-            const LINE_END: &str = "\n";
+        Ok(())
+    }
 
-            write!(self, "{}", LINE_END)?;
+    /// Ends the current line, if needed, and leaves a blank one after it.
+    fn ensure_blank_line(&mut self) -> Result<()> {
+        for _ in self.writer.trailing_newlines()..2 {
+            writeln!(self)?;
         }
 
         Ok(())
     }
 
-    fn write_buf(&mut self) -> io::Result<()> {
-        self.writer.write_all(&self.buf)
+    fn write_buf(&mut self) -> Result<()> {
+        // every newline goes through here, so this is the only place where line endings need to be fixed
+        let text = normalize_newlines(&self.buf, self.line_ending);
+
+        self.writer.write_str(&text)
     }
 
-    fn write_fmt(&mut self, args: fmt::Arguments) -> io::Result<()> {
+    fn write_fmt(&mut self, args: fmt::Arguments) -> Result<()> {
         self.buf.clear();
 
-        self.buf.write_fmt(args)?;
-
-        self.at_newline = self.buf.last().map(|&b| b == b'\n').unwrap_or_default();
+        fmt::write(&mut self.buf, args)?;
 
         self.write_buf()
     }
 
-    fn run(mut self) -> io::Result<()> {
-        while let Some(event) = self.iter.next() {
+    /// Writes an opening tag without any attribute, e.g. `[b]`.
+    fn open_tag(&mut self, name: &str) -> Result<()> {
+        let case = self.options.tag_case;
+
+        write!(self, "[{}]", case.apply(name))
+    }
+
+    /// Writes an opening tag with a single unnamed value, e.g. `[url=http://example.com]`.
+    fn open_tag_value(&mut self, name: &str, value: &str) -> Result<()> {
+        let case = self.options.tag_case;
+        let value = self.options.attr_quoting.apply(value, false);
+
+        write!(self, "[{}={value}]", case.apply(name))
+    }
+
+    /// Writes an opening tag with named attributes, e.g. `[list start="3"]`.
+    fn open_tag_attrs(&mut self, name: &str, attrs: &[(&str, &str)]) -> Result<()> {
+        let case = self.options.tag_case;
+        let quoting = self.options.attr_quoting;
+
+        let attrs: String = attrs
+            .iter()
+            .map(|(key, value)| format!(" {}={}", case.apply(key), quoting.apply(value, true)))
+            .collect();
+
+        write!(self, "[{}{attrs}]", case.apply(name))
+    }
+
+    fn close_tag(&mut self, name: &str) -> Result<()> {
+        let case = self.options.tag_case;
+
+        write!(self, "[/{}]", case.apply(name))
+    }
+
+    /// The part of the input an event comes from. Start and end events span the whole element, so they're trimmed
+    /// to what comes before and after its content, respectively.
+    fn event_source(&mut self, event: &Event, range: Range<usize>) -> Range<usize> {
+        let start = range.start.max(self.mapped);
+
+        let end = match event {
+            Event::Start(_) => self
+                .iter
+                .peek()
+                .map_or(range.end, |(_, next)| next.start.min(range.end)),
+            _ => range.end,
+        };
+
+        start..end.max(start)
+    }
+
+    fn run(mut self) -> Result<SpanMap> {
+        while let Some((event, range)) = self.iter.next() {
             use Event::*;
 
+            let src = self.event_source(&event, range.clone());
+            let dst_start = self.writer.offset();
+
+            // text that has been written out unchanged maps byte by byte
+            let verbatim = matches!(&event, Text(text) if src == range && text.len() == src.len());
+
             match event {
+                // the alt text of images is dropped, along with anything it holds
+                Start(Tag::Image(..)) if self.in_images > 0 => {
+                    self.in_images += 1;
+                }
+                Start(Tag::Image(_, dest, _)) => {
+                    self.in_images += 1;
+
+                    if let Some(dest) = self.options.link_policy.apply(&dest) {
+                        self.image(&dest)?;
+                    }
+                }
+                End(Tag::Image(..)) => {
+                    self.in_images -= 1;
+                }
+                _ if self.in_images > 0 => {}
                 Start(tag) => {
                     self.start_tag(tag)?;
                 }
                 End(tag) => {
                     self.end_tag(tag)?;
                 }
+                Text(text) if self.in_code_block => {
+                    write!(self, "{text}")?;
+                }
                 Text(text) => {
+                    let text = restore_smileys(&text, self.options);
+
                     write!(self, "{text}")?;
                 }
                 Code(text) => {
-                    write!(self, "[c={DEFAULT_ANON_ICODELANG}]")?;
+                    let options = self.options;
+
+                    // dialects without inline code get a regular code block
+                    let name = match self.names().inline_code {
+                        Some(name) => {
+                            self.open_tag_value(name, &options.inline_code_lang)?;
+                            name
+                        }
+                        None => {
+                            self.open_tag("code")?;
+                            "code"
+                        }
+                    };
+
                     write!(self, "{text}")?;
-                    write!(self, "[/c]")?;
+                    self.close_tag(name)?;
                 }
                 SoftBreak => {
                     writeln!(self)?;
                 }
                 HardBreak => {
-                    write!(self, "\n\n")?;
+                    self.hard_break()?;
+                }
+                Html(html) => {
+                    self.html(&html)?;
+                }
+                FootnoteReference(label) => {
+                    self.footnote_marker(&label)?;
+                }
+                TaskListMarker(checked) => {
+                    let options = self.options;
+
+                    write!(self, " {} ", options.task_markers.marker(checked))?;
                 }
                 Rule => {
-                    writeln!(self, "[hr]")?;
+                    match self.names().rule {
+                        Some(name) => self.open_tag(name)?,
+                        None => write!(self, "----------")?,
+                    }
+
+                    writeln!(self)?;
                 }
-                _ => continue,
+            }
+
+            if let Some(spans) = &mut self.spans {
+                let verbatim = verbatim && self.writer.offset() - dst_start == src.len();
+
+                spans.push(src.clone(), dst_start..self.writer.offset(), verbatim);
+                self.mapped = src.end;
             }
         }
 
+        Ok(self.spans.unwrap_or_default())
+    }
+
+    /// Writes a comment as the options tell, ending it like a paragraph if it's a block of its own.
+    fn write_comment(&mut self, text: &str, block: bool) -> Result<()> {
+        let options = self.options;
+
+        let CommentPolicy::Tag(name) = &options.comments else {
+            return Ok(());
+        };
+
+        self.open_tag(name)?;
+        write!(self, "{}", text.trim())?;
+        self.close_tag(name)?;
+
+        if block {
+            self.ensure_blank_line()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the metadata block at the start of the input, given what's between its delimiters, as a comment.
+    fn metadata(&mut self, metadata: &str, src: Range<usize>) -> Result<()> {
+        self.write_comment(metadata, true)?;
+
+        if let Some(spans) = &mut self.spans {
+            spans.push(src.clone(), 0..self.writer.offset(), false);
+            self.mapped = src.end;
+        }
+
+        Ok(())
+    }
+
+    fn hard_break(&mut self) -> Result<()> {
+        write!(self, "\n\n")
+    }
+
+    /// Handles a piece of HTML: comments are written as the options tell, and so is any other HTML.
+    fn html(&mut self, html: &str) -> Result<()> {
+        // blocks of HTML come a line at a time, so comments may span several events
+        let mut comment = match self.comment.take() {
+            Some(comment) => comment,
+            None if html.starts_with("<!--") => String::new(),
+            None => return self.other_html(html),
+        };
+
+        comment.push_str(html);
+
+        match comment[4..].find("-->") {
+            Some(end) => self.write_comment(&comment[4..end + 4], comment.ends_with('\n')),
+            None => {
+                self.comment = Some(comment);
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes a piece of HTML other than a comment as [`HtmlPolicy`] tells, ending it like a paragraph if it's the
+    /// last line of a block of HTML.
+    fn other_html(&mut self, html: &str) -> Result<()> {
+        // raw HTML would let anything through content restricted to some schemes, so it's translated instead
+        let policy = match self.options.html {
+            HtmlPolicy::Verbatim if self.options.link_policy.is_restrictive() => {
+                HtmlPolicy::Translate
+            }
+            policy => policy,
+        };
+
+        match policy {
+            HtmlPolicy::Strip => return Ok(()),
+            HtmlPolicy::Verbatim => write!(self, "{html}")?,
+            HtmlPolicy::Translate => {
+                for piece in html::pieces(html) {
+                    match piece {
+                        // whitespace laying out blocks of HTML, such as the line break after `<div>`
+                        Piece::Text(text)
+                            if text.trim().is_empty() && self.writer.at_line_start() => {}
+                        Piece::Text(text) => write!(self, "{}", html::unescape(text))?,
+                        Piece::Tag(tag) => self.html_tag(&tag)?,
+                    }
+                }
+            }
+        }
+
+        // inline HTML always ends with a tag, while blocks of HTML come a line at a time
+        let block_ends =
+            html.ends_with('\n') && !matches!(self.iter.peek(), Some((Event::Html(_), _)));
+
+        if block_ends {
+            self.ensure_blank_line()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the BBCode counterpart of an HTML tag, if it has one.
+    fn html_tag(&mut self, tag: &HtmlTag) -> Result<()> {
+        let TagNames { bold, italic, .. } = *self.names();
+
+        let simple = [("b", bold), ("i", italic[0])];
+
+        if let Some(&(_, name)) = simple.iter().find(|(html, _)| tag.is(html)) {
+            return match tag.closing {
+                true => self.close_tag(name),
+                false => self.open_tag(name),
+            };
+        }
+
+        if tag.is("a") {
+            return match tag.closing {
+                true => match self.html_links.pop() {
+                    Some(true) => self.close_tag("url"),
+                    _ => Ok(()),
+                },
+                false => {
+                    let href = tag.attribute("href");
+                    let href = href
+                        .as_deref()
+                        .and_then(|href| self.options.link_policy.apply(href));
+                    self.html_links.push(href.is_some());
+
+                    match href {
+                        Some(href) => self.open_tag_value("url", &encode_attribute(&href)),
+                        None => Ok(()),
+                    }
+                }
+            };
+        }
+
+        match tag.attribute("src") {
+            Some(src) if tag.is("img") && !tag.closing => {
+                match self.options.link_policy.apply(&src) {
+                    Some(src) => self.image(&src),
+                    None => Ok(()),
+                }
+            }
+            _ if tag.is("br") => self.hard_break(),
+            _ => Ok(()),
+        }
+    }
+
+    fn image(&mut self, src: &str) -> Result<()> {
+        self.open_tag("img")?;
+        write!(self, "{}", encode_attribute(src))?;
+        self.close_tag("img")
+    }
+
+    /// Writes the marker of a footnote, e.g. `[sup][1][/sup]`. No dialect has footnotes, so they're superscript
+    /// labels, which are read back as footnotes when converting to Markdown.
+    fn footnote_marker(&mut self, label: &str) -> Result<()> {
+        self.open_tag("sup")?;
+        write!(self, "[{label}]")?;
+        self.close_tag("sup")
+    }
+
+    fn names(&self) -> &'static TagNames {
+        self.options.dialect.names()
+    }
+
+    /// The tags wrapping a heading of the given level, outermost first. Dialects without `[big]` use bold instead.
+    fn heading_tags(&self, level: HeadingLevel) -> Vec<(&'static str, Option<String>)> {
+        use HeadingStyle::*;
+
+        let TagNames { bold, big, .. } = *self.names();
+
+        match (self.options.headings.style(level as usize), big) {
+            (Big, Some(big)) => vec![(big, None)],
+            (BigBold, Some(big)) => vec![(big, None), (bold, None)],
+            (Big | BigBold | Bold, _) => vec![(bold, None)],
+            (Size(size), _) => vec![("size", Some(size.to_string()))],
+            (Plain, _) => vec![],
+        }
+    }
+
+    fn open_heading(&mut self, level: HeadingLevel) -> Result<()> {
+        for (name, value) in self.heading_tags(level) {
+            match value {
+                Some(value) => self.open_tag_value(name, &value)?,
+                None => self.open_tag(name)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn close_heading(&mut self, level: HeadingLevel) -> Result<()> {
+        for (name, _) in self.heading_tags(level).into_iter().rev() {
+            self.close_tag(name)?;
+        }
+
         Ok(())
     }
 
     /// Writes the start of an HTML tag.
-    fn start_tag(&mut self, tag: Tag<'a>) -> io::Result<()> {
+    fn start_tag(&mut self, tag: Tag<'a>) -> Result<()> {
         use Tag::*;
 
+        let options = self.options;
+
         match tag {
             Paragraph => Ok(()),
-            Heading(..) => write!(self, "[big]"),
-            BlockQuote => writeln!(self, "[quote]"),
+            Heading(level, ..) => self.open_heading(level),
+            BlockQuote => {
+                self.open_tag("quote")?;
+                writeln!(self)
+            }
             CodeBlock(info) => {
+                self.in_code_block = true;
+
                 use CodeBlockKind::*;
 
                 let lang = match &info {
@@ -110,74 +468,111 @@ where
                         let lang = info.split(' ').next().unwrap();
 
                         if lang.is_empty() {
-                            "code"
+                            &options.code_lang
                         } else {
                             lang
                         }
                     }
-                    Indented => DEFAULT_ANON_CODELANG,
+                    Indented => &options.code_lang,
                 };
 
-                writeln!(self, "[code={lang}]")
+                self.open_tag_value("code", lang)?;
+                writeln!(self)
             }
-            List(Some(1)) => writeln!(self, "[list type=\"1\"]"),
             List(Some(start)) => {
-                writeln!(self, "[list start=\"{start}\"]")
+                match (self.names().list, start) {
+                    (ListSyntax::Attributes, 1) => self.open_tag_attrs("list", &[("type", "1")])?,
+                    (ListSyntax::Attributes, start) => {
+                        self.open_tag_attrs("list", &[("start", &start.to_string())])?
+                    }
+                    // the start is lost, there's no way to represent it
+                    (ListSyntax::Value, _) => self.open_tag_value("list", "1")?,
+                }
+
+                writeln!(self)
+            }
+            List(None) => {
+                self.open_tag("list")?;
+                writeln!(self)
             }
-            List(None) => writeln!(self, "[list]"),
             Item => {
                 self.ensure_newline()?;
 
-                write!(self, "[*]")
+                self.open_tag("*")
             }
-            Emphasis => write!(self, "[cur]"),
-            Strong => write!(self, "[b]"),
-            Strikethrough => write!(self, "[del]"),
-            Link(_, dest, _) => {
-                write!(self, "[url={dest}]")
+            Emphasis => self.open_tag(self.names().italic[0]),
+            Strong => self.open_tag(self.names().bold),
+            Strikethrough => self.open_tag(self.names().strike[0]),
+            Link(kind, dest, _) => {
+                let dest = options.link_policy.apply(&dest);
+                self.links.push(dest.is_some());
+
+                match dest {
+                    // the text of an autolink is the URL itself, so it will be written as the tag's content
+                    Some(_)
+                        if kind == LinkType::Autolink && options.links == LinkStyle::Autolink =>
+                    {
+                        self.open_tag("url")
+                    }
+                    Some(dest) => self.open_tag_value("url", &encode_attribute(&dest)),
+                    None => Ok(()),
+                }
             }
-            Image(_, dest, _) => {
-                write!(self, "[img]{dest}[/img]")
+            FootnoteDefinition(label) => {
+                self.ensure_newline()?;
+                self.footnote_marker(&label)?;
+
+                write!(self, ": ")
             }
             _ => Ok(()),
         }
     }
 
-    fn end_tag(&mut self, tag: Tag) -> io::Result<()> {
+    fn end_tag(&mut self, tag: Tag) -> Result<()> {
         use Tag::*;
 
         match tag {
             Paragraph => {
-                write!(self, "\n\n")?;
+                self.ensure_blank_line()?;
             }
-            Heading(..) => {
-                write!(self, "[/big]\n\n")?;
+            Heading(level, ..) => {
+                self.close_heading(level)?;
+                self.ensure_blank_line()?;
             }
             BlockQuote => {
-                writeln!(self, "[/quote]")?;
+                self.close_tag("quote")?;
+                writeln!(self)?;
             }
             CodeBlock(_) => {
+                self.in_code_block = false;
+
                 self.ensure_newline()?;
-                writeln!(self, "[/code]")?;
+                self.close_tag("code")?;
+                writeln!(self)?;
             }
             List(_) => {
                 self.ensure_newline()?;
-                writeln!(self, "[/list]")?;
+                self.close_tag("list")?;
+                writeln!(self)?;
             }
             Item => {}
             Emphasis => {
-                write!(self, "[/cur]")?;
+                self.close_tag(self.names().italic[0])?;
             }
             Strong => {
-                write!(self, "[/b]")?;
+                self.close_tag(self.names().bold)?;
             }
             Strikethrough => {
-                write!(self, "[/del]")?;
+                self.close_tag(self.names().strike[0])?;
             }
             Link(_, _, _) => {
-                write!(self, "[/url]")?;
+                // links the policy dropped have nothing to close
+                let written = self.links.pop() == Some(true);
+
+                if written {
+                    self.close_tag("url")?;
+                }
             }
-            Image(_, _, _) => {} // do nothing, the image has already been closed in the start function
             _ => {}
         }
         Ok(())
@@ -188,11 +583,15 @@ where
 /// This functions does not support the entitety of Markdown due to NERDZ-flavoured BBCode
 /// not supporting certain features.
 ///
-/// This function supports some markdown extensions, such as strikethrough.
+/// This function supports some markdown extensions, such as strikethrough and footnotes. As BBCode has no footnotes,
+/// references are written as superscript labels, e.g. `[sup][1][/sup]`, and each definition as a line starting with
+/// its label, e.g. `[sup][1][/sup]: text`, where Markdown defines it, so those at the end of a document become a
+/// trailing list of notes. Superscript labels are read back as footnotes by [`to_markdown`](crate::to_markdown).
 ///
 /// # Examples
 ///
 /// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
 /// use std::{error::Error, io::{self, Write}, str};
 /// use bibi::dump_bbcode;
 ///
@@ -206,11 +605,406 @@ where
 ///
 ///     Ok(())
 /// }
-pub fn dump_bbcode(writer: impl io::Write, contents: &str) -> io::Result<()> {
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
+/// # main().unwrap() }
+/// ```
+pub fn dump_bbcode(writer: impl io::Write, contents: &str) -> Result<()> {
+    dump_bbcode_with(writer, contents, &ConvertOptions::default())
+}
+
+/// Same as [`dump_bbcode`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+///
+/// # Examples
+///
+/// ```
+/// use std::{error::Error, io::{self, Write}, str};
+/// use bibi::{dump_bbcode_with, AttributeQuoting, ConvertOptions, TagCase};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let mut writer = Vec::new();
+///     let options = ConvertOptions {
+///         tag_case: TagCase::Upper,
+///         attr_quoting: AttributeQuoting::Always,
+///         ..Default::default()
+///     };
+///
+///     dump_bbcode_with(&mut writer, "**Hello** [everybody](https://nerdz.eu)", &options)?;
+///
+///     assert_eq!(
+///         str::from_utf8(&writer)?,
+///         "[B]Hello[/B] [URL=\"https://nerdz.eu\"]everybody[/URL]\n\n"
+///     );
+///
+///     Ok(())
+/// }
+pub fn dump_bbcode_with(
+    writer: impl io::Write,
+    contents: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    convert(IoWriter(writer), contents, options, false)?;
+
+    Ok(())
+}
+
+/// Same as [`dump_bbcode`], but writes into a [`fmt::Write`], such as a `String`.
+///
+/// # Examples
+///
+/// ```
+/// use std::error::Error;
+/// use bibi::dump_bbcode_fmt;
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let mut post = String::from("[cur]Today's recipe:[/cur]\n");
+///     dump_bbcode_fmt(&mut post, "- eggs\n- bacon")?;
+///
+///     assert_eq!(post, "[cur]Today's recipe:[/cur]\n[list]\n[*]eggs\n[*]bacon\n[/list]\n");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn dump_bbcode_fmt(writer: impl fmt::Write, contents: &str) -> Result<()> {
+    dump_bbcode_fmt_with(writer, contents, &ConvertOptions::default())
+}
+
+/// Same as [`dump_bbcode_fmt`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+pub fn dump_bbcode_fmt_with(
+    writer: impl fmt::Write,
+    contents: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    convert(FmtWriter(writer), contents, options, false)?;
+
+    Ok(())
+}
+
+fn convert(
+    writer: impl WriteFmt,
+    contents: &str,
+    options: &ConvertOptions,
+    track: bool,
+) -> Result<SpanMap> {
+    let metadata = metadata_block(contents);
+    let start = metadata.map_or(0, |(_, end)| end);
 
-    let parser = Parser::new_ext(contents, options);
+    let parser = Parser::new_ext(&contents[start..], markdown_options())
+        .into_offset_iter()
+        .map(|(event, range)| (event, range.start + start..range.end + start));
 
-    BBCode::new(parser, writer).run()
+    let line_ending = options.line_ending.resolve(contents);
+
+    let mut bbcode = BBCode::new(parser, writer, options, line_ending, track);
+
+    if let Some((metadata, end)) = metadata {
+        bbcode.metadata(metadata, 0..end)?;
+    }
+
+    bbcode.run()
+}
+
+/// Tells whether `contents` starts the way a metadata block does, i.e. with a `---` line followed by one that isn't
+/// blank, so that a rule followed by a paragraph isn't mistaken for one.
+pub(super) fn opens_metadata_block(contents: &str) -> bool {
+    let mut lines = contents.split_inclusive('\n');
+
+    lines.next().is_some_and(|line| line.trim_end() == "---")
+        && lines.next().is_some_and(|line| !line.trim().is_empty())
+}
+
+/// Finds the metadata block at the start of `contents`, if any, returning what's between its delimiters along with
+/// where it ends. The block is closed by a `---` or `...` line.
+pub(super) fn metadata_block(contents: &str) -> Option<(&str, usize)> {
+    if !opens_metadata_block(contents) {
+        return None;
+    }
+
+    let start = contents.find('\n')? + 1;
+    let mut pos = start;
+
+    for line in contents[start..].split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some((&contents[start..pos], pos + line.len()));
+        }
+
+        pos += line.len();
+    }
+
+    None
+}
+
+/// Writes the metadata of a document as [`convert`] does, for conversions that parse the rest of it on their own.
+pub(super) fn write_metadata_fmt(
+    writer: impl fmt::Write,
+    metadata: &str,
+    options: &ConvertOptions,
+) -> Result<()> {
+    let line_ending = options.line_ending.resolve("");
+    let events = iter::empty();
+
+    BBCode::new(events, FmtWriter(writer), options, line_ending, false).metadata(metadata, 0..0)
+}
+
+/// Writes the BBCode representation of the given [pulldown-cmark](https://docs.rs/pulldown-cmark/0.9) events to
+/// the given writer. This is the same as [`dump_bbcode`], except that the Markdown has already been parsed, so that
+/// its events can be filtered or rewritten before being converted.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// use std::{error::Error, str};
+/// use bibi::write_bbcode;
+/// use pulldown_cmark::{Event, Parser, Tag};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     // drop images and their alt text, keep everything else
+///     let mut in_image = false;
+///     let events = Parser::new("Look ![a cat](cat.png) at *this*").filter(|event| match event {
+///         Event::Start(Tag::Image(..)) => {
+///             in_image = true;
+///             false
+///         }
+///         Event::End(Tag::Image(..)) => {
+///             in_image = false;
+///             false
+///         }
+///         _ => !in_image,
+///     });
+///
+///     let mut writer = Vec::new();
+///     write_bbcode(&mut writer, events)?;
+///
+///     assert_eq!(str::from_utf8(&writer)?, "Look  at [cur]this[/cur]\n\n");
+///
+///     Ok(())
+/// }
+/// # main().unwrap() }
+/// ```
+pub fn write_bbcode<'a>(
+    writer: impl io::Write,
+    events: impl IntoIterator<Item = Event<'a>>,
+) -> Result<()> {
+    write_bbcode_with(writer, events, &ConvertOptions::default())
+}
+
+/// Same as [`write_bbcode`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+///
+/// As there's no input text to look at, [`LineEnding::MatchInput`](crate::LineEnding::MatchInput) always
+/// results in `\n`.
+pub fn write_bbcode_with<'a>(
+    writer: impl io::Write,
+    events: impl IntoIterator<Item = Event<'a>>,
+    options: &ConvertOptions,
+) -> Result<()> {
+    convert_events(IoWriter(writer), events, options)
+}
+
+/// Same as [`write_bbcode`], but writes into a [`fmt::Write`], such as a `String`.
+pub fn write_bbcode_fmt<'a>(
+    writer: impl fmt::Write,
+    events: impl IntoIterator<Item = Event<'a>>,
+) -> Result<()> {
+    write_bbcode_fmt_with(writer, events, &ConvertOptions::default())
+}
+
+/// Same as [`write_bbcode_fmt`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+pub fn write_bbcode_fmt_with<'a>(
+    writer: impl fmt::Write,
+    events: impl IntoIterator<Item = Event<'a>>,
+    options: &ConvertOptions,
+) -> Result<()> {
+    convert_events(FmtWriter(writer), events, options)
+}
+
+fn convert_events<'a>(
+    writer: impl WriteFmt,
+    events: impl IntoIterator<Item = Event<'a>>,
+    options: &ConvertOptions,
+) -> Result<()> {
+    // there's no source to map the events to, so they all get an empty range
+    let events = events.into_iter().map(|event| (event, 0..0));
+
+    let line_ending = options.line_ending.resolve("");
+
+    BBCode::new(events, writer, options, line_ending, false).run()?;
+
+    Ok(())
+}
+
+/// Converts the given Markdown to NERDZ BBCode, returning it as a string. See [`dump_bbcode`] for details.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// # fn main() -> bibi::Result<()> {
+/// assert_eq!(bibi::to_bbcode("*Hello*")?, "[cur]Hello[/cur]\n\n");
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+pub fn to_bbcode(contents: &str) -> Result<String> {
+    to_bbcode_with(contents, &ConvertOptions::default())
+}
+
+/// Same as [`to_bbcode`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+pub fn to_bbcode_with(contents: &str, options: &ConvertOptions) -> Result<String> {
+    let mut ret = String::new();
+
+    convert(FmtWriter(&mut ret), contents, options, false)?;
+
+    Ok(ret)
+}
+
+/// Escapes whatever would make `line` start a block rather than a paragraph, such as the `-` of a list item or the
+/// `#` of a heading, along with its indentation.
+fn escape_block_start(line: &str) -> Cow<'_, str> {
+    let line = line.trim_start();
+
+    let first = Parser::new_ext(line, markdown_options()).next();
+
+    if line.is_empty() || matches!(first, Some(Event::Start(Tag::Paragraph))) {
+        return line.into();
+    }
+
+    // the marker of an ordered list item comes after its number
+    let at = line.find(|c: char| !c.is_ascii_digit()).unwrap_or_default();
+
+    format!("{}\\{}", &line[..at], &line[at..]).into()
+}
+
+/// Same as [`to_bbcode`], but reads the Markdown as a single inline run, such as a title or a status message, and
+/// returns BBCode on a single line, without any trailing line break. Line breaks become spaces, and whatever would
+/// start a block, such as `1.` or `#`, is kept as it's written.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// # fn main() -> bibi::Result<()> {
+/// assert_eq!(bibi::to_bbcode_inline("# *Hello*\nthere")?, "# [cur]Hello[/cur] there");
+/// assert_eq!(bibi::to_bbcode_inline("1. **first**")?, "1. [b]first[/b]");
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+pub fn to_bbcode_inline(contents: &str) -> Result<String> {
+    to_bbcode_inline_with(contents, &ConvertOptions::default())
+}
+
+/// Same as [`to_bbcode_inline`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+pub fn to_bbcode_inline_with(contents: &str, options: &ConvertOptions) -> Result<String> {
+    let line = single_line(contents);
+    let line = escape_block_start(line.trim_end());
+
+    // with no block markers left, everything is in a single paragraph, whose tags would only add line breaks
+    let events = Parser::new_ext(&line, markdown_options()).filter(|event| {
+        !matches!(
+            event,
+            Event::Start(Tag::Paragraph) | Event::End(Tag::Paragraph)
+        )
+    });
+
+    let mut ret = String::new();
+    convert_events(FmtWriter(&mut ret), events, options)?;
+
+    Ok(ret)
+}
+
+/// Same as [`to_bbcode`], but also returns which part of the Markdown produced which part of the BBCode, as
+/// [`Spans`](crate::Spans). See [`to_markdown_spans`](crate::to_markdown_spans) for details.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// # fn main() -> bibi::Result<()> {
+/// let (bbcode, spans) = bibi::to_bbcode_spans("*Hi* there")?;
+///
+/// assert_eq!(bbcode, "[cur]Hi[/cur] there\n\n");
+/// assert_eq!(spans, [(0..1, 0..5), (1..3, 5..7), (3..4, 7..13), (4..10, 13..19), (10..10, 19..21)]);
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+pub fn to_bbcode_spans(contents: &str) -> Result<(String, Spans)> {
+    to_bbcode_spans_with(contents, &ConvertOptions::default())
+}
+
+/// Same as [`to_bbcode_spans`], but allows tweaking the generated BBCode through the given [`ConvertOptions`].
+pub fn to_bbcode_spans_with(contents: &str, options: &ConvertOptions) -> Result<(String, Spans)> {
+    let (bbcode, map) = bbcode_map(contents, options)?;
+
+    Ok((bbcode, map.into_spans()))
+}
+
+/// Converts Markdown to BBCode, returning the [`SpanMap`] of the conversion.
+pub(super) fn bbcode_map(contents: &str, options: &ConvertOptions) -> Result<(String, SpanMap)> {
+    let mut ret = String::new();
+
+    let map = convert(FmtWriter(&mut ret), contents, options, true)?;
+
+    Ok((ret, map))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{to_bbcode, to_bbcode_with, to_markdown, ConvertOptions, HtmlPolicy, LinkPolicy};
+
+    #[test]
+    fn restricted_html_is_never_verbatim() {
+        let options = ConvertOptions {
+            html: HtmlPolicy::Verbatim,
+            link_policy: LinkPolicy::untrusted(),
+            ..Default::default()
+        };
+
+        let bbcode = to_bbcode_with(
+            "<a href=\"javascript:alert(1)\">x</a> <img src=\"data:x\" onerror=\"alert(1)\"> \
+             <script>alert(1)</script> <a href=\"https://nerdz.eu\">y</a>",
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(bbcode, "x  alert(1) [url=https://nerdz.eu]y[/url]\n\n");
+    }
+
+    #[test]
+    fn footnotes_are_superscript_labels() {
+        let markdown = "Hi[^1] there[^note].\n\n[^1]: First.\n[^note]: Second.\n";
+        let bbcode = to_bbcode(markdown).unwrap();
+
+        assert_eq!(
+            bbcode,
+            "Hi[sup][1][/sup] there[sup][note][/sup].\n\n\
+             [sup][1][/sup]: First.\n\n\
+             [sup][note][/sup]: Second.\n\n"
+        );
+        assert_eq!(
+            to_markdown(&bbcode),
+            "Hi[^1] there[^note].\n\n[^1]: First.\n\n[^note]: Second.\n\n"
+        );
+        assert_eq!(to_bbcode(&to_markdown(&bbcode)).unwrap(), bbcode);
+    }
+
+    #[test]
+    fn alt_text_is_dropped() {
+        assert_eq!(to_bbcode("![alt](x.png)").unwrap(), "[img]x.png[/img]\n\n");
+        assert_eq!(
+            to_bbcode("a ![cat **photo**](foo/cat_photo.png) b").unwrap(),
+            "a [img]foo/cat_photo.png[/img] b\n\n"
+        );
+        assert_eq!(
+            to_bbcode("![a ![b](b.png) `c`](a.png)").unwrap(),
+            "[img]a.png[/img]\n\n"
+        );
+    }
+
+    #[test]
+    fn links_around_images_are_kept() {
+        assert_eq!(
+            to_bbcode("[![alt](x.png) text](https://nerdz.eu)").unwrap(),
+            "[url=https://nerdz.eu][img]x.png[/img] text[/url]\n\n"
+        );
+    }
 }
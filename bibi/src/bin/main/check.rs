@@ -0,0 +1,206 @@
+//! Round trips, telling what doesn't survive converting a file and then converting it back, so that the losses of
+//! a migration can be assessed before running it.
+
+use std::{collections::BTreeMap, fmt};
+
+use pulldown_cmark::{Event, Tag};
+
+use bibi::{
+    supported_tags, to_bbcode_with, to_markdown_with, tokenize, ConvertOptions, Dialect, Direction,
+    TokenKind,
+};
+
+use crate::{markup::parse, Format};
+
+/// How many words of the text are shown from where it changes.
+const EXCERPT: usize = 6;
+
+/// Something that doesn't survive a round trip.
+#[derive(Debug)]
+pub enum Change {
+    /// A construct appears a different number of times after the round trip, e.g. because it has no counterpart in
+    /// the other format.
+    Count {
+        construct: String,
+        before: usize,
+        after: usize,
+    },
+    /// The text differs after the round trip, starting from the given words.
+    Text { before: String, after: String },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Count {
+                construct,
+                before,
+                after,
+            } => write!(
+                f,
+                "{construct} appears {before} time(s) before the round trip and {after} after"
+            ),
+            Change::Text { before, after } => {
+                write!(f, "the text changes from {before:?} to {after:?}")
+            }
+        }
+    }
+}
+
+/// The constructs of a text, counted by name, along with the words of its text.
+#[derive(Default)]
+pub struct Shape {
+    counts: BTreeMap<String, usize>,
+    words: Vec<String>,
+}
+
+impl Shape {
+    pub fn count(&mut self, construct: String) {
+        *self.counts.entry(construct).or_default() += 1;
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        self.words
+            .extend(text.split_whitespace().map(str::to_owned));
+    }
+
+    /// Tells how `after` differs from this shape.
+    pub fn changes(&self, after: &Shape) -> Vec<Change> {
+        let constructs = self.counts.keys().chain(after.counts.keys());
+        let mut changed = BTreeMap::new();
+
+        for construct in constructs {
+            let before = self.counts.get(construct).copied().unwrap_or_default();
+            let after = after.counts.get(construct).copied().unwrap_or_default();
+
+            if before != after {
+                changed.insert(construct.clone(), (before, after));
+            }
+        }
+
+        let mut ret: Vec<_> = changed
+            .into_iter()
+            .map(|(construct, (before, after))| Change::Count {
+                construct,
+                before,
+                after,
+            })
+            .collect();
+
+        let common = self
+            .words
+            .iter()
+            .zip(&after.words)
+            .take_while(|(before, after)| before == after)
+            .count();
+
+        if common < self.words.len().max(after.words.len()) {
+            let excerpt = |words: &[String]| {
+                let end = words.len().min(common + EXCERPT);
+
+                words[common.min(end)..end].join(" ")
+            };
+
+            ret.push(Change::Text {
+                before: excerpt(&self.words),
+                after: excerpt(&after.words),
+            });
+        }
+
+        ret
+    }
+}
+
+/// The tags of some BBCode, by the name the dialect gives them, along with its text.
+fn bbcode_shape(bbcode: &str) -> Shape {
+    let known = supported_tags(Direction::ToMarkdown, Dialect::default());
+    let mut ret = Shape::default();
+
+    for token in tokenize(bbcode) {
+        match token.kind {
+            TokenKind::Open { name } => {
+                let name = known
+                    .iter()
+                    .find(|tag| tag.matches(name))
+                    .map_or_else(|| name.to_lowercase(), |tag| tag.name.to_owned());
+
+                ret.count(format!("[{name}]"));
+            }
+            TokenKind::Text(text) => ret.add_text(text),
+            _ => {}
+        }
+    }
+
+    ret
+}
+
+/// The name of a Markdown construct in messages, or `None` for those that are only there to hold others.
+fn construct_name(tag: &Tag) -> Option<String> {
+    let name = match tag {
+        Tag::Paragraph | Tag::TableHead | Tag::TableRow | Tag::TableCell => return None,
+        Tag::Heading(level, ..) => return Some(format!("heading ({level})")),
+        Tag::BlockQuote => "block quote",
+        Tag::CodeBlock(_) => "code block",
+        Tag::List(Some(_)) => "ordered list",
+        Tag::List(None) => "list",
+        Tag::Item => "list item",
+        Tag::FootnoteDefinition(_) => "footnote",
+        Tag::Table(_) => "table",
+        Tag::Emphasis => "emphasis",
+        Tag::Strong => "strong emphasis",
+        Tag::Strikethrough => "strikethrough",
+        Tag::Link(..) => "link",
+        Tag::Image(..) => "image",
+    };
+
+    Some(name.to_owned())
+}
+
+/// The constructs of some Markdown, along with its text.
+fn markdown_shape(markdown: &str) -> Shape {
+    let mut ret = Shape::default();
+
+    for event in parse(markdown) {
+        match event {
+            Event::Start(tag) => {
+                if let Some(name) = construct_name(&tag) {
+                    ret.count(name);
+                }
+            }
+            Event::Code(text) => {
+                ret.count("inline code".to_owned());
+                ret.add_text(&text);
+            }
+            Event::Text(text) | Event::Html(text) => ret.add_text(&text),
+            Event::Rule => ret.count("rule".to_owned()),
+            _ => {}
+        }
+    }
+
+    ret
+}
+
+/// Counts the constructs of some Markdown by name, leaving out those that only hold others, such as paragraphs.
+pub fn count_constructs(markdown: &str) -> BTreeMap<String, usize> {
+    markdown_shape(markdown).counts
+}
+
+/// Converts `contents` from `from` to its counterpart and back with `options`, telling what changed.
+pub fn round_trip(
+    contents: &str,
+    from: Format,
+    options: &ConvertOptions,
+) -> bibi::Result<Vec<Change>> {
+    Ok(match from {
+        Format::Bbcode => {
+            let back = to_bbcode_with(&to_markdown_with(contents, options), options)?;
+
+            bbcode_shape(contents).changes(&bbcode_shape(&back))
+        }
+        Format::Markdown => {
+            let back = to_markdown_with(&to_bbcode_with(contents, options)?, options);
+
+            markdown_shape(contents).changes(&markdown_shape(&back))
+        }
+    })
+}
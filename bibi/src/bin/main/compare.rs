@@ -0,0 +1,419 @@
+//! Side by side renderings of BBCode, as NERDZ shows it, and of the Markdown it has been converted to, so that the
+//! visual differences a conversion introduces can be checked by eye.
+
+use pulldown_cmark::{escape::escape_html, Event, Tag};
+
+use bibi::{tokenize, LinkPolicy, Token, TokenKind};
+
+use crate::{
+    check::{Change, Shape},
+    markup::{parse, sanitizing, to_html},
+};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 1em 2em; }
+.panes { display: flex; gap: 2em; }
+.panes > section { flex: 1; min-width: 0; border: 1px solid #ccc; padding: 0 1em; }
+.differences li, .same { background: #fff3b0; }
+.big { font-size: 1.5em; }
+blockquote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 1em; }
+pre { background: #f4f4f4; padding: 0.5em; overflow-x: auto; }";
+
+fn escape(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    escape_html(&mut ret, text).expect("writing to a String never fails");
+
+    ret
+}
+
+/// How an element looks, for the differences between the renderings. BBCode and Markdown constructs that look the
+/// same have the same name.
+fn look_of_tag(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "b" => "bold",
+        "cur" | "i" => "italic",
+        "del" => "strikethrough",
+        "big" => "large text",
+        "url" => "link",
+        "img" => "image",
+        "quote" => "quote",
+        "list" => "list",
+        "*" => "list item",
+        "code" => "code block",
+        "c" => "inline code",
+        "hr" => "rule",
+        _ => return None,
+    })
+}
+
+fn look_of_construct(tag: &Tag) -> Option<&'static str> {
+    Some(match tag {
+        Tag::Strong => "bold",
+        Tag::Emphasis => "italic",
+        Tag::Strikethrough => "strikethrough",
+        Tag::Heading(..) => "large text",
+        Tag::Link(..) => "link",
+        Tag::Image(..) => "image",
+        Tag::BlockQuote => "quote",
+        Tag::List(_) => "list",
+        Tag::Item => "list item",
+        Tag::CodeBlock(_) => "code block",
+        _ => return None,
+    })
+}
+
+/// Finds where the closing tag of `name` ends in `bbcode`, from `from` on, ignoring case.
+fn find_close(bbcode: &str, from: usize, name: &str) -> Option<(usize, usize)> {
+    let close = format!("[/{}]", name.to_ascii_lowercase());
+    let start = from + bbcode[from..].to_ascii_lowercase().find(&close)?;
+
+    Some((start, start + close.len()))
+}
+
+/// Renders BBCode to HTML the way NERDZ does, as far as the tags the converter knows are concerned. Anything else
+/// is shown as it's written, like NERDZ does with the tags it doesn't know.
+struct BbcodeRenderer<'t> {
+    bbcode: &'t str,
+    /// What links and images may point to, as the page is no safer than what it shows.
+    links: &'t LinkPolicy,
+    html: String,
+    /// The tags still open, by name in lowercase, along with the HTML closing them.
+    open: Vec<(String, &'static str)>,
+    shape: Shape,
+}
+
+impl<'t> BbcodeRenderer<'t> {
+    fn text(&mut self, text: &str) {
+        // whitespace between the items of a list isn't shown
+        if self.open.last().is_some_and(|(name, _)| name == "list") && text.trim().is_empty() {
+            return;
+        }
+
+        self.shape.add_text(text);
+        self.html.push_str(&escape(text).replace('\n', "<br>\n"));
+    }
+
+    fn push(&mut self, name: &str, open: &str, close: &'static str) {
+        self.html.push_str(open);
+        self.open.push((name.to_owned(), close));
+
+        if let Some(look) = look_of_tag(name) {
+            self.shape.count(look.to_owned());
+        }
+    }
+
+    /// Closes every tag up to the last `name`, returning whether it's open at all.
+    fn close(&mut self, name: &str) -> bool {
+        let Some(pos) = self.open.iter().rposition(|(open, _)| open == name) else {
+            return false;
+        };
+
+        for (_, close) in self.open.drain(pos..).rev() {
+            self.html.push_str(close);
+        }
+
+        true
+    }
+
+    /// Renders a tag whose content is what it refers to, i.e. `[url]P[/url]` or `[img]P[/img]`, returning where
+    /// it ends.
+    fn render_target(&mut self, name: &str, end: usize) -> Option<usize> {
+        let (close_start, close_end) = find_close(self.bbcode, end, name)?;
+        let target = &self.bbcode[end..close_start];
+
+        if target.contains(['\n', '[']) {
+            return None;
+        }
+
+        let html = match (name, self.links.apply(target)) {
+            ("url", Some(href)) => format!("<a href=\"{}\">{}</a>", escape(&href), escape(target)),
+            ("url", None) => escape(target),
+            (_, Some(src)) => format!("<img src=\"{}\" alt=\"\">", escape(&src)),
+            (_, None) => String::new(),
+        };
+
+        self.html.push_str(&html);
+        self.shape.count(look_of_tag(name)?.to_owned());
+
+        if name == "url" {
+            self.shape.add_text(target);
+        }
+
+        Some(close_end)
+    }
+
+    /// Renders a code tag, whose content is shown verbatim, returning where it ends.
+    fn render_code(&mut self, name: &str, end: usize) -> Option<usize> {
+        let (close_start, close_end) = find_close(self.bbcode, end, name)?;
+        let code = self.bbcode[end..close_start].trim_matches('\n');
+
+        let html = match name {
+            "code" => format!("<pre><code>{}</code></pre>", escape(code)),
+            _ => format!("<code>{}</code>", escape(code)),
+        };
+
+        self.html.push_str(&html);
+        self.shape.count(look_of_tag(name)?.to_owned());
+        self.shape.add_text(code);
+
+        Some(close_end)
+    }
+
+    /// Renders an opening tag, given its value and attributes, returning where the input is to be read from next if
+    /// it's been read past the tag, or `None` if it's not a tag NERDZ knows.
+    fn render_open(
+        &mut self,
+        name: &str,
+        value: Option<&str>,
+        attrs: &[(&str, &str)],
+        end: usize,
+    ) -> Option<Option<usize>> {
+        let attr = |key: &str| {
+            attrs
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|&(_, value)| value)
+        };
+
+        match (name, value) {
+            ("b", None) => self.push(name, "<strong>", "</strong>"),
+            ("cur" | "i", None) => self.push(name, "<em>", "</em>"),
+            ("del", None) => self.push(name, "<del>", "</del>"),
+            ("big", None) => self.push(name, "<span class=\"big\">", "</span>"),
+            ("quote", None) => self.push(name, "<blockquote>", "</blockquote>"),
+            ("url", Some(href)) => match self.links.apply(href) {
+                Some(href) => {
+                    let open = format!("<a href=\"{}\">", escape(&href));
+                    self.push(name, &open, "</a>");
+                }
+                None => self.push(name, "", ""),
+            },
+            ("url" | "img", None) => return self.render_target(name, end).map(Some),
+            ("code", _) | ("c", _) => return self.render_code(name, end).map(Some),
+            ("hr", None) => {
+                self.html.push_str("<hr>\n");
+                self.shape.count(look_of_tag(name)?.to_owned());
+            }
+            ("list", None) => match (attr("type"), attr("start")) {
+                (None, None) => self.push(name, "<ul>\n", "</ul>\n"),
+                (kind, start) => {
+                    let kind = kind.unwrap_or("1");
+                    let start = start
+                        .and_then(|start| start.parse::<i64>().ok())
+                        .unwrap_or(1);
+
+                    let open = format!("<ol type=\"{}\" start=\"{start}\">\n", escape(kind));
+                    self.push(name, &open, "</ol>\n");
+                }
+            },
+            ("*", None) if self.open.iter().any(|(open, _)| open == "list") => {
+                // an item ends where the next one starts
+                if self.open.last().is_some_and(|(open, _)| open == "*") {
+                    self.close("*");
+                }
+
+                self.push(name, "<li>", "</li>\n");
+            }
+            _ => return None,
+        }
+
+        Some(None)
+    }
+
+    fn render(mut self) -> (String, Shape) {
+        let tokens: Vec<Token> = tokenize(self.bbcode).collect();
+        let mut skip_to = 0;
+        let mut idx = 0;
+
+        while idx < tokens.len() {
+            let Token { kind, span } = tokens[idx].clone();
+            idx += 1;
+
+            if span.start < skip_to {
+                continue;
+            }
+
+            let mut end = span.end;
+
+            let rendered = match kind {
+                TokenKind::Text(text) => {
+                    self.text(text);
+                    continue;
+                }
+                TokenKind::Open { name } => {
+                    let mut value = None;
+                    let mut attrs = vec![];
+
+                    while let Some(Token {
+                        kind: TokenKind::Attribute { name, value: val },
+                        ..
+                    }) = tokens.get(idx)
+                    {
+                        match name {
+                            Some(name) => attrs.push((*name, *val)),
+                            None => value = Some(*val),
+                        }
+
+                        idx += 1;
+                    }
+
+                    let name = name.to_lowercase();
+
+                    match self.render_open(&name, value, &attrs, span.end) {
+                        Some(next) => {
+                            end = next.unwrap_or(end);
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                TokenKind::Close { name } => self.close(&name.to_lowercase()),
+                _ => false,
+            };
+
+            if !rendered {
+                let bbcode = self.bbcode;
+                self.text(&bbcode[span]);
+            }
+
+            skip_to = end;
+        }
+
+        while let Some((_, close)) = self.open.pop() {
+            self.html.push_str(close);
+        }
+
+        (self.html, self.shape)
+    }
+}
+
+/// Renders BBCode to HTML the way NERDZ does, along with how it looks.
+fn render_bbcode(bbcode: &str, links: &LinkPolicy) -> (String, Shape) {
+    BbcodeRenderer {
+        bbcode,
+        links,
+        html: String::with_capacity(bbcode.len() * 2),
+        open: vec![],
+        shape: Shape::default(),
+    }
+    .render()
+}
+
+/// How some Markdown looks once rendered.
+fn markdown_shape(markdown: &str) -> Shape {
+    let mut ret = Shape::default();
+
+    for event in parse(markdown) {
+        match event {
+            Event::Start(tag) => {
+                if let Some(look) = look_of_construct(&tag) {
+                    ret.count(look.to_owned());
+                }
+            }
+            Event::Code(text) => {
+                ret.count("inline code".to_owned());
+                ret.add_text(&text);
+            }
+            Event::Text(text) | Event::Html(text) => ret.add_text(&text),
+            Event::Rule => ret.count("rule".to_owned()),
+            _ => {}
+        }
+    }
+
+    ret
+}
+
+fn describe(change: &Change) -> String {
+    match change {
+        Change::Count {
+            construct,
+            before,
+            after,
+        } => format!("{construct}: {before} in the BBCode, {after} in the Markdown"),
+        Change::Text { before, after } => {
+            format!("the text changes from \u{201c}{before}\u{201d} to \u{201c}{after}\u{201d}")
+        }
+    }
+}
+
+/// Renders a page showing `bbcode`, rendered as NERDZ does, side by side with `markdown`, rendered as HTML, listing
+/// the visual differences between them on top.
+///
+/// Both are rendered as untrusted content, following `links` as [`sanitizing`] tells, as they may come from anyone.
+pub fn compare_page(title: &str, bbcode: &str, markdown: &str, links: &LinkPolicy) -> String {
+    let links = sanitizing(links);
+    let (bbcode_html, bbcode_shape) = render_bbcode(bbcode, &links);
+    let changes = bbcode_shape.changes(&markdown_shape(markdown));
+
+    let differences = match changes.len() {
+        0 => "<p class=\"same\">No visual differences</p>".to_owned(),
+        len => {
+            let items: String = changes
+                .iter()
+                .map(|change| format!("<li>{}</li>\n", escape(&describe(change))))
+                .collect();
+
+            format!("<h2>{len} visual difference(s)</h2>\n<ul>\n{items}</ul>")
+        }
+    };
+
+    let title = escape(title);
+
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+{STYLE}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<section class=\"differences\">
+{differences}
+</section>
+<div class=\"panes\">
+<section>
+<h2>BBCode</h2>
+{bbcode_html}
+</section>
+<section>
+<h2>Markdown</h2>
+{}</section>
+</div>
+</body>
+</html>
+",
+        to_html(markdown, &links)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bibi::{to_markdown, LinkPolicy};
+
+    use super::compare_page;
+
+    #[test]
+    fn runs_no_script() {
+        for bbcode in [
+            "<script>alert(1)</script>",
+            "<img src=x onerror=alert(1)>",
+            "[url=javascript:alert(1)]x[/url] [url]javascript:alert(1)[/url]",
+            "[img]javascript:alert(1)[/img]",
+        ] {
+            let page = compare_page(
+                "<script>",
+                bbcode,
+                &to_markdown(bbcode),
+                &LinkPolicy::default(),
+            );
+
+            assert!(!page.contains("<script"), "{bbcode:?} -> {page}");
+            assert!(!page.contains("<img src=x"), "{bbcode:?} -> {page}");
+            assert!(!page.contains("=\"javascript:"), "{bbcode:?} -> {page}");
+        }
+    }
+}
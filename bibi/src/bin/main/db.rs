@@ -0,0 +1,288 @@
+//! Bulk conversion of a text column of an SQLite table or a CSV file, so that whole databases can be migrated at
+//! once.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, stdout, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{ArgGroup, Args, ValueEnum};
+use csv::{StringRecord, WriterBuilder};
+use rayon::prelude::*;
+use rusqlite::{params, types::Value, Connection};
+
+use bibi::{to_bbcode_with, to_markdown_with, ConvertOptions};
+
+/// How many rows are read, converted and written at once.
+const BATCH: usize = 1000;
+
+/// The formats a column can be converted to.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Target {
+    /// Markdown, from BBCode
+    #[value(alias = "md")]
+    Markdown,
+    /// BBCode, from Markdown
+    Bbcode,
+}
+
+impl Target {
+    fn convert(self, text: &str, options: &ConvertOptions) -> bibi::Result<String> {
+        match self {
+            Target::Markdown => Ok(to_markdown_with(text, options)),
+            Target::Bbcode => to_bbcode_with(text, options),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("source").args(["sqlite", "csv"]).required(true)))]
+pub struct DbArgs {
+    /// The SQLite database holding the rows to convert
+    #[arg(long, value_name = "FILE", requires = "table")]
+    sqlite: Option<PathBuf>,
+
+    /// The CSV file holding the rows to convert, whose first row names its columns
+    #[arg(long, value_name = "FILE")]
+    csv: Option<PathBuf>,
+
+    /// The table holding the rows to convert
+    #[arg(long, requires = "sqlite")]
+    table: Option<String>,
+
+    /// The column holding the text to convert
+    #[arg(long)]
+    column: String,
+
+    /// The format the text is converted to
+    #[arg(long, value_enum, default_value_t = Target::Markdown)]
+    to: Target,
+
+    /// Write the converted text into COLUMN, which is added if missing, rather than over the original text
+    #[arg(long, value_name = "COLUMN")]
+    into: Option<String>,
+
+    /// The column telling the rows of the table apart, whose values must be unique
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        default_value = "rowid",
+        requires = "sqlite"
+    )]
+    key: String,
+
+    /// Write the converted rows as CSV into FILE rather than back into the database, or to stdout for --csv
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+/// Quotes an SQL identifier.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Shows an SQLite value in messages and CSV files.
+fn show(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(n) => n.to_string(),
+        Value::Real(n) => n.to_string(),
+        Value::Text(text) => text.clone(),
+        Value::Blob(blob) => String::from_utf8_lossy(blob).into_owned(),
+    }
+}
+
+/// Opens FILE for writing, or stdout if there's none.
+fn create(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(stdout()),
+    })
+}
+
+/// Fails if some rows couldn't be converted, after their errors have been reported.
+fn check(failed: usize, converted: usize) -> Result<(), Box<dyn Error>> {
+    eprintln!("{converted} row(s) converted");
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{failed} row(s) could not be converted").into()),
+    }
+}
+
+/// Converts the column of every row of the table a batch at a time, in key order, writing each batch back within
+/// a single transaction. NULLs are left alone.
+fn convert_sqlite(
+    path: &Path,
+    table: &str,
+    args: &DbArgs,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut conn = Connection::open(path)?;
+
+    let table = quote(table);
+    let key = quote(&args.key);
+    let column = quote(&args.column);
+    let target = args.into.as_deref().unwrap_or(&args.column);
+
+    let mut csv = match &args.output {
+        Some(output) => {
+            let mut csv = WriterBuilder::new().from_path(output)?;
+            csv.write_record([args.key.as_str(), target])?;
+
+            Some(csv)
+        }
+        None => None,
+    };
+
+    if csv.is_none() && args.into.is_some() {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+            params![args.table, target],
+            |row| row.get(0),
+        )?;
+
+        if !exists {
+            conn.execute(
+                &format!("ALTER TABLE {table} ADD COLUMN {} TEXT", quote(target)),
+                [],
+            )?;
+        }
+    }
+
+    let select = format!(
+        "SELECT {key}, {column} FROM {table} WHERE ?1 IS NULL OR {key} > ?1 ORDER BY {key} LIMIT {BATCH}"
+    );
+    let update = format!("UPDATE {table} SET {} = ?1 WHERE {key} = ?2", quote(target));
+
+    let (mut failed, mut converted) = (0, 0);
+    let mut last = Value::Null;
+
+    loop {
+        let rows: Vec<(Value, Option<String>)> = conn
+            .prepare_cached(&select)?
+            .query_map([&last], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        let Some((key, _)) = rows.last() else {
+            break;
+        };
+
+        last = key.clone();
+
+        let results: Vec<_> = rows
+            .par_iter()
+            .map(|(_, text)| text.as_deref().map(|text| args.to.convert(text, options)))
+            .collect();
+
+        let tx = conn.transaction()?;
+
+        for ((key, _), result) in rows.iter().zip(results) {
+            match result {
+                None => {}
+                Some(Err(err)) => {
+                    eprintln!("{}: row {}: {err}", path.display(), show(key));
+                    failed += 1;
+                }
+                Some(Ok(text)) => {
+                    match &mut csv {
+                        Some(csv) => csv.write_record([show(key), text])?,
+                        None => {
+                            tx.prepare_cached(&update)?.execute(params![text, key])?;
+                        }
+                    }
+
+                    converted += 1;
+                }
+            }
+        }
+
+        tx.commit()?;
+    }
+
+    if let Some(csv) = &mut csv {
+        csv.flush()?;
+    }
+
+    check(failed, converted)
+}
+
+/// Converts the column of every record of a CSV file a batch at a time, writing the whole file back out with the
+/// converted text. Records failing to convert keep their text as it was.
+fn convert_csv(path: &Path, args: &DbArgs, options: &ConvertOptions) -> Result<(), Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut headers = reader.headers()?.clone();
+
+    let column = headers
+        .iter()
+        .position(|name| name == args.column)
+        .ok_or_else(|| format!("{}: no column named {}", path.display(), args.column))?;
+
+    let target = match &args.into {
+        Some(into) => match headers.iter().position(|name| name == into) {
+            Some(target) => target,
+            None => {
+                headers.push_field(into);
+                headers.len() - 1
+            }
+        },
+        None => column,
+    };
+
+    let mut writer = WriterBuilder::new().from_writer(create(args.output.as_deref())?);
+    writer.write_record(&headers)?;
+
+    let (mut failed, mut converted) = (0, 0);
+    let mut records = reader.records();
+
+    loop {
+        let batch: Vec<StringRecord> = records.by_ref().take(BATCH).collect::<Result<_, _>>()?;
+
+        if batch.is_empty() {
+            break;
+        }
+
+        let results: Vec<_> = batch
+            .par_iter()
+            .map(|record| {
+                args.to
+                    .convert(record.get(column).unwrap_or_default(), options)
+            })
+            .collect();
+
+        for (record, result) in batch.iter().zip(results) {
+            let mut fields: Vec<_> = record.iter().map(str::to_owned).collect();
+            fields.resize(fields.len().max(target + 1), String::new());
+
+            match result {
+                Ok(text) => {
+                    fields[target] = text;
+                    converted += 1;
+                }
+                Err(err) => {
+                    let line = record.position().map_or(0, |pos| pos.line());
+
+                    eprintln!("{}:{line}: {err}", path.display());
+                    failed += 1;
+                }
+            }
+
+            writer.write_record(&fields)?;
+        }
+    }
+
+    writer.flush()?;
+
+    check(failed, converted)
+}
+
+/// Runs the `db` subcommand, converting with `options`.
+pub fn convert(args: DbArgs, options: &ConvertOptions) -> Result<(), Box<dyn Error>> {
+    match (&args.sqlite, &args.table, &args.csv) {
+        (Some(db), Some(table), _) => convert_sqlite(db, table, &args, options),
+        (_, _, Some(csv)) => convert_csv(csv, &args, options),
+        _ => unreachable!("clap requires either --sqlite and --table or --csv"),
+    }
+}
@@ -0,0 +1,228 @@
+//! Export of a directory of BBCode posts into the content tree of a static site generator.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::OsStr,
+    fs::{self, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use bibi::{to_markdown_with, ConvertOptions};
+
+use crate::{format_date, Clock};
+
+/// The static site generators posts can be exported for.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Site {
+    /// TOML front matter, posts in `content/posts/SLUG.md`
+    Hugo,
+    /// YAML front matter, posts in `_posts/DATE-SLUG.md`
+    Jekyll,
+}
+
+/// The metadata of every post, by file name.
+type Metadata = HashMap<String, Map<String, Value>>;
+
+/// Reads a JSONL file holding one object per post, each naming its post in a `file` field.
+fn read_metadata(path: &Path) -> Result<Metadata, Box<dyn Error>> {
+    let mut ret = Metadata::new();
+
+    for (i, line) in read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut entry: Map<String, Value> = serde_json::from_str(line)
+            .map_err(|err| format!("{}:{}: {err}", path.display(), i + 1))?;
+
+        let Some(Value::String(file)) = entry.remove("file") else {
+            return Err(format!("{}:{}: missing `file` field", path.display(), i + 1).into());
+        };
+
+        ret.insert(file, entry);
+    }
+
+    Ok(ret)
+}
+
+/// Whether `key` can be written without quotes, both in TOML and YAML.
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+fn key(key: &str) -> String {
+    match is_bare_key(key) {
+        true => key.to_owned(),
+        false => Value::from(key).to_string(),
+    }
+}
+
+/// Writes a JSON value as TOML. TOML has no null, so nulls are left out.
+fn toml_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Array(items) => {
+            let items: Vec<_> = items.iter().filter_map(toml_value).collect();
+
+            Some(format!("[{}]", items.join(", ")))
+        }
+        Value::Object(fields) => {
+            let fields: Vec<_> = fields
+                .iter()
+                .filter_map(|(name, value)| Some(format!("{} = {}", key(name), toml_value(value)?)))
+                .collect();
+
+            Some(format!("{{ {} }}", fields.join(", ")))
+        }
+        // JSON strings, numbers and booleans are valid TOML as well
+        _ => Some(value.to_string()),
+    }
+}
+
+impl Site {
+    pub fn front_matter(self, fields: &Map<String, Value>) -> String {
+        let mut ret = String::new();
+
+        match self {
+            Site::Hugo => {
+                ret.push_str("+++\n");
+
+                for (name, value) in fields {
+                    if let Some(value) = toml_value(value) {
+                        ret.push_str(&format!("{} = {value}\n", key(name)));
+                    }
+                }
+
+                ret.push_str("+++\n");
+            }
+            Site::Jekyll => {
+                ret.push_str("---\n");
+
+                // JSON is valid YAML
+                for (name, value) in fields {
+                    ret.push_str(&format!("{}: {value}\n", key(name)));
+                }
+
+                ret.push_str("---\n");
+            }
+        }
+
+        ret
+    }
+
+    pub fn post_path(self, out: &Path, slug: &str, date: &str) -> PathBuf {
+        match self {
+            Site::Hugo => out.join("content").join("posts").join(format!("{slug}.md")),
+            Site::Jekyll => out.join("_posts").join(format!("{date}-{slug}.md")),
+        }
+    }
+}
+
+/// Returns the `YYYY-MM-DD` date at the start of `date`, if any.
+pub fn day_of(date: &str) -> Option<&str> {
+    let day = date.get(..10)?;
+    let is_day = day.bytes().enumerate().all(|(i, b)| match i {
+        4 | 7 => b == b'-',
+        _ => b.is_ascii_digit(),
+    });
+
+    is_day.then_some(day)
+}
+
+/// Converts every BBCode post in `dir` into a Markdown file with front matter, in the content tree of `site` rooted
+/// at `out`.
+///
+/// The front matter holds the fields from the post's entry in `metadata`, if any, and from a `SLUG.json` sidecar
+/// file next to it, which takes precedence. The title defaults to the name of the file, and the date to when it was
+/// last modified, as told by `clock`.
+pub fn export(
+    dir: &Path,
+    out: &Path,
+    site: Site,
+    metadata: Option<&Path>,
+    clock: Clock,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut metadata = match metadata {
+        Some(path) => read_metadata(path)?,
+        None => Metadata::new(),
+    };
+
+    let mut posts: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+
+    posts.retain(|path| path.is_file() && path.extension() != Some(OsStr::new("json")));
+    posts.sort();
+
+    for path in posts {
+        let slug = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut fields = metadata
+            .remove(&file_name)
+            .or_else(|| metadata.remove(&slug))
+            .unwrap_or_default();
+
+        let sidecar = path.with_extension("json");
+
+        if sidecar.is_file() {
+            let extra: Map<String, Value> = serde_json::from_str(&read_to_string(&sidecar)?)
+                .map_err(|err| format!("{}: {err}", sidecar.display()))?;
+
+            fields.extend(extra);
+        }
+
+        let title = fields
+            .remove("title")
+            .unwrap_or_else(|| Value::from(slug.as_str()));
+
+        let date = match fields.remove("date") {
+            Some(date) => date,
+            None => Value::from(format_date(clock.modified(&path)?)),
+        };
+
+        let day = date
+            .as_str()
+            .and_then(day_of)
+            .map(str::to_owned)
+            .unwrap_or_else(|| format_date(clock.now()));
+
+        let mut front = Map::new();
+        front.insert("title".to_owned(), title);
+        front.insert("date".to_owned(), date);
+        front.extend(fields);
+
+        let target = site.post_path(out, &slug, &day);
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = format!(
+            "{}\n{}",
+            site.front_matter(&front),
+            to_markdown_with(&read_to_string(&path)?, options)
+        );
+
+        fs::write(&target, contents)?;
+
+        eprintln!("{} -> {}", path.display(), target.display());
+    }
+
+    Ok(())
+}
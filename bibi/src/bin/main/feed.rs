@@ -0,0 +1,229 @@
+//! Conversion of the BBCode bodies of the entries of an RSS or Atom feed, leaving the rest of the feed untouched.
+
+use std::{error::Error, str};
+
+use clap::ValueEnum;
+use pulldown_cmark::html;
+use quick_xml::{
+    events::{BytesStart, BytesText, Event},
+    name::QName,
+    Reader, Writer,
+};
+
+use bibi::{to_markdown_with, BbcodeParser, ConvertOptions};
+
+use crate::markup::sanitizing;
+
+/// The formats the bodies of feed entries can be converted to.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Body {
+    /// HTML, which every feed reader renders
+    Html,
+    /// Markdown, given as plain text
+    Markdown,
+}
+
+impl Body {
+    fn convert(self, bbcode: &str, options: &ConvertOptions) -> String {
+        match self {
+            // feed readers render the HTML of every entry, which must run no script
+            Body::Html => {
+                let options = ConvertOptions {
+                    link_policy: sanitizing(&options.link_policy),
+                    ..options.clone()
+                };
+
+                let mut ret = String::new();
+                html::push_html(&mut ret, BbcodeParser::with_options(bbcode, &options));
+
+                ret
+            }
+            Body::Markdown => to_markdown_with(bbcode, options),
+        }
+    }
+
+    /// The value of the `type` attribute of Atom bodies holding the converted text.
+    fn atom_type(self) -> &'static str {
+        match self {
+            Body::Html => "html",
+            Body::Markdown => "text",
+        }
+    }
+}
+
+/// The kinds of feed whose entry bodies get converted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    Rss,
+    Atom,
+}
+
+/// Tells whether the element `name`, within `parent`, holds the body of an RSS item or an Atom entry.
+fn body_of(parent: &[u8], name: QName) -> Option<Kind> {
+    match parent {
+        b"item" if matches!(name.as_ref(), b"description" | b"content:encoded") => Some(Kind::Rss),
+        b"entry" if matches!(name.local_name().as_ref(), b"content" | b"summary") => {
+            Some(Kind::Atom)
+        }
+        _ => None,
+    }
+}
+
+/// Whether an Atom body with the given start tag holds text, rather than XHTML, Base64 or a link to its content.
+fn is_atom_text(start: &BytesStart) -> Result<bool, Box<dyn Error>> {
+    if start.try_get_attribute("src")?.is_some() {
+        return Ok(false);
+    }
+
+    let is_text = match start.try_get_attribute("type")? {
+        Some(kind) => matches!(&*kind.unescape_value()?, "text" | "html"),
+        None => true,
+    };
+
+    Ok(is_text)
+}
+
+/// Copies `start` replacing its `type` attribute with `kind`.
+fn with_type<'a>(start: &BytesStart<'a>, kind: &str) -> Result<BytesStart<'a>, Box<dyn Error>> {
+    let mut ret = start.clone();
+    ret.clear_attributes();
+
+    for attr in start.attributes() {
+        let attr = attr?;
+
+        if attr.key.as_ref() != b"type" {
+            ret.push_attribute(attr);
+        }
+    }
+
+    ret.push_attribute(("type", kind));
+
+    Ok(ret)
+}
+
+/// Converts the body started by `start`, reading it up to its end tag. Bodies holding elements rather than just
+/// text, such as Atom XHTML content, are copied as they are.
+fn convert_body<'a>(
+    reader: &mut Reader<&'a [u8]>,
+    writer: &mut Writer<Vec<u8>>,
+    start: BytesStart<'a>,
+    kind: Kind,
+    body: Body,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut events = Vec::new();
+    let mut bbcode = String::new();
+    let mut is_text = kind == Kind::Rss || is_atom_text(&start)?;
+    let mut depth = 0usize;
+
+    loop {
+        let event = reader.read_event()?;
+
+        match &event {
+            Event::Text(text) => bbcode.push_str(&text.unescape()?),
+            Event::CData(cdata) => bbcode.push_str(str::from_utf8(cdata)?),
+            Event::Start(_) => {
+                depth += 1;
+                is_text = false;
+            }
+            Event::Empty(_) => is_text = false,
+            Event::End(_) if depth == 0 => {
+                events.push(event);
+                break;
+            }
+            Event::End(_) => depth -= 1,
+            Event::Eof => return Err("unexpected end of feed within an entry".into()),
+            _ => {}
+        }
+
+        events.push(event);
+    }
+
+    if !is_text {
+        writer.write_event(Event::Start(start))?;
+
+        for event in events {
+            writer.write_event(event)?;
+        }
+
+        return Ok(());
+    }
+
+    let start = match kind {
+        Kind::Rss => start,
+        Kind::Atom => with_type(&start, body.atom_type())?,
+    };
+
+    let converted = body.convert(bbcode.trim(), options);
+
+    writer.write_event(Event::Start(start))?;
+    writer.write_event(Event::Text(BytesText::new(converted.trim_end())))?;
+
+    // the end tag is always the last event read
+    if let Some(end) = events.pop() {
+        writer.write_event(end)?;
+    }
+
+    Ok(())
+}
+
+/// Converts the BBCode held by the bodies of the items of an RSS feed, or the entries of an Atom one, into `body`.
+///
+/// RSS items have their `description` and `content:encoded` converted, Atom entries their `summary` and `content`,
+/// whose `type` is updated to match. Everything else is copied as it is.
+pub fn convert_feed(
+    feed: &str,
+    body: Body,
+    options: &ConvertOptions,
+) -> Result<String, Box<dyn Error>> {
+    let mut reader = Reader::from_str(feed);
+    let mut writer = Writer::new(Vec::new());
+
+    // the local names of the elements enclosing the current one
+    let mut parents: Vec<Vec<u8>> = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            Event::Start(start) => {
+                let kind = parents
+                    .last()
+                    .and_then(|parent| body_of(parent, start.name()));
+
+                match kind {
+                    Some(kind) => {
+                        convert_body(&mut reader, &mut writer, start, kind, body, options)?
+                    }
+                    None => {
+                        parents.push(start.local_name().as_ref().to_vec());
+                        writer.write_event(Event::Start(start))?;
+                    }
+                }
+            }
+            Event::End(end) => {
+                parents.pop();
+                writer.write_event(Event::End(end))?;
+            }
+            event => writer.write_event(event)?,
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use bibi::ConvertOptions;
+
+    use super::Body;
+
+    #[test]
+    fn html_bodies_run_no_script() {
+        let html = Body::Html.convert(
+            "<script>alert(1)</script> [url=javascript:alert(1)]x[/url]",
+            &ConvertOptions::default(),
+        );
+
+        assert_eq!(html, "<p>&lt;script&gt;alert(1)&lt;/script&gt; x</p>\n");
+    }
+}
@@ -0,0 +1,313 @@
+//! Synthetic posts, made of the constructs real ones use, so that benchmarks and fuzzers aren't limited to
+//! hand-written fixtures.
+//!
+//! This module is also included by the benchmarks, so it only depends on what they can use too.
+
+use std::{
+    error::Error,
+    fs,
+    io::{stdout, Write},
+    ops::Range,
+    path::PathBuf,
+};
+
+use clap::Args;
+
+use bibi::{to_markdown_with, ConvertOptions};
+
+/// Words posts are made of, with some accented, CJK and emoji ones, whose characters span several bytes or code
+/// points.
+const WORDS: &[&str] = &[
+    "the",
+    "a",
+    "post",
+    "thread",
+    "nerdz",
+    "forum",
+    "today",
+    "really",
+    "never",
+    "code",
+    "link",
+    "picture",
+    "reply",
+    "quote",
+    "list",
+    "and",
+    "or",
+    "but",
+    "with",
+    "from",
+    "about",
+    "new",
+    "old",
+    "great",
+    "città",
+    "perché",
+    "già",
+    "日本語",
+    "ありがとう",
+    "🎉",
+    "👍🏽",
+    "👨‍👩‍👧",
+    "café",
+    "naïve",
+];
+
+/// Lines of the code blocks, some of which look like tags.
+const CODE: &[&str] = &[
+    "fn main() {",
+    "    println!(\"[b]not bold[/b]\");",
+    "}",
+    "let list = [1, 2, 3];",
+    "if a[i] > b[j] { return; }",
+    "# not a heading",
+];
+
+/// Tags no dialect knows, as opened and closed.
+const UNKNOWN: &[(&str, &str)] = &[
+    ("[spoiler]", "[/spoiler]"),
+    ("[color=red]", "[/color]"),
+    ("[user]", "[/user]"),
+    ("[twitter]", "[/twitter]"),
+];
+
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+    /// How many posts to generate
+    #[arg(long, default_value_t = 100)]
+    posts: usize,
+
+    /// The approximate size of every post, in bytes
+    #[arg(long, default_value_t = 2000)]
+    size: usize,
+
+    /// The share of words wrapped in inline tags, from 0 to 1, which also tells how many blocks are headings,
+    /// quotes, lists or code rather than paragraphs
+    #[arg(long, default_value_t = 0.2, value_parser = parse_density)]
+    density: f64,
+
+    /// Seed of the generator, which always generates the same posts from the same seed
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Generate Markdown, by converting the generated BBCode, rather than BBCode
+    #[arg(long)]
+    markdown: bool,
+
+    /// Write every post to a file of its own in this directory, e.g. as fuzzing seeds, instead of to stdout
+    #[arg(long, value_name = "DIR")]
+    out: Option<PathBuf>,
+}
+
+fn parse_density(density: &str) -> Result<f64, String> {
+    match density.parse::<f64>() {
+        Ok(density) if (0.0..=1.0).contains(&density) => Ok(density),
+        _ => Err(format!("not a number between 0 and 1: {density}")),
+    }
+}
+
+/// A SplitMix64 generator, which is small and, unlike those of external crates, guaranteed to generate the same
+/// numbers from the same seed on every platform and release.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A number in `range`.
+    fn between(&mut self, range: Range<usize>) -> usize {
+        range.start + self.below(range.len())
+    }
+
+    /// Whether something with probability `p` happens.
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+/// Generates BBCode posts with a given density of tags.
+pub struct Generator {
+    rng: Rng,
+    density: f64,
+}
+
+impl Generator {
+    pub fn new(seed: u64, density: f64) -> Self {
+        Self {
+            rng: Rng(seed),
+            density,
+        }
+    }
+
+    /// Generates a post of about `size` bytes, made of blocks separated by blank lines.
+    pub fn post(&mut self, size: usize) -> String {
+        let mut ret = String::with_capacity(size + size / 4);
+
+        while ret.len() < size {
+            if !ret.is_empty() {
+                ret.push_str("\n\n");
+            }
+
+            self.block(&mut ret);
+        }
+
+        ret
+    }
+
+    fn block(&mut self, out: &mut String) {
+        if !self.rng.chance(self.density) {
+            return self.paragraph(out);
+        }
+
+        match self.rng.below(6) {
+            0 => {
+                out.push_str("[big]");
+                self.sentence(out);
+                out.push_str("[/big]");
+            }
+            1 => {
+                out.push_str("[quote]");
+                self.paragraph(out);
+                out.push_str("[/quote]");
+            }
+            2 => self.list(out),
+            3 => {
+                out.push_str("[code=rust]\n");
+
+                for _ in 0..self.rng.between(1..8) {
+                    out.push_str(self.rng.pick(CODE));
+                    out.push('\n');
+                }
+
+                out.push_str("[/code]");
+            }
+            4 => out.push_str("[hr]"),
+            _ => {
+                let (open, close) = self.rng.pick(UNKNOWN);
+
+                out.push_str(open);
+                self.sentence(out);
+                out.push_str(close);
+            }
+        }
+    }
+
+    fn list(&mut self, out: &mut String) {
+        let head = match self.rng.below(4) {
+            0 => format!(" type=\"{}\"", self.rng.pick(&["1", "a", "A", "i", "I"])),
+            1 => format!(" start=\"{}\"", self.rng.between(1..20)),
+            _ => String::new(),
+        };
+
+        out.push_str(&format!("[list{head}]\n"));
+
+        for _ in 0..self.rng.between(2..7) {
+            out.push_str("[*]");
+            self.sentence(out);
+            out.push('\n');
+        }
+
+        out.push_str("[/list]");
+    }
+
+    fn paragraph(&mut self, out: &mut String) {
+        for i in 0..self.rng.between(1..5) {
+            if i > 0 {
+                out.push(self.rng.pick(&[' ', '\n']));
+            }
+
+            self.sentence(out);
+        }
+    }
+
+    fn sentence(&mut self, out: &mut String) {
+        for i in 0..self.rng.between(3..16) {
+            if i > 0 {
+                out.push(' ');
+            }
+
+            let word = self.rng.pick(WORDS);
+
+            match self.rng.chance(self.density) {
+                true => self.inline(word, out),
+                false => out.push_str(word),
+            }
+        }
+
+        out.push('.');
+    }
+
+    /// Writes `word` within an inline tag, sometimes nested in another.
+    fn inline(&mut self, word: &str, out: &mut String) {
+        let tagged = match self.rng.below(8) {
+            0 => format!("[b]{word}[/b]"),
+            1 => format!("[i]{word}[/i]"),
+            2 => format!("[cur]{word}[/cur]"),
+            3 => format!("[del]{word}[/del]"),
+            4 => format!("[c=inline]{word}[/c]"),
+            5 => format!("[url=https://nerdz.eu/{word}]{word}[/url]"),
+            6 => format!("[img]https://nerdz.eu/static/{word}.png[/img]"),
+            _ => format!("[b][i]{word}[/i][/b]"),
+        };
+
+        out.push_str(&tagged);
+    }
+}
+
+/// Runs the `generate` subcommand, writing the posts to stdout, separated by blank lines, or to files of their own.
+/// Posts are converted to Markdown with `options`, if they're to be.
+pub fn generate(args: GenerateArgs, options: &ConvertOptions) -> Result<(), Box<dyn Error>> {
+    let GenerateArgs {
+        posts,
+        size,
+        density,
+        seed,
+        markdown,
+        out,
+    } = args;
+
+    let mut generator = Generator::new(seed, density);
+    let extension = match markdown {
+        true => "md",
+        false => "bb",
+    };
+
+    if let Some(dir) = &out {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut stdout = stdout().lock();
+
+    for i in 0..posts {
+        let post = generator.post(size);
+
+        let post = match markdown {
+            true => to_markdown_with(&post, options),
+            false => post,
+        };
+
+        match &out {
+            Some(dir) => fs::write(dir.join(format!("post-{i:05}.{extension}")), post)?,
+            None => write!(stdout, "{post}\n\n")?,
+        }
+    }
+
+    Ok(())
+}
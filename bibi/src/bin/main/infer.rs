@@ -0,0 +1,280 @@
+//! Surveys of the tags a corpus uses that no dialect knows about, so that new dialects can be defined starting
+//! from what the content actually needs.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{read_to_string, File},
+    io::{self, stdout, Write},
+    path::PathBuf,
+};
+
+use clap::Args;
+use rayon::prelude::*;
+use serde_json::Value;
+use unicode_segmentation::UnicodeSegmentation;
+
+use bibi::{convert_with_report_with, ConvertOptions, DiagnosticKind, Dialect};
+
+use crate::walk::{walk, Found};
+
+/// How many characters, i.e. grapheme clusters, around a tag are shown in its sample contexts, before and after it.
+const CONTEXT: (usize, usize) = (30, 50);
+
+#[derive(Args, Debug)]
+pub struct InferArgs {
+    /// The files making up the corpus, or directories to scan recursively
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Only scan the files below directories whose names match this pattern, where `*` matches any run of
+    /// characters and `?` any single one. Can be repeated
+    #[arg(long, default_value = "*")]
+    glob: Vec<String>,
+
+    /// The dialect the corpus is compared to, `nerdz` or `phpbb` [default: nerdz]
+    #[arg(long, value_parser = parse_dialect)]
+    dialect: Option<Dialect>,
+
+    /// How many sample contexts are shown for every tag
+    #[arg(long, default_value_t = 3)]
+    samples: usize,
+
+    /// Write the skeleton dialect to this file, instead of stdout
+    #[arg(long, value_name = "FILE")]
+    skeleton: Option<PathBuf>,
+}
+
+fn parse_dialect(name: &str) -> Result<Dialect, String> {
+    match name.to_lowercase().as_str() {
+        #[cfg(feature = "nerdz")]
+        "nerdz" => Ok(Dialect::Nerdz),
+        #[cfg(feature = "phpbb")]
+        "phpbb" => Ok(Dialect::Phpbb),
+        _ => Err(format!("unknown or disabled dialect: {name}")),
+    }
+}
+
+/// What the corpus tells about a tag the dialect doesn't know.
+#[derive(Default)]
+struct Unknown {
+    /// How many times it's opened.
+    count: usize,
+    /// How many files use it.
+    files: usize,
+    /// Whether it's ever given a value or attributes, e.g. `[color=red]`.
+    valued: bool,
+    /// Whether it's ever closed, or it's void like `[hr]`.
+    closed: bool,
+    /// Some excerpts of where it's used.
+    samples: Vec<String>,
+}
+
+impl Unknown {
+    fn add(&mut self, other: Unknown, samples: usize) {
+        self.count += other.count;
+        self.files += other.files;
+        self.valued |= other.valued;
+        self.closed |= other.closed;
+
+        let missing = samples.saturating_sub(self.samples.len());
+        self.samples.extend(other.samples.into_iter().take(missing));
+    }
+
+    /// An example of the tag, with `P` standing for its content and `Q` for its value, like those of
+    /// [`TagInfo`](bibi::TagInfo).
+    fn bbcode(&self, name: &str) -> String {
+        let open = match self.valued {
+            true => format!("[{name}=Q]"),
+            false => format!("[{name}]"),
+        };
+
+        match self.closed {
+            true => format!("{open}P[/{name}]"),
+            false => open,
+        }
+    }
+}
+
+/// The line around `offset` in `content`, on a single line and cut to [`CONTEXT`].
+fn excerpt(content: &str, offset: usize) -> String {
+    let start = content[..offset].rfind('\n').map_or(0, |pos| pos + 1);
+    let end = content[offset..]
+        .find('\n')
+        .map_or(content.len(), |pos| offset + pos);
+
+    let before: Vec<_> = content[start..offset]
+        .graphemes(true)
+        .rev()
+        .take(CONTEXT.0)
+        .collect();
+    let after = content[offset..end].graphemes(true).take(CONTEXT.1);
+
+    before
+        .into_iter()
+        .rev()
+        .chain(after)
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+/// The tags of a single file the dialect doesn't know, by name in lowercase.
+fn survey(content: &str, options: &ConvertOptions, samples: usize) -> BTreeMap<String, Unknown> {
+    let (_, diagnostics) = convert_with_report_with(content, options);
+    let mut ret: BTreeMap<String, Unknown> = BTreeMap::new();
+
+    for diag in diagnostics {
+        if diag.kind != DiagnosticKind::UnknownTag {
+            continue;
+        }
+
+        let tag = ret.entry(diag.tag.to_lowercase()).or_default();
+        let rest = &content[diag.offset + 1..];
+
+        if rest.starts_with('/') {
+            tag.closed = true;
+
+            continue;
+        }
+
+        tag.count += 1;
+        tag.files = 1;
+        tag.valued |= !rest[diag.tag.len()..].starts_with(']');
+
+        if tag.samples.len() < samples {
+            tag.samples.push(excerpt(content, diag.offset));
+        }
+    }
+
+    // closing tags alone are reported as unmatched by the conversion, not here
+    ret.retain(|_, tag| tag.count > 0);
+
+    ret
+}
+
+/// Quotes a string for TOML, whose basic strings escape the same way JSON ones do.
+fn toml_string(s: &str) -> String {
+    Value::from(s).to_string()
+}
+
+/// A key for TOML, quoted only if it must be.
+fn toml_key(key: &str) -> String {
+    match key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        true => key.to_owned(),
+        false => toml_string(key),
+    }
+}
+
+/// Writes the skeleton of a dialect defining every tag in `unknown`, most used first, to be completed by hand.
+fn write_skeleton(
+    mut out: impl Write,
+    unknown: &[(String, Unknown)],
+    files: usize,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "# Skeleton of a dialect, inferred from {files} file(s). Fill in the Markdown every tag becomes, where"
+    )?;
+    writeln!(
+        out,
+        "# {{content}} stands for its content and {{value}} for its value, and remove those that shouldn't be converted."
+    )?;
+
+    for (name, tag) in unknown {
+        writeln!(out)?;
+        writeln!(out, "[tags.{}]", toml_key(name))?;
+        writeln!(out, "# used {} time(s) in {} file(s)", tag.count, tag.files)?;
+
+        for sample in &tag.samples {
+            writeln!(out, "# e.g. {sample}")?;
+        }
+
+        writeln!(out, "aliases = []")?;
+        writeln!(out, "bbcode = {}", toml_string(&tag.bbcode(name)))?;
+        writeln!(out, "markdown = \"\"")?;
+    }
+
+    Ok(())
+}
+
+/// Runs the `infer` subcommand, reporting the unknown tags of a corpus to stderr and writing a skeleton dialect
+/// defining them.
+pub fn infer(args: InferArgs) -> Result<(), Box<dyn Error>> {
+    let InferArgs {
+        paths,
+        glob,
+        dialect,
+        samples,
+        skeleton,
+    } = args;
+
+    let options = ConvertOptions {
+        dialect: dialect.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let mut inputs = vec![];
+
+    for path in paths {
+        match path.is_dir() {
+            true => inputs.extend(walk(&path, &glob)?.0),
+            false => inputs.push(Found::file(&path)),
+        }
+    }
+
+    let surveys: Vec<_> = inputs
+        .par_iter()
+        .map(|Found { path, .. }| {
+            read_to_string(path)
+                .map(|content| survey(&content, &options, samples))
+                .map_err(|err| format!("{}: {err}", path.display()))
+        })
+        .collect();
+
+    let mut unknown: BTreeMap<String, Unknown> = BTreeMap::new();
+    let mut failed = 0;
+
+    for survey in surveys {
+        match survey {
+            Ok(tags) => {
+                for (name, tag) in tags {
+                    unknown.entry(name).or_default().add(tag, samples);
+                }
+            }
+            Err(err) => {
+                eprintln!("Error: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    let mut unknown: Vec<_> = unknown.into_iter().collect();
+    unknown.sort_by(|(a_name, a), (b_name, b)| b.count.cmp(&a.count).then(a_name.cmp(b_name)));
+
+    for (name, tag) in &unknown {
+        eprintln!("[{name}]: {} time(s) in {} file(s)", tag.count, tag.files);
+
+        for sample in &tag.samples {
+            eprintln!("    {sample}");
+        }
+    }
+
+    let scanned = inputs.len() - failed;
+
+    eprintln!("{} unknown tag(s) in {scanned} file(s)", unknown.len());
+
+    match skeleton {
+        Some(path) => write_skeleton(File::create(&path)?, &unknown, scanned)
+            .map_err(|err| format!("{}: {err}", path.display()))?,
+        None => write_skeleton(stdout().lock(), &unknown, scanned)?,
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{failed} file(s) couldn't be read").into()),
+    }
+}
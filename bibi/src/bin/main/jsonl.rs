@@ -0,0 +1,66 @@
+//! A line-based protocol converting a stream of JSON requests, so that pipelines can convert millions of posts
+//! through a single process.
+//!
+//! Every line of the input is an object such as `{"id": 1, "direction": "bb2md", "text": "[b]Hi[/b]"}`, where `id`
+//! is anything that tells the requests apart, and is answered in order by a line such as
+//! `{"id": 1, "ok": true, "text": "**Hi**"}`, or `{"id": 1, "ok": false, "error": "..."}` if it couldn't be
+//! converted.
+
+use std::{
+    error::Error,
+    io::{stdin, stdout, BufRead, Write},
+};
+
+use serde_json::{json, Map, Value};
+
+use bibi::{to_bbcode_with, to_markdown_with, ConvertOptions};
+
+/// Converts a single request, returning its `id`, if any, along with the converted text or what went wrong.
+fn answer(line: &str, options: &ConvertOptions) -> (Value, Result<String, String>) {
+    let request: Map<String, Value> = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return (Value::Null, Err(format!("invalid request: {err}"))),
+    };
+
+    let id = request.get("id").cloned().unwrap_or_default();
+
+    let text = match request.get("text") {
+        Some(Value::String(text)) => text,
+        Some(_) => return (id, Err("text must be a string".to_owned())),
+        None => return (id, Err("missing text".to_owned())),
+    };
+
+    let converted = match request.get("direction").and_then(Value::as_str) {
+        Some("bb2md") => Ok(to_markdown_with(text, options)),
+        Some("md2bb") => to_bbcode_with(text, options).map_err(|err| err.to_string()),
+        Some(direction) => Err(format!(
+            "unknown direction {direction:?}, expected \"bb2md\" or \"md2bb\""
+        )),
+        None => Err("missing direction".to_owned()),
+    };
+
+    (id, converted)
+}
+
+/// Answers every request read from stdin on stdout, converting with `options`, until stdin ends. Blank lines are
+/// skipped.
+pub fn serve_lines(options: &ConvertOptions) -> Result<(), Box<dyn Error>> {
+    let mut out = stdout().lock();
+
+    for line in stdin().lock().lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match answer(&line, options) {
+            (id, Ok(text)) => json!({ "id": id, "ok": true, "text": text }),
+            (id, Err(error)) => json!({ "id": id, "ok": false, "error": error }),
+        };
+
+        writeln!(out, "{response}")?;
+    }
+
+    Ok(())
+}
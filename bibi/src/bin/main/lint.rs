@@ -0,0 +1,238 @@
+//! Accessibility checks of converted content, which often ends up on websites that must be usable with screen
+//! readers, along with fixes for what can be fixed without a human.
+
+use std::{fmt, ops::Range};
+
+use pulldown_cmark::{Event, LinkType, Tag};
+
+use crate::markup::parse;
+
+/// What a lint is about.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rule {
+    /// An image without alternative text, which screen readers can't describe. Fixed by deriving the text from the
+    /// name of the image.
+    MissingAlt,
+    /// A link whose label is just its URL, which screen readers spell out in full.
+    UrlLabel,
+    /// A heading in all caps, which is harder to read and may be spelled out letter by letter. Fixed by turning it
+    /// into sentence case.
+    AllCapsHeading,
+}
+
+impl Rule {
+    fn name(self) -> &'static str {
+        match self {
+            Rule::MissingAlt => "missing-alt",
+            Rule::UrlLabel => "url-label",
+            Rule::AllCapsHeading => "all-caps-heading",
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            Rule::MissingAlt => "image without alternative text",
+            Rule::UrlLabel => "link labelled with its URL",
+            Rule::AllCapsHeading => "heading in all caps",
+        }
+    }
+}
+
+/// Something flagged by [`lint`].
+#[derive(Debug)]
+pub struct Lint {
+    pub rule: Rule,
+    /// The Markdown it has been found in.
+    pub excerpt: String,
+    /// Whether it has been fixed.
+    pub fixed: bool,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            rule,
+            excerpt,
+            fixed,
+        } = self;
+
+        write!(f, "{}: {}: {excerpt}", rule.name(), rule.message())?;
+
+        match fixed {
+            true => write!(f, " (fixed)"),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Derives the alternative text of an image from its name, e.g. `a cat` from `http://x.y/a-cat.png?s=2`.
+fn alt_from(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.trim_end_matches('/').rsplit('/').next()?;
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+
+    let alt = stem.replace(['-', '_', '+'], " ");
+    let alt = alt.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    Some(alt).filter(|alt| !alt.is_empty())
+}
+
+/// Whether `label` is just `url`, possibly without its scheme.
+fn is_url_label(label: &str, url: &str) -> bool {
+    let label = label.trim();
+    let bare = url.split_once("://").map_or(url, |(_, rest)| rest);
+
+    !url.starts_with("mailto:")
+        && (label == url || label == bare || label == bare.trim_end_matches('/'))
+}
+
+/// Whether a heading is in all caps, which takes at least two words, so that acronyms are left alone.
+fn is_all_caps(text: &str) -> bool {
+    let words = text
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .count();
+
+    words > 1 && text.chars().any(char::is_uppercase) && !text.chars().any(char::is_lowercase)
+}
+
+/// An image, link or heading whose content is being collected.
+struct Open<'a> {
+    tag: Tag<'a>,
+    range: Range<usize>,
+    text: String,
+    /// The pieces of text, as their range in the source and their text.
+    pieces: Vec<(Range<usize>, String)>,
+    /// Whether every piece of text is written as-is in the source, e.g. without escapes.
+    verbatim: bool,
+}
+
+/// Turns a piece of text in all caps into sentence case, given whether it starts the sentence, i.e. whether no
+/// letter came before it.
+fn sentence_case(piece: &str, first: &mut bool) -> String {
+    let mut ret = piece.to_lowercase();
+
+    if !*first {
+        return ret;
+    }
+
+    if let Some((pos, c)) = ret.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        ret.replace_range(pos..pos + c.len_utf8(), &c.to_uppercase().to_string());
+        *first = false;
+    }
+
+    ret
+}
+
+/// Checks the given Markdown, returning what's been flagged, and the Markdown with what could be fixed fixed if
+/// `fix` is set.
+pub fn lint(markdown: &str, fix: bool) -> (String, Vec<Lint>) {
+    let mut lints = vec![];
+    let mut edits: Vec<(Range<usize>, String)> = vec![];
+    let mut open: Vec<Open> = vec![];
+
+    for (event, range) in parse(markdown).into_offset_iter() {
+        match event {
+            Event::Start(tag @ (Tag::Image(..) | Tag::Link(..) | Tag::Heading(..))) => {
+                open.push(Open {
+                    tag,
+                    range,
+                    text: String::new(),
+                    pieces: vec![],
+                    verbatim: true,
+                })
+            }
+            Event::Text(text) => {
+                for item in &mut open {
+                    item.text.push_str(&text);
+
+                    match markdown[range.clone()] == *text {
+                        true => item.pieces.push((range.clone(), text.to_string())),
+                        false => item.verbatim = false,
+                    }
+                }
+            }
+            // code is never changed, so it's not a piece
+            Event::Code(text) => {
+                for item in &mut open {
+                    item.text.push_str(&text);
+                }
+            }
+            Event::End(Tag::Image(..) | Tag::Link(..) | Tag::Heading(..)) => {
+                let Some(Open {
+                    tag,
+                    range,
+                    text,
+                    pieces,
+                    verbatim,
+                }) = open.pop()
+                else {
+                    continue;
+                };
+
+                let excerpt = markdown[range.clone()].to_owned();
+
+                match tag {
+                    Tag::Image(_, url, _) if text.trim().is_empty() => {
+                        // only inline images can be given a text in place, i.e. `![](url)`
+                        let alt = alt_from(&url).filter(|_| fix && excerpt.starts_with("![]"));
+                        let fixed = alt.is_some();
+
+                        if let Some(alt) = alt {
+                            edits.push((range.start + 2..range.start + 2, alt));
+                        }
+
+                        lints.push(Lint {
+                            rule: Rule::MissingAlt,
+                            excerpt,
+                            fixed,
+                        });
+                    }
+                    Tag::Link(kind, url, _)
+                        if kind == LinkType::Autolink || is_url_label(&text, &url) =>
+                    {
+                        lints.push(Lint {
+                            rule: Rule::UrlLabel,
+                            excerpt,
+                            fixed: false,
+                        });
+                    }
+                    Tag::Heading(..) if is_all_caps(&text) => {
+                        let fixed = fix && verbatim;
+
+                        if fixed {
+                            let mut first = true;
+
+                            for (range, piece) in pieces {
+                                edits.push((range, sentence_case(&piece, &mut first)));
+                            }
+                        }
+
+                        lints.push(Lint {
+                            rule: Rule::AllCapsHeading,
+                            excerpt: excerpt.trim_end().to_owned(),
+                            fixed,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+
+    let mut ret = String::with_capacity(markdown.len());
+    let mut copied = 0;
+
+    for (range, text) in edits {
+        ret.push_str(&markdown[copied..range.start]);
+        ret.push_str(&text);
+        copied = range.end;
+    }
+
+    ret.push_str(&markdown[copied..]);
+
+    (ret, lints)
+}
@@ -0,0 +1,605 @@
+//! Import of mailing list archives, as mbox files or Maildir directories, into the content tree of a static site
+//! generator.
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use bibi::{to_markdown_with, ConvertOptions};
+
+use crate::{
+    export::{day_of, Site},
+    format_date, Clock,
+};
+
+/// A message, or a part of a multipart one, split into its headers and its body.
+struct Part<'a> {
+    /// The headers as `(name, value)`, with folded values joined back into a single line.
+    headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+impl<'a> Part<'a> {
+    fn parse(raw: &'a [u8]) -> Self {
+        let (head, body) = match find(raw, b"\n\n")
+            .into_iter()
+            .chain(find(raw, b"\r\n\r\n"))
+            .min()
+        {
+            Some(pos) if raw[pos] == b'\n' => (&raw[..pos], &raw[pos + 2..]),
+            Some(pos) => (&raw[..pos], &raw[pos + 4..]),
+            None => (raw, &raw[raw.len()..]),
+        };
+
+        let mut headers: Vec<(String, String)> = vec![];
+
+        for line in String::from_utf8_lossy(head).lines() {
+            match (line.starts_with([' ', '\t']), headers.last_mut()) {
+                (true, Some((_, value))) => value.push_str(line),
+                _ => {
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.push((name.trim().to_owned(), value.trim().to_owned()));
+                    }
+                }
+            }
+        }
+
+        Self { headers, body }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The plain text of the part, or of the first plain text part it contains if it's a multipart one.
+    fn text(&self) -> Option<String> {
+        let content_type = self.header("content-type").unwrap_or("text/plain");
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        if mime.starts_with("multipart/") {
+            let boundary = param(content_type, "boundary")?;
+
+            return split_multipart(self.body, boundary)
+                .into_iter()
+                .find_map(|raw| Part::parse(raw).text());
+        }
+
+        if mime != "text/plain" {
+            return None;
+        }
+
+        let encoding = self
+            .header("content-transfer-encoding")
+            .map(str::to_ascii_lowercase);
+
+        let body = match encoding.as_deref() {
+            Some("quoted-printable") => Cow::Owned(decode_quoted_printable(self.body)),
+            Some("base64") => Cow::Owned(decode_base64(self.body)),
+            _ => Cow::Borrowed(self.body),
+        };
+
+        Some(decode_charset(&body, param(content_type, "charset")))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Returns the parameter `name` of a header such as `text/plain; charset="utf-8"`.
+fn param<'a>(value: &'a str, name: &str) -> Option<&'a str> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// Splits the body of a multipart message into its parts.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+
+    let mut ret = vec![];
+    let mut start = None;
+    let mut pos = 0;
+
+    for line in body.split_inclusive(|&b| b == b'\n') {
+        let rest = line
+            .trim_ascii_end()
+            .strip_prefix(delimiter.as_bytes())
+            .filter(|rest| rest.is_empty() || *rest == b"--");
+
+        if let Some(rest) = rest {
+            if let Some(start) = start {
+                ret.push(&body[start..pos]);
+            }
+
+            if rest == b"--" {
+                break;
+            }
+
+            start = Some(pos + line.len());
+        }
+
+        pos += line.len();
+    }
+
+    ret
+}
+
+fn decode_quoted_printable(text: &[u8]) -> Vec<u8> {
+    let hex = |digits: &[u8]| {
+        let digits = std::str::from_utf8(digits).ok()?;
+
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_hexdigit())
+            .then(|| u8::from_str_radix(digits, 16).ok())?
+    };
+
+    let mut ret = Vec::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i + 1..];
+
+        match text[i] {
+            // soft line breaks only tell where the sender wrapped a line that was too long
+            b'=' if rest.starts_with(b"\r\n") => i += 3,
+            b'=' if rest.starts_with(b"\n") => i += 2,
+            b'=' => match rest.get(..2).and_then(hex) {
+                Some(b) => {
+                    ret.push(b);
+                    i += 3;
+                }
+                None => {
+                    ret.push(b'=');
+                    i += 1;
+                }
+            },
+            b => {
+                ret.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    ret
+}
+
+fn decode_base64(text: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(text.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for &b in text {
+        let value = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            // line breaks and padding
+            _ => continue,
+        };
+
+        acc = (acc << 6 | u32::from(value)) & 0xFFFF;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            ret.push((acc >> bits) as u8);
+        }
+    }
+
+    ret
+}
+
+/// Decodes text in the given charset. Only UTF-8, its ASCII subset and Latin-1 are known, anything else is read as
+/// UTF-8.
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    match charset.map(str::to_ascii_lowercase).as_deref() {
+        Some("iso-8859-1" | "latin1") => bytes.iter().map(|&b| char::from(b)).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decodes the encoded word at the start of `text`, e.g. `=?UTF-8?Q?caf=C3=A9?=`, returning it along with its
+/// length.
+fn encoded_word(text: &str) -> Option<(String, usize)> {
+    let inner = text.strip_prefix("=?")?;
+    let (charset, inner) = inner.split_once('?')?;
+    let (encoding, inner) = inner.split_once('?')?;
+    let end = inner.find("?=")?;
+    let data = &inner.as_bytes()[..end];
+
+    let bytes = match encoding {
+        "Q" | "q" => {
+            let data: Vec<_> = data
+                .iter()
+                .map(|&b| if b == b'_' { b' ' } else { b })
+                .collect();
+
+            decode_quoted_printable(&data)
+        }
+        "B" | "b" => decode_base64(data),
+        _ => return None,
+    };
+
+    // the charset may be followed by a language, e.g. `UTF-8*it`
+    let charset = charset.split('*').next();
+
+    Some((
+        decode_charset(&bytes, charset),
+        text.len() - inner.len() + end + 2,
+    ))
+}
+
+/// Decodes the encoded words of a header, such as `Subject` or `From`.
+fn decode_header(value: &str) -> String {
+    let mut ret = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+
+    while let Some(start) = rest.find("=?") {
+        match encoded_word(&rest[start..]) {
+            Some((word, len)) => {
+                let before = &rest[..start];
+
+                // the blanks between two encoded words are only there to fold long headers
+                if !(after_word && before.trim().is_empty()) {
+                    ret.push_str(before);
+                }
+
+                ret.push_str(&word);
+                rest = &rest[start + len..];
+                after_word = true;
+            }
+            None => {
+                ret.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                after_word = false;
+            }
+        }
+    }
+
+    ret.push_str(rest);
+
+    ret
+}
+
+/// Turns a message date, e.g. `Tue, 1 Jul 2003 10:52:37 +0200`, into an RFC 3339 one. Obsolete time zones, such as
+/// `EST`, are taken as UTC.
+fn parse_date(date: &str) -> Option<String> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    let date = date.split_once(',').map_or(date, |(_, rest)| rest);
+    let mut fields = date.split_whitespace();
+
+    let day: u32 = fields.next()?.parse().ok()?;
+    let month = fields.next()?;
+    let month = MONTHS
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(month))?
+        + 1;
+
+    let year = fields.next()?;
+    let year = match (year.len(), year.parse::<u32>().ok()?) {
+        // two and three digit years, as RFC 2822 reads them
+        (..=2, year) if year < 50 => year + 2000,
+        (_, year) if year < 1000 => year + 1900,
+        (_, year) => year,
+    };
+
+    let mut time = fields.next()?.split(':').map(str::parse::<u32>);
+    let hour = time.next()?.ok()?;
+    let minute = time.next()?.ok()?;
+    let second = time.next().unwrap_or(Ok(0)).ok()?;
+
+    let offset = match fields.next() {
+        Some(zone) if zone.len() == 5 && zone.starts_with(['+', '-']) => zone[1..]
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| format!("{}:{}", &zone[..3], &zone[3..]))?,
+        _ => "Z".to_owned(),
+    };
+
+    Some(format!(
+        "{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}{offset}"
+    ))
+}
+
+/// Turns the subject of a message into a slug, leaving out the prefixes of replies and the names of lists, so that
+/// the messages of a thread share it.
+fn slug_of(subject: &str) -> String {
+    let mut subject = subject.trim();
+
+    loop {
+        let stripped = ["re:", "fwd:", "fw:"]
+            .iter()
+            .find(|prefix| {
+                subject
+                    .get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+            })
+            .map(|prefix| &subject[prefix.len()..])
+            .or_else(|| {
+                let list = subject.strip_prefix('[')?;
+
+                list.find(']').map(|end| &list[end + 1..])
+            });
+
+        match stripped {
+            Some(stripped) => subject = stripped.trim_start(),
+            None => break,
+        }
+    }
+
+    let slug: String = subject
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c.to_lowercase().next().unwrap_or(c),
+            false => '-',
+        })
+        .collect();
+
+    let slug = slug
+        .split('-')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    match slug.is_empty() {
+        true => "message".to_owned(),
+        false => slug,
+    }
+}
+
+/// The number of `>` quoting a line of a message, and the text they quote.
+fn quote_level(line: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = line;
+
+    while let Some(quoted) = rest.strip_prefix('>') {
+        depth += 1;
+        rest = quoted.strip_prefix(' ').unwrap_or(quoted);
+    }
+
+    (depth, rest)
+}
+
+/// Converts the body of a message to Markdown. The text at each quote level is converted on its own, and then
+/// quoted again, so that replies keep their nesting.
+fn to_markdown_quoted(body: &str, options: &ConvertOptions) -> String {
+    let mut groups: Vec<(usize, String)> = vec![];
+
+    for line in body.lines() {
+        let (depth, text) = quote_level(line);
+
+        match groups.last_mut() {
+            Some((last, group)) if *last == depth => {
+                group.push('\n');
+                group.push_str(text);
+            }
+            _ => groups.push((depth, text.to_owned())),
+        }
+    }
+
+    let mut ret = String::new();
+    let mut previous = None;
+
+    for (depth, text) in groups {
+        // a line quoted at the level both groups share, so that the first doesn't go on into the second
+        if let Some(previous) = previous {
+            ret.push_str(&">".repeat(depth.min(previous)));
+            ret.push('\n');
+        }
+
+        let prefix = "> ".repeat(depth);
+
+        for line in to_markdown_with(&text, options).lines() {
+            match line.is_empty() {
+                true => ret.push_str(prefix.trim_end()),
+                false => {
+                    ret.push_str(&prefix);
+                    ret.push_str(line);
+                }
+            }
+
+            ret.push('\n');
+        }
+
+        previous = Some(depth);
+    }
+
+    ret
+}
+
+/// Removes the `>` mbox writers add to the lines of a message starting with `From `, possibly already quoted.
+fn unescape_from(message: &[u8]) -> Vec<u8> {
+    message
+        .split_inclusive(|&b| b == b'\n')
+        .flat_map(|line| {
+            let unquoted = line.iter().position(|&b| b != b'>').unwrap_or(line.len());
+
+            match unquoted > 0 && line[unquoted..].starts_with(b"From ") {
+                true => &line[1..],
+                false => line,
+            }
+        })
+        .copied()
+        .collect()
+}
+
+/// A raw message, along with a name telling where it comes from.
+type Message = (String, Vec<u8>);
+
+/// Reads the messages of an mbox file, or of the `cur` and `new` folders of a Maildir.
+fn read_messages(source: &Path) -> Result<Vec<Message>, Box<dyn Error>> {
+    if source.is_dir() {
+        let mut files = vec![];
+
+        for folder in ["cur", "new"] {
+            let folder = source.join(folder);
+
+            if folder.is_dir() {
+                for entry in fs::read_dir(folder)? {
+                    files.push(entry?.path());
+                }
+            }
+        }
+
+        if files.is_empty() {
+            return Err(
+                format!("{}: not a Maildir, or no messages in it", source.display()).into(),
+            );
+        }
+
+        files.sort();
+
+        return files
+            .into_iter()
+            .map(|path| Ok((path.display().to_string(), fs::read(&path)?)))
+            .collect();
+    }
+
+    let mbox = fs::read(source)?;
+    let mut starts = vec![];
+    let mut pos = 0;
+
+    for line in mbox.split_inclusive(|&b| b == b'\n') {
+        if line.starts_with(b"From ") {
+            starts.push(pos);
+        }
+
+        pos += line.len();
+    }
+
+    starts.push(mbox.len());
+
+    Ok(starts
+        .windows(2)
+        .enumerate()
+        .map(|(i, range)| {
+            let message = &mbox[range[0]..range[1]];
+
+            // the envelope line isn't part of the message
+            let message = message
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(&message[message.len()..], |end| &message[end + 1..]);
+
+            (
+                format!("{}:{}", source.display(), i + 1),
+                unescape_from(message),
+            )
+        })
+        .collect())
+}
+
+/// Converts every message of the mbox file or Maildir at `source` into a Markdown file with front matter, in the
+/// content tree of `site` rooted at `out`.
+///
+/// The plain text body of each message is converted as BBCode, keeping the text it quotes as nested quotes. The
+/// front matter holds its subject as the title, along with its date, author and message ID. Messages without a date
+/// are filed under the current one, as told by `clock`.
+pub fn import(
+    source: &Path,
+    out: &Path,
+    site: Site,
+    clock: Clock,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut taken = HashSet::<PathBuf>::new();
+
+    for (name, raw) in read_messages(source)? {
+        let message = Part::parse(&raw);
+
+        let Some(body) = message.text() else {
+            eprintln!("{name}: no plain text body, skipped");
+
+            continue;
+        };
+
+        let title = message
+            .header("subject")
+            .map(decode_header)
+            .unwrap_or_else(|| "(no subject)".to_owned());
+
+        let date = message
+            .header("date")
+            .map(|date| parse_date(date).unwrap_or_else(|| date.to_owned()));
+
+        let day = date
+            .as_deref()
+            .and_then(day_of)
+            .map(str::to_owned)
+            .unwrap_or_else(|| format_date(clock.now()));
+
+        let mut front = Map::new();
+        front.insert("title".to_owned(), Value::from(title.as_str()));
+
+        if let Some(date) = date {
+            front.insert("date".to_owned(), Value::from(date));
+        }
+
+        if let Some(author) = message.header("from") {
+            front.insert("author".to_owned(), Value::from(decode_header(author)));
+        }
+
+        if let Some(id) = message.header("message-id") {
+            let id = id.trim_start_matches('<').trim_end_matches('>');
+
+            front.insert("message_id".to_owned(), Value::from(id));
+        }
+
+        let slug = slug_of(&title);
+        let mut target = site.post_path(out, &slug, &day);
+
+        for n in 2.. {
+            if !taken.contains(&target) {
+                break;
+            }
+
+            target = site.post_path(out, &format!("{slug}-{n}"), &day);
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = format!(
+            "{}\n{}",
+            site.front_matter(&front),
+            to_markdown_quoted(&body, options)
+        );
+
+        fs::write(&target, contents)?;
+
+        eprintln!("{name} -> {}", target.display());
+
+        taken.insert(target);
+    }
+
+    Ok(())
+}
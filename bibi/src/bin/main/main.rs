@@ -0,0 +1,1626 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    env,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fs::{self, read_to_string},
+    io::{self, stdin, stdout, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::{ArgGroup, CommandFactory, Parser as ClapParser, ValueEnum};
+use clap_complete::Shell;
+use clap_mangen::Man;
+use rayon::prelude::*;
+
+use bibi::{
+    check_limits, convert_with_report_with, parse_ast_with, to_bbcode_with, ConvertOptions,
+    Diagnostic, DiagnosticKind, Direction, HtmlPolicy, LimitExceeded, Limits, LinkPolicy,
+    LinkStyle, MarkdownTarget, Profile, SmileyStyle, Snapshot, UnclosedTags,
+};
+
+use check::round_trip;
+use compare::compare_page;
+#[cfg(feature = "db")]
+use db::DbArgs;
+use export::{export, Site};
+use feed::{convert_feed, Body};
+use generate::{generate, GenerateArgs};
+use infer::{infer, InferArgs};
+use jsonl::serve_lines;
+use lint::{lint, Lint};
+use mail::import;
+use manifest::{content_hash, duplicates, Manifest};
+use markup::{to_html, to_plain, PlainStyle};
+#[cfg(feature = "serve")]
+use serve::{serve, ServeArgs};
+use stats::Stats;
+use supported::{supported, SupportedArgs};
+use walk::{walk, Found};
+use watch::watch;
+
+mod check;
+mod compare;
+#[cfg(feature = "db")]
+mod db;
+mod export;
+mod feed;
+mod generate;
+mod infer;
+mod jsonl;
+mod lint;
+mod mail;
+mod manifest;
+mod markup;
+#[cfg(feature = "serve")]
+mod serve;
+mod stats;
+mod supported;
+mod walk;
+mod watch;
+
+/// Converts posts between Markdown and BBCode, in either direction, from files, directory trees or standard input.
+#[derive(ClapParser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("tree").args(["export", "mail"])))]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(after_help = "`bibi convert ARGS` is the same as `bibi ARGS`, converting files as told.")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Files to parse, directory of posts with --export, or mbox file or Maildir with --mail. When given more than
+    /// one file, they're converted in parallel, each into a file next to it with the extension of the output format
+    /// (`.md`, `.bbcode`, `.html` or `.txt`). Without any file, or with `-`, stdin is read instead
+    files: Vec<PathBuf>,
+
+    /// Convert every file below the directories given, along with the other files given, as if they all were
+    /// given, mirroring the structure of the directories into --output, if any
+    #[arg(short, long, conflicts_with_all = ["export", "mail", "feed"])]
+    recursive: bool,
+
+    /// Only convert the files below the directories whose name matches PATTERN, where `*` matches any run of
+    /// characters and `?` any single one. May be given more than once
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        default_value = "*",
+        requires = "recursive"
+    )]
+    glob: Vec<String>,
+
+    /// Keep running, converting the files again whenever they, or any file below the directories given, change
+    #[arg(short, long, conflicts_with = "in_place")]
+    watch: bool,
+
+    /// Write the output to PATH rather than to stdout, or with more than one file, into the directory PATH, which
+    /// is created if missing
+    #[arg(
+        short,
+        long,
+        visible_alias = "out-dir",
+        value_name = "PATH",
+        conflicts_with_all = ["export", "mail", "in_place"]
+    )]
+    output: Option<PathBuf>,
+
+    /// Replace every file with its conversion
+    #[arg(long, conflicts_with_all = ["export", "mail", "feed"])]
+    in_place: bool,
+
+    /// Keep a copy of every file replaced by --in-place, with `.bak` appended to its name
+    #[arg(long, requires = "in_place")]
+    backup: bool,
+
+    /// The format of the input [default: the opposite of --to, if it's BBCode or Markdown, or else Markdown for
+    /// `.md` and `.markdown` files and BBCode otherwise; required for stdin]
+    #[arg(long, value_enum)]
+    from: Option<Format>,
+
+    /// The format of the output [default: Markdown for BBCode input, BBCode for Markdown input]
+    #[arg(long, value_enum)]
+    to: Option<Target>,
+
+    /// How formatting is rendered with --to plain
+    #[arg(long, value_enum, default_value_t = PlainStyle::Bare)]
+    plain_style: PlainStyle,
+
+    /// Rather than converting the files, convert them into their counterpart and back, telling the constructs that
+    /// don't survive the round trip
+    #[arg(long, conflicts_with_all = ["to", "output", "in_place", "export", "mail", "feed"])]
+    check: bool,
+
+    /// Read a JSON object per line from stdin, such as `{"id": 1, "direction": "bb2md", "text": "[b]Hi[/b]"}`, and
+    /// answer each with a line on stdout, such as `{"id": 1, "ok": true, "text": "**Hi**"}` or
+    /// `{"id": 1, "ok": false, "error": "..."}`. The direction is either `bb2md` or `md2bb`
+    #[arg(
+        long,
+        conflicts_with_all = ["files", "recursive", "watch", "to", "output", "in_place", "check", "export", "mail", "feed"]
+    )]
+    jsonl: bool,
+
+    /// Rather than converting BBCode files to Markdown, write an HTML page for each showing it rendered as NERDZ
+    /// does, side by side with the Markdown it's converted to, listing the visual differences between the two
+    #[arg(long, conflicts_with_all = ["to", "check", "stats_only", "export", "mail", "feed"])]
+    compare: bool,
+
+    /// Print the images without alternative text, links labelled with their URL and headings in all caps found in
+    /// the converted content to stderr, since they're hard to read with screen readers
+    #[arg(long)]
+    lint: bool,
+
+    /// Fix what --lint flags where possible, deriving alternative text from the names of images and turning
+    /// headings in all caps into sentence case
+    #[arg(long, requires = "lint")]
+    fix: bool,
+
+    /// Mark every tag that couldn't be converted from BBCode with an HTML comment right before it, e.g.
+    /// `<!-- bibi: unknown tag [color=red] left as-is -->`, so that losses are easy to find when reviewing
+    #[arg(long)]
+    annotate_losses: bool,
+
+    /// What to do with the BBCode tags that are opened but never closed, such as [b] in `[b]oops`, rather than
+    /// what the configuration says: `keep` them as they are, `close` them where their paragraph ends or `strip` them
+    #[arg(long, value_enum, value_name = "POLICY")]
+    unclosed: Option<Unclosed>,
+
+    /// Convert the tags Markdown has no syntax for, such as [u], [color=red] or [center], to inline HTML, for
+    /// renderers that accept it like GitHub's, rather than leaving them as-is
+    #[arg(long)]
+    html_fallbacks: bool,
+
+    /// What to do with the HTML of Markdown converted to BBCode, other than comments, rather than what the
+    /// configuration says: `strip` it, copy it `verbatim` or `translate` <b>, <i>, <a href>, <img src> and <br>
+    #[arg(long, value_enum, value_name = "POLICY")]
+    markdown_html: Option<MarkdownHtml>,
+
+    /// What NERDZ smileys such as :D or <3 become in the Markdown, rather than what the configuration says, and
+    /// whether emoji and shortcodes become smileys in the BBCode: `keep` them as they are, or turn them into `emoji`
+    /// or `shortcode`s such as :grinning:
+    #[arg(long, value_enum, value_name = "STYLE")]
+    smileys: Option<Smileys>,
+
+    /// How links are written, rather than what the configuration says: `inline`, as `autolink`s if their text is
+    /// their URL, or as `reference`s defined at the end of the Markdown, which keeps long posts readable
+    #[arg(long, value_enum, value_name = "STYLE")]
+    links: Option<Links>,
+
+    /// Only keep the links and images using these schemes, e.g. `http,https,mailto`, rather than those the
+    /// configuration allows, dropping links such as `javascript:` ones but keeping their text
+    #[arg(long, value_name = "SCHEMES", value_delimiter = ',')]
+    link_schemes: Option<Vec<String>>,
+
+    /// Resolve relative links and images against this URL, rather than the one of the configuration
+    #[arg(long, value_name = "URL")]
+    base_url: Option<String>,
+
+    /// Mark the links of the HTML output with rel="nofollow", so that search engines don't follow them
+    #[arg(long)]
+    nofollow: bool,
+
+    /// Use the options suiting the platform the output is meant for, `plain`, `github`, `discord` or `nerdz`,
+    /// rather than those of a configuration file
+    #[arg(long, value_parser = parse_profile)]
+    #[cfg_attr(feature = "config", arg(conflicts_with = "config"))]
+    profile: Option<Profile>,
+
+    /// Fail, rather than warn, when the converted content exceeds the known limits of the --profile platform, such
+    /// as the length of posts
+    #[arg(long, requires = "profile")]
+    strict: bool,
+
+    /// With --strict, write a snapshot of what the parser of the output sees where it exceeds the limits of the
+    /// platform into a file of this directory named after the input, to debug why it does
+    #[arg(long, value_name = "DIR", requires = "strict")]
+    snapshots: Option<PathBuf>,
+
+    /// Read the conversion options from this TOML file, rather than from `bibi.toml` in the current directory, if
+    /// there's one
+    #[cfg(feature = "config")]
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Print how many times every file uses every tag to stderr, telling the unknown ones and how many are never
+    /// closed, or the constructs for Markdown, along with the total with more than one file
+    #[arg(long)]
+    stats: bool,
+
+    /// Print the stats of --stats, rather than converting the files
+    #[arg(long, conflicts_with_all = ["to", "output", "in_place", "export", "mail", "feed", "check"])]
+    stats_only: bool,
+
+    /// Print the tags that couldn't be converted to stderr (BBCode input only)
+    #[arg(long)]
+    report: bool,
+
+    /// Print the files whose converted content is the same as an earlier file's, once whitespace is normalized, to
+    /// stderr (more than one file only)
+    #[arg(long)]
+    report_dupes: bool,
+
+    /// How converted files are named when converting more than one
+    #[arg(long, value_enum, default_value_t = Naming::Input, conflicts_with = "in_place")]
+    name_by: Naming,
+
+    /// Make the output the same on every run, so that the outputs of different runs can be compared: files are
+    /// converted in path order, and dates that aren't given are taken from SOURCE_DATE_EPOCH, or else are
+    /// 1970-01-01, rather than from the clock or when files were last modified
+    #[arg(long)]
+    reproducible: bool,
+
+    /// Write a JSON object per file to FILE, one per line, telling its output, the hash of its normalized content
+    /// and the earlier file with the same content, if any (more than one file only)
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+
+    /// Wrap the output into a template, replacing `{{content}}`, `{{title}}` and `{{date}}`
+    #[arg(long, value_name = "FILE")]
+    template: Option<String>,
+
+    /// The title given to the template [default: the name of the file, without extension]
+    #[arg(long, requires = "template")]
+    title: Option<String>,
+
+    /// The date given to the template [default: today, as YYYY-MM-DD]
+    #[arg(long, requires = "template")]
+    date: Option<String>,
+
+    /// Convert every BBCode post in the input directory into a static site content tree rooted at DIR
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["template", "report"])]
+    export: Option<PathBuf>,
+
+    /// Convert every message in the input mbox file or Maildir into a static site content tree rooted at DIR
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["template", "report", "feed"])]
+    mail: Option<PathBuf>,
+
+    /// The static site generator to export or import mail for
+    #[arg(long, value_enum, default_value_t = Site::Hugo, requires = "tree")]
+    site: Site,
+
+    /// The metadata of the exported posts, as JSON objects, one per line, naming their post in a `file` field
+    #[arg(long, value_name = "FILE", requires = "export")]
+    metadata: Option<PathBuf>,
+
+    /// Read the file as an RSS or Atom feed, converting the BBCode bodies of its entries
+    #[arg(long, conflicts_with_all = ["template", "report", "export"])]
+    feed: bool,
+
+    /// The format the bodies of the feed entries are converted to
+    #[arg(long, value_enum, default_value_t = Body::Html, requires = "feed")]
+    feed_body: Body,
+
+    /// Rather than converting the BBCode file, write what it's parsed into as JSON: `ast` is its syntax tree, with
+    /// every tag as an element holding its value, attributes, content and where it is in the file
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = ["to", "check", "compare", "stats_only", "export", "mail", "feed", "template"]
+    )]
+    emit: Option<Emit>,
+
+    /// Print the man page of the CLI in roff, e.g. for packaging
+    #[arg(long, exclusive = true)]
+    generate_manpage: bool,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Convert a text column of every row of an SQLite table or a CSV file
+    #[cfg(feature = "db")]
+    Db(DbArgs),
+    /// Serve conversions over HTTP, with `POST /to-markdown` and `POST /to-bbcode` taking the text to convert as
+    /// their body, and `POST /compare` the BBCode to compare with its conversion, as with --compare
+    #[cfg(feature = "serve")]
+    Serve(ServeArgs),
+    /// Report the tags a corpus uses that the dialect doesn't know, with how often and where they're used, and
+    /// write the skeleton of a dialect defining them as TOML
+    Infer(InferArgs),
+    /// Generate synthetic posts with a given size and density of tags, for benchmarks and as fuzzing seeds
+    Generate(GenerateArgs),
+    /// Print which tags every dialect supports, what they're converted to and how faithfully, as tables or as JSON
+    Supported(SupportedArgs),
+    /// Print the completions of the arguments for the given shell
+    #[command(hide = true)]
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// What --emit writes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Emit {
+    /// The syntax tree of the BBCode
+    Ast,
+}
+
+/// What --unclosed does with dangling tags, see [`UnclosedTags`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Unclosed {
+    /// Leave them as they are
+    Keep,
+    /// Close them where their line ends
+    Close,
+    /// Remove them, keeping their content
+    Strip,
+}
+
+/// What --markdown-html does with the HTML of Markdown, see [`HtmlPolicy`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum MarkdownHtml {
+    /// Leave it out
+    Strip,
+    /// Copy it as-is
+    Verbatim,
+    /// Turn the tags with a BBCode counterpart into it, leaving out the others
+    Translate,
+}
+
+/// What --smileys turns smileys into, see [`SmileyStyle`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Smileys {
+    /// Nothing, leaving them as they are
+    Keep,
+    /// Emoji, such as 😀
+    Emoji,
+    /// Shortcodes, such as :grinning:
+    Shortcode,
+}
+
+/// How --links writes links, see [`LinkStyle`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Links {
+    /// Always in their explicit form, such as [Q](P)
+    Inline,
+    /// In their short form, such as <P>, if their text is the URL itself
+    Autolink,
+    /// As references, such as [Q][1], defined at the end of the Markdown
+    Reference,
+}
+
+/// How the outputs of a batch are named.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Naming {
+    /// After their input, with the extension of the output format
+    Input,
+    /// After the first 16 digits of the hash of their normalized content, in the same place, so that files with
+    /// the same content end up being the same file
+    Hash,
+}
+
+/// Where the dates that aren't given come from.
+#[derive(Clone, Copy, Debug)]
+enum Clock {
+    /// The system clock, and the files themselves.
+    System,
+    /// A fixed time, for output that's the same on every run.
+    Fixed(SystemTime),
+}
+
+impl Clock {
+    /// The fixed clock of reproducible runs, set to SOURCE_DATE_EPOCH, if any, as is customary for reproducible
+    /// builds.
+    fn reproducible() -> Result<Self, Box<dyn Error>> {
+        let epoch = match env::var("SOURCE_DATE_EPOCH") {
+            Ok(secs) => secs
+                .trim()
+                .parse()
+                .map_err(|_| format!("SOURCE_DATE_EPOCH is not a number of seconds: {secs}"))?,
+            Err(_) => 0,
+        };
+
+        Ok(Clock::Fixed(UNIX_EPOCH + Duration::from_secs(epoch)))
+    }
+
+    fn now(self) -> SystemTime {
+        match self {
+            Clock::System => SystemTime::now(),
+            Clock::Fixed(time) => time,
+        }
+    }
+
+    /// When the file at `path` was last modified.
+    fn modified(self, path: &Path) -> io::Result<SystemTime> {
+        match self {
+            Clock::System => fs::metadata(path)?.modified(),
+            Clock::Fixed(time) => Ok(time),
+        }
+    }
+}
+
+/// The formats the CLI reads.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Format {
+    /// BBCode, converted to Markdown by default
+    Bbcode,
+    /// Markdown, converted to BBCode by default
+    Markdown,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension.
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("md" | "markdown") => Format::Markdown,
+            _ => Format::Bbcode,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Bbcode => "BBCode",
+            Format::Markdown => "Markdown",
+        }
+    }
+
+    /// What the format is converted to by default.
+    fn counterpart(self) -> Target {
+        match self {
+            Format::Bbcode => Target::Markdown,
+            Format::Markdown => Target::Bbcode,
+        }
+    }
+}
+
+/// The formats the CLI writes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Target {
+    /// BBCode, from Markdown
+    Bbcode,
+    /// Markdown, from BBCode
+    Markdown,
+    /// HTML, from either
+    Html,
+    /// Plain text without any markup, from either
+    Plain,
+}
+
+impl Target {
+    /// The format that can only be converted to this one.
+    fn source(self) -> Option<Format> {
+        match self {
+            Target::Bbcode => Some(Format::Markdown),
+            Target::Markdown => Some(Format::Bbcode),
+            Target::Html | Target::Plain => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Target::Bbcode => "bbcode",
+            Target::Markdown => "md",
+            Target::Html => "html",
+            Target::Plain => "txt",
+        }
+    }
+}
+
+/// Works out what the file at `path` is converted from and to, from what the flags tell and its extension.
+fn direction(
+    path: &Path,
+    from: Option<Format>,
+    to: Option<Target>,
+) -> Result<(Format, Target), FileError> {
+    let from = from
+        .or_else(|| to.and_then(Target::source))
+        .or_else(|| (!is_stdin(path)).then(|| Format::of(path)))
+        .ok_or("reading from stdin needs --from or --to to tell what it holds")?;
+
+    let to = to.unwrap_or_else(|| from.counterpart());
+
+    if to.source().is_some_and(|source| source != from) {
+        return Err(format!(
+            "the input is {} already, there's nothing to convert",
+            from.name()
+        )
+        .into());
+    }
+
+    Ok((from, to))
+}
+
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Reads the file at `path`, or stdin if it's `-`.
+fn read_input(path: &Path) -> io::Result<String> {
+    if !is_stdin(path) {
+        return read_to_string(path);
+    }
+
+    let mut ret = String::new();
+    stdin().read_to_string(&mut ret)?;
+
+    Ok(ret)
+}
+
+/// The name of the file at `path` in messages.
+fn display_name(path: &Path) -> String {
+    match is_stdin(path) {
+        true => "<stdin>".to_owned(),
+        false => path.display().to_string(),
+    }
+}
+
+/// The date of the given time in UTC, as YYYY-MM-DD.
+fn format_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    // days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Replaces the `{{name}}` placeholders in `template` with their values in a single pass, so that placeholders
+/// within the values themselves are left alone. Unknown placeholders are kept as-is.
+fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut ret = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        ret.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest.find("}}").and_then(|end| {
+            let name = rest[2..end].trim();
+
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| (*value, end + 2))
+        });
+
+        match value {
+            Some((value, len)) => {
+                ret.push_str(value);
+                rest = &rest[len..];
+            }
+            None => {
+                ret.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+
+    ret.push_str(rest);
+
+    ret
+}
+
+fn print_report(file: &str, diagnostics: &[Diagnostic]) {
+    if diagnostics.is_empty() {
+        eprintln!("{file}: every tag was converted");
+
+        return;
+    }
+
+    for diag in diagnostics {
+        eprintln!("{file}: {diag}");
+    }
+
+    // recovered tags are converted after all
+    let unconverted: Vec<_> = diagnostics
+        .iter()
+        .filter(|diag| diag.kind != DiagnosticKind::RecoveredTag)
+        .collect();
+
+    if unconverted.is_empty() {
+        eprintln!("{file}: every tag was converted");
+
+        return;
+    }
+
+    let mut by_tag = BTreeMap::new();
+
+    for diag in &unconverted {
+        *by_tag.entry(diag.tag.to_lowercase()).or_insert(0) += 1;
+    }
+
+    let summary = by_tag
+        .iter()
+        .map(|(tag, count)| format!("[{tag}] x{count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    eprintln!(
+        "{file}: {} tag(s) left unconverted: {summary}",
+        unconverted.len()
+    );
+}
+
+/// The errors of the conversion of a single file, which may happen on any thread.
+type FileError = Box<dyn Error + Send + Sync>;
+
+/// A converted file, along with the tags that couldn't be converted, if it's BBCode, what the lints flagged, if
+/// they ran, the limits of the platform it exceeds and its stats, if asked for.
+struct Converted {
+    output: String,
+    diagnostics: Option<Vec<Diagnostic>>,
+    lints: Vec<Lint>,
+    exceeded: Vec<LimitExceeded>,
+    stats: Option<Stats>,
+}
+
+/// How to wrap converted files into a template.
+struct Template<'a> {
+    template: &'a str,
+    title: Option<&'a str>,
+    date: &'a str,
+}
+
+/// How converted files are rendered, whatever their format.
+struct Rendering<'a> {
+    plain_style: PlainStyle,
+    template: Option<&'a Template<'a>>,
+    /// Whether the content is linted, and whether what's flagged is fixed where possible.
+    lint: bool,
+    fix: bool,
+    /// How the files are converted.
+    options: ConvertOptions,
+    /// Whether the output is a page comparing the BBCode with the Markdown it's converted to.
+    compare: bool,
+    /// The limits of the platform the content is meant for, and whether exceeding them is an error.
+    limits: Option<Limits>,
+    strict: bool,
+    /// Where the snapshots of the files exceeding the limits in strict mode go, if anywhere.
+    snapshots: Option<&'a Path>,
+    /// Whether the stats of the input are gathered.
+    stats: bool,
+}
+
+fn parse_profile(name: &str) -> Result<Profile, String> {
+    Profile::from_name(name).ok_or_else(|| format!("unknown or disabled profile: {name}"))
+}
+
+/// The configuration file read when none is given, if it's in the current directory.
+#[cfg(feature = "config")]
+const CONFIG_FILE: &str = "bibi.toml";
+
+/// Reads the conversion options from the configuration file at `path`, or from [`CONFIG_FILE`] if there's none,
+/// falling back to the defaults if that doesn't exist either.
+#[cfg(feature = "config")]
+fn conversion_options(path: Option<&Path>) -> bibi::Result<ConvertOptions> {
+    match path {
+        Some(path) => ConvertOptions::from_config_file(path),
+        None if Path::new(CONFIG_FILE).is_file() => ConvertOptions::from_config_file(CONFIG_FILE),
+        None => Ok(ConvertOptions::default()),
+    }
+}
+
+/// Converts the file at `path`, or stdin if it's `-`, as told by [`direction`], rendering the result as told by
+/// `rendering`.
+fn convert_file(
+    path: &Path,
+    (from, to): (Format, Target),
+    rendering: &Rendering,
+) -> Result<Converted, FileError> {
+    let contents = read_input(path)?;
+
+    if rendering.compare && from != Format::Bbcode {
+        return Err("--compare compares BBCode with the Markdown it's converted to, but the input is Markdown".into());
+    }
+
+    let original = rendering.compare.then(|| contents.clone());
+
+    let (markdown, diagnostics, stats) = match from {
+        Format::Markdown => {
+            let stats = rendering.stats.then(|| Stats::of_markdown(&contents));
+
+            (contents, None, stats)
+        }
+        Format::Bbcode => {
+            let (markdown, diagnostics) = convert_with_report_with(&contents, &rendering.options);
+            let stats = rendering
+                .stats
+                .then(|| Stats::of_bbcode(&contents, &diagnostics));
+
+            (markdown, Some(diagnostics), stats)
+        }
+    };
+
+    let (markdown, lints) = match rendering.lint {
+        true => lint(&markdown, rendering.fix),
+        false => (markdown, vec![]),
+    };
+
+    let output = match (to, &original) {
+        (_, Some(original)) => compare_page(
+            &display_name(path),
+            original,
+            &markdown,
+            &rendering.options.link_policy,
+        ),
+        (Target::Bbcode, None) => to_bbcode_with(&markdown, &rendering.options)?,
+        (Target::Markdown, None) => markdown,
+        (Target::Html, None) => to_html(&markdown, &rendering.options.link_policy),
+        (Target::Plain, None) => to_plain(&markdown, rendering.plain_style),
+    };
+
+    let exceeded = match (rendering.limits, to, &original) {
+        (Some(limits), Target::Markdown, None) => {
+            check_limits(&output, Direction::ToMarkdown, &limits)
+        }
+        (Some(limits), Target::Bbcode, None) => check_limits(&output, Direction::ToBBCode, &limits),
+        _ => vec![],
+    };
+
+    if rendering.strict && !exceeded.is_empty() {
+        let saved = match rendering.snapshots {
+            Some(dir) => {
+                let target = save_snapshots(dir, path, &output, to, &exceeded)?;
+                format!(" (snapshot in {})", target.display())
+            }
+            None => String::new(),
+        };
+
+        let exceeded: Vec<_> = exceeded.iter().map(ToString::to_string).collect();
+
+        return Err(format!(
+            "exceeds the limits of the platform: {}{saved}",
+            exceeded.join("; ")
+        )
+        .into());
+    }
+
+    let output = match rendering.template {
+        Some(Template {
+            template,
+            title,
+            date,
+        }) => {
+            let stem = Some(path)
+                .filter(|path| !is_stdin(path))
+                .and_then(Path::file_stem)
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let title = title.unwrap_or(&stem);
+
+            render(
+                template,
+                &[("content", &output), ("title", title), ("date", date)],
+            )
+        }
+        None => output,
+    };
+
+    Ok(Converted {
+        output,
+        diagnostics,
+        lints,
+        exceeded,
+        stats,
+    })
+}
+
+/// Writes a [`Snapshot`] of the parser of `output` where it exceeds each limit into a file of `dir` named after the
+/// input at `path`, returning its path.
+fn save_snapshots(
+    dir: &Path,
+    path: &Path,
+    output: &str,
+    to: Target,
+    exceeded: &[LimitExceeded],
+) -> Result<PathBuf, FileError> {
+    let direction = match to {
+        Target::Bbcode => Direction::ToBBCode,
+        _ => Direction::ToMarkdown,
+    };
+
+    let name = match is_stdin(path) {
+        true => OsStr::new("stdin"),
+        false => path.file_name().unwrap_or(path.as_os_str()),
+    };
+
+    let mut target = dir.join(name);
+    target.as_mut_os_string().push(".snapshot");
+
+    let contents: String = exceeded
+        .iter()
+        .map(|exceeded| {
+            let snapshot = Snapshot::take(output, direction, exceeded.offset);
+            format!("{}: {exceeded}\n{snapshot}\n", display_name(path))
+        })
+        .collect();
+
+    fs::create_dir_all(dir)?;
+    fs::write(&target, contents)?;
+
+    Ok(target)
+}
+
+/// Where converted files go.
+#[derive(Clone, Copy, Debug)]
+enum Destination<'a> {
+    /// To stdout for a single file, or next to their input for more than one.
+    Default,
+    /// Into the given file, or directory for more than one file, where they keep their path relative to the
+    /// directory they were found in, if any.
+    Output(&'a Path),
+    /// Over their input, keeping a `.bak` copy of it if asked to.
+    InPlace { backup: bool },
+}
+
+impl Destination<'_> {
+    /// Where the conversion of `path` into `to` goes when converting more than one file, or a single one into a
+    /// directory.
+    fn target(self, input: &Found, to: Target) -> PathBuf {
+        match self {
+            Destination::Default => input.path.with_extension(to.extension()),
+            Destination::Output(dir) => dir.join(input.relative.with_extension(to.extension())),
+            Destination::InPlace { .. } => input.path.clone(),
+        }
+    }
+
+    fn backup(self) -> bool {
+        matches!(self, Destination::InPlace { backup: true })
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut ret = path.as_os_str().to_owned();
+    ret.push(suffix);
+
+    ret.into()
+}
+
+/// Writes a converted file, keeping a `.bak` copy of the file it replaces if asked to. The file is written
+/// elsewhere first and then moved in place, so that a failure never leaves it half written.
+fn write_output(target: &Path, contents: &str, backup: bool) -> io::Result<()> {
+    if backup {
+        fs::copy(target, with_suffix(target, ".bak"))?;
+    }
+
+    let temp = with_suffix(target, ".bibi-tmp");
+
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, target)
+}
+
+/// Writes the output of a single file to `target`, or to stdout if there's none.
+fn emit(target: Option<&Path>, contents: &str, backup: bool) -> io::Result<()> {
+    match target {
+        Some(target) => write_output(target, contents, backup),
+        None => stdout().write_all(contents.as_bytes()),
+    }
+}
+
+/// How to convert more than one file.
+struct Batch<'a> {
+    from: Option<Format>,
+    to: Option<Target>,
+    rendering: &'a Rendering<'a>,
+    report: bool,
+    report_dupes: bool,
+    manifest: Option<&'a Path>,
+    destination: Destination<'a>,
+    name_by: Naming,
+    /// How many files found in directories were left alone.
+    skipped: usize,
+}
+
+/// A file converted by [`convert_batch`], as where it went, how it was converted and the hash of its content.
+type BatchResult = Result<(PathBuf, Converted, String), FileError>;
+
+/// Converts every file into the file given by [`Destination::target`], or named as told by [`Naming`], in
+/// parallel. Errors are reported for each file, after which the whole batch fails if any file did.
+fn convert_batch(inputs: &[Found], batch: &Batch) -> Result<(), Box<dyn Error>> {
+    let files: Vec<_> = inputs.iter().map(|input| input.path.clone()).collect();
+
+    if files.iter().any(|path| is_stdin(path)) {
+        return Err("stdin can't be converted along with other files".into());
+    }
+
+    if let Destination::Output(dir) = batch.destination {
+        fs::create_dir_all(dir)?;
+    }
+
+    let target_of = |input: &Found| -> Result<_, FileError> {
+        let direction = direction(&input.path, batch.from, batch.to)?;
+
+        Ok((direction, batch.destination.target(input, direction.1)))
+    };
+
+    // files may have the same target, e.g. `a/post.txt` and `b/post.txt` going into the same directory, in which
+    // case the first one gets it. Files named after their content can only have the same target as identical ones
+    let mut claimed = HashMap::new();
+    let clashes: Vec<_> = inputs
+        .iter()
+        .enumerate()
+        .map(|(idx, input)| {
+            if batch.name_by == Naming::Hash {
+                return None;
+            }
+
+            match claimed.entry(target_of(input).ok()?.1) {
+                Entry::Occupied(first) => Some(*first.get()),
+                Entry::Vacant(entry) => {
+                    entry.insert(idx);
+
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let in_place = matches!(batch.destination, Destination::InPlace { .. });
+
+    let converted: Vec<Result<_, FileError>> = inputs
+        .par_iter()
+        .zip(&clashes)
+        .map(|(input, clash)| {
+            let (direction, target) = target_of(input)?;
+
+            if let Some(first) = clash {
+                let first = display_name(&files[*first]);
+
+                return Err(
+                    format!("{} is the output of {first} already", target.display()).into(),
+                );
+            }
+
+            let converted = convert_file(&input.path, direction, batch.rendering)?;
+            let hash = content_hash(&converted.output);
+
+            let target = match batch.name_by {
+                Naming::Input => target,
+                Naming::Hash => {
+                    target.with_file_name(format!("{}.{}", &hash[..16], direction.1.extension()))
+                }
+            };
+
+            // a target may be another input, e.g. `post.txt` and `post.md`
+            if !in_place && files.contains(&target) {
+                return Err(format!("{} would be overwritten", target.display()).into());
+            }
+
+            Ok((target, converted, hash))
+        })
+        .collect();
+
+    let dupes = duplicates(
+        converted
+            .iter()
+            .map(|result| result.as_ref().ok().map(|(_, _, hash)| hash.as_str())),
+    );
+
+    // identical files named after their content go to the same place, and are only written once
+    let written: Vec<_> = converted
+        .iter()
+        .zip(&dupes)
+        .map(|(result, dupe)| match (result, dupe) {
+            (Ok((target, ..)), Some(first)) => converted[*first]
+                .as_ref()
+                .is_ok_and(|(first, ..)| first == target),
+            _ => false,
+        })
+        .collect();
+
+    let results: Vec<BatchResult> = converted
+        .into_par_iter()
+        .zip(written)
+        .map(|(result, written)| {
+            let (target, converted, hash) = result?;
+
+            if !written {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                write_output(&target, &converted.output, batch.destination.backup())?;
+            }
+
+            Ok((target, converted, hash))
+        })
+        .collect();
+
+    let mut manifest = batch.manifest.map(Manifest::create).transpose()?;
+    let mut failed = 0;
+    let mut total = Stats::default();
+
+    for ((path, result), dupe) in files.iter().zip(results).zip(&dupes) {
+        let file = display_name(path);
+
+        match result {
+            Ok((target, converted, hash)) => {
+                eprintln!("{file} -> {}", target.display());
+
+                if let Some(diagnostics) = converted.diagnostics.filter(|_| batch.report) {
+                    print_report(&file, &diagnostics);
+                }
+
+                for lint in converted.lints {
+                    eprintln!("{file}: {lint}");
+                }
+
+                for exceeded in converted.exceeded {
+                    eprintln!("{file}: warning: {exceeded}");
+                }
+
+                if let Some(stats) = converted.stats {
+                    stats.print(&file);
+                    total.add(&stats);
+                }
+
+                let original = dupe.map(|idx| display_name(&files[idx]));
+
+                if let Some(original) = original.as_deref().filter(|_| batch.report_dupes) {
+                    eprintln!("{file}: same content as {original}");
+                }
+
+                if let Some(manifest) = &mut manifest {
+                    let output = target.display().to_string();
+
+                    manifest.converted(&file, &output, &hash, original.as_deref())?;
+                }
+            }
+            Err(err) => {
+                eprintln!("{file}: {err}");
+
+                if let Some(manifest) = &mut manifest {
+                    manifest.failed(&file, &err.to_string())?;
+                }
+
+                failed += 1;
+            }
+        }
+    }
+
+    if let Some(manifest) = manifest {
+        manifest.finish()?;
+    }
+
+    if batch.rendering.stats {
+        total.print("total");
+    }
+
+    if batch.report_dupes {
+        let count = dupes.iter().flatten().count();
+
+        eprintln!("{count} of {} files are duplicates", files.len());
+    }
+
+    eprintln!(
+        "{} converted, {} skipped, {failed} failed",
+        files.len() - failed,
+        batch.skipped
+    );
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{failed} of {} files could not be converted", files.len()).into()),
+    }
+}
+
+/// Finds the files to convert among `files`, replacing directories with the files below them that match `globs`
+/// and aren't in the format they'd be converted to already, along with how many other files they hold.
+///
+/// The files a tree holds next to others they'd be converted from, e.g. `a.md` next to `a.bb`, are taken to be
+/// the outputs of an earlier run rather than inputs, so that converting a tree again, as `--watch` does, never
+/// converts its own outputs back.
+fn gather(
+    files: Vec<PathBuf>,
+    globs: &[String],
+    from: Option<Format>,
+    to: Option<Target>,
+) -> Result<(Vec<Found>, usize), Box<dyn Error>> {
+    let mut inputs = vec![];
+    let mut skipped = 0;
+
+    for path in files {
+        if !path.is_dir() {
+            inputs.push(Found::file(&path));
+
+            continue;
+        }
+
+        let (found, unmatched) = walk(&path, globs)?;
+        let (matched, before) = (found.len(), inputs.len());
+
+        let outputs: HashSet<PathBuf> = found
+            .iter()
+            .filter_map(|input| {
+                let (_, to) = direction(&input.path, from, to).ok()?;
+
+                Some(input.path.with_extension(to.extension()))
+            })
+            .collect();
+
+        // a tree may hold files in the target format already, e.g. those converted by an earlier run
+        inputs.extend(found.into_iter().filter(|input| {
+            !outputs.contains(&input.path)
+                && direction(&input.path, from, to)
+                    .is_ok_and(|(_, to)| input.path.extension() != Some(OsStr::new(to.extension())))
+        }));
+
+        skipped += unmatched + matched - (inputs.len() - before);
+    }
+
+    Ok((inputs, skipped))
+}
+
+/// Converts every file into its counterpart and back in parallel, printing what doesn't survive the round trip.
+/// Fails if anything doesn't, or if any file couldn't be converted.
+fn check_files(
+    inputs: &[Found],
+    from: Option<Format>,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    let results: Vec<Result<_, FileError>> = inputs
+        .par_iter()
+        .map(|input| {
+            let (from, _) = direction(&input.path, from, None)?;
+
+            Ok(round_trip(&read_input(&input.path)?, from, options)?)
+        })
+        .collect();
+
+    let mut unstable = 0;
+
+    for (input, result) in inputs.iter().zip(results) {
+        let file = display_name(&input.path);
+
+        match result {
+            Ok(changes) if changes.is_empty() => eprintln!("{file}: survives the round trip"),
+            Ok(changes) => {
+                for change in changes {
+                    eprintln!("{file}: {change}");
+                }
+
+                unstable += 1;
+            }
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                unstable += 1;
+            }
+        }
+    }
+
+    match unstable {
+        0 => Ok(()),
+        _ => Err(format!(
+            "{unstable} of {} files don't survive the round trip",
+            inputs.len()
+        )
+        .into()),
+    }
+}
+
+/// Prints the stats of every file, along with their total with more than one file, gathering them in parallel.
+fn survey(
+    inputs: &[Found],
+    from: Option<Format>,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    let results: Vec<Result<_, FileError>> = inputs
+        .par_iter()
+        .map(|input| {
+            let (from, _) = direction(&input.path, from, None)?;
+            let contents = read_input(&input.path)?;
+
+            Ok(match from {
+                Format::Bbcode => {
+                    let (_, diagnostics) = convert_with_report_with(&contents, options);
+
+                    Stats::of_bbcode(&contents, &diagnostics)
+                }
+                Format::Markdown => Stats::of_markdown(&contents),
+            })
+        })
+        .collect();
+
+    let mut total = Stats::default();
+    let mut failed = 0;
+
+    for (input, result) in inputs.iter().zip(results) {
+        let file = display_name(&input.path);
+
+        match result {
+            Ok(stats) => {
+                stats.print(&file);
+                total.add(&stats);
+            }
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    if inputs.len() > 1 {
+        total.print("total");
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{failed} of {} files could not be read", inputs.len()).into()),
+    }
+}
+
+/// The only file given, for the modes that take a single input.
+fn single(files: &[PathBuf], mode: &str) -> Result<PathBuf, Box<dyn Error>> {
+    match files {
+        [file] => Ok(file.clone()),
+        _ => Err(format!("{mode} takes a single input").into()),
+    }
+}
+
+/// Reads the arguments, where a leading `convert` is the same as none at all. Converting is what bibi does without
+/// a subcommand, and a `convert` subcommand of its own would have to repeat every flag of the conversion, which
+/// the other subcommands don't take. A file named `convert` can still be given as `./convert`.
+fn parse_args() -> Args {
+    let mut args: Vec<OsString> = env::args_os().collect();
+
+    if args.get(1).is_some_and(|arg| arg == "convert") {
+        args.remove(1);
+    }
+
+    Args::parse_from(args)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = parse_args();
+
+    if let Some(command) = args.command.take() {
+        let options = conversion_options_of(&args)?;
+
+        return match command {
+            #[cfg(feature = "db")]
+            Command::Db(args) => db::convert(args, &options),
+            #[cfg(feature = "serve")]
+            Command::Serve(args) => serve(args, options),
+            Command::Infer(args) => infer(args),
+            Command::Generate(args) => generate(args, &options),
+            Command::Supported(args) => supported(args),
+            Command::Completions { shell } => {
+                let mut command = Args::command();
+                let name = command.get_name().to_owned();
+
+                clap_complete::generate(shell, &mut command, name, &mut stdout());
+
+                Ok(())
+            }
+        };
+    }
+
+    if args.generate_manpage {
+        Man::new(Args::command()).render(&mut stdout())?;
+
+        return Ok(());
+    }
+
+    if args.jsonl {
+        return serve_lines(&conversion_options_of(&args)?);
+    }
+
+    if args.files.is_empty() {
+        args.files.push(PathBuf::from("-"));
+    }
+
+    if !args.watch {
+        return run(&args);
+    }
+
+    if args.files.iter().any(|path| is_stdin(path)) {
+        return Err("stdin can't be watched for changes".into());
+    }
+
+    watch(&args.files, || run(&args))
+}
+
+/// The conversion options the arguments tell, i.e. those of the profile or of the configuration file, as
+/// [`conversion_options`] finds it, along with those given on the command line, which take precedence.
+fn conversion_options_of(args: &Args) -> Result<ConvertOptions, Box<dyn Error>> {
+    let &Args {
+        annotate_losses,
+        unclosed,
+        html_fallbacks,
+        markdown_html,
+        smileys,
+        links,
+        ref link_schemes,
+        ref base_url,
+        nofollow,
+        ..
+    } = args;
+
+    #[cfg(feature = "config")]
+    let options = match args.profile {
+        Some(profile) => profile.options(),
+        None => conversion_options(args.config.as_deref()).map_err(|err| err.to_string())?,
+    };
+    #[cfg(not(feature = "config"))]
+    let options = args
+        .profile
+        .map_or_else(ConvertOptions::default, Profile::options);
+
+    Ok(ConvertOptions {
+        annotate_losses,
+        unclosed: match unclosed {
+            Some(Unclosed::Keep) => UnclosedTags::Keep,
+            Some(Unclosed::Close) => UnclosedTags::Close,
+            Some(Unclosed::Strip) => UnclosedTags::Strip,
+            None => options.unclosed,
+        },
+        html: match markdown_html {
+            Some(MarkdownHtml::Strip) => HtmlPolicy::Strip,
+            Some(MarkdownHtml::Verbatim) => HtmlPolicy::Verbatim,
+            Some(MarkdownHtml::Translate) => HtmlPolicy::Translate,
+            None => options.html,
+        },
+        smiley_style: match smileys {
+            Some(Smileys::Keep) => SmileyStyle::Keep,
+            Some(Smileys::Emoji) => SmileyStyle::Emoji,
+            Some(Smileys::Shortcode) => SmileyStyle::Shortcode,
+            None => options.smiley_style,
+        },
+        links: match links {
+            Some(Links::Inline) => LinkStyle::Inline,
+            Some(Links::Autolink) => LinkStyle::Autolink,
+            Some(Links::Reference) => LinkStyle::Reference,
+            None => options.links,
+        },
+        link_policy: LinkPolicy {
+            schemes: link_schemes.clone().or(options.link_policy.schemes),
+            base_url: base_url.clone().or(options.link_policy.base_url),
+            nofollow: nofollow || options.link_policy.nofollow,
+        },
+        target: match html_fallbacks {
+            true => MarkdownTarget::Html,
+            false => options.target,
+        },
+        ..options
+    })
+}
+
+/// Does whatever the arguments tell, besides running subcommands.
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let &Args {
+        ref files,
+        recursive,
+        ref glob,
+        output: ref output_path,
+        in_place,
+        backup,
+        from,
+        to,
+        plain_style,
+        lint,
+        fix,
+        stats,
+        stats_only,
+        check,
+        compare,
+        report,
+        report_dupes,
+        name_by,
+        reproducible,
+        ref manifest,
+        ref template,
+        ref title,
+        ref date,
+        export: ref export_dir,
+        ref mail,
+        site,
+        ref metadata,
+        feed,
+        feed_body,
+        ..
+    } = args;
+
+    // the comparisons are HTML pages
+    let to = match compare {
+        true => Some(Target::Html),
+        false => to,
+    };
+
+    let clock = match reproducible {
+        true => Clock::reproducible()?,
+        false => Clock::System,
+    };
+
+    let destination = match (&output_path, in_place) {
+        (Some(output), _) => Destination::Output(output),
+        (None, true) => Destination::InPlace { backup },
+        (None, false) => Destination::Default,
+    };
+
+    let options = conversion_options_of(args)?;
+
+    if let Some(out) = export_dir {
+        return export(
+            &single(files, "--export")?,
+            out,
+            site,
+            metadata.as_deref(),
+            clock,
+            &options,
+        );
+    }
+
+    if let Some(out) = mail {
+        return import(&single(files, "--mail")?, out, site, clock, &options);
+    }
+
+    if check || stats_only {
+        let (inputs, _) = gather(files.clone(), glob, from, to)?;
+
+        return match check {
+            true => check_files(&inputs, from, &options),
+            false => survey(&inputs, from, &options),
+        };
+    }
+
+    let template = template.as_ref().map(read_to_string).transpose()?;
+    let date = date.clone().unwrap_or_else(|| format_date(clock.now()));
+
+    let template = template.as_deref().map(|template| Template {
+        template,
+        title: title.as_deref(),
+        date: &date,
+    });
+
+    if let Some(Emit::Ast) = args.emit {
+        let path = single(files, "--emit")?;
+
+        if from.unwrap_or_else(|| Format::of(&path)) != Format::Bbcode {
+            return Err("--emit ast parses BBCode, but the input is Markdown".into());
+        }
+
+        let ast = parse_ast_with(&read_input(&path)?, &options);
+        emit(
+            output_path.as_deref(),
+            &(serde_json::to_string_pretty(&ast)? + "\n"),
+            false,
+        )?;
+
+        return Ok(());
+    }
+
+    let rendering = Rendering {
+        plain_style,
+        template: template.as_ref(),
+        lint,
+        fix,
+        compare,
+        limits: args.profile.map(Profile::limits),
+        strict: args.strict,
+        snapshots: args.snapshots.as_deref(),
+        options,
+        stats,
+    };
+
+    if feed {
+        let contents = read_input(&single(files, "--feed")?)?;
+        emit(
+            output_path.as_deref(),
+            &convert_feed(&contents, feed_body, &rendering.options)?,
+            false,
+        )?;
+
+        return Ok(());
+    }
+
+    if recursive || files.len() > 1 {
+        let (mut inputs, skipped) = gather(files.clone(), glob, from, to)?;
+
+        if reproducible {
+            inputs.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+
+        let batch = Batch {
+            from,
+            to,
+            rendering: &rendering,
+            report,
+            report_dupes,
+            manifest: manifest.as_deref(),
+            destination,
+            name_by,
+            skipped,
+        };
+
+        return convert_batch(&inputs, &batch);
+    }
+
+    if report_dupes || manifest.is_some() || name_by != Naming::Input {
+        return Err("--report-dupes, --manifest and --name-by need more than one file".into());
+    }
+
+    let path = &files[0];
+
+    let direction = direction(path, from, to).map_err(|err| err as Box<dyn Error>)?;
+
+    let target = match destination {
+        Destination::Default => None,
+        _ if is_stdin(path) && (in_place || output_path.as_deref().is_some_and(Path::is_dir)) => {
+            return Err("stdin can only be written to stdout or to a file".into());
+        }
+        Destination::Output(output) if !output.is_dir() => Some(output.to_owned()),
+        _ => Some(destination.target(&Found::file(path), direction.1)),
+    };
+
+    let Converted {
+        output,
+        diagnostics,
+        lints,
+        exceeded,
+        stats,
+    } = convert_file(path, direction, &rendering).map_err(|err| err as Box<dyn Error>)?;
+
+    emit(target.as_deref(), &output, destination.backup())?;
+
+    if let Some(diagnostics) = diagnostics.filter(|_| report) {
+        print_report(&display_name(path), &diagnostics);
+    }
+
+    for lint in lints {
+        eprintln!("{}: {lint}", display_name(path));
+    }
+
+    for exceeded in exceeded {
+        eprintln!("{}: warning: {exceeded}", display_name(path));
+    }
+
+    if let Some(stats) = stats {
+        stats.print(&display_name(path));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use clap::CommandFactory;
+
+    use super::{gather, Args};
+
+    #[test]
+    fn arguments_are_well_formed() {
+        let command = Args::command();
+        command.clone().debug_assert();
+
+        let about = command.get_about().unwrap().to_string();
+        assert!(about.starts_with("Converts posts between Markdown and BBCode"));
+    }
+
+    #[test]
+    fn outputs_are_no_inputs() {
+        let tree = env::temp_dir().join(format!("bibi-gather-{}", process::id()));
+        fs::create_dir_all(tree.join("sub")).unwrap();
+
+        for (name, contents) in [
+            ("a.bb", "[b]a[/b]"),
+            ("a.md", "**a**"),
+            ("a.bbcode", "[b]a[/b]"),
+            ("sub/b.txt", "[i]b[/i]"),
+            ("sub/b.md", "*b*"),
+            ("c.md", "*c*"),
+        ] {
+            fs::write(tree.join(name), contents).unwrap();
+        }
+
+        let (inputs, skipped) = gather(vec![tree.clone()], &["*".to_owned()], None, None).unwrap();
+        let mut names: Vec<_> = inputs.iter().map(|input| input.relative.clone()).collect();
+        names.sort();
+
+        fs::remove_dir_all(&tree).unwrap();
+
+        assert_eq!(names, ["a.bb", "c.md", "sub/b.txt"].map(PathBuf::from));
+        assert_eq!(skipped, 3);
+    }
+}
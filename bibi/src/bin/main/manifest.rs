@@ -0,0 +1,108 @@
+//! Content hashes of converted files, telling which posts of a batch are the same as others, and the manifest
+//! listing them.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Normalizes converted content, so that posts differing only in whitespace are the same: lines lose their trailing
+/// blanks and terminators, and blank lines are collapsed into one, or dropped at either end.
+fn normalize(content: &str) -> String {
+    let mut ret = String::with_capacity(content.len());
+    let mut after_blank = false;
+
+    for line in content.lines().map(str::trim_end) {
+        if line.is_empty() {
+            after_blank = !ret.is_empty();
+
+            continue;
+        }
+
+        if after_blank {
+            ret.push('\n');
+            after_blank = false;
+        }
+
+        ret.push_str(line);
+        ret.push('\n');
+    }
+
+    ret
+}
+
+/// The SHA-256 of the normalized content, in hex.
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(normalize(content)))
+}
+
+/// Tells, for every hash, the index of the first one before it that's the same, if any. Missing hashes are never
+/// the same as any other.
+pub fn duplicates<'a>(hashes: impl IntoIterator<Item = Option<&'a str>>) -> Vec<Option<usize>> {
+    let mut first = HashMap::new();
+
+    hashes
+        .into_iter()
+        .enumerate()
+        .map(|(i, hash)| match first.entry(hash?) {
+            Entry::Occupied(original) => Some(*original.get()),
+            Entry::Vacant(entry) => {
+                entry.insert(i);
+
+                None
+            }
+        })
+        .collect()
+}
+
+/// The manifest of a batch, holding a JSON object for every file, one per line, in the order the files were given.
+pub struct Manifest {
+    out: BufWriter<File>,
+}
+
+impl Manifest {
+    pub fn create(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// Adds a file converted into `output`, whose content has the given hash and is the same as `duplicate_of`'s.
+    pub fn converted(
+        &mut self,
+        file: &str,
+        output: &str,
+        hash: &str,
+        duplicate_of: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.push(json!({
+            "file": file,
+            "output": output,
+            "hash": hash,
+            "duplicate_of": duplicate_of,
+        }))
+    }
+
+    /// Adds a file that couldn't be converted.
+    pub fn failed(&mut self, file: &str, err: &str) -> Result<(), Box<dyn Error>> {
+        self.push(json!({ "file": file, "error": err }))
+    }
+
+    fn push(&mut self, entry: Value) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "{entry}")?;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! Rendering of Markdown into the formats the CLI can write besides BBCode and Markdown itself.
+
+use clap::ValueEnum;
+use pulldown_cmark::{html, Event, HeadingLevel, Parser, Tag};
+
+use bibi::LinkPolicy;
+
+/// How formatting is rendered in plain text.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum PlainStyle {
+    /// Dropped, keeping nothing but the text itself
+    Bare,
+    /// As textual conventions, such as indented quotes and `1)` lists, with bold text in UPPERCASE, e.g. for
+    /// notifications sent as plain text
+    Upper,
+    /// As textual conventions, like `upper`, but with bold text between *asterisks*
+    Asterisks,
+}
+
+/// Parses Markdown the way the conversions do.
+pub fn parse(markdown: &str) -> Parser<'_, '_> {
+    Parser::new_ext(markdown, bibi::markdown_options())
+}
+
+/// The policy to render some content with in a page, i.e. `links` if it restricts schemes, or else `links`
+/// restricted to those [`LinkPolicy::untrusted`] allows, as the page must run no script the content holds.
+pub fn sanitizing(links: &LinkPolicy) -> LinkPolicy {
+    LinkPolicy {
+        schemes: links.schemes.clone().or(LinkPolicy::untrusted().schemes),
+        ..links.clone()
+    }
+}
+
+pub fn to_html(markdown: &str, links: &LinkPolicy) -> String {
+    let mut ret = String::with_capacity(markdown.len() * 2);
+    html::push_html(&mut ret, links.apply_to_events(parse(markdown)));
+
+    ret
+}
+
+/// Renders Markdown as plain text, rendering formatting as told by `style`.
+pub fn to_plain(markdown: &str, style: PlainStyle) -> String {
+    match style {
+        PlainStyle::Bare => to_bare(markdown),
+        PlainStyle::Upper | PlainStyle::Asterisks => {
+            Lossy::new(style == PlainStyle::Upper).render(markdown)
+        }
+    }
+}
+
+/// Renders Markdown as plain text, keeping nothing but the text itself, with blocks separated by blank lines and
+/// list items on lines of their own.
+fn to_bare(markdown: &str) -> String {
+    use Event::*;
+
+    let mut ret = String::with_capacity(markdown.len());
+
+    let end_block = |ret: &mut String| {
+        if !ret.is_empty() {
+            ret.truncate(ret.trim_end().len());
+            ret.push_str("\n\n");
+        }
+    };
+
+    for event in parse(markdown) {
+        match event {
+            Text(text) | Code(text) | Html(text) => ret.push_str(&text),
+            FootnoteReference(label) => ret.push_str(&format!("[{label}]")),
+            TaskListMarker(checked) => ret.push_str(if checked { "[x] " } else { "[ ] " }),
+            Start(Tag::FootnoteDefinition(label)) => ret.push_str(&format!("[{label}] ")),
+            SoftBreak | HardBreak => ret.push('\n'),
+            // nested lists start on a line of their own
+            Start(Tag::List(_)) if !ret.is_empty() && !ret.ends_with('\n') => ret.push('\n'),
+            Start(Tag::Item) => ret.push_str("- "),
+            End(Tag::Item) => {
+                ret.truncate(ret.trim_end().len());
+                ret.push('\n');
+            }
+            End(
+                Tag::Paragraph
+                | Tag::FootnoteDefinition(_)
+                | Tag::Heading(..)
+                | Tag::CodeBlock(_)
+                | Tag::BlockQuote
+                | Tag::List(_)
+                | Tag::Table(_),
+            )
+            | Rule => end_block(&mut ret),
+            _ => {}
+        }
+    }
+
+    ret.truncate(ret.trim_end().len());
+
+    if !ret.is_empty() {
+        ret.push('\n');
+    }
+
+    ret
+}
+
+/// Renders Markdown as plain text that keeps some of its formatting as textual conventions: bold text is in
+/// UPPERCASE or between `*asterisks*`, italics between `_underscores_`, quotes and code blocks are indented, list
+/// items start with `-` or `1)`, headings are underlined and links are followed by their URL.
+struct Lossy {
+    ret: String,
+    /// Whether bold text is in uppercase rather than between asterisks.
+    upper: bool,
+    /// How many bold spans are open.
+    strong: usize,
+    /// How many quotes are open.
+    quotes: usize,
+    /// The number of the next item of every open list, or `None` for unordered lists.
+    lists: Vec<Option<u64>>,
+    /// Whether a code block is open.
+    code: bool,
+    /// The URL of every open link or image, and where its text starts.
+    links: Vec<(String, usize)>,
+    /// Whether nothing has been written yet on the current line, not even its indentation.
+    at_line_start: bool,
+}
+
+impl Lossy {
+    fn new(upper: bool) -> Self {
+        Self {
+            ret: String::new(),
+            upper,
+            strong: 0,
+            quotes: 0,
+            lists: vec![],
+            code: false,
+            links: vec![],
+            at_line_start: true,
+        }
+    }
+
+    /// The indentation of the lines of the innermost block, given by quotes, code blocks and the first `levels`
+    /// lists, whose items are indented past their marker.
+    fn indent(&self, levels: usize) -> String {
+        let mut ret = "    ".repeat(self.quotes);
+
+        for _ in &self.lists[..levels] {
+            ret.push_str("   ");
+        }
+
+        if self.code {
+            ret.push_str("    ");
+        }
+
+        ret
+    }
+
+    fn start_line(&mut self) {
+        if self.at_line_start {
+            let indent = self.indent(self.lists.len());
+            self.ret.push_str(&indent);
+            self.at_line_start = false;
+        }
+    }
+
+    fn newline(&mut self) {
+        self.ret.truncate(self.ret.trim_end_matches(' ').len());
+        self.ret.push('\n');
+        self.at_line_start = true;
+    }
+
+    fn write(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            self.start_line();
+
+            match self.upper && self.strong > 0 {
+                true => self.ret.push_str(&line.to_uppercase()),
+                false => self.ret.push_str(line),
+            }
+        }
+    }
+
+    /// Ends a block, leaving a blank line after it.
+    fn end_block(&mut self) {
+        if !self.ret.is_empty() {
+            self.ret.truncate(self.ret.trim_end().len());
+            self.ret.push_str("\n\n");
+        }
+
+        self.at_line_start = true;
+    }
+
+    fn end_line(&mut self) {
+        if !self.at_line_start {
+            self.newline();
+        }
+    }
+
+    fn start_item(&mut self) {
+        self.end_line();
+
+        let indent = self.indent(self.lists.len() - 1);
+
+        let marker = match self.lists.last_mut() {
+            Some(Some(number)) => {
+                *number += 1;
+
+                format!("{}) ", *number - 1)
+            }
+            _ => "- ".to_owned(),
+        };
+
+        self.ret.push_str(&indent);
+        self.ret.push_str(&marker);
+        self.at_line_start = false;
+    }
+
+    fn underline(&mut self, level: HeadingLevel) {
+        let line_start = self.ret.rfind('\n').map_or(0, |pos| pos + 1);
+        let width = self.ret[line_start..].trim().chars().count();
+
+        let c = match level {
+            HeadingLevel::H1 => "=",
+            _ => "-",
+        };
+
+        self.newline();
+        self.write(&c.repeat(width));
+        self.end_block();
+    }
+
+    fn render(mut self, markdown: &str) -> String {
+        use Event::*;
+
+        for event in parse(markdown) {
+            match event {
+                Text(text) | Code(text) | Html(text) => self.write(&text),
+                FootnoteReference(label) => self.write(&format!("[{label}]")),
+                TaskListMarker(checked) => self.write(if checked { "[x] " } else { "[ ] " }),
+                Start(Tag::FootnoteDefinition(label)) => {
+                    self.end_line();
+                    self.write(&format!("[{label}] "));
+                }
+                End(Tag::FootnoteDefinition(_)) => self.end_block(),
+                SoftBreak | HardBreak => self.newline(),
+                Start(Tag::Strong) => {
+                    if !self.upper {
+                        self.write("*");
+                    }
+
+                    self.strong += 1;
+                }
+                End(Tag::Strong) => {
+                    self.strong -= 1;
+
+                    if !self.upper {
+                        self.write("*");
+                    }
+                }
+                Start(Tag::Emphasis) | End(Tag::Emphasis) => self.write("_"),
+                Start(Tag::Strikethrough) | End(Tag::Strikethrough) => self.write("~"),
+                Start(Tag::BlockQuote) => {
+                    self.end_line();
+                    self.quotes += 1;
+                }
+                End(Tag::BlockQuote) => {
+                    self.end_block();
+                    self.quotes -= 1;
+                }
+                Start(Tag::CodeBlock(_)) => {
+                    self.end_line();
+                    self.code = true;
+                }
+                End(Tag::CodeBlock(_)) => {
+                    self.code = false;
+                    self.end_block();
+                }
+                Start(Tag::List(first)) => {
+                    self.end_line();
+                    self.lists.push(first);
+                }
+                End(Tag::List(_)) => {
+                    self.lists.pop();
+
+                    if self.lists.is_empty() {
+                        self.end_block();
+                    }
+                }
+                Start(Tag::Item) => self.start_item(),
+                End(Tag::Item) => self.end_line(),
+                Start(Tag::Link(_, url, _) | Tag::Image(_, url, _)) => {
+                    self.links.push((url.into_string(), self.ret.len()));
+                }
+                End(Tag::Link(..) | Tag::Image(..)) => {
+                    let Some((url, start)) = self.links.pop() else {
+                        continue;
+                    };
+
+                    // bare links are just their URL already
+                    if !url.is_empty() && self.ret[start..].trim() != url {
+                        self.write(&format!(" ({url})"));
+                    }
+                }
+                End(Tag::Heading(level, ..)) => self.underline(level),
+                End(Tag::Paragraph) => self.end_block(),
+                Rule => {
+                    self.end_block();
+                    self.write("----------");
+                    self.end_block();
+                }
+                _ => {}
+            }
+        }
+
+        self.ret.truncate(self.ret.trim_end().len());
+
+        if !self.ret.is_empty() {
+            self.ret.push('\n');
+        }
+
+        self.ret
+    }
+}
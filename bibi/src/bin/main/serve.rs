@@ -0,0 +1,194 @@
+//! A small HTTP server converting whatever is posted to it, so that backends written in other languages can use
+//! bibi without spawning a process per conversion.
+
+use std::{
+    error::Error,
+    io::{self, Read},
+    net::SocketAddr,
+    num::NonZeroUsize,
+    sync::Arc,
+    thread,
+};
+
+use clap::Args;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use bibi::{to_bbcode_with, to_markdown_with, ConvertOptions};
+
+use crate::compare::compare_page;
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// The address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// How many requests are handled at once [default: the number of CPUs]
+    #[arg(long)]
+    threads: Option<NonZeroUsize>,
+
+    /// The largest body accepted, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 1 << 20)]
+    max_body: usize,
+}
+
+/// A response to a request, before being sent.
+struct Reply {
+    status: u16,
+    body: String,
+    content_type: &'static str,
+}
+
+impl Reply {
+    fn ok(body: String, content_type: &'static str) -> Self {
+        Self {
+            status: 200,
+            body,
+            content_type,
+        }
+    }
+
+    /// An error, as a JSON object holding its message in `error`.
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }).to_string(),
+            content_type: "application/json",
+        }
+    }
+}
+
+const TEXT: &str = "text/plain; charset=utf-8";
+const HTML: &str = "text/html; charset=utf-8";
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name, value).expect("header names and values are always valid here")
+}
+
+/// Reads the body of `request` as UTF-8, refusing it if it's larger than `max` bytes.
+fn read_body(request: &mut Request, max: usize) -> Result<String, Reply> {
+    let too_large = || Reply::error(413, format!("the body must be at most {max} bytes"));
+
+    if request.body_length().is_some_and(|len| len > max) {
+        return Err(too_large());
+    }
+
+    let mut body = vec![];
+
+    // one more byte than allowed tells whether bodies of unknown length are too large
+    request
+        .as_reader()
+        .take(max as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|err| Reply::error(400, format!("can't read the body: {err}")))?;
+
+    if body.len() > max {
+        return Err(too_large());
+    }
+
+    String::from_utf8(body).map_err(|_| Reply::error(400, "the body is not valid UTF-8"))
+}
+
+/// How an endpoint converts the body of a request.
+type Convert<'a> = &'a dyn Fn(&str) -> bibi::Result<String>;
+
+fn handle(request: &mut Request, max_body: usize, options: &ConvertOptions) -> Reply {
+    let path = request.url().split('?').next().unwrap_or_default();
+
+    let (convert, content_type): (Convert, _) = match path {
+        "/to-markdown" => (&|text| Ok(to_markdown_with(text, options)), TEXT),
+        "/to-bbcode" => (&|text| to_bbcode_with(text, options), TEXT),
+        "/compare" => (
+            &|text| {
+                let markdown = to_markdown_with(text, options);
+
+                Ok(compare_page(
+                    "Comparison",
+                    text,
+                    &markdown,
+                    &options.link_policy,
+                ))
+            },
+            HTML,
+        ),
+        _ => return Reply::error(404, format!("no such endpoint: {path}")),
+    };
+
+    if *request.method() != Method::Post {
+        return Reply::error(405, format!("{path} only accepts POST"));
+    }
+
+    let body = match read_body(request, max_body) {
+        Ok(body) => body,
+        Err(reply) => return reply,
+    };
+
+    match convert(&body) {
+        Ok(converted) => Reply::ok(converted, content_type),
+        Err(err) => Reply::error(422, err.to_string()),
+    }
+}
+
+fn respond(mut request: Request, max_body: usize, options: &ConvertOptions) -> io::Result<()> {
+    let Reply {
+        status,
+        body,
+        content_type,
+    } = handle(&mut request, max_body, options);
+
+    let mut response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", content_type));
+
+    if status == 405 {
+        response.add_header(header("Allow", "POST"));
+    }
+
+    request.respond(response)
+}
+
+/// Runs the `serve` subcommand, serving requests until killed:
+///
+/// - `POST /to-markdown` converts the BBCode in the body to Markdown;
+/// - `POST /to-bbcode` converts the Markdown in the body to BBCode;
+/// - `POST /compare` returns an HTML page comparing the BBCode in the body, rendered as NERDZ does, with the
+///   Markdown it's converted to, for previews.
+///
+/// Conversions are made with `options` and returned as plain text, while errors are JSON objects holding their
+/// message in `error`.
+pub fn serve(args: ServeArgs, options: ConvertOptions) -> Result<(), Box<dyn Error>> {
+    let options = Arc::new(options);
+    let server =
+        Arc::new(Server::http(args.listen).map_err(|err| format!("{}: {err}", args.listen))?);
+
+    let threads = args
+        .threads
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+
+    eprintln!("Listening on http://{}", args.listen);
+
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let options = Arc::clone(&options);
+
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let url = request.url().to_owned();
+
+                    if let Err(err) = respond(request, args.max_body, &options) {
+                        eprintln!("{url}: {err}");
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        worker.join().map_err(|_| "a worker thread panicked")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Statistics about the tags files use, e.g. to survey a forum dump before deciding which tags need supporting.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use bibi::{count_tags, Diagnostic, DiagnosticKind};
+
+use crate::check::count_constructs;
+
+/// How many times a file uses every tag, or every construct for Markdown, along with which tags are unknown and how
+/// many aren't terminated.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// How long the file is, in grapheme clusters, so that an emoji or an accented letter counts once however many
+    /// code points it's made of.
+    characters: usize,
+    counts: BTreeMap<String, usize>,
+    unknown: BTreeSet<String>,
+    unclosed: usize,
+    unmatched: usize,
+}
+
+impl Stats {
+    /// The stats of some BBCode, given the diagnostics of its conversion.
+    pub fn of_bbcode(bbcode: &str, diagnostics: &[Diagnostic]) -> Self {
+        let mut ret = Self {
+            characters: bbcode.graphemes(true).count(),
+            counts: count_tags(bbcode)
+                .into_iter()
+                .map(|(name, count)| (format!("[{name}]"), count))
+                .collect(),
+            ..Default::default()
+        };
+
+        for diag in diagnostics {
+            match diag.kind {
+                DiagnosticKind::UnknownTag => {
+                    ret.unknown.insert(format!("[{}]", diag.tag.to_lowercase()));
+                }
+                DiagnosticKind::UnclosedTag => ret.unclosed += 1,
+                DiagnosticKind::UnmatchedClose => ret.unmatched += 1,
+                _ => {}
+            }
+        }
+
+        ret
+    }
+
+    pub fn of_markdown(markdown: &str) -> Self {
+        Self {
+            characters: markdown.graphemes(true).count(),
+            counts: count_constructs(markdown),
+            ..Default::default()
+        }
+    }
+
+    /// Adds the stats of another file to these, e.g. for the total of a batch.
+    pub fn add(&mut self, other: &Stats) {
+        self.characters += other.characters;
+
+        for (name, count) in &other.counts {
+            *self.counts.entry(name.clone()).or_default() += count;
+        }
+
+        self.unknown.extend(other.unknown.iter().cloned());
+        self.unclosed += other.unclosed;
+        self.unmatched += other.unmatched;
+    }
+
+    /// Prints the stats to stderr, as those of `name`.
+    pub fn print(&self, name: &str) {
+        let characters = self.characters;
+
+        if self.counts.is_empty() {
+            eprintln!("{name}: {characters} character(s), no tags");
+        } else {
+            let breakdown = self
+                .counts
+                .iter()
+                .map(|(tag, count)| match self.unknown.contains(tag) {
+                    true => format!("{tag} x{count} (unknown)"),
+                    false => format!("{tag} x{count}"),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            eprintln!("{name}: {characters} character(s), {breakdown}");
+        }
+
+        if self.unclosed > 0 || self.unmatched > 0 {
+            eprintln!(
+                "{name}: {} tag(s) never closed, {} closing tag(s) never opened",
+                self.unclosed, self.unmatched
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! The conversion matrix of the dialects, i.e. which tags every conversion supports, what they become and how
+//! faithfully, generated from the tables of the library so that what UIs show never drifts from what converts.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io::{stdout, Write},
+};
+
+use clap::Args;
+
+use bibi::{supported_tags, Dialect, Direction, TagInfo};
+
+#[derive(Args, Debug)]
+pub struct SupportedArgs {
+    /// Print the matrix as JSON, by dialect and by direction, rather than as tables
+    #[arg(long)]
+    json: bool,
+
+    /// Only print the tags of this dialect, `nerdz` or `phpbb`, rather than of all of them
+    #[arg(long, value_parser = parse_dialect)]
+    dialect: Option<Dialect>,
+}
+
+fn parse_dialect(name: &str) -> Result<Dialect, String> {
+    Dialect::ALL
+        .iter()
+        .copied()
+        .find(|dialect| dialect.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("unknown or disabled dialect: {name}"))
+}
+
+/// The directions of the matrix, along with how they're called in it.
+const DIRECTIONS: [(Direction, &str); 2] = [
+    (Direction::ToMarkdown, "to-markdown"),
+    (Direction::ToBBCode, "to-bbcode"),
+];
+
+/// Runs the `supported` subcommand, printing the conversion matrix of the dialects to stdout.
+pub fn supported(args: SupportedArgs) -> Result<(), Box<dyn Error>> {
+    let SupportedArgs { json, dialect } = args;
+
+    let dialects = match &dialect {
+        Some(dialect) => std::slice::from_ref(dialect),
+        None => Dialect::ALL,
+    };
+
+    let matrix: BTreeMap<&str, BTreeMap<&str, &[TagInfo]>> = dialects
+        .iter()
+        .map(|&dialect| {
+            let directions = DIRECTIONS
+                .into_iter()
+                .map(|(direction, name)| (name, supported_tags(direction, dialect)))
+                .collect();
+
+            (dialect.name(), directions)
+        })
+        .collect();
+
+    let mut out = stdout().lock();
+
+    if json {
+        serde_json::to_writer_pretty(&mut out, &matrix)?;
+        writeln!(out)?;
+
+        return Ok(());
+    }
+
+    for (i, (dialect, directions)) in matrix.iter().enumerate() {
+        for (j, (direction, tags)) in directions.iter().enumerate() {
+            if i + j > 0 {
+                writeln!(out)?;
+            }
+
+            writeln!(out, "{dialect} {direction}:")?;
+
+            for tag in *tags {
+                let aliases = match tag.aliases {
+                    [] => String::new(),
+                    aliases => format!(" (also {})", aliases.join(", ")),
+                };
+
+                writeln!(
+                    out,
+                    "  {:<22} {:<22} {:?}{aliases}",
+                    tag.bbcode.replace('\n', "\\n"),
+                    tag.markdown.replace('\n', "\\n"),
+                    tag.fidelity,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
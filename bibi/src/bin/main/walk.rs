@@ -0,0 +1,103 @@
+//! Walking of directory trees, finding the files to convert by their names.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A file found in a directory tree.
+pub struct Found {
+    pub path: PathBuf,
+    /// The path of the file from the root of the tree.
+    pub relative: PathBuf,
+}
+
+impl Found {
+    /// A file given by itself, rather than found below a directory.
+    pub fn file(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            relative: path.file_name().map(PathBuf::from).unwrap_or_default(),
+        }
+    }
+}
+
+/// Tells whether `name` matches a glob `pattern`, where `*` matches any run of characters and `?` any single one.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<_> = pattern.chars().collect();
+    let name: Vec<_> = name.chars().collect();
+
+    // where to resume after the last `*`, as positions in the pattern and in the name
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the last `*` take one more character
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Finds every file below `root` whose name matches any of `globs`, in name order, along with the number of files
+/// that don't. Hidden files and directories are left alone, and so are symbolic links to directories.
+pub fn walk(root: &Path, globs: &[String]) -> io::Result<(Vec<Found>, usize)> {
+    let mut found = vec![];
+    let mut skipped = 0;
+
+    walk_dir(root, Path::new(""), globs, &mut found, &mut skipped)?;
+
+    Ok((found, skipped))
+}
+
+fn walk_dir(
+    dir: &Path,
+    relative: &Path,
+    globs: &[String],
+    found: &mut Vec<Found>,
+    skipped: &mut usize,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_name = entry.file_name();
+        let name = file_name.to_string_lossy();
+
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        let relative = relative.join(&file_name);
+
+        if entry.file_type()?.is_dir() {
+            walk_dir(&path, &relative, globs, found, skipped)?;
+        } else if !path.is_file() {
+            continue;
+        } else if globs.iter().any(|glob| matches(glob, &name)) {
+            found.push(Found { path, relative });
+        } else {
+            *skipped += 1;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Re-running conversions whenever their inputs change, e.g. to keep a preview up to date while editing.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// How often the inputs are checked for changes.
+const INTERVAL: Duration = Duration::from_millis(300);
+
+/// When every file at or below the given paths was last modified, so that comparing two snapshots tells whether
+/// any file changed, appeared or went away.
+type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+fn snapshot(paths: &[PathBuf]) -> Snapshot {
+    let mut ret = Snapshot::new();
+
+    for path in paths {
+        add(&mut ret, path);
+    }
+
+    ret
+}
+
+fn add(snapshot: &mut Snapshot, path: &Path) {
+    if !path.is_dir() {
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        snapshot.insert(path.to_owned(), modified);
+
+        return;
+    }
+
+    let entries = fs::read_dir(path).and_then(|entries| entries.collect::<io::Result<Vec<_>>>());
+
+    // a directory that can't be read counts as empty until it can
+    for entry in entries.unwrap_or_default() {
+        if !entry.file_name().to_string_lossy().starts_with('.') {
+            add(snapshot, &entry.path());
+        }
+    }
+}
+
+/// Runs `run`, and then again every time any file at or below `paths` changes, forever. Errors are reported
+/// without stopping, so that the next change can fix them.
+pub fn watch(
+    paths: &[PathBuf],
+    mut run: impl FnMut() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut last = None;
+
+    loop {
+        let current = snapshot(paths);
+
+        if last.as_ref() != Some(&current) {
+            if let Err(err) = run() {
+                eprintln!("Error: {err}");
+            }
+
+            eprintln!("Watching for changes...");
+
+            // outputs may be written next to the inputs, which mustn't trigger another run
+            last = Some(snapshot(paths));
+        }
+
+        thread::sleep(INTERVAL);
+    }
+}
@@ -0,0 +1,330 @@
+//! Reading [`ConvertOptions`] from TOML, so that the conversions of a project can be configured once in a file
+//! rather than on every invocation.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    bbcode::Dialect,
+    error::{Error, Result},
+    options::{
+        CommentPolicy, ConvertOptions, CustomTag, EscapePolicy, HtmlPolicy, LinkStyle,
+        MarkdownTarget, Profile, Smiley, SmileyStyle, UnclosedTags,
+    },
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    profile: Option<String>,
+    dialect: Option<String>,
+    code_lang: Option<String>,
+    inline_code_lang: Option<String>,
+    escaping: Option<Escaping>,
+    comment_tag: Option<String>,
+    html: Option<Html>,
+    target: Option<Target>,
+    max_heading_level: Option<usize>,
+    task_checked: Option<String>,
+    task_unchecked: Option<String>,
+    unclosed: Option<Unclosed>,
+    smiley_style: Option<Smileys>,
+    links: Option<Links>,
+    user_url: Option<String>,
+    link_schemes: Option<Vec<String>>,
+    base_url: Option<String>,
+    nofollow: Option<bool>,
+    #[serde(default)]
+    tags: BTreeMap<String, TagConfig>,
+    #[serde(default)]
+    synonyms: BTreeMap<String, String>,
+    #[serde(default)]
+    smileys: BTreeMap<String, SmileyConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Escaping {
+    Verbatim,
+    Markdown,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Html {
+    Strip,
+    Verbatim,
+    Translate,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Unclosed {
+    Keep,
+    Close,
+    Strip,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Smileys {
+    Keep,
+    Emoji,
+    Shortcode,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Links {
+    Inline,
+    Autolink,
+    Reference,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Target {
+    Plain,
+    Html,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagConfig {
+    #[serde(default)]
+    aliases: Vec<String>,
+    /// An example of the tag, such as those written by `bibi infer`, which only documents it.
+    #[allow(dead_code)]
+    bbcode: Option<String>,
+    markdown: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SmileyConfig {
+    emoji: String,
+    shortcode: String,
+}
+
+fn parse_dialect(name: &str) -> Option<Dialect> {
+    match name.to_lowercase().as_str() {
+        #[cfg(feature = "nerdz")]
+        "nerdz" => Some(Dialect::Nerdz),
+        #[cfg(feature = "phpbb")]
+        "phpbb" => Some(Dialect::Phpbb),
+        _ => None,
+    }
+}
+
+impl ConvertOptions {
+    /// Reads the options from a TOML configuration, leaving whatever it doesn't set to its default, or to that of
+    /// its profile. Every key is optional:
+    ///
+    /// - `profile`, the name of a [`Profile`], whose options the others are applied to;
+    /// - `dialect`, either `nerdz` or `phpbb`;
+    /// - `code_lang` and `inline_code_lang`, the languages of code without one;
+    /// - `escaping`, either `verbatim` or `markdown`;
+    /// - `comment_tag`, the tag Markdown comments and metadata are kept in, see [`CommentPolicy::Tag`];
+    /// - `html`, either `strip`, `verbatim` or `translate`, see [`HtmlPolicy`];
+    /// - `target`, either `plain` or `html`, see [`MarkdownTarget`];
+    /// - `max_heading_level`, the deepest heading level of the generated Markdown, from 1 to 6;
+    /// - `task_checked` and `task_unchecked`, the checkboxes of task list items in BBCode, see [`TaskMarkers`](crate::TaskMarkers);
+    /// - `unclosed`, either `keep`, `close` or `strip`, see [`UnclosedTags`];
+    /// - `smiley_style`, either `keep`, `emoji` or `shortcode`, see [`SmileyStyle`];
+    /// - `links`, either `inline`, `autolink` or `reference`, see [`LinkStyle`];
+    /// - `user_url`, the URL the names of users are appended to, see [`ConvertOptions::user_url`];
+    /// - `link_schemes`, the schemes links and images may use, `base_url`, the URL relative ones are resolved
+    ///   against, and `nofollow`, see [`LinkPolicy`](crate::LinkPolicy);
+    /// - `tags`, a table of [`CustomTag`]s by name, each with its `markdown` template and optionally its `aliases`
+    ///   and a `bbcode` example, which is ignored, replacing those of the profile with the same name;
+    /// - `synonyms`, a table of the names of tags by their synonyms, see [`ConvertOptions::synonyms`];
+    /// - `smileys`, a table of [`Smiley`]s by code, each with its `emoji` and `shortcode`, replacing those with the
+    ///   same code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bibi::{to_markdown_with, ConvertOptions};
+    ///
+    /// let options = ConvertOptions::from_config(
+    ///     r#"
+    ///     user_url = "https://nerdz.eu/"
+    ///
+    ///     [tags.spoiler]
+    ///     markdown = "||{content}||"
+    ///
+    ///     [synonyms]
+    ///     bold = "b"
+    ///     "#,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(
+    ///     to_markdown_with("[user]mcilloni[/user]: [spoiler][bold]it works[/bold][/spoiler]", &options),
+    ///     "[mcilloni](https://nerdz.eu/mcilloni): ||**it works**||"
+    /// );
+    ///
+    /// assert!(ConvertOptions::from_config("dialect = \"markdown\"").is_err());
+    /// ```
+    pub fn from_config(toml: &str) -> Result<Self> {
+        let invalid = |message: String| Error::Config {
+            path: None,
+            message,
+        };
+
+        let config: Config =
+            toml::from_str(toml).map_err(|err| invalid(err.to_string().trim_end().to_owned()))?;
+        let mut ret = match config.profile {
+            Some(name) => Profile::from_name(&name)
+                .ok_or_else(|| invalid(format!("unknown or disabled profile: {name}")))?
+                .options(),
+            None => Self::default(),
+        };
+
+        if let Some(name) = config.dialect {
+            ret.dialect = parse_dialect(&name)
+                .ok_or_else(|| invalid(format!("unknown or disabled dialect: {name}")))?;
+        }
+
+        if let Some(lang) = config.code_lang {
+            ret.code_lang = lang;
+        }
+
+        if let Some(lang) = config.inline_code_lang {
+            ret.inline_code_lang = lang;
+        }
+
+        if let Some(escaping) = config.escaping {
+            ret.escaping = match escaping {
+                Escaping::Verbatim => EscapePolicy::Verbatim,
+                Escaping::Markdown => EscapePolicy::Markdown,
+            };
+        }
+
+        if let Some(name) = config.comment_tag {
+            ret.comments = CommentPolicy::Tag(name);
+        }
+
+        if let Some(html) = config.html {
+            ret.html = match html {
+                Html::Strip => HtmlPolicy::Strip,
+                Html::Verbatim => HtmlPolicy::Verbatim,
+                Html::Translate => HtmlPolicy::Translate,
+            };
+        }
+
+        if let Some(target) = config.target {
+            ret.target = match target {
+                Target::Plain => MarkdownTarget::Plain,
+                Target::Html => MarkdownTarget::Html,
+            };
+        }
+
+        if let Some(level) = config.max_heading_level {
+            if !(1..=6).contains(&level) {
+                return Err(invalid(format!(
+                    "max_heading_level must be between 1 and 6, not {level}"
+                )));
+            }
+
+            ret.max_heading_level = level;
+        }
+
+        for (marker, set) in [
+            (config.task_checked, &mut ret.task_markers.checked),
+            (config.task_unchecked, &mut ret.task_markers.unchecked),
+        ] {
+            match marker {
+                Some(marker) if marker.trim().is_empty() => {
+                    return Err(invalid("task list markers can't be blank".to_owned()));
+                }
+                Some(marker) => *set = marker,
+                None => {}
+            }
+        }
+
+        if let Some(unclosed) = config.unclosed {
+            ret.unclosed = match unclosed {
+                Unclosed::Keep => UnclosedTags::Keep,
+                Unclosed::Close => UnclosedTags::Close,
+                Unclosed::Strip => UnclosedTags::Strip,
+            };
+        }
+
+        if let Some(style) = config.smiley_style {
+            ret.smiley_style = match style {
+                Smileys::Keep => SmileyStyle::Keep,
+                Smileys::Emoji => SmileyStyle::Emoji,
+                Smileys::Shortcode => SmileyStyle::Shortcode,
+            };
+        }
+
+        if let Some(links) = config.links {
+            ret.links = match links {
+                Links::Inline => LinkStyle::Inline,
+                Links::Autolink => LinkStyle::Autolink,
+                Links::Reference => LinkStyle::Reference,
+            };
+        }
+
+        ret.smileys
+            .retain(|smiley| !config.smileys.contains_key(&smiley.code));
+        ret.smileys
+            .extend(config.smileys.into_iter().map(|(code, smiley)| Smiley {
+                code,
+                emoji: smiley.emoji,
+                shortcode: smiley.shortcode,
+            }));
+
+        if config.user_url.is_some() {
+            ret.user_url = config.user_url;
+        }
+
+        if config.link_schemes.is_some() {
+            ret.link_policy.schemes = config.link_schemes;
+        }
+
+        if config.base_url.is_some() {
+            ret.link_policy.base_url = config.base_url;
+        }
+
+        if let Some(nofollow) = config.nofollow {
+            ret.link_policy.nofollow = nofollow;
+        }
+
+        ret.synonyms.extend(config.synonyms);
+
+        ret.custom_tags.retain(|tag| {
+            !config
+                .tags
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case(&tag.name))
+        });
+        ret.custom_tags
+            .extend(config.tags.into_iter().map(|(name, tag)| CustomTag {
+                name,
+                aliases: tag.aliases,
+                markdown: tag.markdown,
+            }));
+
+        Ok(ret)
+    }
+
+    /// Same as [`ConvertOptions::from_config`], but reads the configuration from the file at `path`.
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let with_path = |message: String| Error::Config {
+            path: Some(path.to_owned()),
+            message,
+        };
+
+        let toml = fs::read_to_string(path).map_err(|err| with_path(err.to_string()))?;
+
+        Self::from_config(&toml).map_err(|err| match err {
+            Error::Config { message, .. } => with_path(message),
+            err => err,
+        })
+    }
+}
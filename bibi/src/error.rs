@@ -0,0 +1,142 @@
+use alloc::string::String;
+use core::{fmt, result, str::Utf8Error};
+#[cfg(feature = "std")]
+use std::{error, io, path::PathBuf};
+
+/// The error type returned by the conversion functions.
+///
+/// It can be turned into an [`io::Error`](std::io::Error), so callers that only care about I/O can keep using `?`
+/// inside functions returning [`io::Result`](std::io::Result). Without the `std` feature, the variants referring to
+/// I/O are left out.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use std::io::{self, Write};
+/// use bibi::{dump_bbcode, Error};
+///
+/// struct Broken;
+///
+/// impl Write for Broken {
+///     fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+///         Err(io::ErrorKind::BrokenPipe.into())
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// match dump_bbcode(Broken, "**Hello**") {
+///     Err(Error::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::BrokenPipe),
+///     other => panic!("unexpected result: {other:?}"),
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Writing the output to an [`io::Write`] failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// Writing the output to a [`fmt::Write`] failed.
+    Fmt(fmt::Error),
+    /// The input contains markup that can't be converted, i.e. a tag that is never closed, found by
+    /// [`to_markdown_strict`](crate::to_markdown_strict).
+    Parse {
+        /// The byte offset of the offending markup in the input.
+        offset: usize,
+        /// The name of the tag the error refers to.
+        tag: String,
+    },
+    /// The input is not valid UTF-8.
+    Encoding {
+        /// The byte offset of the first invalid sequence in the input.
+        offset: usize,
+    },
+    /// A configuration file couldn't be read, or is invalid.
+    #[cfg(feature = "std")]
+    Config {
+        /// The path of the file, if it was read from one.
+        path: Option<PathBuf>,
+        /// What's wrong with it.
+        message: String,
+    },
+}
+
+/// A specialized [`Result`](result::Result) type for conversions.
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Error::*;
+
+        match self {
+            #[cfg(feature = "std")]
+            Io(err) => write!(f, "failed to write the output: {err}"),
+            Fmt(err) => write!(f, "failed to write the output: {err}"),
+            Parse { offset, tag } => write!(f, "malformed [{tag}] tag at byte {offset}"),
+            Encoding { offset } => write!(f, "invalid UTF-8 at byte {offset}"),
+            #[cfg(feature = "std")]
+            Config {
+                path: Some(path),
+                message,
+            } => write!(f, "{}: {message}", path.display()),
+            #[cfg(feature = "std")]
+            Config {
+                path: None,
+                message,
+            } => write!(f, "invalid configuration: {message}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use Error::*;
+
+        match self {
+            Io(err) => Some(err),
+            Fmt(err) => Some(err),
+            Parse { .. } | Encoding { .. } | Config { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::Fmt(err)
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(err: Utf8Error) -> Self {
+        Self::Encoding {
+            offset: err.valid_up_to(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        use Error::*;
+
+        match err {
+            Io(err) => err,
+            Fmt(_) => io::Error::other(err),
+            Parse { .. } | Encoding { .. } | Config { .. } => {
+                io::Error::new(io::ErrorKind::InvalidData, err)
+            }
+        }
+    }
+}
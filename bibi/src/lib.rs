@@ -1,3 +1,48 @@
+//! Converts between Markdown and the BBCode of NERDZ and phpBB.
+//!
+//! The stable surface of the crate is made of the items exported from its root and from [`prelude`]. Those taking or
+//! yielding Markdown events, such as [`BbcodeParser`] and [`write_bbcode`], use the ones of
+//! [pulldown-cmark](https://docs.rs/pulldown-cmark/0.9) 0.9, which callers depend on themselves to handle them.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 mod bbcode;
+#[cfg(feature = "config")]
+mod config;
+mod error;
+mod options;
+mod writefmt;
+
+pub mod prelude;
 
-pub use bbcode::{dump_bbcode, dump_markdown};
+#[cfg(feature = "std")]
+pub use bbcode::{
+    check_limits, dump_bbcode, dump_bbcode_fmt, dump_bbcode_fmt_with, dump_bbcode_streaming,
+    dump_bbcode_streaming_with, dump_bbcode_with, dump_markdown, dump_markdown_streaming,
+    dump_markdown_streaming_with, dump_markdown_with, markdown_options, to_bbcode,
+    to_bbcode_audited, to_bbcode_audited_with, to_bbcode_inline, to_bbcode_inline_with,
+    to_bbcode_spans, to_bbcode_spans_with, to_bbcode_with, to_markdown_audited,
+    to_markdown_audited_with, write_bbcode, write_bbcode_fmt, write_bbcode_fmt_with,
+    write_bbcode_with, BbcodeParser, BlockWriter, Converter, LimitExceeded, LimitKind, Limits,
+    SharedWriter, Snapshot, StreamConverter, Substitution,
+};
+pub use bbcode::{
+    convert_with_report, convert_with_report_with, count_tags, count_tags_with, dump_markdown_fmt,
+    dump_markdown_fmt_with, parse_ast, parse_ast_with, supported_tags, to_markdown,
+    to_markdown_inline, to_markdown_inline_with, to_markdown_post, to_markdown_post_with,
+    to_markdown_spans, to_markdown_spans_with, to_markdown_strict, to_markdown_strict_with,
+    to_markdown_with, tokenize, Attribute, Diagnostic, DiagnosticKind, Dialect, Direction, Element,
+    Fidelity, Node, Post, Spans, TagInfo, Token, TokenKind,
+};
+#[cfg(feature = "tokio")]
+pub use bbcode::{
+    dump_bbcode_async, dump_bbcode_async_with, dump_markdown_async, dump_markdown_async_with,
+};
+pub use error::{Error, Result};
+pub use options::{
+    AttributeQuoting, CommentPolicy, ConvertOptions, CustomTag, EmphasisMarker, EscapePolicy,
+    HeadingMap, HeadingStyle, HtmlPolicy, LineEnding, LinkPolicy, LinkStyle, MarkdownTarget,
+    Profile, SignatureDelimiter, Smiley, SmileyStyle, TagCase, TaskMarkers, ThematicBreak,
+    UnclosedTags,
+};
@@ -0,0 +1,1051 @@
+#[cfg(feature = "std")]
+use alloc::format;
+use alloc::{
+    borrow::{Cow, ToOwned},
+    collections::BTreeMap,
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+#[cfg(feature = "std")]
+use crate::bbcode::Limits;
+use crate::bbcode::{Dialect, DEFAULT_ANON_CODELANG, DEFAULT_ANON_ICODELANG};
+
+/// The casing used for tag and attribute names in the generated BBCode.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum TagCase {
+    /// Always emit lowercase tags, e.g. `[b]`.
+    Lower,
+    /// Always emit uppercase tags, e.g. `[B]`, as some older boards expect.
+    Upper,
+    /// Keep the casing the dialect normally uses for its tags (lowercase for NERDZ).
+    #[default]
+    Preserve,
+}
+
+impl TagCase {
+    /// Applies this casing to the given tag or attribute name.
+    pub fn apply(self, name: &str) -> Cow<'_, str> {
+        use TagCase::*;
+
+        match self {
+            Lower => name.to_lowercase().into(),
+            Upper => name.to_uppercase().into(),
+            Preserve => name.into(),
+        }
+    }
+}
+
+/// How attribute values are quoted in the generated BBCode, e.g. `[url=P]` vs `[url="P"]`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum AttributeQuoting {
+    /// Never quote values.
+    Never,
+    /// Always quote values.
+    Always,
+    /// Only quote values containing characters that would otherwise break the tag, such as spaces or `]`.
+    WhenNeeded,
+    /// Quote values the way the dialect usually does (for NERDZ, `[url=P]` but `[list start="N"]`).
+    #[default]
+    Preserve,
+}
+
+impl AttributeQuoting {
+    /// Applies this quoting style to the given attribute value. `quoted_by_default` tells whether the dialect
+    /// normally quotes this specific attribute.
+    #[cfg(feature = "std")]
+    pub(crate) fn apply(self, value: &str, quoted_by_default: bool) -> Cow<'_, str> {
+        use AttributeQuoting::*;
+
+        let quote = match self {
+            Never => false,
+            Always => true,
+            WhenNeeded => {
+                value.is_empty()
+                    || value.contains(|c: char| c.is_whitespace() || "\"[]=".contains(c))
+            }
+            Preserve => quoted_by_default,
+        };
+
+        if quote {
+            format!("\"{value}\"").into()
+        } else {
+            value.into()
+        }
+    }
+}
+
+/// How a heading level is represented in BBCode.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum HeadingStyle {
+    /// `[big]P[/big]`.
+    #[default]
+    Big,
+    /// `[big][b]P[/b][/big]`.
+    BigBold,
+    /// `[b]P[/b]`. Bold lines can't be told apart from regular bold text, so they are never turned back into
+    /// headings.
+    Bold,
+    /// `[size=N]P[/size]`, for dialects supporting it.
+    Size(u8),
+    /// Heading formatting is dropped altogether, leaving just the text.
+    Plain,
+}
+
+/// Maps each of the six Markdown heading levels to a [`HeadingStyle`].
+///
+/// When converting to Markdown, a line entirely wrapped in the style of a level becomes a heading of that level;
+/// if more levels share the same style, the highest one wins. `[big]` lines are rendered as bold text if no
+/// level uses [`HeadingStyle::Big`].
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(all(feature = "std", feature = "nerdz"))] {
+/// use std::{error::Error, str};
+/// use bibi::{dump_bbcode_with, dump_markdown_with, ConvertOptions, HeadingMap};
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     let options = ConvertOptions {
+///         headings: HeadingMap::hierarchical(),
+///         ..Default::default()
+///     };
+///
+///     let mut bbcode = Vec::new();
+///     dump_bbcode_with(&mut bbcode, "# Title\n\n## Section", &options)?;
+///
+///     let bbcode = str::from_utf8(&bbcode)?;
+///     assert_eq!(bbcode, "[big]Title[/big]\n\n[big][b]Section[/b][/big]\n\n");
+///
+///     let mut markdown = Vec::new();
+///     dump_markdown_with(&mut markdown, bbcode, &options)?;
+///
+///     assert_eq!(str::from_utf8(&markdown)?, "# Title\n\n## Section\n\n");
+///
+///     Ok(())
+/// }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct HeadingMap([HeadingStyle; 6]);
+
+impl HeadingMap {
+    /// Creates a map from the styles of levels 1 to 6.
+    pub const fn new(levels: [HeadingStyle; 6]) -> Self {
+        Self(levels)
+    }
+
+    /// Uses the same style for every level.
+    pub const fn uniform(style: HeadingStyle) -> Self {
+        Self([style; 6])
+    }
+
+    /// `[big]` for level 1, `[big][b]` for level 2 and bold text for everything else.
+    pub const fn hierarchical() -> Self {
+        use HeadingStyle::*;
+
+        Self([Big, BigBold, Bold, Bold, Bold, Bold])
+    }
+
+    /// The style of the given level, starting from 1. Levels past 6 use the style of level 6.
+    pub fn style(&self, level: usize) -> HeadingStyle {
+        self.0[level.clamp(1, 6) - 1]
+    }
+
+    /// The highest level using the given style, if any.
+    pub fn level_of(&self, style: HeadingStyle) -> Option<usize> {
+        self.0.iter().position(|&s| s == style).map(|pos| pos + 1)
+    }
+}
+
+impl Default for HeadingMap {
+    fn default() -> Self {
+        Self::uniform(HeadingStyle::Big)
+    }
+}
+
+/// The character used to delimit emphasis and strong emphasis in the generated Markdown.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum EmphasisMarker {
+    /// `*emphasis*` and `**strong**`.
+    #[default]
+    Asterisk,
+    /// `_emphasis_` and `__strong__`.
+    Underscore,
+}
+
+impl EmphasisMarker {
+    pub(crate) const fn emphasis(self) -> &'static str {
+        use EmphasisMarker::*;
+
+        match self {
+            Asterisk => "*",
+            Underscore => "_",
+        }
+    }
+
+    pub(crate) const fn strong(self) -> &'static str {
+        use EmphasisMarker::*;
+
+        match self {
+            Asterisk => "**",
+            Underscore => "__",
+        }
+    }
+}
+
+/// How horizontal rules are written in the generated Markdown.
+///
+/// When reading BBCode, a line holding nothing but `[hr]`, `[hr/]` or four or more dashes is a rule.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{to_markdown_with, ConvertOptions, ThematicBreak};
+///
+/// let options = ConvertOptions {
+///     thematic_break: ThematicBreak::Asterisks,
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(to_markdown_with("above\n[hr/]\nbelow", &options), "above\n***\nbelow");
+///
+/// // dashes right below some text would turn it into a heading, so they get a blank line
+/// assert_eq!(bibi::to_markdown("above\n----------\nbelow"), "above\n\n---\nbelow");
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ThematicBreak {
+    /// `---`.
+    #[default]
+    Dashes,
+    /// `***`.
+    Asterisks,
+    /// `___`.
+    Underscores,
+}
+
+impl ThematicBreak {
+    pub(crate) const fn marker(self) -> &'static str {
+        use ThematicBreak::*;
+
+        match self {
+            Dashes => "---",
+            Asterisks => "***",
+            Underscores => "___",
+        }
+    }
+}
+
+/// Which lines separate the body of a post from the signature its author appended to it, for
+/// [`to_markdown_post`](crate::to_markdown_post).
+///
+/// Only the last delimiter of a post counts, and only if it's not within a quote, a list or a code block. A
+/// delimiter with nothing but whitespace after it is not a signature delimiter, and a rule is converted as usual.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum SignatureDelimiter {
+    /// Posts have no signature.
+    #[default]
+    Disabled,
+    /// A line holding nothing but a horizontal rule, i.e. `[hr]`, `[hr/]` or a long line of dashes.
+    Rule,
+    /// The Usenet convention, i.e. a line holding nothing but `-- `. The trailing space is optional, as plenty of
+    /// editors and forums strip it anyway.
+    Dashes,
+    /// Either of the above.
+    Any,
+}
+
+impl SignatureDelimiter {
+    pub(crate) const fn allows_rule(self) -> bool {
+        matches!(self, Self::Rule | Self::Any)
+    }
+
+    pub(crate) const fn allows_dashes(self) -> bool {
+        matches!(self, Self::Dashes | Self::Any)
+    }
+}
+
+/// How links are written.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LinkStyle {
+    /// Always use the explicit form, i.e. `[url=P]Q[/url]` and `[Q](P)`.
+    #[default]
+    Inline,
+    /// Links whose text is the URL itself are written in their short form, i.e. `[url]P[/url]` and `<P>`.
+    Autolink,
+    /// Links are written as references in the generated Markdown, i.e. `[Q][1]`, whose destinations are listed at
+    /// its end, e.g. `[1]: P`, so that long posts full of links stay readable. Links without text and images are
+    /// left inline, and the generated BBCode is the same as with [`LinkStyle::Inline`].
+    ///
+    /// ```
+    /// use bibi::{to_markdown_with, ConvertOptions, LinkStyle};
+    ///
+    /// let options = ConvertOptions {
+    ///     links: LinkStyle::Reference,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert_eq!(
+    ///     to_markdown_with(
+    ///         "[url=https://nerdz.eu]NERDZ[/url] and [url=https://nerdz.eu]again[/url]\n[code]see [this](x)[/code]",
+    ///         &options,
+    ///     ),
+    ///     "[NERDZ][1] and [again][1]\n```\nsee [this](x)\n```\n\n[1]: https://nerdz.eu\n"
+    /// );
+    /// ```
+    Reference,
+}
+
+/// Whether text that isn't markup should be escaped in the generated output.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum EscapePolicy {
+    /// Copy text as-is, even if it contains characters the target format considers special.
+    #[default]
+    Verbatim,
+    /// Backslash-escape characters Markdown would otherwise interpret (`*`, `_`, `` ` ``, `<`, ...).
+    /// This only affects the generation of Markdown, BBCode has no escaping mechanism.
+    Markdown,
+}
+
+/// What becomes of the comments (`<!-- ... -->`) and of the metadata block of the Markdown, i.e. the YAML front
+/// matter between `---` lines at its very start, in the generated BBCode. Any other HTML is up to [`HtmlPolicy`].
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// # fn main() -> bibi::Result<()> {
+/// use bibi::{to_bbcode_with, CommentPolicy, ConvertOptions};
+///
+/// let markdown = "---\ntitle: Hi\n---\n\n**Hi** <!-- TODO: more -->";
+///
+/// assert_eq!(to_bbcode_with(markdown, &ConvertOptions::default())?, "[b]Hi[/b] \n\n");
+///
+/// let options = ConvertOptions {
+///     comments: CommentPolicy::Tag("comment".to_owned()),
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     to_bbcode_with(markdown, &options)?,
+///     "[comment]title: Hi[/comment]\n\n[b]Hi[/b] [comment]TODO: more[/comment]\n\n"
+/// );
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub enum CommentPolicy {
+    /// Leave them out.
+    #[default]
+    Strip,
+    /// Keep them within the tag with the given name, e.g. `[comment]P[/comment]` for `comment`, for dialects or
+    /// boards that hide it.
+    Tag(String),
+}
+
+/// What becomes of the HTML of the Markdown, other than comments, in the generated BBCode.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// # fn main() -> bibi::Result<()> {
+/// use bibi::{to_bbcode_with, ConvertOptions, HtmlPolicy};
+///
+/// let markdown = "Hi<br><b>there</b>, see <a href=\"https://nerdz.eu/?a=1&amp;b=2\">NERDZ</a>";
+/// let convert = |html| {
+///     let options = ConvertOptions {
+///         html,
+///         ..Default::default()
+///     };
+///
+///     to_bbcode_with(markdown, &options)
+/// };
+///
+/// assert_eq!(convert(HtmlPolicy::Strip)?, "Hithere, see NERDZ\n\n");
+/// assert_eq!(convert(HtmlPolicy::Verbatim)?, format!("{markdown}\n\n"));
+/// assert_eq!(
+///     convert(HtmlPolicy::Translate)?,
+///     "Hi\n\n[b]there[/b], see [url=https://nerdz.eu/?a=1&b=2]NERDZ[/url]\n\n"
+/// );
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum HtmlPolicy {
+    /// Leave out the tags and whole blocks of HTML, keeping only the text between inline tags.
+    #[default]
+    Strip,
+    /// Copy it as-is, for boards that render HTML within BBCode. Untrusted content, whose
+    /// [`LinkPolicy::schemes`] are restricted, is translated instead.
+    Verbatim,
+    /// Turn `<b>`, `<i>`, `<a href>`, `<img src>` and `<br>` into their BBCode counterparts, leaving out any other
+    /// tag but keeping the text within blocks of HTML.
+    Translate,
+}
+
+/// How the checkboxes of task list items are written in the generated BBCode, which has none of its own, and
+/// recognized at the start of list items when reading BBCode back into Markdown task lists.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use bibi::{to_bbcode_with, to_markdown_with, ConvertOptions, TaskMarkers};
+///
+/// # fn main() -> bibi::Result<()> {
+/// let markdown = "- [x] write\n- [ ] test\n";
+///
+/// assert_eq!(
+///     bibi::to_bbcode(markdown)?,
+///     "[list]\n[*] [x] write\n[*] [ ] test\n[/list]\n"
+/// );
+///
+/// let options = ConvertOptions {
+///     task_markers: TaskMarkers {
+///         checked: "☑".to_owned(),
+///         unchecked: "☐".to_owned(),
+///     },
+///     ..Default::default()
+/// };
+///
+/// let bbcode = to_bbcode_with(markdown, &options)?;
+/// assert_eq!(bbcode, "[list]\n[*] ☑ write\n[*] ☐ test\n[/list]\n");
+///
+/// assert_eq!(to_markdown_with(&bbcode, &options), "- [x] write\n- [ ] test\n\n");
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TaskMarkers {
+    /// The marker of done items, `[x]` by default.
+    pub checked: String,
+    /// The marker of items still to do, `[ ]` by default.
+    pub unchecked: String,
+}
+
+impl Default for TaskMarkers {
+    fn default() -> Self {
+        Self {
+            checked: "[x]".to_owned(),
+            unchecked: "[ ]".to_owned(),
+        }
+    }
+}
+
+impl TaskMarkers {
+    /// The marker of an item that is done or not.
+    pub(crate) fn marker(&self, checked: bool) -> &str {
+        match checked {
+            true => &self.checked,
+            false => &self.unchecked,
+        }
+    }
+
+    /// Tells whether `item` starts with either marker, followed by whitespace, returning whether it's checked and
+    /// what follows the marker and its whitespace.
+    pub(crate) fn strip<'i>(&self, item: &'i str) -> Option<(bool, &'i str)> {
+        [true, false].into_iter().find_map(|checked| {
+            let rest = item.strip_prefix(self.marker(checked))?;
+
+            rest.starts_with([' ', '\t'])
+                .then(|| (checked, rest.trim_start_matches([' ', '\t'])))
+        })
+    }
+}
+
+/// What becomes of the tags that are opened but never closed in the BBCode read, such as `[b]` in `[b]oops`. Code
+/// tags, whose content can't be told apart from what follows it, and the tags the dialect doesn't know are always
+/// left as they are.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "nerdz")] {
+/// use bibi::{to_markdown_with, ConvertOptions, UnclosedTags};
+///
+/// let bbcode = "[b]oops, [cur]this[/cur] is bold\n\nbut not this";
+///
+/// assert_eq!(bibi::to_markdown(bbcode), "[b]oops, *this* is bold\n\nbut not this");
+///
+/// let close = ConvertOptions {
+///     unclosed: UnclosedTags::Close,
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     to_markdown_with(bbcode, &close),
+///     "**oops, *this* is bold**\n\nbut not this"
+/// );
+///
+/// let strip = ConvertOptions {
+///     unclosed: UnclosedTags::Strip,
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     to_markdown_with(bbcode, &strip),
+///     "oops, *this* is bold\n\nbut not this"
+/// );
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum UnclosedTags {
+    /// Leave them as they are, as text.
+    #[default]
+    Keep,
+    /// Close them where their line ends, as no inline tag can span lines, or where the tag they're within is
+    /// closed, whichever comes first. Quotes and lists, which may span several lines, are closed where the input
+    /// ends instead, if nothing encloses them.
+    Close,
+    /// Remove them, keeping their content.
+    Strip,
+}
+
+/// What the smileys of the BBCode read, such as `:D` or `<3`, become in the generated Markdown, see
+/// [`ConvertOptions::smileys`]. Unless they're kept, the emoji and shortcodes of the Markdown read become smileys
+/// again in the generated BBCode, whichever the style.
+///
+/// Smileys are only recognized on their own, i.e. between spaces, tags or punctuation, so that the `:/` of a URL
+/// or the `xD` of a word are left alone, and never within code.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// # fn main() -> bibi::Result<()> {
+/// use bibi::{to_bbcode_with, to_markdown_with, ConvertOptions, Smiley, SmileyStyle};
+///
+/// let mut options = ConvertOptions {
+///     smiley_style: SmileyStyle::Emoji,
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(to_markdown_with("[b]Hi[/b] :D <3", &options), "**Hi** 😀 ❤️");
+/// assert_eq!(to_bbcode_with("**Hi** 😀 ❤", &options)?, "[b]Hi[/b] :D <3\n\n");
+///
+/// options.smiley_style = SmileyStyle::Shortcode;
+/// options.smileys.push(Smiley {
+///     code: "[emo]facepalm[/emo]".to_owned(),
+///     emoji: "🤦".to_owned(),
+///     shortcode: "facepalm".to_owned(),
+/// });
+///
+/// assert_eq!(
+///     to_markdown_with("[emo]facepalm[/emo] https://nerdz.eu/:D", &options),
+///     ":facepalm: https://nerdz.eu/:D"
+/// );
+/// assert_eq!(to_bbcode_with("sure :grinning:", &options)?, "sure :D\n\n");
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum SmileyStyle {
+    /// Leave smileys as they are, and so emoji and shortcodes.
+    #[default]
+    Keep,
+    /// Emoji, e.g. 😀 for `:D`.
+    Emoji,
+    /// Shortcodes, e.g. `:grinning:` for `:D`, for renderers that turn them into emoji, like GitHub's or
+    /// Discord's.
+    Shortcode,
+}
+
+/// A smiley and what it stands for, see [`ConvertOptions::smileys`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Smiley {
+    /// The smiley as written in BBCode, e.g. `:D`, which can also be made of tags, e.g. `[emo]facepalm[/emo]`.
+    pub code: String,
+    /// The emoji the smiley stands for, e.g. 😀.
+    pub emoji: String,
+    /// The shortcode of the emoji, without colons, e.g. `grinning`.
+    pub shortcode: String,
+}
+
+/// The smileys of NERDZ, along with their emoji and shortcodes. The first smiley of an emoji is the one it becomes.
+const NERDZ_SMILEYS: &[(&str, &str, &str)] = &[
+    (":)", "🙂", "slightly_smiling_face"),
+    (":(", "🙁", "slightly_frowning_face"),
+    (":D", "😀", "grinning"),
+    (";)", "😉", "wink"),
+    (":P", "😛", "stuck_out_tongue"),
+    (":O", "😮", "open_mouth"),
+    (":'(", "😢", "cry"),
+    (":|", "😐", "neutral_face"),
+    (":S", "😕", "confused"),
+    (":@", "😠", "angry"),
+    ("xD", "😆", "laughing"),
+    ("^^", "😊", "blush"),
+    ("<3", "❤️", "heart"),
+];
+
+impl Smiley {
+    /// The smileys of NERDZ, which [`ConvertOptions::smileys`] defaults to.
+    pub fn nerdz() -> Vec<Smiley> {
+        NERDZ_SMILEYS
+            .iter()
+            .map(|&(code, emoji, shortcode)| Smiley {
+                code: code.to_owned(),
+                emoji: emoji.to_owned(),
+                shortcode: shortcode.to_owned(),
+            })
+            .collect()
+    }
+}
+
+/// What links and images may point to, so that BBCode or Markdown from untrusted sources can be converted for
+/// display without turning `[url]javascript:alert(1)[/url]` into a working link. It applies to `[url]` and `[img]`
+/// in the BBCode read, and to the links, images and translated HTML of the Markdown read, see
+/// [`LinkPolicy::apply`].
+///
+/// The [`Default`] value allows anything, as the conversion always did.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// # fn main() -> bibi::Result<()> {
+/// use bibi::{to_bbcode_with, to_markdown_with, ConvertOptions, LinkPolicy};
+///
+/// let options = ConvertOptions {
+///     link_policy: LinkPolicy {
+///         base_url: Some("https://nerdz.eu/".to_owned()),
+///         ..LinkPolicy::untrusted()
+///     },
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     to_markdown_with("[url=javascript:alert(1)]win[/url] [url=/rules]rules[/url]", &options),
+///     "win [rules](https://nerdz.eu/rules)"
+/// );
+/// assert_eq!(
+///     to_bbcode_with("[win](JavaScript:alert(1)) ![](data:image/png;base64,AAAA)", &options)?,
+///     "win \n\n"
+/// );
+/// # Ok(())
+/// # }
+/// # main().unwrap() }
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LinkPolicy {
+    /// The schemes links and images may use, e.g. `https`, regardless of case, or `None` to allow any. Those using
+    /// any other scheme are dropped, and so are those whose scheme can't be read, while links without a scheme are
+    /// relative, and always allowed.
+    ///
+    /// Content restricted to some schemes is untrusted, so raw HTML is never let through it either: `<` and `&` are
+    /// escaped in the text of the BBCode read, the HTML of the Markdown rendered by
+    /// [`BbcodeParser`](crate::BbcodeParser) is shown as text, and [`HtmlPolicy::Verbatim`] translates the HTML of
+    /// the Markdown converted to BBCode like [`HtmlPolicy::Translate`] does.
+    pub schemes: Option<Vec<String>>,
+
+    /// The URL relative links and images are resolved against, if any, e.g. `https://nerdz.eu/`.
+    pub base_url: Option<String>,
+
+    /// Whether the links of the HTML rendered from [`BbcodeParser`](crate::BbcodeParser) are marked with
+    /// `rel="nofollow"`, so that search engines don't follow them.
+    pub nofollow: bool,
+}
+
+impl LinkPolicy {
+    /// A policy suiting untrusted content, which only allows links to `http`, `https` and `mailto`.
+    pub fn untrusted() -> Self {
+        Self {
+            schemes: Some(["http", "https", "mailto"].map(str::to_owned).into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// What the renderer of the generated Markdown accepts besides Markdown itself, which tells what becomes of the
+/// tags Markdown has no syntax for.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{to_markdown_with, ConvertOptions, MarkdownTarget};
+///
+/// let bbcode = "[center][u]Rules[/u][/center]\n[color=red]Be nice[/color]";
+///
+/// assert_eq!(to_markdown_with(bbcode, &ConvertOptions::default()), bbcode);
+///
+/// let options = ConvertOptions {
+///     target: MarkdownTarget::Html,
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     to_markdown_with(bbcode, &options),
+///     "<div align=\"center\"><ins>Rules</ins></div>\n<span style=\"color: red\">Be nice</span>"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum MarkdownTarget {
+    /// Markdown alone, so those tags are left as-is.
+    #[default]
+    Plain,
+    /// Markdown with inline HTML, e.g. on GitHub, so those tags become the HTML closest to them, such as `<ins>` for
+    /// `[u]`, `<span style="color: Q">` for `[color=Q]` or `<div align="center">` for `[center]`. Renderers that
+    /// sanitize HTML may still drop part of it, like the styles, but never the content.
+    Html,
+}
+
+/// The line terminator used in the generated output.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LineEnding {
+    /// Unix-style `\n`.
+    #[default]
+    Lf,
+    /// Windows-style `\r\n`.
+    Crlf,
+    /// Use whatever the input uses, defaulting to `\n` if the input has no line breaks at all.
+    MatchInput,
+}
+
+impl LineEnding {
+    /// Returns the actual terminator to use when converting the given input.
+    pub(crate) fn resolve(self, input: &str) -> &'static str {
+        use LineEnding::*;
+
+        match self {
+            Lf => "\n",
+            Crlf => "\r\n",
+            MatchInput => match input.find('\n') {
+                Some(pos) if input[..pos].ends_with('\r') => "\r\n",
+                _ => "\n",
+            },
+        }
+    }
+}
+
+/// A tag the dialect doesn't know, converted to Markdown by filling in a template.
+///
+/// The template is copied as-is, except for `{content}`, which stands for the content of the tag, converted as
+/// usual, and `{value}`, which stands for its value, if any, e.g. `red` in `[color=red]`. The content is never
+/// dropped: if the template has no `{content}`, it comes right after it.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{to_markdown_with, ConvertOptions, CustomTag};
+///
+/// let options = ConvertOptions {
+///     custom_tags: vec![CustomTag {
+///         name: "spoiler".to_owned(),
+///         aliases: vec!["hide".to_owned()],
+///         markdown: "<details><summary>{value}</summary>{content}</details>".to_owned(),
+///     }],
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     to_markdown_with("[spoiler=Ending][b]he dies[/b][/spoiler]", &options),
+///     "<details><summary>Ending</summary>**he dies**</details>"
+/// );
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CustomTag {
+    /// The name of the tag, e.g. `spoiler` for `[spoiler]`. Tag names are case-insensitive.
+    pub name: String,
+    /// Other names for the same tag.
+    pub aliases: Vec<String>,
+    /// The template the tag becomes.
+    pub markdown: String,
+}
+
+/// Knobs controlling how a conversion is performed, in either direction.
+///
+/// The [`Default`] value reproduces the behaviour of the plain [`dump_bbcode`](crate::dump_bbcode) and
+/// [`dump_markdown`](crate::dump_markdown) functions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConvertOptions {
+    /// The BBCode dialect to read or write.
+    pub dialect: Dialect,
+
+    /// The casing of the tags written by the BBCode writer.
+    pub tag_case: TagCase,
+
+    /// How attribute values are quoted by the BBCode writer.
+    pub attr_quoting: AttributeQuoting,
+
+    /// The language used for code blocks that don't specify one, i.e. `[code=code]`.
+    pub code_lang: String,
+
+    /// The language used for inline code, i.e. `[c=inline]`.
+    pub inline_code_lang: String,
+
+    /// The line terminator used in the output.
+    pub line_ending: LineEnding,
+
+    /// How each heading level is converted.
+    pub headings: HeadingMap,
+
+    /// The deepest heading level used by the generated Markdown, from 1 to 6, for renderers that don't support
+    /// them all. Deeper headings become headings of this level.
+    pub max_heading_level: usize,
+
+    /// The delimiter used for emphasis (`[i]`, `[cur]`) in the generated Markdown.
+    pub emphasis_marker: EmphasisMarker,
+
+    /// The delimiter used for strong emphasis (`[b]`) in the generated Markdown.
+    pub strong_marker: EmphasisMarker,
+
+    /// How horizontal rules (`[hr]`) are written in the generated Markdown.
+    pub thematic_break: ThematicBreak,
+
+    /// Which lines start the signature of a post, see [`to_markdown_post`](crate::to_markdown_post).
+    pub signature: SignatureDelimiter,
+
+    /// How links are written.
+    pub links: LinkStyle,
+
+    /// Whether plain text should be escaped.
+    pub escaping: EscapePolicy,
+
+    /// What becomes of Markdown comments and metadata blocks in the generated BBCode.
+    pub comments: CommentPolicy,
+
+    /// What becomes of any other HTML of the Markdown in the generated BBCode.
+    pub html: HtmlPolicy,
+
+    /// What the renderer of the generated Markdown accepts, which tells whether the tags Markdown has no syntax
+    /// for become HTML.
+    pub target: MarkdownTarget,
+
+    /// How the checkboxes of task list items are written in the generated BBCode and recognized in the BBCode read.
+    pub task_markers: TaskMarkers,
+
+    /// What becomes of the tags that are opened but never closed in the BBCode read.
+    pub unclosed: UnclosedTags,
+
+    /// What the smileys of the BBCode read become in the generated Markdown, and whether emoji and shortcodes
+    /// become smileys in the generated BBCode.
+    pub smiley_style: SmileyStyle,
+
+    /// The smileys known, [`Smiley::nerdz`] by default. Those with the longest code are matched first.
+    pub smileys: Vec<Smiley>,
+
+    /// What links and images may point to.
+    pub link_policy: LinkPolicy,
+
+    /// Whether the generated Markdown marks every tag that couldn't be converted with an HTML comment right before
+    /// it, e.g. `<!-- bibi: unknown tag [color=red] left as-is -->`, so that the losses of a migration are easy to
+    /// find and fix by hand. Conversions returning spans or audits ignore it, since their offsets refer to the
+    /// Markdown without comments.
+    ///
+    /// ```
+    /// use bibi::{to_markdown_with, ConvertOptions};
+    ///
+    /// let options = ConvertOptions {
+    ///     annotate_losses: true,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert_eq!(
+    ///     to_markdown_with("[b]Hi[/b] [spoiler]boo[/spoiler]", &options),
+    ///     "**Hi** <!-- bibi: unknown tag [spoiler] left as-is -->[spoiler]boo[/spoiler]"
+    /// );
+    /// ```
+    pub annotate_losses: bool,
+
+    /// Tags the dialect doesn't know, converted to Markdown as their templates tell. They have no effect on the
+    /// generated BBCode.
+    pub custom_tags: Vec<CustomTag>,
+
+    /// The URL the names of users are appended to, if `[user]P[/user]` is to become a link to the profile of `P`
+    /// in the generated Markdown, e.g. `https://nerdz.eu/`. Otherwise, `[user]` is an unknown tag.
+    pub user_url: Option<String>,
+
+    /// Other names for tags, mapped to the name of the tag they stand for, e.g. `strike` to `del`, so that BBCode
+    /// written for a slightly different dialect is read as if it used the names of this one. Synonyms are
+    /// case-insensitive and can stand for custom tags too, but not for code tags, whose content is never read.
+    ///
+    /// ```
+    /// # #[cfg(feature = "nerdz")] {
+    /// use bibi::{to_markdown_with, ConvertOptions};
+    ///
+    /// let options = ConvertOptions {
+    ///     synonyms: [("strike", "del"), ("em", "i")]
+    ///         .map(|(synonym, name)| (synonym.to_owned(), name.to_owned()))
+    ///         .into(),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert_eq!(
+    ///     to_markdown_with("[STRIKE]old[/strike] [em]new[/em]", &options),
+    ///     "~~old~~ *new*"
+    /// );
+    /// # }
+    /// ```
+    pub synonyms: BTreeMap<String, String>,
+}
+
+impl ConvertOptions {
+    /// Returns these options with [`LineEnding::MatchInput`] resolved against the given input, for conversions that
+    /// only see part of it at a time but must agree on the line terminator.
+    pub(crate) fn with_line_ending_of(&self, input: &str) -> Cow<'_, Self> {
+        if self.line_ending != LineEnding::MatchInput {
+            return Cow::Borrowed(self);
+        }
+
+        let line_ending = match self.line_ending.resolve(input) {
+            "\r\n" => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        };
+
+        Cow::Owned(Self {
+            line_ending,
+            ..self.clone()
+        })
+    }
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            dialect: Dialect::default(),
+            tag_case: TagCase::default(),
+            attr_quoting: AttributeQuoting::default(),
+            code_lang: DEFAULT_ANON_CODELANG.to_owned(),
+            inline_code_lang: DEFAULT_ANON_ICODELANG.to_owned(),
+            line_ending: LineEnding::default(),
+            headings: HeadingMap::default(),
+            max_heading_level: 6,
+            emphasis_marker: EmphasisMarker::default(),
+            strong_marker: EmphasisMarker::default(),
+            thematic_break: ThematicBreak::default(),
+            signature: SignatureDelimiter::default(),
+            links: LinkStyle::default(),
+            escaping: EscapePolicy::default(),
+            comments: CommentPolicy::default(),
+            html: HtmlPolicy::default(),
+            target: MarkdownTarget::default(),
+            task_markers: TaskMarkers::default(),
+            unclosed: UnclosedTags::default(),
+            smiley_style: SmileyStyle::default(),
+            smileys: Smiley::nerdz(),
+            link_policy: LinkPolicy::default(),
+            annotate_losses: false,
+            custom_tags: vec![],
+            user_url: None,
+            synonyms: BTreeMap::new(),
+        }
+    }
+}
+
+/// A platform the converted text is meant for, bundling the options that suit what it renders, so that they don't
+/// have to be picked one by one. Profiles are a starting point: their options can be tweaked like any other.
+///
+/// # Examples
+///
+/// ```
+/// use bibi::{to_markdown_with, Profile};
+///
+/// let bbcode = "[u]Ending[/u]: [spoiler]he dies[/spoiler]";
+///
+/// let github = Profile::Github.options();
+/// assert_eq!(
+///     to_markdown_with(bbcode, &github),
+///     "<ins>Ending</ins>: <details><summary>Spoiler</summary>he dies</details>"
+/// );
+///
+/// let discord = Profile::from_name("discord").unwrap().options();
+/// assert_eq!(to_markdown_with(bbcode, &discord), "__Ending__: ||he dies||");
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Profile {
+    /// Markdown alone, as any CommonMark renderer shows it, so tags Markdown has no syntax for are left as-is.
+    Plain,
+    /// GitHub Flavored Markdown, which accepts inline HTML, see [`MarkdownTarget::Html`].
+    Github,
+    /// Discord, whose Markdown has no HTML and only three heading levels, but has syntax of its own for underlines
+    /// (`__P__`) and spoilers (`||P||`).
+    Discord,
+    /// NERDZ, i.e. its BBCode dialect and the default options.
+    #[cfg(feature = "nerdz")]
+    Nerdz,
+}
+
+impl Profile {
+    /// Every profile.
+    pub const ALL: &'static [Profile] = &[
+        Profile::Plain,
+        Profile::Github,
+        Profile::Discord,
+        #[cfg(feature = "nerdz")]
+        Profile::Nerdz,
+    ];
+
+    /// The name of the profile, in lowercase, e.g. `github`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Profile::Plain => "plain",
+            Profile::Github => "github",
+            Profile::Discord => "discord",
+            #[cfg(feature = "nerdz")]
+            Profile::Nerdz => "nerdz",
+        }
+    }
+
+    /// The profile with the given name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|profile| profile.name().eq_ignore_ascii_case(name))
+    }
+
+    /// What the platform accepts at most, as far as it's known, to be checked with
+    /// [`check_limits`](crate::check_limits).
+    #[cfg(feature = "std")]
+    pub fn limits(self) -> Limits {
+        match self {
+            Profile::Github => Limits {
+                post_length: Some(65536),
+                ..Default::default()
+            },
+            Profile::Discord => Limits {
+                post_length: Some(2000),
+                quote_depth: Some(1),
+                ..Default::default()
+            },
+            _ => Limits::default(),
+        }
+    }
+
+    /// The options of the profile.
+    pub fn options(self) -> ConvertOptions {
+        let template = |name: &str, markdown: &str| CustomTag {
+            name: name.to_owned(),
+            aliases: vec![],
+            markdown: markdown.to_owned(),
+        };
+
+        match self {
+            Profile::Plain => ConvertOptions::default(),
+            Profile::Github => ConvertOptions {
+                target: MarkdownTarget::Html,
+                ..Default::default()
+            },
+            Profile::Discord => ConvertOptions {
+                max_heading_level: 3,
+                custom_tags: vec![
+                    template("u", "__{content}__"),
+                    template("spoiler", "||{content}||"),
+                ],
+                ..Default::default()
+            },
+            #[cfg(feature = "nerdz")]
+            Profile::Nerdz => ConvertOptions {
+                dialect: Dialect::Nerdz,
+                ..Default::default()
+            },
+        }
+    }
+}
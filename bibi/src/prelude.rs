@@ -0,0 +1,38 @@
+//! The types most conversions need, to be glob-imported at once.
+//!
+//! Everything here is part of the stable surface of the crate: new items may be added in minor releases, but
+//! none is removed or changes meaning without a major one. Functions are left out, and so is [`Result`](crate::Result),
+//! which would shadow the one of the standard library, so that a glob import never clashes with the names of the
+//! caller's own.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use bibi::prelude::*;
+//!
+//! fn convert(converter: &Converter, post: &str) -> bibi::Result<String> {
+//!     let (markdown, diagnostics) = bibi::convert_with_report_with(post, converter.options());
+//!
+//!     for Diagnostic { offset, tag, kind } in diagnostics {
+//!         if kind == DiagnosticKind::UnclosedTag {
+//!             return Err(Error::Parse { offset, tag });
+//!         }
+//!     }
+//!
+//!     Ok(markdown)
+//! }
+//!
+//! let converter = Converter::new(ConvertOptions::default());
+//!
+//! assert_eq!(convert(&converter, "[b]Hi[/b]").unwrap(), "**Hi**");
+//! assert!(convert(&converter, "[b]Hi").is_err());
+//! # }
+//! ```
+
+pub use crate::{
+    ConvertOptions, Diagnostic, DiagnosticKind, Dialect, Direction, Error, Token, TokenKind,
+};
+
+#[cfg(feature = "std")]
+pub use crate::{BbcodeParser, Converter};
@@ -0,0 +1,111 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::error::Result;
+
+/// A sink for UTF-8 text, abstracting over [`io::Write`](std::io::Write) and [`fmt::Write`] so that the converters can write
+/// straight into a `String` without going through an intermediate byte buffer.
+pub(crate) trait WriteFmt {
+    fn write_str(&mut self, s: &str) -> Result<()>;
+}
+
+impl<T: WriteFmt + ?Sized> WriteFmt for &mut T {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        (**self).write_str(s)
+    }
+}
+
+/// Adapts an [`io::Write`] into a [`WriteFmt`].
+#[cfg(feature = "std")]
+pub(crate) struct IoWriter<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: io::Write> WriteFmt for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        Ok(self.0.write_all(s.as_bytes())?)
+    }
+}
+
+/// Wraps a [`WriteFmt`], keeping track of where it is in what it has written: the byte offset, for mapping the output
+/// back to the input, the column, in characters, and how many line breaks it ends with, for telling whether a new
+/// line or a blank one has to be started. Both `\n` and `\r\n` count as line breaks, so that this works the same
+/// whatever the line ending. As everything goes through `str`s, what's written is always valid UTF-8 and never ends in
+/// the middle of a character.
+#[cfg(feature = "std")]
+pub(crate) struct Tracked<W> {
+    inner: W,
+    offset: usize,
+    column: usize,
+    /// How many line breaks in a row the output ends with.
+    newlines: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: WriteFmt> Tracked<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            column: 0,
+            newlines: 0,
+        }
+    }
+
+    /// How many bytes have been written so far.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Whether nothing has been written yet, or the last thing written ends a line.
+    pub(crate) fn at_line_start(&self) -> bool {
+        self.column == 0
+    }
+
+    /// How many line breaks in a row the output ends with, e.g. two after a blank line.
+    pub(crate) fn trailing_newlines(&self) -> usize {
+        self.newlines
+    }
+}
+
+/// Counts the line breaks `s` ends with, telling whether they make up the whole of it.
+#[cfg(feature = "std")]
+fn count_trailing_newlines(mut s: &str) -> (usize, bool) {
+    let mut count = 0;
+
+    while let Some(rest) = s.strip_suffix('\n') {
+        s = rest.strip_suffix('\r').unwrap_or(rest);
+        count += 1;
+    }
+
+    (count, s.is_empty())
+}
+
+#[cfg(feature = "std")]
+impl<W: WriteFmt> WriteFmt for Tracked<W> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        self.inner.write_str(s)?;
+        self.offset += s.len();
+
+        self.column = match s.rfind('\n') {
+            Some(end) => s[end + 1..].chars().count(),
+            None => self.column + s.chars().count(),
+        };
+
+        self.newlines = match count_trailing_newlines(s) {
+            (count, true) => self.newlines + count,
+            (count, false) => count,
+        };
+
+        Ok(())
+    }
+}
+
+/// Adapts a [`fmt::Write`] into a [`WriteFmt`].
+pub(crate) struct FmtWriter<W>(pub W);
+
+impl<W: fmt::Write> WriteFmt for FmtWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result<()> {
+        Ok(self.0.write_str(s)?)
+    }
+}